@@ -0,0 +1,90 @@
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryInto;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use syconf_lib::{Dependencies, Value};
+
+/// The first byte of a cache entry, incremented on incompatible changes of the entry layout.
+const ENTRY_VERSION: u8 = 2;
+
+/// A cached evaluation result in the `--cache-dir`.
+///
+/// An entry consists of the entry version, the fingerprint of the dependencies at the time of the evaluation,
+/// the length-prefixed dependencies and warnings as JSON, and the value encoded by `Value::to_bytes`.
+pub struct Cache {
+    path: PathBuf,
+}
+
+impl Cache {
+    /// `input` is the input file name or the content of stdin.
+    /// Relative paths in the config depend on the working directory, so it is part of the key too.
+    pub fn new(dir: &str, input: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        std::env::current_dir().ok().hash(&mut hasher);
+        input.hash(&mut hasher);
+        Self {
+            path: PathBuf::from(dir).join(format!("{:016x}.cache", hasher.finish())),
+        }
+    }
+
+    /// Returns the cached value and the warnings of its evaluation if none of its dependencies changed since it was stored.
+    /// Missing, corrupted, and outdated entries are ignored.
+    pub fn load(&self) -> Option<(Value, Vec<String>)> {
+        let entry = fs::read(&self.path).ok()?;
+        let (&version, entry) = entry.split_first()?;
+        if version != ENTRY_VERSION || entry.len() < 8 {
+            return None;
+        }
+        let (fingerprint, entry) = entry.split_at(8);
+        let (deps, entry) = split_prefixed(entry)?;
+        let deps: Dependencies = serde_json::from_slice(deps).ok()?;
+        if deps.fingerprint().to_le_bytes() != fingerprint {
+            return None;
+        }
+        let (warnings, value) = split_prefixed(entry)?;
+        let warnings = serde_json::from_slice(warnings).ok()?;
+        Some((Value::from_bytes(value).ok()?, warnings))
+    }
+
+    /// Values that cannot be cached, e.g. functions, and write errors are ignored,
+    /// the config is evaluated again on the next run then.
+    pub fn store(&self, value: &Value, deps: &Dependencies, warnings: &[String]) {
+        let (value, json, warnings) = match (
+            value.to_bytes(),
+            serde_json::to_vec(deps),
+            serde_json::to_vec(warnings),
+        ) {
+            (Ok(value), Ok(json), Ok(warnings)) => (value, json, warnings),
+            _ => return,
+        };
+        let mut entry = vec![ENTRY_VERSION];
+        entry.extend_from_slice(&deps.fingerprint().to_le_bytes());
+        for section in &[json, warnings] {
+            entry.extend_from_slice(&(section.len() as u32).to_le_bytes());
+            entry.extend_from_slice(section);
+        }
+        entry.extend_from_slice(&value);
+        let tmp = self.path.with_extension("tmp");
+        let _ = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&tmp, &entry))
+            .and_then(|_| fs::rename(&tmp, &self.path));
+    }
+}
+
+/// Splits a section prefixed by its length off the entry.
+fn split_prefixed(entry: &[u8]) -> Option<(&[u8], &[u8])> {
+    if entry.len() < 4 {
+        return None;
+    }
+    let (len, entry) = entry.split_at(4);
+    let len = u32::from_le_bytes(len.try_into().ok()?) as usize;
+    if entry.len() < len {
+        return None;
+    }
+    Some(entry.split_at(len))
+}
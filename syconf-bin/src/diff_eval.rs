@@ -0,0 +1,152 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+
+use syconf_lib::{Change, Difference, EvalOptions, FileLoader, FsLoader, Value};
+
+/// Differences listed per group in the markdown output, the remaining ones are only counted.
+const MAX_LISTED: usize = 50;
+/// Characters of a value shown in the markdown output.
+const MAX_VALUE_LEN: usize = 80;
+
+/// Reads the files relative to a directory, e.g. an unpacked copy of the base version.
+struct DirLoader(PathBuf);
+
+impl FileLoader for DirLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(self.0.join(path))
+    }
+}
+
+/// Reads the files as of a git revision with `git show`, relative to the working directory.
+struct GitLoader(String);
+
+impl FileLoader for GitLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        if path.is_absolute() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "absolute paths cannot be read from a git revision",
+            ));
+        }
+        let out = Command::new("git")
+            .arg("show")
+            .arg(format!("{}:./{}", self.0, path.display()))
+            .output()?;
+        if !out.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                String::from_utf8_lossy(&out.stderr).trim().to_string(),
+            ));
+        }
+        String::from_utf8(out.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// A directory is read as it is, anything else is a git revision. Without a version the working tree is read.
+fn loader(version: Option<&str>) -> Rc<dyn FileLoader> {
+    match version {
+        None => Rc::new(FsLoader),
+        Some(dir) if Path::new(dir).is_dir() => Rc::new(DirLoader(dir.into())),
+        Some(rev) => Rc::new(GitLoader(rev.to_string())),
+    }
+}
+
+/// Compiles and evaluates the entry point with the imports and the files of `read_file` as of the version.
+pub fn evaluate(entry: &str, version: Option<&str>) -> Result<Value, String> {
+    let name = version.unwrap_or("working tree");
+    let loader = loader(version);
+    let options = EvalOptions {
+        file_loader: loader.clone(),
+        ..Default::default()
+    };
+    syconf_lib::compile_file_with_loader(entry, loader)
+        .and_then(|compiled| compiled.evaluate_with_options(&options))
+        .map_err(|e| format!("{}: {}", name, e))
+}
+
+pub fn render_markdown(entry: &str, diffs: &[Difference]) -> String {
+    let noun = if diffs.len() == 1 {
+        "change"
+    } else {
+        "changes"
+    };
+    let mut out = format!("**{} effective {}** in `{}`\n", diffs.len(), noun, entry);
+    for title in &["Added", "Removed", "Changed"] {
+        let members: Vec<&Difference> = diffs
+            .iter()
+            .filter(|d| group(&d.change) == *title)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n#### {}\n", title));
+        for diff in members.iter().take(MAX_LISTED) {
+            let path = if diff.path.is_empty() {
+                "<root>"
+            } else {
+                diff.path.as_str()
+            };
+            let values = match &diff.change {
+                Change::Added(x) | Change::Removed(x) => format!("`{}`", truncate(x)),
+                Change::Changed(base, head) => {
+                    format!("`{}` → `{}`", truncate(base), truncate(head))
+                }
+            };
+            out.push_str(&format!("- `{}`: {}\n", path, values));
+        }
+        if members.len() > MAX_LISTED {
+            out.push_str(&format!("- ... and {} more\n", members.len() - MAX_LISTED));
+        }
+    }
+    out
+}
+
+pub fn render_json(diffs: &[Difference]) -> String {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for diff in diffs {
+        let path = ("path", Value::String(diff.path.as_str().into()));
+        match &diff.change {
+            Change::Added(x) => added.push(hashmap(vec![path, ("value", crate::printable(x))])),
+            Change::Removed(x) => removed.push(hashmap(vec![path, ("value", crate::printable(x))])),
+            Change::Changed(base, head) => changed.push(hashmap(vec![
+                path,
+                ("base", crate::printable(base)),
+                ("head", crate::printable(head)),
+            ])),
+        }
+    }
+    hashmap(vec![
+        ("added", Value::List(added.into())),
+        ("removed", Value::List(removed.into())),
+        ("changed", Value::List(changed.into())),
+    ])
+    .to_json()
+    .unwrap()
+}
+
+fn hashmap(entries: Vec<(&str, Value)>) -> Value {
+    Value::HashMap(Rc::new(
+        entries.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+    ))
+}
+
+fn group(change: &Change) -> &'static str {
+    match change {
+        Change::Added(_) => "Added",
+        Change::Removed(_) => "Removed",
+        Change::Changed(..) => "Changed",
+    }
+}
+
+fn truncate(value: &Value) -> String {
+    let s = value.to_string();
+    match s.char_indices().nth(MAX_VALUE_LEN) {
+        Some((ix, _)) => format!("{}...", &s[..ix]),
+        None => s,
+    }
+}
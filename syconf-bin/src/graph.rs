@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use syconf_lib::{ImportGraph, Value};
+
+/// Renders the graph for graphviz, the files are labeled with their sizes and the number of importers,
+/// and the imports with the line and column of the import statement.
+pub fn render_dot(graph: &ImportGraph) -> String {
+    let mut out = "digraph imports {\n    node [shape=box];\n".to_string();
+    for file in &graph.files {
+        let path = file.path.display().to_string();
+        let noun = if file.importers == 1 {
+            "importer"
+        } else {
+            "importers"
+        };
+        out.push_str(&format!(
+            "    {} [label={}];\n",
+            quote(&path),
+            quote(&format!(
+                "{}\n{} bytes, {} {}",
+                path, file.size, file.importers, noun
+            ))
+        ));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "    {} -> {} [label=\"{}:{}\"];\n",
+            quote(&edge.from.display().to_string()),
+            quote(&edge.to.display().to_string()),
+            edge.location.line_no(),
+            edge.location.column_no()
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn render_json(graph: &ImportGraph) -> String {
+    let path = |x: &std::path::Path| Value::String(x.display().to_string().into());
+    let files: Vec<Value> = graph
+        .files
+        .iter()
+        .map(|file| {
+            hashmap(vec![
+                ("path", path(&file.path)),
+                ("size", Value::Int(file.size as i64)),
+                ("importers", Value::Int(file.importers as i64)),
+            ])
+        })
+        .collect();
+    let edges: Vec<Value> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            hashmap(vec![
+                ("from", path(&edge.from)),
+                ("to", path(&edge.to)),
+                ("line", Value::Int(edge.location.line_no() as i64)),
+                ("column", Value::Int(edge.location.column_no() as i64)),
+            ])
+        })
+        .collect();
+    hashmap(vec![
+        ("files", Value::List(files.into())),
+        ("edges", Value::List(edges.into())),
+    ])
+    .to_json()
+    .unwrap()
+}
+
+fn hashmap(entries: Vec<(&str, Value)>) -> Value {
+    Value::HashMap(Rc::new(
+        entries.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+    ))
+}
+
+/// A DOT string literal, where `\n` is a line break of the label.
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
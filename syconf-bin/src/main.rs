@@ -1,13 +1,24 @@
-use std::collections::BTreeMap;
-use std::fs::File;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{self, File};
 use std::io;
 use std::io::{Read, Write};
+use std::path::Path;
 use std::rc::Rc;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use tracing::Level;
+use tracing_subscriber::EnvFilter;
 
-use syconf_lib::Value;
+use syconf_lib::{
+    EvalOptions, Recording, Value, WorkspaceOptions, YamlOptions, YamlQuoting, YamlStyle,
+};
+
+use crate::cache::Cache;
+
+mod cache;
+mod diff_eval;
+mod graph;
 
 fn main() {
     let matches = App::new("syconf")
@@ -17,8 +28,25 @@ fn main() {
             Arg::with_name("debug")
                 .long("debug")
                 .short("d")
+                .global(true)
                 .help("Turn on debug output"),
         )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .global(true)
+                .help("Print every evaluated function call with its result to stderr"),
+        )
+        .arg(
+            Arg::with_name("explain-defaults")
+                .long("explain-defaults")
+                .help("Print the defaults that getenv and its variants, get, get_ci, unwrap_or, and unwrap_or_else fell back to to stderr"),
+        )
+        .arg(
+            Arg::with_name("deny-warnings")
+                .long("deny-warnings")
+                .help("Fail on warnings, e.g. on the use of deprecated builtins, instead of printing them to stderr"),
+        )
         .arg(
             Arg::with_name("input")
                 .long("input")
@@ -29,23 +57,237 @@ fn main() {
                 .default_value("stdin"),
         )
         .arg(
-            Arg::with_name("output")
-                .long("output")
-                .short("o")
-                .help("Output file name")
+            Arg::with_name("overlay")
+                .long("overlay")
+                .help("Deep-merge a JSON, JSON5, YAML, or TOML file over the result, in the order of the flags")
                 .takes_value(true)
-                .value_name("FILE")
-                .default_value("stdout"),
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILE"),
         )
         .arg(
-            Arg::with_name("format")
-                .long("format")
-                .short("f")
-                .help("Output format")
+            Arg::with_name("merge-stdin")
+                .long("merge-stdin")
+                .help("Deep-merge data from stdin over the result, in the order of the --overlay flags")
                 .takes_value(true)
-                .value_name("FORMAT")
-                .possible_values(&["json", "yaml", "toml"])
-                .default_value("json"),
+                .possible_values(&["json", "json5", "yaml", "toml"])
+                .value_name("FORMAT"),
+        )
+        .arg(
+            Arg::with_name("merge-stdin-under")
+                .long("merge-stdin-under")
+                .requires("merge-stdin")
+                .help("Merge the data from stdin under the result instead, as defaults"),
+        )
+        .arg(
+            Arg::with_name("max-imports")
+                .long("max-imports")
+                .help("Fail if the config imports more files, directly or transitively")
+                .takes_value(true)
+                .value_name("COUNT"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .help("Write the sources, files, and environment variables the evaluation read to a recording")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("record-redact")
+                .long("record-redact")
+                .requires("record")
+                .help("Record the values of environment variables matching the glob as \"***\", e.g. '*_TOKEN'")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .conflicts_with("record")
+                .help("Evaluate a recording instead of the input, reading nothing but the recording")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .help("Cache the result and reuse it while the files and environment variables it read do not change")
+                .takes_value(true)
+                .value_name("DIR"),
+        )
+        .args(&output_args())
+        .subcommand(
+            SubCommand::with_name("eval")
+                .about("Evaluates a config file")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The config file, '-' reads it from stdin")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("base-dir")
+                        .long("base-dir")
+                        .help("The directory that imports of a config from stdin are relative to")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::with_name("var")
+                        .long("var")
+                        .help("Bind a string variable in the config file")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME=VALUE"),
+                )
+                .args(&output_args())
+                .after_help(
+                    r#"EXAMPLES:
+    syconf eval config.sy --format yaml --output config.yaml
+    syconf eval deploy.sy --var env=prod --var region=eu-west-1
+    generate-config | syconf eval - --base-dir conf/"#,
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("expr")
+                .about("Evaluates an expression given on the command line")
+                .arg(
+                    Arg::with_name("expression")
+                        .help("The expression to evaluate")
+                        .required(true)
+                        .value_name("EXPRESSION"),
+                )
+                .arg(
+                    Arg::with_name("stdin-as")
+                        .long("stdin-as")
+                        .help("Bind the content of stdin as a string variable")
+                        .takes_value(true)
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::with_name("arg")
+                        .long("arg")
+                        .help("Bind a string variable")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME=VALUE"),
+                )
+                .arg(
+                    Arg::with_name("argjson")
+                        .long("argjson")
+                        .help("Bind a variable given as JSON")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("NAME=JSON"),
+                )
+                .args(&output_args())
+                .after_help(
+                    r#"EXAMPLES:
+    syconf expr '"a,b,c".split(",").len()'
+    echo "$JSON" | syconf expr --stdin-as data 'data.parse_json().services.keys()'
+    syconf expr --arg env=prod --argjson 'limits={"cpu": 2}' '"${env}: ${limits.cpu}"' --raw"#,
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Evaluates many entry points and runs cross-file checks over their values")
+                .arg(
+                    Arg::with_name("workspace")
+                        .long("workspace")
+                        .help("Entry point files or glob patterns, '*' also matches '/'")
+                        .takes_value(true)
+                        .multiple(true)
+                        .required(true)
+                        .value_name("GLOB"),
+                )
+                .arg(
+                    Arg::with_name("checks")
+                        .long("checks")
+                        .help("A syconf file evaluating to a check function or a hashmap of check functions")
+                        .takes_value(true)
+                        .value_name("FILE"),
+                )
+                .after_help(
+                    r#"A check function gets a hashmap of the values by the entry paths,
+and returns a list of violations like {entry: "services/api.sy", message: "..."}.
+
+EXAMPLES:
+    syconf check --workspace 'services/*.sy' --checks checks.sy"#,
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff-eval")
+                .about("Evaluates an entry point as of two versions and shows how the values differ")
+                .arg(
+                    Arg::with_name("base")
+                        .long("base")
+                        .help("A git revision or a directory with the base version")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("REV_OR_DIR"),
+                )
+                .arg(
+                    Arg::with_name("head")
+                        .long("head")
+                        .help("A git revision or a directory with the new version, the working tree if omitted")
+                        .takes_value(true)
+                        .value_name("REV_OR_DIR"),
+                )
+                .arg(
+                    Arg::with_name("entry")
+                        .help("The entry point, relative to the directories or the working directory")
+                        .required(true)
+                        .value_name("ENTRY"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Output format")
+                        .takes_value(true)
+                        .possible_values(&["markdown", "json"])
+                        .default_value("markdown"),
+                )
+                .after_help(
+                    r#"The files of a git revision are read with 'git show', no checkout is needed.
+Prints "no effective changes" and exits with 0 if both values are equal, otherwise exits with 1.
+
+EXAMPLES:
+    syconf diff-eval --base origin/main services/api.sy
+    syconf diff-eval --base old/ --head new/ api.sy --format json"#,
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Shows the files a config imports and where they are imported")
+                .arg(
+                    Arg::with_name("entry")
+                        .help("The entry point")
+                        .required(true)
+                        .value_name("ENTRY"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Output format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "json"])
+                        .default_value("dot"),
+                )
+                .after_help(
+                    r#"The files are labeled with their sizes and the number of files importing them,
+the imports with the line and column of the import statement.
+
+EXAMPLES:
+    syconf graph main.sy | dot -Tsvg > imports.svg
+    syconf graph main.sy --format json"#,
+                ),
         )
         .get_matches();
 
@@ -53,31 +295,353 @@ fn main() {
         tracing_subscriber::fmt()
             .with_max_level(Level::DEBUG)
             .init();
+    } else if matches.is_present("explain") {
+        tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(format!(
+                "{}=info",
+                syconf_lib::EXPLAIN_TARGET
+            )))
+            .with_writer(io::stderr)
+            .with_ansi(false)
+            .without_time()
+            .with_level(false)
+            .with_target(false)
+            .init();
+    }
+
+    match matches.subcommand() {
+        ("check", Some(check_matches)) => check_workspace(check_matches),
+        ("diff-eval", Some(diff_matches)) => diff_eval(diff_matches),
+        ("graph", Some(graph_matches)) => import_graph(graph_matches),
+        _ => {}
     }
 
-    let result = match matches.value_of("input").unwrap() {
-        "stdin" => {
-            let mut s = String::new();
-            io::stdin().read_to_string(&mut s).unwrap();
-            syconf_lib::parse_string(&s)
+    let (result, matches) = match matches.subcommand() {
+        ("eval", Some(eval_matches)) => (evaluate_file(eval_matches), eval_matches),
+        ("expr", Some(expr_matches)) => (evaluate_expr(expr_matches), expr_matches),
+        _ => (
+            evaluate_input(&matches).and_then(|value| merge_overlays(&matches, value)),
+            &matches,
+        ),
+    };
+
+    let value = result.unwrap_or_else(|e| fail(e));
+    write_output(matches, &value);
+}
+
+fn output_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .help("Output file name")
+            .takes_value(true)
+            .value_name("FILE")
+            .default_value("stdout"),
+        Arg::with_name("format")
+            .long("format")
+            .short("f")
+            .help("Output format")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(&["json", "yaml", "toml"])
+            .default_value("json"),
+        Arg::with_name("raw").long("raw").short("r").help(
+            "Output a string, number, boolean, or null result as it is instead of serializing it",
+        ),
+        Arg::with_name("yaml-style")
+            .long("yaml-style")
+            .help("Style of YAML hashmaps and lists")
+            .takes_value(true)
+            .value_name("STYLE")
+            .possible_values(&["block", "flow"])
+            .default_value("block"),
+        Arg::with_name("yaml-quoting")
+            .long("yaml-quoting")
+            .help("Quote only YAML strings that would be misread, or all of them")
+            .takes_value(true)
+            .value_name("QUOTING")
+            .possible_values(&["minimal", "always"])
+            .default_value("minimal"),
+    ]
+}
+
+fn evaluate_input(matches: &ArgMatches) -> Result<Value, String> {
+    let input = matches.value_of("input").unwrap();
+    let replay = matches.value_of("replay");
+    if input == "stdin" && replay.is_none() && matches.is_present("merge-stdin") {
+        return Err(
+            "--merge-stdin needs the config from --input, as stdin holds the data to merge".into(),
+        );
+    }
+    let stdin = match input {
+        "stdin" if replay.is_none() => Some(read_stdin()),
+        _ => None,
+    };
+    let explain_defaults = matches.is_present("explain-defaults");
+    let deny_warnings = matches.is_present("deny-warnings");
+    let max_imports = matches
+        .value_of("max-imports")
+        .map(|x| {
+            x.parse::<usize>()
+                .map_err(|_| format!("--max-imports expects a count, got '{}'", x))
+        })
+        .transpose()?;
+    let cache = matches
+        .value_of("cache-dir")
+        .map(|dir| Cache::new(dir, stdin.as_deref().unwrap_or(input)));
+    let record = matches.value_of("record");
+    // A cached result does not tell which defaults were applied, the warnings are stored with it though,
+    // and it may have been evaluated without the import limit. Recording and replaying need the evaluation.
+    if let Some((value, warnings)) = cache
+        .as_ref()
+        .filter(|_| !explain_defaults && !deny_warnings && max_imports.is_none())
+        .filter(|_| record.is_none() && replay.is_none())
+        .and_then(Cache::load)
+    {
+        for warning in &warnings {
+            eprintln!("WARNING: {}", warning);
         }
-        file => syconf_lib::parse_file(file),
+        return Ok(value);
+    }
+    let (compiled, options) = match (replay, &stdin) {
+        (Some(file), _) => Recording::load(Path::new(file))
+            .map_err(|e| e.to_string())?
+            .replay(),
+        (None, Some(content)) => syconf_lib::compile_string_with_base(content, Path::new("."))
+            .map(|x| (x, EvalOptions::default())),
+        (None, None) => syconf_lib::compile_file(input).map(|x| (x, EvalOptions::default())),
     }
-    .map(|x| to_serializable(&x));
+    .map_err(|e| e.to_string())?;
+    let options = EvalOptions {
+        track_defaults: explain_defaults,
+        deny_warnings,
+        max_imports,
+        record: record.map(Into::into),
+        record_redact: matches
+            .values_of("record-redact")
+            .into_iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect(),
+        ..options
+    };
+    let (value, report) = compiled
+        .evaluate_with_report(&options)
+        .map_err(|e| e.to_string())?;
+    for applied in &report.defaults_applied {
+        eprintln!("DEFAULT: {}", applied);
+    }
+    let warnings: Vec<String> = report.warnings.iter().map(ToString::to_string).collect();
+    for warning in &warnings {
+        eprintln!("WARNING: {}", warning);
+    }
+    if let Some(cache) = cache {
+        cache.store(&value, &report.dependencies, &warnings);
+    }
+    Ok(value)
+}
+
+/// Merges the `--overlay` files and the `--merge-stdin` data in the order of the flags,
+/// the data of `--merge-stdin-under` goes below the value instead.
+fn merge_overlays(matches: &ArgMatches, value: Value) -> Result<Value, String> {
+    let mut overlays = Vec::new();
+    let files = matches.values_of("overlay").into_iter().flatten();
+    let indices = matches.indices_of("overlay").into_iter().flatten();
+    for (file, ix) in files.zip(indices) {
+        let format = data_format(file)?;
+        let data = fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|content| syconf_lib::parse_data(&content, format).map_err(|e| e.message))
+            .map_err(|e| format!("Overlay '{}': {}", file, e))?;
+        overlays.push((ix, data));
+    }
+    let mut under = None;
+    if let Some(format) = matches.value_of("merge-stdin") {
+        let data = syconf_lib::parse_data(&read_stdin(), format)
+            .map_err(|e| format!("Data from stdin: {}", e.message))?;
+        if matches.is_present("merge-stdin-under") {
+            under = Some(data);
+        } else {
+            overlays.push((matches.index_of("merge-stdin").unwrap(), data));
+        }
+    }
+    if overlays.is_empty() && under.is_none() {
+        return Ok(value);
+    }
+    if !matches!(value, Value::HashMap(_)) {
+        return Err(format!(
+            "Overlays can only be merged into a hashmap, the config evaluated to {}",
+            value
+        ));
+    }
+    overlays.sort_by_key(|(ix, _)| *ix);
+    let mut out = match under {
+        Some(data) => data.deep_merge(&value),
+        None => Ok(value),
+    }
+    .map_err(|e| e.message)?;
+    for (_, data) in overlays {
+        out = out.deep_merge(&data).map_err(|e| e.message)?;
+    }
+    Ok(out)
+}
+
+fn data_format(file: &str) -> Result<&'static str, String> {
+    match Path::new(file).extension().and_then(|x| x.to_str()) {
+        Some("json") => Ok("json"),
+        Some("json5") => Ok("json5"),
+        Some("yaml") | Some("yml") => Ok("yaml"),
+        Some("toml") => Ok("toml"),
+        _ => Err(format!(
+            "Cannot tell the format of '{}', expected a .json, .json5, .yaml, .yml, or .toml file",
+            file
+        )),
+    }
+}
 
-    let val = match result {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("ERROR: {}", e);
-            std::process::exit(1);
+fn evaluate_file(matches: &ArgMatches) -> Result<Value, String> {
+    let mut vars = HashMap::new();
+    for arg in matches.values_of("var").into_iter().flatten() {
+        let (name, value) = split_binding(arg)?;
+        vars.insert(name, Value::String(value.into()));
+    }
+    let compiled = match matches.value_of("file").unwrap() {
+        "-" => {
+            let base_dir = Path::new(matches.value_of("base-dir").unwrap());
+            syconf_lib::compile_string_with_base_and_vars(&read_stdin(), base_dir, &vars)
         }
+        file => syconf_lib::compile_file_with_vars(file, &vars),
+    };
+    compiled
+        .and_then(|x| x.evaluate())
+        .map_err(|e| e.to_string())
+}
+
+fn evaluate_expr(matches: &ArgMatches) -> Result<Value, String> {
+    let mut vars = HashMap::new();
+    if let Some(name) = matches.value_of("stdin-as") {
+        vars.insert(name.to_string(), Value::String(read_stdin().into()));
+    }
+    for arg in matches.values_of("arg").into_iter().flatten() {
+        let (name, value) = split_binding(arg)?;
+        vars.insert(name, Value::String(value.into()));
+    }
+    for arg in matches.values_of("argjson").into_iter().flatten() {
+        let (name, json) = split_binding(arg)?;
+        let value = serde_json::from_str(json)
+            .map_err(|e| format!("Cannot parse JSON of --argjson '{}': {}", name, e))?;
+        vars.insert(name, value);
+    }
+    syconf_lib::parse_string_with_vars(matches.value_of("expression").unwrap(), &vars)
+        .map_err(|e| e.to_string())
+}
+
+/// Prints the errors and violations and exits with a failure if there are any.
+fn check_workspace(matches: &ArgMatches) -> ! {
+    let patterns: Vec<&str> = matches.values_of("workspace").unwrap().collect();
+    let entries = syconf_lib::expand_entries(&patterns).unwrap_or_else(|e| fail(e));
+    let checks = match matches.value_of("checks") {
+        Some(file) => syconf_lib::load_checks(Path::new(file)).unwrap_or_else(|e| fail(e)),
+        None => Vec::new(),
+    };
+    let options = WorkspaceOptions {
+        checks,
+        ..Default::default()
+    };
+    let result = syconf_lib::evaluate_workspace(&entries, &options);
+    for (entry, error) in &result.errors {
+        eprintln!("ERROR: {}: {}", entry.display(), error);
+    }
+    for violation in &result.violations {
+        eprintln!("VIOLATION: {}", violation);
+    }
+    if !result.is_ok() {
+        std::process::exit(1);
+    }
+    let noun = if entries.len() == 1 {
+        "entry point"
+    } else {
+        "entry points"
     };
+    println!("Checked {} {}", entries.len(), noun);
+    std::process::exit(0);
+}
+
+/// Prints the differences and exits with a failure if there are any.
+fn diff_eval(matches: &ArgMatches) -> ! {
+    let entry = matches.value_of("entry").unwrap();
+    let base = diff_eval::evaluate(entry, matches.value_of("base")).unwrap_or_else(|e| fail(e));
+    let head = diff_eval::evaluate(entry, matches.value_of("head")).unwrap_or_else(|e| fail(e));
+    let diffs = syconf_lib::structural_diff(&base, &head);
+    match matches.value_of("format").unwrap() {
+        "json" => println!("{}", diff_eval::render_json(&diffs)),
+        _ if diffs.is_empty() => println!("no effective changes"),
+        _ => print!("{}", diff_eval::render_markdown(entry, &diffs)),
+    }
+    std::process::exit(if diffs.is_empty() { 0 } else { 1 });
+}
+
+fn import_graph(matches: &ArgMatches) -> ! {
+    let compiled =
+        syconf_lib::compile_file(matches.value_of("entry").unwrap()).unwrap_or_else(|e| fail(e));
+    let graph = compiled.import_graph();
+    match matches.value_of("format").unwrap() {
+        "json" => println!("{}", graph::render_json(graph)),
+        _ => print!("{}", graph::render_dot(graph)),
+    }
+    std::process::exit(0);
+}
+
+/// Splits `name=value` of the variable arguments.
+fn split_binding(arg: &str) -> Result<(String, &str), String> {
+    match arg.find('=') {
+        Some(ix) if ix > 0 => Ok((arg[..ix].to_string(), &arg[ix + 1..])),
+        _ => Err(format!("Expected NAME=VALUE, got '{}'", arg)),
+    }
+}
+
+fn read_stdin() -> String {
+    let mut s = String::new();
+    io::stdin().read_to_string(&mut s).unwrap();
+    s
+}
+
+fn fail(message: impl Display) -> ! {
+    eprintln!("ERROR: {}", message);
+    std::process::exit(1);
+}
 
-    let ser = match matches.value_of("format").unwrap() {
-        "json" => serde_json::to_string(&val).unwrap(),
-        "yaml" => serde_yaml::to_string(&val).unwrap(),
-        "toml" => toml::ser::to_string(&val).unwrap(),
-        _ => unreachable!(),
+fn write_output(matches: &ArgMatches, value: &Value) {
+    let ser = match (value, matches.is_present("raw")) {
+        (Value::String(s), true) => s.to_string(),
+        (Value::Null | Value::Bool(_) | Value::Int(_) | Value::Float(_), true) => value.to_string(),
+        _ => match matches.value_of("format").unwrap() {
+            "json" => printable(value).to_json().unwrap_or_else(|e| fail(e)),
+            "yaml" => {
+                let options = YamlOptions {
+                    style: match matches.value_of("yaml-style").unwrap() {
+                        "flow" => YamlStyle::Flow,
+                        _ => YamlStyle::Block,
+                    },
+                    quoting: match matches.value_of("yaml-quoting").unwrap() {
+                        "always" => YamlQuoting::Always,
+                        _ => YamlQuoting::Minimal,
+                    },
+                };
+                printable(value).to_yaml(&options).unwrap_or_else(|e| fail(e))
+            }
+            "toml" => printable(value)
+                .to_toml()
+                .map_err(|e| match value {
+                    Value::HashMap(_) => e,
+                    _ => e.with_hint(
+                        "TOML documents are tables, use '--format json' or '--format yaml' for other values",
+                    ),
+                })
+                .unwrap_or_else(|e| fail(e)),
+            _ => unreachable!(),
+        },
     };
 
     match matches.value_of("output").unwrap() {
@@ -89,27 +653,14 @@ fn main() {
     }
 }
 
-#[derive(serde::Serialize)]
-#[serde(untagged)]
-enum SerializableValue {
-    Bool(bool),
-    Int(i32),
-    String(Rc<str>),
-    HashMap(BTreeMap<Rc<str>, SerializableValue>),
-    List(Rc<[SerializableValue]>),
-}
-
-fn to_serializable(v: &Value) -> SerializableValue {
+/// Replaces the functions by `"<function>"`, so that any value can be printed.
+fn printable(v: &Value) -> Value {
     match v {
-        Value::Bool(x) => SerializableValue::Bool(*x),
-        Value::Int(x) => SerializableValue::Int(*x),
-        Value::String(x) => SerializableValue::String(x.clone()),
-        Value::HashMap(x) => SerializableValue::HashMap(
-            x.iter()
-                .map(|(k, v)| (k.clone(), to_serializable(v)))
-                .collect(),
-        ),
-        Value::List(x) => SerializableValue::List(x.iter().map(to_serializable).collect()),
-        Value::Func(_) => SerializableValue::String("<function>".into()),
+        Value::HashMap(x) => Value::HashMap(Rc::new(
+            x.iter().map(|(k, v)| (k.clone(), printable(v))).collect(),
+        )),
+        Value::List(x) => Value::List(x.iter().map(printable).collect()),
+        Value::Func(_) => Value::String("<function>".into()),
+        x => x.clone(),
     }
 }
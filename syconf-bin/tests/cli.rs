@@ -0,0 +1,613 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_syconf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A command that does not read stdin may exit before the write, which is a broken pipe.
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin.as_bytes()) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "{}", e);
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn explain() {
+    let out = run(
+        &["--explain"],
+        r#"
+    let add = (a, b) => a + b
+    in
+    {sum: add(1, 2)}
+    "#,
+    );
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), r#"{"sum":3}"#);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(
+        stderr.contains("<input_string>:2:27: Add(1, 2) = 3"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("<input_string>:4:14: add(1, 2) = 3"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn explain_defaults() {
+    let config = r#"
+    let cfg = {port: 8080}
+    in
+    {
+        port: cfg.get("port", 80),
+        host: getenv("SYCONF_CLI_UNSET", "localhost"),
+    }
+    "#;
+    let out = run(&["--explain-defaults"], config);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "DEFAULT: <input_string>:6:21: getenv('SYCONF_CLI_UNSET') used the default \"localhost\"\n"
+    );
+    let out = run(&[], config);
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn deprecation_warnings() {
+    let config = "merge_deep({a: 1}, {b: 2})";
+    let out = run(&[], config);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), r#"{"a":1,"b":2}"#);
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "WARNING: <input_string>:1:1: 'merge_deep' is deprecated since 0.1.1 and will be removed in 0.2.0, use 'deep_merge' instead\n"
+    );
+    let out = run(&["--deny-warnings"], config);
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("'merge_deep' is deprecated"));
+    assert!(run(&["--deny-warnings"], "deep_merge({a: 1}, {b: 2})")
+        .status
+        .success());
+}
+
+fn shell(script: &str) -> Output {
+    Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .env("SYCONF", env!("CARGO_BIN_EXE_syconf"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn expr() {
+    let out = run(&["expr", r#""a,b,c".split(",").len()"#], "");
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "3");
+
+    let out = run(
+        &[
+            "expr",
+            "--arg",
+            "env=prod",
+            "--argjson",
+            r#"limits={"cpu": 2}"#,
+            "--raw",
+            r#""${env}: ${limits.cpu}""#,
+        ],
+        "",
+    );
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "prod: 2");
+
+    let out = run(&["expr", "-f", "yaml", "{a: [1]}"], "");
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "a:\n  - 1\n");
+}
+
+#[test]
+fn expr_stdin_pipeline() {
+    let out = shell(
+        r#"JSON='{"services": {"web": {}, "api": {}}}'
+        echo "$JSON" | "$SYCONF" expr --stdin-as data 'data.parse_json().services.keys()'"#,
+    );
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), r#"["api","web"]"#);
+}
+
+#[test]
+fn expr_errors() {
+    let out = run(&["expr", "--arg", "novalue", "1"], "");
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("Expected NAME=VALUE, got 'novalue'"));
+
+    let out = run(&["expr", "--argjson", "x={", "x"], "");
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("Cannot parse JSON of --argjson 'x'"));
+}
+
+#[test]
+fn cache_dir() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-cache-{}", std::process::id()));
+    let cache = dir.join("cache");
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("config.sy");
+    let data = dir.join("data.txt");
+    std::fs::write(&config, r#"{name: read_file("data.txt").trim()}"#).unwrap();
+    std::fs::write(&data, "a").unwrap();
+
+    let eval = || {
+        let out = Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(["--explain", "--cache-dir", "cache", "--input", "config.sy"])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        (
+            String::from_utf8(out.stdout).unwrap(),
+            String::from_utf8(out.stderr)
+                .unwrap()
+                .contains("read_file(\"data.txt\")"),
+        )
+    };
+    assert_eq!(eval(), (r#"{"name":"a"}"#.to_string(), true));
+    // The second run is served from the cache without evaluating the config.
+    assert_eq!(eval(), (r#"{"name":"a"}"#.to_string(), false));
+
+    std::fs::write(&data, "b").unwrap();
+    assert_eq!(eval(), (r#"{"name":"b"}"#.to_string(), true));
+    assert_eq!(eval(), (r#"{"name":"b"}"#.to_string(), false));
+
+    for entry in std::fs::read_dir(&cache).unwrap() {
+        std::fs::write(entry.unwrap().path(), b"\x02garbage").unwrap();
+    }
+    assert_eq!(eval(), (r#"{"name":"b"}"#.to_string(), true));
+
+    // The warnings of the evaluation are printed for a cached result too.
+    std::fs::write(&config, "merge_deep({a: 1}, {b: 2})").unwrap();
+    for _ in 0..2 {
+        let out = Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(["--cache-dir", "cache", "--input", "config.sy"])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        assert!(String::from_utf8(out.stderr)
+            .unwrap()
+            .contains("WARNING: config.sy:1:1: 'merge_deep' is deprecated"));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_workspace() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-workspace-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("services")).unwrap();
+    for (name, port) in &[("api", 8080), ("web", 80), ("worker", 8080)] {
+        std::fs::write(
+            dir.join("services").join(format!("{}.sy", name)),
+            format!("{{name: {:?}, port: {}}}", name, port),
+        )
+        .unwrap();
+    }
+    std::fs::write(
+        dir.join("checks.sy"),
+        r#"{
+            duplicate_ports: (results) =>
+                results.keys()
+                    .filter((p) => results.keys().filter((q) => q != p and results[q].port == results[p].port).len() > 0)
+                    .map((p) => {entry: p, message: "port ${results[p].port} is used twice"}),
+        }"#,
+    )
+    .unwrap();
+    let check = |pattern: &str| {
+        Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(["check", "--workspace", pattern, "--checks", "checks.sy"])
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let out = check("services/*.sy");
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "VIOLATION: services/api.sy: port 8080 is used twice\n\
+         VIOLATION: services/worker.sy: port 8080 is used twice\n"
+    );
+
+    let out = check("services/[!w]*.sy");
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "Checked 1 entry point\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diff_eval() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-diff-eval-{}", std::process::id()));
+    let write = |version: &str, name: &str, content: &str| {
+        std::fs::create_dir_all(dir.join(version)).unwrap();
+        std::fs::write(dir.join(version).join(name), content).unwrap();
+    };
+    write(
+        "base",
+        "api.sy",
+        r#"let common = import "common.sy" in {db: common.db, replicas: 2, tags: ["a", "b"]}"#,
+    );
+    write(
+        "base",
+        "common.sy",
+        r#"{db: {host: read_file("host.txt"), port: 5432}}"#,
+    );
+    write("base", "host.txt", "db1");
+    // The same values written differently.
+    write(
+        "same",
+        "api.sy",
+        r#"{db: {host: "db1", port: 5000 + 432}, replicas: 1 + 1, tags: ["a", "b"]}"#,
+    );
+    write(
+        "head",
+        "api.sy",
+        r#"let common = import "common.sy" in {db: common.db, tags: ["a", "c"], tls: true}"#,
+    );
+    write(
+        "head",
+        "common.sy",
+        r#"{db: {host: read_file("host.txt"), port: 5433}}"#,
+    );
+    write("head", "host.txt", "db2");
+    let diff_eval = |head: &str, format: &str| {
+        Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(["diff-eval", "--base", "base", "--head", head, "api.sy"])
+            .args(["--format", format])
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let out = diff_eval("same", "markdown");
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "no effective changes\n"
+    );
+
+    let out = diff_eval("head", "markdown");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        r#"**5 effective changes** in `api.sy`
+
+#### Added
+- `tls`: `true`
+
+#### Removed
+- `replicas`: `2`
+
+#### Changed
+- `db.host`: `"db1"` → `"db2"`
+- `db.port`: `5432` → `5433`
+- `tags[1]`: `"b"` → `"c"`
+"#
+    );
+
+    let out = diff_eval("head", "json");
+    assert_eq!(out.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        json["added"],
+        serde_json::json!([{"path": "tls", "value": true}])
+    );
+    assert_eq!(json["changed"][1]["head"], serde_json::json!(5433));
+
+    let out = diff_eval("missing", "markdown");
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .starts_with("ERROR: missing: "));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn non_hashmap_roots() {
+    let cases: &[(&str, &str, &str, &str, &str)] = &[
+        // root, json, yaml, --raw, toml (empty if it must fail)
+        ("[1, 'a']", r#"[1,"a"]"#, "- 1\n- a\n", r#"[1,"a"]"#, ""),
+        ("42", "42", "42\n", "42", ""),
+        ("1.5", "1.5", "1.5\n", "1.5", ""),
+        ("true", "true", "true\n", "true", ""),
+        ("null", "null", "null\n", "null", ""),
+        ("'a b'", r#""a b""#, "a b\n", "a b", ""),
+        ("{a: 1}", r#"{"a":1}"#, "a: 1\n", r#"{"a":1}"#, "a = 1\n"),
+        // Functions are printed as "<function>" in every format.
+        (
+            "{a: (x) => x, b: 1}",
+            r#"{"a":"<function>","b":1}"#,
+            "a: <function>\nb: 1\n",
+            r#"{"a":"<function>","b":1}"#,
+            "a = \"<function>\"\nb = 1\n",
+        ),
+    ];
+    for (root, json, yaml, raw, toml) in cases {
+        let output = |args: &[&str]| {
+            let out = run(args, root);
+            (out.status.success(), String::from_utf8(out.stdout).unwrap())
+        };
+        assert_eq!(output(&[]), (true, json.to_string()), "{}", root);
+        assert_eq!(
+            output(&["-f", "yaml"]),
+            (true, yaml.to_string()),
+            "{}",
+            root
+        );
+        assert_eq!(output(&["--raw"]), (true, raw.to_string()), "{}", root);
+
+        let out = run(&["-f", "toml"], root);
+        if toml.is_empty() {
+            assert!(!out.status.success(), "{}", root);
+            assert!(out.stdout.is_empty(), "{}", root);
+            let stderr = String::from_utf8(out.stderr).unwrap();
+            assert!(
+                stderr.contains("the top level must be a hashmap")
+                    && stderr.contains("use '--format json'"),
+                "{}",
+                stderr
+            );
+        } else {
+            assert!(out.status.success(), "{}", root);
+            assert_eq!(String::from_utf8(out.stdout).unwrap(), *toml);
+        }
+    }
+}
+
+#[test]
+fn overlays() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-overlays-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str, content: &str| {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let base = file(
+        "base.sy",
+        r#"{db: {host: "db1", port: 5432}, replicas: 1, tags: ["a"]}"#,
+    );
+    let yaml = file("prod.yaml", "db:\n  host: db-prod\nreplicas: 3\n");
+    let json = file("local.json", r#"{"replicas": 5, "tags": ["b"]}"#);
+    let stdout = |out: Output| {
+        assert!(out.status.success(), "{:?}", out);
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    // Overlays apply in the order of the flags, lists are replaced.
+    let out = run(&["-i", &base, "--overlay", &yaml, "--overlay", &json], "");
+    assert_eq!(
+        stdout(out),
+        r#"{"db":{"host":"db-prod","port":5432},"replicas":5,"tags":["b"]}"#
+    );
+    let out = run(&["-i", &base, "--overlay", &json, "--overlay", &yaml], "");
+    assert_eq!(
+        stdout(out),
+        r#"{"db":{"host":"db-prod","port":5432},"replicas":3,"tags":["b"]}"#
+    );
+
+    // Data from stdin is merged at the position of its flag, or under the result.
+    let out = run(
+        &["-i", &base, "--merge-stdin", "yaml", "--overlay", &json],
+        "replicas: 2\ndb: {user: admin}\n",
+    );
+    assert_eq!(
+        stdout(out),
+        r#"{"db":{"host":"db1","port":5432,"user":"admin"},"replicas":5,"tags":["b"]}"#
+    );
+    let out = run(
+        &["-i", &base, "--merge-stdin", "json", "--merge-stdin-under"],
+        r#"{"replicas": 2, "region": "eu"}"#,
+    );
+    assert_eq!(
+        stdout(out),
+        r#"{"db":{"host":"db1","port":5432},"region":"eu","replicas":1,"tags":["a"]}"#
+    );
+    let shell_out = shell(&format!(
+        r#"printf 'replicas: 7\n' | "$SYCONF" -i {} --merge-stdin yaml -f yaml"#,
+        base
+    ));
+    assert!(stdout(shell_out).contains("replicas: 7\n"));
+
+    let stderr = |args: &[&str], stdin: &str| {
+        let out = run(args, stdin);
+        assert!(!out.status.success());
+        assert!(out.stdout.is_empty());
+        String::from_utf8(out.stderr).unwrap()
+    };
+    let list = file("list.sy", "[1, 2]");
+    assert!(stderr(&["-i", &list, "--overlay", &json], "")
+        .contains("Overlays can only be merged into a hashmap, the config evaluated to [1, 2]"));
+    let json_list = file("list.json", "[1]");
+    assert!(stderr(&["-i", &base, "--overlay", &json_list], "").contains("Overlay '"));
+    let ini = file("x.ini", "a = 1");
+    assert!(stderr(&["-i", &base, "--overlay", &ini], "").contains("Cannot tell the format"));
+    assert!(stderr(&["--merge-stdin", "yaml"], "{}").contains("--merge-stdin needs the config"));
+    assert!(stderr(&["-i", &base, "--merge-stdin", "json"], "{").contains("Data from stdin"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_graph() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-graph-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(
+        dir.join("main.sy"),
+        "{\n  db: import \"lib/db.sy\",\n  common: import \"lib/common.sy\",\n}",
+    )
+    .unwrap();
+    std::fs::write(dir.join("lib/db.sy"), r#"{common: import "common.sy"}"#).unwrap();
+    std::fs::write(dir.join("lib/common.sy"), "{}").unwrap();
+    let syconf = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let out = syconf(&["graph", "main.sy"]);
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        r#"digraph imports {
+    node [shape=box];
+    "main.sy" [label="main.sy\n63 bytes, 0 importers"];
+    "lib/db.sy" [label="lib/db.sy\n28 bytes, 1 importer"];
+    "lib/common.sy" [label="lib/common.sy\n2 bytes, 2 importers"];
+    "main.sy" -> "lib/db.sy" [label="2:7"];
+    "lib/db.sy" -> "lib/common.sy" [label="1:10"];
+    "main.sy" -> "lib/common.sy" [label="3:11"];
+}
+"#
+    );
+
+    let out = syconf(&["graph", "main.sy", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(json["files"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        json["edges"][1],
+        serde_json::json!({"from": "lib/db.sy", "to": "lib/common.sy", "line": 1, "column": 10})
+    );
+
+    let out = syconf(&["--input", "main.sy", "--max-imports", "1"]);
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "ERROR: lib/db.sy:1:10: 2 files are imported, more than the limit of 1\n"
+    );
+    let out = syconf(&["--input", "main.sy", "--max-imports", "2"]);
+    assert!(out.status.success(), "{:?}", out);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn record_and_replay() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-replay-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("conf")).unwrap();
+    std::fs::write(
+        dir.join("conf/main.sy"),
+        r#"{db: import "db.sy", ca: read_file("ca.pem"), region: getenv("REGION", "eu")}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("conf/db.sy"), "{port: 5432}").unwrap();
+    std::fs::write(dir.join("ca.pem"), "CERT").unwrap();
+    let syconf = |args: &[&str], region: &str| {
+        Command::new(env!("CARGO_BIN_EXE_syconf"))
+            .args(args)
+            .current_dir(&dir)
+            .env("REGION", region)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    let recorded = syconf(
+        &["-i", "conf/main.sy", "--record", "rec.json", "-f", "yaml"],
+        "us",
+    );
+    assert!(recorded.status.success(), "{:?}", recorded);
+    assert_eq!(
+        String::from_utf8_lossy(&recorded.stdout),
+        "ca: CERT\ndb:\n  port: 5432\nregion: us\n"
+    );
+
+    std::fs::write(dir.join("conf/db.sy"), "{port: 1}").unwrap();
+    std::fs::remove_file(dir.join("ca.pem")).unwrap();
+    std::fs::rename(dir.join("rec.json"), dir.join("conf/rec.json")).unwrap();
+    let replayed = syconf(&["--replay", "conf/rec.json", "-f", "yaml"], "ap");
+    assert!(replayed.status.success(), "{:?}", replayed);
+    assert_eq!(replayed.stdout, recorded.stdout);
+
+    let out = syconf(&["-i", "conf/main.sy"], "ap");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Cannot read file 'ca.pem'"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn eval_file() {
+    let dir = std::env::temp_dir().join(format!("syconf-cli-eval-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("conf")).unwrap();
+    std::fs::write(
+        dir.join("conf/main.sy"),
+        r#"{db: import "db.sy", env: env, name: "${env}-${region}"}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("conf/db.sy"), "{port: 5432}").unwrap();
+    let main = dir.join("conf/main.sy");
+    let main = main.to_str().unwrap();
+
+    let out = run(
+        &["eval", main, "--var", "env=prod", "--var", "region=eu"],
+        "",
+    );
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        r#"{"db":{"port":5432},"env":"prod","name":"prod-eu"}"#
+    );
+
+    let toml = dir.join("out.toml");
+    let out = run(
+        &[
+            "eval",
+            "-",
+            "--base-dir",
+            dir.join("conf").to_str().unwrap(),
+            "--format",
+            "toml",
+            "--output",
+            toml.to_str().unwrap(),
+        ],
+        r#"{db: import "db.sy"}"#,
+    );
+    assert!(out.status.success(), "{:?}", out);
+    assert_eq!(
+        std::fs::read_to_string(&toml).unwrap(),
+        "[db]\nport = 5432\n"
+    );
+
+    let out = run(&["eval", main, "--var", "env=prod"], "");
+    assert!(!out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(
+        stderr.starts_with(&format!(
+            "ERROR: {}:1:48: Variable 'region' is not defined",
+            main
+        )),
+        "{}",
+        stderr
+    );
+    let out = run(&["eval", main, "--var", "env"], "");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "ERROR: Expected NAME=VALUE, got 'env'\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
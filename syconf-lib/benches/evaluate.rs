@@ -0,0 +1,49 @@
+//! Compares parsing and compiling a config on every evaluation with evaluating a compiled config.
+//! Run with `cargo bench -p syconf-lib`.
+
+use std::time::{Duration, Instant};
+
+const CONFIG: &str = r#"
+let base = {
+    image: "app",
+    ports: [80, 443],
+    env: {LOG: "info", REGION: "eu"},
+}
+let service = (name, replicas) => merge(base, {
+    name: name,
+    replicas: replicas,
+    labels: {app: name, tier: "${name}-tier"},
+})
+in
+{
+    services: ["api", "web", "worker", "cron", "admin"].map((x) => service(x, 3)),
+    total: fold(0, (acc, ix, x) => acc + x, [1, 2, 3, 4, 5, 6, 7, 8]),
+}
+"#;
+
+const ITERATIONS: u32 = 2000;
+
+fn measure(name: &str, f: impl Fn()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iteration = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.2?}/iter", name, per_iteration);
+    per_iteration
+}
+
+fn main() {
+    let parse = measure("parse_string", || {
+        syconf_lib::parse_string(CONFIG).unwrap();
+    });
+    let compiled = syconf_lib::compile_string(CONFIG).unwrap();
+    let evaluate = measure("Compiled::evaluate", || {
+        compiled.evaluate().unwrap();
+    });
+    println!(
+        "speedup: {:.1}x",
+        parse.as_secs_f64() / evaluate.as_secs_f64()
+    );
+}
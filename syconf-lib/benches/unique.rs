@@ -0,0 +1,32 @@
+//! Compares the hash based `unique` builtin with a pairwise equality scan on 10k hashmaps.
+//! Run with `cargo bench -p syconf-lib --bench unique`.
+
+use std::time::Instant;
+
+use syconf_lib::Value;
+
+const LIST: &str = r#"iterate(10000, (acc, ix) => acc.append({id: ix / 2, name: "service", tags: ["a", "b"]}), [])"#;
+
+fn main() {
+    let list = syconf_lib::parse_string(LIST).unwrap();
+    let vars = vec![("list".to_string(), list.clone())]
+        .into_iter()
+        .collect();
+
+    let start = Instant::now();
+    let unique = syconf_lib::parse_string_with_vars("unique(list)", &vars).unwrap();
+    let hashed = start.elapsed();
+
+    let start = Instant::now();
+    let mut scanned: Vec<&Value> = Vec::new();
+    for x in list.as_list().unwrap() {
+        if !scanned.contains(&x) {
+            scanned.push(x);
+        }
+    }
+    let pairwise = start.elapsed();
+
+    assert_eq!(unique.as_list().unwrap().len(), scanned.len());
+    println!("unique (hash based)      {:>10.2?}", hashed);
+    println!("pairwise equality scan   {:>10.2?}", pairwise);
+}
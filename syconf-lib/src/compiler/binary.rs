@@ -0,0 +1,76 @@
+use crate::compiler::value::check_finite;
+use crate::compiler::{Error, Value};
+
+/// The first byte of the encoded data, incremented on incompatible changes of the encoding.
+const FORMAT_VERSION: u8 = 1;
+
+/// Encodes the value as CBOR prefixed by the format version.
+pub fn to_bytes(value: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = vec![FORMAT_VERSION];
+    serde_cbor::to_writer(&mut out, value).map_err(|e| anyhow!("Cannot encode value: {}", e))?;
+    Ok(out)
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Value, Error> {
+    match bytes.split_first() {
+        Some((&FORMAT_VERSION, data)) => {
+            let value =
+                serde_cbor::from_slice(data).map_err(|e| anyhow!("Cannot decode value: {}", e))?;
+            check_finite(&value, "").map_err(|e| anyhow!("Cannot decode value: {}", e.message))?;
+            Ok(value)
+        }
+        Some((version, _)) => Err(format!("Unsupported binary format version {}", version).into()),
+        None => Err("Cannot decode an empty value".into()),
+    }
+}
+
+#[cfg(test)]
+fn arb_value() -> impl proptest::strategy::Strategy<Value = Value> {
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::Int),
+        any::<f64>()
+            .prop_filter("finite", |x| x.is_finite())
+            .prop_map(Value::Float),
+        ".*".prop_map(|x| Value::String(x.into())),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(|x| Value::List(x.into())),
+            prop::collection::hash_map(".*", inner, 0..8).prop_map(|x| {
+                let hm: HashMap<_, _> = x.into_iter().map(|(k, v)| (k.into(), v)).collect();
+                Value::HashMap(Rc::new(hm))
+            }),
+        ]
+    })
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn binary_round_trip(value in arb_value()) {
+        let bytes = to_bytes(&value).unwrap();
+        proptest::prop_assert_eq!(from_bytes(&bytes).unwrap(), value);
+    }
+}
+
+#[test]
+fn binary_errors() {
+    let value = crate::parse_string(r#"{a: [1, "x", null, true]}"#).unwrap();
+    let bytes = value.to_bytes().unwrap();
+    assert_eq!(Value::from_bytes(&bytes).unwrap(), value);
+    assert!(Value::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Value::from_bytes(&[]).is_err());
+    let mut other_version = bytes.clone();
+    other_version[0] = FORMAT_VERSION + 1;
+    assert!(Value::from_bytes(&other_version).is_err());
+    assert!(crate::parse_string("{f: (x) => x}")
+        .unwrap()
+        .to_bytes()
+        .is_err());
+}
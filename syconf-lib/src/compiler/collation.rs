@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::compiler::{Error, Value};
+
+/// The string order of `sort` and `sort_by`, by default strings are compared by their code points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Collation {
+    /// Compares letters first without accents and case, then the accents, then the case with lower case first,
+    /// so that e.g. `"Ärzte"` comes before `"Zebra"`. This is a small subset of the Unicode collation algorithm
+    /// that handles characters decomposing into a base character and combining marks.
+    pub unicode: bool,
+    /// Strings that differ only in case are equal and keep their order.
+    pub case_insensitive: bool,
+    /// Compares runs of ASCII digits by their numeric value, e.g. `"host2"` comes before `"host10"`.
+    pub numeric: bool,
+}
+
+impl Collation {
+    /// Reads options given as a hashmap in a config, e.g. `{collate: "unicode", numeric: true}`.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let mut collation = Self::default();
+        for (key, val) in value.as_hashmap()? {
+            match (key.as_ref(), val) {
+                ("collate", Value::String(x)) if x.as_ref() == "unicode" => {
+                    collation.unicode = true
+                }
+                ("collate", Value::String(x)) if x.as_ref() == "codepoint" => {
+                    collation.unicode = false
+                }
+                ("case_insensitive", Value::Bool(x)) => collation.case_insensitive = *x,
+                ("numeric", Value::Bool(x)) => collation.numeric = *x,
+                _ => {
+                    return Err(
+                        format!("Unsupported sort option {}: {}", key, val.summary()).into(),
+                    )
+                }
+            }
+        }
+        Ok(collation)
+    }
+
+    /// Strings are compared by the collation, other values by their natural order, which fails for mixed types.
+    pub fn compare_values(&self, a: &Value, b: &Value) -> Result<Ordering, Error> {
+        match (a, b) {
+            (Value::String(a), Value::String(b)) => Ok(self.compare(a, b)),
+            _ => a.partial_cmp(b).ok_or_else(|| {
+                format!("Cannot compare {} and {}", a.summary(), b.summary()).into()
+            }),
+        }
+    }
+
+    /// Sorts the values of `(key, value)` pairs by their keys, stable. Fails before sorting unless all keys can be compared
+    /// with each other, i.e. they are all numbers, all strings, or all bools, so the order of the comparisons does not matter.
+    pub fn sort_keyed(&self, keyed: Vec<(Value, Value)>) -> Result<Vec<Value>, Error> {
+        if let Some((first, _)) = keyed.first() {
+            for (key, _) in &keyed[1..] {
+                self.compare_values(first, key)?;
+            }
+        }
+        let sorted = try_sort_by(keyed, |(a, _), (b, _)| self.compare_values(a, b))?;
+        Ok(sorted.into_iter().map(|(_, x)| x).collect())
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        let levels: &[fn(&str) -> String] = match (self.unicode, self.case_insensitive) {
+            (true, true) => &[primary, secondary],
+            (true, false) => &[primary, secondary, tertiary],
+            (false, true) => &[str::to_lowercase],
+            (false, false) => &[str::to_string],
+        };
+        for level in levels {
+            let ord = self.compare_runs(&level(a), &level(b));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        if self.case_insensitive {
+            Ordering::Equal
+        } else {
+            // E.g. numerically equal `"01"` and `"1"` still get a stable order.
+            a.cmp(b)
+        }
+    }
+
+    fn compare_runs(&self, a: &str, b: &str) -> Ordering {
+        if !self.numeric {
+            return a.cmp(b);
+        }
+        let (mut a, mut b) = (a, b);
+        loop {
+            match (next_run(a), next_run(b)) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some((x, rest_a)), Some((y, rest_b))) => {
+                    let ord = if is_digits(x) && is_digits(y) {
+                        let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                        x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                    } else {
+                        x.cmp(y)
+                    };
+                    if ord != Ordering::Equal {
+                        return ord;
+                    }
+                    a = rest_a;
+                    b = rest_b;
+                }
+            }
+        }
+    }
+}
+
+/// A stable merge sort that stops at the first error of `compare`. Unlike `slice::sort_by`, it does not panic
+/// if `compare` is not a total order, e.g. a comparator function of a config, the order is unspecified then.
+pub fn try_sort_by<T>(
+    mut items: Vec<T>,
+    mut compare: impl FnMut(&T, &T) -> Result<Ordering, Error>,
+) -> Result<Vec<T>, Error> {
+    fn merge_sort<T>(
+        mut items: Vec<T>,
+        compare: &mut impl FnMut(&T, &T) -> Result<Ordering, Error>,
+    ) -> Result<Vec<T>, Error> {
+        if items.len() <= 1 {
+            return Ok(items);
+        }
+        let right = items.split_off(items.len() / 2);
+        let mut left = merge_sort(items, compare)?.into_iter().peekable();
+        let mut right = merge_sort(right, compare)?.into_iter().peekable();
+        let mut out = Vec::with_capacity(left.len() + right.len());
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            // Only a strictly smaller element of the right half goes first, which keeps the sort stable.
+            let next = match compare(b, a)? {
+                Ordering::Less => right.next(),
+                _ => left.next(),
+            };
+            out.extend(next);
+        }
+        out.extend(left);
+        out.extend(right);
+        Ok(out)
+    }
+    if items.len() > 1 {
+        items = merge_sort(items, &mut compare)?;
+    }
+    Ok(items)
+}
+
+#[test]
+fn try_sort_by_is_stable_and_fallible() {
+    let items: Vec<(i32, usize)> = [3, 1, 2, 1, 3, 0].iter().copied().zip(0..).collect();
+    let sorted = try_sort_by(items.clone(), |a, b| Ok(a.0.cmp(&b.0))).unwrap();
+    assert_eq!(sorted, vec![(0, 5), (1, 1), (1, 3), (2, 2), (3, 0), (3, 4)]);
+    let err = try_sort_by(items, |a, b| match a.0 + b.0 {
+        4 => Err("four".into()),
+        _ => Ok(a.0.cmp(&b.0)),
+    })
+    .unwrap_err();
+    assert_eq!(err.message, "four");
+    // An inconsistent order gives some permutation instead of a panic.
+    let sorted = try_sort_by((0..500).collect(), |a: &i64, b| {
+        Ok(if *a > b * 2 {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        })
+    })
+    .unwrap();
+    assert_eq!(sorted.len(), 500);
+}
+
+/// Splits off the leading run of either ASCII digits or other characters.
+fn next_run(s: &str) -> Option<(&str, &str)> {
+    let first = s.chars().next()?;
+    let end = s
+        .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+        .unwrap_or(s.len());
+    Some(s.split_at(end))
+}
+
+fn is_digits(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn primary(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn secondary(s: &str) -> String {
+    s.nfd().collect::<String>().to_lowercase()
+}
+
+/// Swaps the case, so that lower case comes first.
+fn tertiary(s: &str) -> String {
+    s.nfd()
+        .flat_map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().collect::<Vec<char>>()
+            } else {
+                c.to_lowercase().collect()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn collation_orderings() {
+    fn sorted<'a>(items: &[&'a str], collation: Collation) -> Vec<&'a str> {
+        let mut items = items.to_vec();
+        items.sort_by(|a, b| collation.compare(a, b));
+        items
+    }
+    let unicode = Collation {
+        unicode: true,
+        ..Default::default()
+    };
+    let names = [
+        "Zebra", "zebra", "Ärzte", "arzt", "Arzt", "École", "ecole", "eclair",
+    ];
+    assert_eq!(
+        sorted(&names, Collation::default()),
+        ["Arzt", "Zebra", "arzt", "eclair", "ecole", "zebra", "Ärzte", "École"]
+    );
+    assert_eq!(
+        sorted(&names, unicode),
+        ["arzt", "Arzt", "Ärzte", "eclair", "ecole", "École", "zebra", "Zebra"]
+    );
+    let hosts = ["host10", "host2", "Host1", "host02", "host", "10", "9"];
+    assert_eq!(
+        sorted(
+            &hosts,
+            Collation {
+                numeric: true,
+                ..Default::default()
+            }
+        ),
+        ["9", "10", "Host1", "host", "host02", "host2", "host10"]
+    );
+    assert_eq!(
+        sorted(
+            &hosts,
+            Collation {
+                numeric: true,
+                case_insensitive: true,
+                ..Default::default()
+            }
+        ),
+        ["9", "10", "host", "Host1", "host2", "host02", "host10"]
+    );
+    assert_eq!(
+        sorted(
+            &["b", "B", "a", "A", "b"],
+            Collation {
+                case_insensitive: true,
+                ..Default::default()
+            }
+        ),
+        ["a", "A", "b", "B", "b"]
+    );
+}
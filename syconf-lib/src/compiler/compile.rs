@@ -1,21 +1,72 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 
 use crate::compiler::context::Context;
-use crate::compiler::functions::FunctionSig;
+use crate::compiler::deprecation::{self, Callable};
+use crate::compiler::error::{did_you_mean, similar_names};
+use crate::compiler::functions::{self, FunctionSig};
+use crate::compiler::import_graph::ImportEdge;
 use crate::compiler::node::{CodeNode, FunctionDefinition, HmEntry, NodeContent};
-use crate::compiler::value::{Func, Value};
-use crate::compiler::{methods, operators, Error, ErrorWithLocation, Location, Source};
+use crate::compiler::value::{is_identifier, Func, Value};
+use crate::compiler::{methods, operators, Error, ErrorWithLocation, FileLoader, Location, Source};
+use crate::depth::DepthGuard;
 use crate::parser::string::ConfigString;
 use crate::parser::*;
 use crate::parser::{Expr, ExprWithLocation};
 
+/// Maximum depth of nested expressions, e.g. long chains of method calls.
+const MAX_COMPILE_DEPTH: usize = 1000;
+
+thread_local! {
+    static COMPILE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
 pub struct Compiler {
     source: Source,
+    /// Sources of all imported files, shared with the compilers of the imports.
+    imports: Rc<RefCell<Vec<Source>>>,
+    /// Variables bound by `import "file.sy" with {...}` when compiling the imported file.
+    injected: Vec<String>,
+    /// Reads the imported files.
+    loader: Rc<dyn FileLoader>,
+    /// Normalized paths of the files being compiled, from the entry point to this file, to detect import cycles.
+    stack: Vec<PathBuf>,
+    /// Compiled imports by their normalized paths and injected variables, shared with the compilers of the imports,
+    /// so that a file imported by many files is read and compiled once.
+    compiled: Rc<RefCell<HashMap<ImportKey, CodeNode>>>,
+    /// Every import statement compiled so far, shared with the compilers of the imports.
+    edges: Rc<RefCell<Vec<ImportEdge>>>,
 }
 
+/// The normalized path of an imported file and the names of the variables injected by the import.
+type ImportKey = (PathBuf, Vec<String>);
+
 impl Compiler {
-    pub fn new(source: Source) -> Self {
-        Self { source }
+    pub fn new(source: Source, loader: Rc<dyn FileLoader>) -> Self {
+        let stack = if source.is_file() {
+            vec![normalize(source.file())]
+        } else {
+            Vec::new()
+        };
+        Self {
+            source,
+            imports: Default::default(),
+            injected: Vec::new(),
+            loader,
+            stack,
+            compiled: Default::default(),
+            edges: Default::default(),
+        }
+    }
+
+    pub fn imports(&self) -> Vec<Source> {
+        self.imports.borrow().clone()
+    }
+
+    pub fn import_edges(&self) -> Vec<ImportEdge> {
+        self.edges.borrow().clone()
     }
 
     fn create_location(&self, location: &Span) -> Location {
@@ -26,17 +77,30 @@ impl Compiler {
     }
 
     pub fn compile(&self, ctx: &Context, expr: &ExprWithLocation) -> Result<CodeNode, Error> {
+        let _guard = DepthGuard::enter(&COMPILE_DEPTH, MAX_COMPILE_DEPTH).ok_or_else(|| {
+            ErrorWithLocation {
+                location: Some(self.create_location(&expr.location)),
+                message: "Expression is nested too deeply".to_string(),
+                hint: None,
+            }
+        })?;
         let cell = match &expr.inner {
             Expr::Value(val) => self.config_value(ctx, val)?,
             Expr::Block(block) => return self.block(ctx, block),
             Expr::Identifier(id) => self.identifier(ctx, id, &expr.location)?,
             Expr::FuncDefinition(fd) => self.func_definition(ctx, fd)?,
             Expr::Math(op) => self.math_op(ctx, op)?,
+            Expr::Negation(x) => NodeContent::FunctionCall {
+                name: "-".to_string(),
+                function: builtin_func_node(&operators::negate),
+                arguments: Some(vec![self.compile(ctx, x)?]),
+            },
             Expr::Comparison(cmp) => self.comparison(ctx, cmp)?,
             Expr::Conditional(cond) => self.conditional(ctx, cond)?,
             Expr::Logical(logical) => self.logical(ctx, logical)?,
             Expr::Suffix(suffix) => self.suffix_operator(ctx, suffix)?,
-            Expr::Import(path) => return self.import(path),
+            Expr::Import(import) => return self.import(ctx, import, &expr.location),
+            Expr::With(with) => return self.with_expr(ctx, with, &expr.location),
         };
         Ok(CodeNode::new(
             cell,
@@ -49,8 +113,19 @@ impl Compiler {
         debug!(?suffix, "suffix_op");
         let args = match &suffix.operator {
             SuffixOperator::FunctionApplication(args) => {
+                if let Some((branches, default)) = cond_literal(ctx, &suffix.base, args) {
+                    return Ok(NodeContent::Cond {
+                        branches: branches
+                            .into_iter()
+                            .map(|(test, value)| {
+                                Ok((self.compile(ctx, test)?, self.compile(ctx, value)?))
+                            })
+                            .collect::<Result<Vec<_>, Error>>()?,
+                        default: self.compile(ctx, default)?,
+                    });
+                }
                 return Ok(NodeContent::FunctionCall {
-                    name: ".apply".to_string(),
+                    name: function_name(&suffix.base),
                     function: base,
                     arguments: Some(args.iter().map(|x| self.compile(ctx, x)).collect::<Result<
                         Vec<CodeNode>,
@@ -65,47 +140,38 @@ impl Compiler {
             ],
             SuffixOperator::Index(ix) => vec![base, self.compile(ctx, ix)?],
         };
+        let name = match &suffix.operator {
+            SuffixOperator::DotField(id) => format!(".{}", id),
+            _ => "[]".to_string(),
+        };
         Ok(NodeContent::FunctionCall {
-            name: ".get".to_string(),
+            name,
             function: builtin_func_node(&methods::index),
             arguments: Some(args),
         })
     }
 
     fn logical(&self, ctx: &Context, logical: &Logical) -> Result<NodeContent, Error> {
-        let (func, name, args): (&'static FunctionSig, &str, Vec<CodeNode>) = match logical {
-            Logical::And(expr1, expr2) => (
-                &operators::and,
-                "and",
-                vec![self.compile(ctx, &expr1)?, self.compile(ctx, &expr2)?],
-            ),
-            Logical::Or(expr1, expr2) => (
-                &operators::or,
-                "or",
-                vec![self.compile(ctx, &expr1)?, self.compile(ctx, &expr2)?],
-            ),
-            Logical::Not(expr1) => (&operators::not, "not", vec![self.compile(ctx, &expr1)?]),
-        };
-        Ok(NodeContent::FunctionCall {
-            name: name.to_string(),
-            function: builtin_func_node(func),
-            arguments: Some(args),
+        Ok(match logical {
+            Logical::And(expr1, expr2) => {
+                NodeContent::And(self.compile(ctx, expr1)?, self.compile(ctx, expr2)?)
+            }
+            Logical::Or(expr1, expr2) => {
+                NodeContent::Or(self.compile(ctx, expr1)?, self.compile(ctx, expr2)?)
+            }
+            Logical::Not(expr1) => NodeContent::FunctionCall {
+                name: "not".to_string(),
+                function: builtin_func_node(&operators::not),
+                arguments: Some(vec![self.compile(ctx, expr1)?]),
+            },
         })
     }
 
     fn conditional(&self, ctx: &Context, cond: &Conditional) -> Result<NodeContent, Error> {
-        let args = vec![
-            self.compile(ctx, &cond.condition)?,
-            self.compile(ctx, &cond.then_branch)?,
-            self.compile(ctx, &cond.else_branch)?,
-        ];
-        Ok(NodeContent::FunctionCall {
-            name: "if".to_string(),
-            function: CodeNode::new(
-                NodeContent::Resolved(Value::Func(Func::new_builtin(&operators::conditional))),
-                None,
-            ),
-            arguments: Some(args),
+        Ok(NodeContent::If {
+            condition: self.compile(ctx, &cond.condition)?,
+            then_branch: self.compile(ctx, &cond.then_branch)?,
+            else_branch: self.compile(ctx, &cond.else_branch)?,
         })
     }
 
@@ -140,40 +206,141 @@ impl Compiler {
 
     fn config_value(&self, ctx: &Context, val: &ConfigValue) -> Result<NodeContent, Error> {
         match val {
+            ConfigValue::Null => Ok(NodeContent::Resolved(Value::Null)),
             ConfigValue::Bool(x) => Ok(NodeContent::Resolved(Value::Bool(*x))),
             ConfigValue::Int(v) => Ok(NodeContent::Resolved(Value::Int(*v))),
+            ConfigValue::Float(v) => Ok(NodeContent::Resolved(Value::Float(
+                v.parse()
+                    .expect("float literals are validated by the parser"),
+            ))),
             ConfigValue::String(s) => self.string(ctx, s),
-            ConfigValue::HashMap(hm) => hm
-                .iter()
-                .map(|HashMapEntry { key, value }| {
-                    Ok(HmEntry {
-                        key: self.compile(ctx, key)?,
-                        value: self.compile(ctx, value)?,
-                    })
-                })
-                .collect::<Result<Vec<HmEntry>, Error>>()
-                .map(NodeContent::HashMap),
-            ConfigValue::List(list) => list
-                .iter()
-                .map(|x| self.compile(ctx, x))
-                .collect::<Result<Vec<CodeNode>, Error>>()
-                .map(NodeContent::List),
+            ConfigValue::HashMap(hm) => self.hashmap(ctx, hm),
+            ConfigValue::List(list) => self.list(ctx, list),
         }
     }
 
+    /// A list with spreads is compiled into a `concat` of its parts,
+    /// where the consecutive single elements form list literals.
+    fn list(&self, ctx: &Context, list: &[ListElement]) -> Result<NodeContent, Error> {
+        let mut parts = Vec::new();
+        let mut singles = Vec::new();
+        for element in list {
+            match element {
+                ListElement::Single(x) => singles.push(self.compile(ctx, x)?),
+                ListElement::Spread(x) => {
+                    if !singles.is_empty() {
+                        parts.push(CodeNode::new(
+                            NodeContent::List(std::mem::take(&mut singles)),
+                            None,
+                        ));
+                    }
+                    parts.push(self.spread(ctx, x, &functions::spread_list)?);
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Ok(NodeContent::List(singles));
+        }
+        if !singles.is_empty() {
+            parts.push(CodeNode::new(NodeContent::List(singles), None));
+        }
+        Ok(NodeContent::FunctionCall {
+            name: "concat".to_string(),
+            function: builtin_func_node(&functions::concat),
+            arguments: Some(parts),
+        })
+    }
+
+    /// A hashmap with spreads is compiled into a `merge` of its parts, so later keys override earlier ones.
+    fn hashmap(&self, ctx: &Context, hm: &[HashMapElement]) -> Result<NodeContent, Error> {
+        let mut parts = Vec::new();
+        let mut entries = Vec::new();
+        for element in hm {
+            match element {
+                HashMapElement::Entry(HashMapEntry { key, value }) => entries.push(HmEntry {
+                    key: self.compile(ctx, key)?,
+                    value: self.compile(ctx, value)?,
+                }),
+                HashMapElement::Spread(x) => {
+                    if !entries.is_empty() {
+                        parts.push(CodeNode::new(
+                            NodeContent::HashMap(std::mem::take(&mut entries)),
+                            None,
+                        ));
+                    }
+                    parts.push(self.spread(ctx, x, &functions::spread_hashmap)?);
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Ok(NodeContent::HashMap(entries));
+        }
+        if !entries.is_empty() {
+            parts.push(CodeNode::new(NodeContent::HashMap(entries), None));
+        }
+        Ok(NodeContent::FunctionCall {
+            name: "merge".to_string(),
+            function: builtin_func_node(&functions::merge),
+            arguments: Some(parts),
+        })
+    }
+
+    /// Checks the type of the spread value, so that a wrong one fails at the spread.
+    fn spread(
+        &self,
+        ctx: &Context,
+        expr: &ExprWithLocation,
+        check: &'static FunctionSig,
+    ) -> Result<CodeNode, Error> {
+        Ok(CodeNode::new(
+            NodeContent::FunctionCall {
+                name: "...".to_string(),
+                function: builtin_func_node(check),
+                arguments: Some(vec![self.compile(ctx, expr)?]),
+            },
+            Some(self.create_location(&expr.location)),
+        ))
+    }
+
+    /// Adjacent raw parts and escape sequences are joined into a single string.
     fn string(&self, ctx: &Context, cs: &[ConfigString]) -> Result<NodeContent, Error> {
-        let kids = cs
-            .iter()
-            .map(|x| match x {
-                ConfigString::Raw(s) => Ok(CodeNode::new(
-                    NodeContent::Resolved(Value::String((*s).into())),
-                    None,
-                )),
-                ConfigString::Interpolated(a) => self.compile(ctx, a),
-            })
-            .collect::<Result<Vec<CodeNode>, Error>>()?;
+        let mut kids = Vec::new();
+        let mut raw: Option<String> = None;
+        for x in cs {
+            match x {
+                ConfigString::Raw(s) => raw.get_or_insert_with(String::new).push_str(s),
+                ConfigString::Escaped(c) => raw.get_or_insert_with(String::new).push(*c),
+                ConfigString::Interpolated(a) => {
+                    if let Some(s) = raw.take() {
+                        kids.push(CodeNode::new(
+                            NodeContent::Resolved(Value::String(s.into())),
+                            None,
+                        ));
+                    }
+                    kids.push(CodeNode::new(
+                        NodeContent::FunctionCall {
+                            name: "${}".to_string(),
+                            function: builtin_func_node(&functions::interpolate),
+                            arguments: Some(vec![self.compile(ctx, a)?]),
+                        },
+                        Some(self.create_location(&a.location)),
+                    ));
+                }
+            }
+        }
+        if kids.is_empty() {
+            return Ok(NodeContent::Resolved(Value::String(
+                raw.unwrap_or_default().into(),
+            )));
+        }
+        if let Some(s) = raw {
+            kids.push(CodeNode::new(
+                NodeContent::Resolved(Value::String(s.into())),
+                None,
+            ));
+        }
         Ok(NodeContent::FunctionCall {
-            function: builtin_func_node(&super::functions::concat_strings),
+            function: builtin_func_node(&functions::concat_strings),
             arguments: Some(kids),
             name: "concat".to_string(),
         })
@@ -184,21 +351,37 @@ impl Compiler {
         debug!(?block.local_assignments, "blocqk");
         for Assignment(id, ex) in &block.local_assignments {
             debug!(?id, ?ex, "assignment1");
-            let node = self.compile(&ns, &ex)?;
+            let node = self.compile(&ns, ex)?;
             debug!(?id, ?node, "assignment2: binding {}", id);
             ns.bind(id.to_string(), node);
         }
         self.compile(&ns, &block.expression)
     }
 
+    /// Binds the name to the shallowly merged hashmap in a new scope, the overrides are evaluated in the outer scope.
+    fn with_expr(&self, ctx: &Context, with: &WithExpr, loc: &Span) -> Result<CodeNode, Error> {
+        let original = CodeNode::new(
+            self.identifier(ctx, with.name, &with.name_location)?,
+            Some(self.create_location(&with.name_location)),
+        );
+        let merged = CodeNode::new(
+            NodeContent::FunctionCall {
+                name: "with".to_string(),
+                function: builtin_func_node(&functions::merge),
+                arguments: Some(vec![original, self.compile(ctx, &with.overrides)?]),
+            },
+            Some(self.create_location(loc)),
+        );
+        let ns = ctx.new_child();
+        ns.bind(with.name.to_string(), merged);
+        self.compile(&ns, &with.body)
+    }
+
     fn identifier(&self, ctx: &Context, id: &str, loc: &Span) -> Result<NodeContent, Error> {
         let func_node = ctx
             .get_value(id)
-            .or_else(|| super::functions::lookup(id).map(|func| builtin_func_node(func)))
-            .ok_or_else(|| ErrorWithLocation {
-                location: Some(self.create_location(loc)),
-                message: format!("Variable '{}' is not defined", id),
-            })?;
+            .or_else(|| super::functions::lookup(id).map(|func| self.builtin(id, func, loc)))
+            .ok_or_else(|| self.undefined_variable(ctx, id, loc))?;
         Ok(NodeContent::FunctionCall {
             name: id.to_string(),
             function: func_node,
@@ -206,6 +389,48 @@ impl Compiler {
         })
     }
 
+    /// Deprecated builtins are wrapped to report their use at `loc`.
+    fn builtin(&self, id: &str, func: &'static FunctionSig, loc: &Span) -> CodeNode {
+        let node = builtin_func_node(func);
+        match deprecation::lookup(Callable::Function, id) {
+            Some(deprecation) => CodeNode::new(
+                NodeContent::Deprecated { deprecation, node },
+                Some(self.create_location(loc)),
+            ),
+            None => node,
+        }
+    }
+
+    /// Suggests similar names of variables in scope and of builtin functions.
+    fn undefined_variable(&self, ctx: &Context, id: &str, loc: &Span) -> ErrorWithLocation {
+        let names = ctx.names();
+        let candidates = names
+            .iter()
+            .map(String::as_str)
+            .chain(super::functions::NAMES.iter().copied())
+            .chain(self.injected.iter().map(String::as_str));
+        let mut hint = did_you_mean(&similar_names(id, candidates));
+        if hint.is_none() && !self.injected.is_empty() {
+            hint = Some(format!(
+                "pass it to the import, e.g. 'import \"./file.sy\" with {{{}: ...}}'",
+                id
+            ));
+        }
+        ErrorWithLocation {
+            location: Some(self.create_location(loc)),
+            message: if self.injected.is_empty() {
+                format!("Variable '{}' is not defined", id)
+            } else {
+                format!(
+                    "Variable '{}' is not defined, variables injected by the import: {}",
+                    id,
+                    self.injected.join(", ")
+                )
+            },
+            hint,
+        }
+    }
+
     fn func_definition(&self, ctx: &Context, fd: &FuncDefinition) -> Result<NodeContent, Error> {
         debug!(?fd.arguments, "function definition");
         let ns = ctx.new_child();
@@ -225,24 +450,254 @@ impl Compiler {
         )))
     }
 
-    fn import(&self, file_name: &str) -> Result<CodeNode, Error> {
-        let src = Source::from_file(
-            self.source
-                .file()
-                .parent()
-                .unwrap()
-                .join(file_name)
-                .as_path(),
-        )?;
-        let (_, expr) =
-            parse_unit(Span::new(src.as_str())).map_err(|e| anyhow!("Cannot parse {}", e))?;
-        Compiler::new(src.clone()).compile(&Context::empty(), &expr)
+    /// An import with bindings is compiled like a call of a function,
+    /// whose arguments are the bound variables and whose body is the imported file.
+    /// Compiles the imported file with the `names` bound as the arguments of the import.
+    fn compile_import(&self, path: &Path, names: &[String], loc: &Span) -> Result<CodeNode, Error> {
+        let src =
+            Source::from_loader(path, self.loader.as_ref()).map_err(|e| ErrorWithLocation {
+                location: e.location.or_else(|| Some(self.create_location(loc))),
+                message: e.message,
+                hint: e.hint,
+            })?;
+        let expr = super::parse(&src)?;
+        self.imports.borrow_mut().push(src.clone());
+
+        let root = Context::empty();
+        for name in names {
+            root.bind(
+                name.clone(),
+                CodeNode::new(NodeContent::FunctionInputArgument(name.clone()), None),
+            );
+        }
+        let mut stack = self.stack.clone();
+        stack.push(path.to_path_buf());
+        Compiler {
+            source: src.clone(),
+            imports: self.imports.clone(),
+            injected: names.to_vec(),
+            loader: self.loader.clone(),
+            stack,
+            compiled: self.compiled.clone(),
+            edges: self.edges.clone(),
+        }
+        .compile(&root, &expr)
+    }
+
+    fn import(&self, ctx: &Context, import: &Import, loc: &Span) -> Result<CodeNode, Error> {
+        let path = match self.source.base_dir() {
+            _ if Path::new(import.path).is_absolute() => normalize(Path::new(import.path)),
+            Some(dir) => normalize(&dir.join(import.path)),
+            None => {
+                return Err(ErrorWithLocation {
+                    location: Some(self.create_location(loc)),
+                    message: "Imports are not allowed when parsing from a string".to_string(),
+                    hint: Some(
+                        "use an absolute path, or pass a base directory for relative imports, \
+                         e.g. with 'parse_string_with_base'"
+                            .to_string(),
+                    ),
+                })
+            }
+        };
+        if let Some(ix) = self.stack.iter().position(|x| *x == path) {
+            let cycle: Vec<String> = self.stack[ix..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|x| x.display().to_string())
+                .collect();
+            return Err(ErrorWithLocation {
+                location: Some(self.create_location(loc)),
+                message: format!("Import cycle: {}", cycle.join(" -> ")),
+                hint: None,
+            });
+        }
+
+        self.edges.borrow_mut().push(ImportEdge {
+            from: normalize(self.source.file()),
+            to: path.clone(),
+            location: self.create_location(loc),
+        });
+
+        let mut names = Vec::with_capacity(import.bindings.len());
+        let mut arguments = Vec::with_capacity(import.bindings.len());
+        for element in &import.bindings {
+            let (key, value) = match element {
+                HashMapElement::Entry(HashMapEntry { key, value }) => (key, value),
+                HashMapElement::Spread(x) => {
+                    return Err(ErrorWithLocation {
+                        location: Some(self.create_location(&x.location)),
+                        message: "Import bindings cannot be spread".to_string(),
+                        hint: None,
+                    })
+                }
+            };
+            let name = binding_name(key).ok_or_else(|| ErrorWithLocation {
+                location: Some(self.create_location(&key.location)),
+                message: "Import bindings must have identifier keys".to_string(),
+                hint: None,
+            })?;
+            names.push(name.to_string());
+            arguments.push(self.compile(ctx, value)?);
+        }
+
+        let key = (path, names);
+        let cached = self.compiled.borrow().get(&key).cloned();
+        let node = match cached {
+            Some(node) => node,
+            None => {
+                let node = self.compile_import(&key.0, &key.1, loc)?;
+                self.compiled.borrow_mut().insert(key.clone(), node.clone());
+                node
+            }
+        };
+        let names = key.1;
+        if names.is_empty() {
+            return Ok(node);
+        }
+        Ok(CodeNode::new(
+            NodeContent::FunctionCall {
+                name: format!("import {:?}", import.path),
+                function: CodeNode::new(
+                    NodeContent::FunctionDefinition(Rc::new(FunctionDefinition {
+                        argument_names: Some(names),
+                        node,
+                    })),
+                    None,
+                ),
+                arguments: Some(arguments),
+            },
+            Some(self.create_location(loc)),
+        ))
     }
 }
 
-fn builtin_func_node(func: &'static (dyn Fn(&[Value]) -> Result<Value, Error>)) -> CodeNode {
+type CondBranches<'e, 'a> = Vec<(&'e ExprWithLocation<'a>, &'e ExprWithLocation<'a>)>;
+
+/// Matches a call of the `cond` builtin with a literal list of `[test, value]` pairs,
+/// that is compiled into a lazily evaluated `NodeContent::Cond`.
+fn cond_literal<'e, 'a>(
+    ctx: &Context,
+    base: &ExprWithLocation,
+    args: &'e [ExprWithLocation<'a>],
+) -> Option<(CondBranches<'e, 'a>, &'e ExprWithLocation<'a>)> {
+    if base.inner != Expr::Identifier("cond") || ctx.get_value("cond").is_some() {
+        return None;
+    }
+    let (list, default) = match args {
+        [ExprWithLocation {
+            inner: Expr::Value(ConfigValue::List(list)),
+            ..
+        }, default] => (list, default),
+        _ => return None,
+    };
+    let branches = list
+        .iter()
+        .map(|x| match x {
+            ListElement::Single(ExprWithLocation {
+                inner: Expr::Value(ConfigValue::List(pair)),
+                ..
+            }) => match pair.as_slice() {
+                [ListElement::Single(test), ListElement::Single(value)] => Some((test, value)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((branches, default))
+}
+
+/// Resolves `.` and `..` without touching the file system, so that it works for any `FileLoader`.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn binding_name<'a>(key: &ExprWithLocation<'a>) -> Option<&'a str> {
+    match &key.inner {
+        Expr::Value(ConfigValue::String(s)) => match s.as_slice() {
+            [ConfigString::Raw(name)] if is_identifier(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Describes the called function in the explain output.
+fn function_name(expr: &ExprWithLocation) -> String {
+    match &expr.inner {
+        Expr::Identifier(id) => id.to_string(),
+        Expr::Suffix(suffix) => match &suffix.operator {
+            SuffixOperator::DotField(id) => format!(".{}", id),
+            _ => "<function>".to_string(),
+        },
+        _ => "<function>".to_string(),
+    }
+}
+
+fn builtin_func_node(func: &'static dyn Fn(&[Value]) -> Result<Value, Error>) -> CodeNode {
     CodeNode::new(
         NodeContent::Resolved(Value::Func(Func::new_builtin(func))),
         None,
     )
 }
+
+#[test]
+fn parameterized_import() {
+    use std::fs;
+    let dir = std::env::temp_dir().join(format!("syconf-import-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("template.sy"),
+        r#"{name: name, url: "http://${name}:${port}", port_of: (x) => port}"#,
+    )
+    .unwrap();
+    fs::write(dir.join("lambda.sy"), r#"(name) => "svc-${name}""#).unwrap();
+    fs::write(dir.join("broken.sy"), "nme").unwrap();
+    let main = dir.join("main.sy");
+    let parse = |code: &str| {
+        fs::write(&main, code).unwrap();
+        crate::parse_file(main.to_str().unwrap())
+    };
+
+    let out = parse(
+        r#"
+    let port = 1
+    let api = import "template.sy" with {name: "api", port: 8080}
+    in
+    [
+        api.url,
+        (import "template.sy" with {name: "web", port: port + 79}).url,
+        api.port_of(0),
+        (import "lambda.sy")("a"),
+        import "lambda.sy"("b"),
+    ]
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        out,
+        crate::parse_string(r#"["http://api:8080", "http://web:80", 8080, "svc-a", "svc-b"]"#)
+            .unwrap()
+    );
+
+    let err = parse(r#"import "broken.sy" with {name: "x", port: 1}"#).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Variable 'nme' is not defined, variables injected by the import: name, port"
+    );
+    assert_eq!(err.hint.as_deref(), Some("did you mean 'name'?"));
+    assert!(parse(r#"import "template.sy" with {"na me": 1}"#).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,178 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::compiler::context::Context;
+use crate::compiler::evaluation::{self, EvalOptions, EvalReport, IoCache};
+use crate::compiler::import_graph::ImportGraph;
+use crate::compiler::node::CodeNode;
+use crate::compiler::replay::Recording;
+use crate::compiler::Dependencies;
+use crate::compiler::{Error, ErrorWithLocation, Source, Value};
+
+/// A compiled config that can be evaluated repeatedly without parsing and compiling its sources again.
+#[derive(Debug, Clone)]
+pub struct Compiled {
+    node: CodeNode,
+    files: Vec<FileState>,
+    graph: ImportGraph,
+    /// The root and the imports, kept for `EvalOptions::record`.
+    sources: Vec<Source>,
+}
+
+/// The state of a source file at compile time.
+#[derive(Debug, Clone)]
+struct FileState {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl Compiled {
+    pub(crate) fn new(node: CodeNode, sources: &[Source], graph: ImportGraph) -> Self {
+        let files = sources
+            .iter()
+            .filter(|src| src.is_file())
+            .map(|src| FileState {
+                path: src.file().clone(),
+                modified: modified(src.file()),
+                hash: hash(src.as_str()),
+            })
+            .collect();
+        Self {
+            node,
+            files,
+            graph,
+            sources: sources.to_vec(),
+        }
+    }
+
+    pub fn evaluate(&self) -> Result<Value, Error> {
+        self.evaluate_with_options(&EvalOptions::default())
+    }
+
+    pub fn evaluate_with_options(&self, options: &EvalOptions) -> Result<Value, Error> {
+        self.evaluate_with_report(options).map(|(value, _)| value)
+    }
+
+    /// Like `evaluate`, also returns the source files and everything read by `read_file` and `getenv`.
+    pub fn evaluate_with_dependencies(&self) -> Result<(Value, Dependencies), Error> {
+        let (value, mut deps) = evaluation::run(&EvalOptions::default(), || {
+            self.node.resolve(&Context::empty())
+        });
+        deps.files.extend(self.files.iter().map(|f| f.path.clone()));
+        Ok((value?, deps))
+    }
+
+    /// Like `evaluate_with_dependencies`, reuses and extends the cached results of `read_file` and `getenv`.
+    pub(crate) fn evaluate_with_cache(
+        &self,
+        options: &EvalOptions,
+        cache: &mut IoCache,
+    ) -> Result<(Value, Dependencies), Error> {
+        self.check_imports(options)?;
+        let (value, mut deps) =
+            evaluation::run_with_cache(options, cache, || self.node.resolve(&Context::empty()));
+        deps.files.extend(self.files.iter().map(|f| f.path.clone()));
+        Ok((value?, deps))
+    }
+
+    /// Like `evaluate_with_dependencies`, also returns the applied defaults if `track_defaults` is enabled.
+    pub fn evaluate_with_report(
+        &self,
+        options: &EvalOptions,
+    ) -> Result<(Value, EvalReport), Error> {
+        self.check_imports(options)?;
+        let (value, mut report) =
+            evaluation::run_with_report(options, &mut IoCache::default(), || {
+                self.node.resolve(&Context::empty())
+            });
+        report
+            .dependencies
+            .files
+            .extend(self.files.iter().map(|f| f.path.clone()));
+        let recorded = match &options.record {
+            Some(path) => Recording::new(&self.sources, &report, &options.record_redact)
+                .and_then(|x| x.write(path)),
+            None => Ok(()),
+        };
+        let value = value?;
+        recorded?;
+        Ok((value, report))
+    }
+
+    /// The files and the import statements between them, e.g. to find out which files are imported the most.
+    pub fn import_graph(&self) -> &ImportGraph {
+        &self.graph
+    }
+
+    /// Fails at the import of the first file over the `max_imports` limit.
+    fn check_imports(&self, options: &EvalOptions) -> Result<(), Error> {
+        let max_imports = match options.max_imports {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        match self.graph.exceeding_import(max_imports) {
+            Some(edge) => Err(ErrorWithLocation {
+                location: Some(edge.location.clone()),
+                message: format!(
+                    "{} files are imported, more than the limit of {}",
+                    self.graph.files.len() - 1,
+                    max_imports
+                ),
+                hint: None,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The root file and all imported files.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|f| f.path.as_path())
+    }
+
+    /// Returns true if any of the files changed since compilation and the config must be compiled again.
+    /// A file whose modification time changed but whose content is the same is not considered changed.
+    pub fn is_stale(&self) -> bool {
+        self.files.iter().any(|f| {
+            if f.modified.is_some() && modified(&f.path) == f.modified {
+                return false;
+            }
+            match fs::read_to_string(&f.path) {
+                Ok(content) => hash(&content) != f.hash,
+                Err(_) => true,
+            }
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn compiled_is_stale() {
+    let dir = std::env::temp_dir().join(format!("syconf-compiled-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("main.sy");
+    let imported = dir.join("imported.sy");
+    fs::write(&main, r#"{a: import "imported.sy"}"#).unwrap();
+    fs::write(&imported, "1").unwrap();
+
+    let compiled = crate::compile_file(main.to_str().unwrap()).unwrap();
+    assert_eq!(compiled.files().collect::<Vec<_>>(), vec![&main, &imported]);
+    assert_eq!(compiled.evaluate().unwrap(), compiled.evaluate().unwrap());
+    assert!(!compiled.is_stale());
+
+    fs::write(&imported, "2").unwrap();
+    assert!(compiled.is_stale());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -25,10 +25,20 @@ impl Context {
         let x = self.0.borrow();
         x.bindings
             .get(val)
-            .map(Clone::clone)
+            .cloned()
             .or_else(|| x.parent.as_ref().and_then(|p| p.get_value(val)))
     }
 
+    /// The names bound in this context and its parents, e.g. for suggestions.
+    pub fn names(&self) -> Vec<String> {
+        let x = self.0.borrow();
+        let mut names: Vec<String> = x.bindings.keys().cloned().collect();
+        if let Some(parent) = &x.parent {
+            names.extend(parent.names());
+        }
+        names
+    }
+
     pub fn new_child(&self) -> Self {
         Self(Rc::new(RefCell::new(ContextRef {
             bindings: HashMap::new(),
@@ -0,0 +1,375 @@
+use std::fmt;
+
+use serde::de::{self, IntoDeserializer, Visitor};
+
+use crate::compiler::value::ValueString;
+use crate::compiler::{Error, Value};
+
+/// Deserializes the value into a Rust type, e.g. a struct deriving `serde::Deserialize`.
+pub fn deserialize<'de, T: de::Deserialize<'de>>(value: &'de Value) -> Result<T, Error> {
+    T::deserialize(ValueDeserializer {
+        value,
+        path: String::new(),
+    })
+    .map_err(|e| format!("Cannot deserialize {}", e).into())
+}
+
+/// The message of the innermost failing value, with the key path to it, e.g. `'server.port'`.
+#[derive(Debug)]
+pub struct DeError {
+    path: Option<String>,
+    message: String,
+}
+
+impl DeError {
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.to_string());
+        }
+        self
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_deref() {
+            Some(path) if !path.is_empty() => write!(f, "'{}': {}", path, self.message),
+            _ => write!(f, "value: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            path: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+struct ValueDeserializer<'de> {
+    value: &'de Value,
+    path: String,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn child(&self, value: &'de Value, key: &str) -> Self {
+        let path = if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+        Self { value, path }
+    }
+
+    fn element(&self, value: &'de Value, ix: usize) -> Self {
+        Self {
+            value,
+            path: format!("{}[{}]", self.path, ix),
+        }
+    }
+
+    fn any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(x) => visitor.visit_bool(*x),
+            Value::Int(x) => visitor.visit_i64(*x),
+            Value::Float(x) => visitor.visit_f64(*x),
+            Value::String(x) => visitor.visit_borrowed_str(x),
+            Value::List(list) => visitor.visit_seq(SeqAccess {
+                de: &self,
+                iter: list.iter().enumerate(),
+            }),
+            Value::HashMap(hm) => {
+                let mut entries: Vec<(&ValueString, &Value)> = hm.iter().collect();
+                entries.sort_by_key(|(k, _)| *k);
+                visitor.visit_map(MapAccess {
+                    de: &self,
+                    entries: entries.into_iter(),
+                    value: None,
+                })
+            }
+            Value::Func(_) => Err(de::Error::custom("a function cannot be deserialized")),
+        }
+        .map_err(|e| e.at(&self.path))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => {
+                let path = self.path.clone();
+                visitor.visit_some(self).map_err(|e| e.at(&path))
+            }
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let path = self.path.clone();
+        visitor.visit_newtype_struct(self).map_err(|e| e.at(&path))
+    }
+
+    /// Unit variants are read from strings, other variants from hashmaps with a single key, e.g. `{tcp: 80}`.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match self.value {
+            Value::String(x) => visitor.visit_enum(x.as_ref().into_deserializer()),
+            Value::HashMap(hm) if hm.len() == 1 => {
+                let (variant, value) = hm.iter().next().unwrap();
+                let value = self.child(value, variant);
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            x => Err(de::Error::custom(format!(
+                "expected a string or a hashmap with a single key, got {}",
+                x.summary()
+            ))),
+        }
+        .map_err(|e| e.at(&self.path))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'a, 'de> {
+    de: &'a ValueDeserializer<'de>,
+    iter: std::iter::Enumerate<std::slice::Iter<'de, Value>>,
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        match self.iter.next() {
+            Some((ix, x)) => seed.deserialize(self.de.element(x, ix)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess<'a, 'de> {
+    de: &'a ValueDeserializer<'de>,
+    entries: std::vec::IntoIter<(&'de ValueString, &'de Value)>,
+    value: Option<(&'de ValueString, &'de Value)>,
+}
+
+impl<'a, 'de> de::MapAccess<'de> for MapAccess<'a, 'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        match self.entries.next() {
+            Some(entry) => {
+                self.value = Some(entry);
+                seed.deserialize(de::value::BorrowedStrDeserializer::new(entry.0))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DeError> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(self.de.child(value, key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de ValueString,
+    value: ValueDeserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = DeError;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), DeError> {
+        let variant = seed.deserialize(de::value::BorrowedStrDeserializer::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), DeError> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, DeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.any(visitor)
+    }
+}
+
+#[test]
+fn deserialize_structs() {
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Listen {
+        Tcp(u16),
+        Unix { path: String },
+    }
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Server {
+        port: u16,
+        hosts: Vec<String>,
+        listen: Listen,
+    }
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config<'a> {
+        name: &'a str,
+        level: Level,
+        ratio: f64,
+        timeout: Option<u32>,
+        retries: Option<u32>,
+        servers: Vec<Server>,
+        labels: HashMap<String, String>,
+    }
+
+    let value = crate::parse_string(
+        r#"{
+            name: "api",
+            level: "info",
+            ratio: 1,
+            timeout: null,
+            servers: [
+                {port: 80, hosts: ["a"], listen: {tcp: 8080}},
+                {port: 81, hosts: [], listen: {unix: {path: "/run/api.sock"}}},
+            ],
+            labels: {team: "core"},
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        deserialize::<Config>(&value).unwrap(),
+        Config {
+            name: "api",
+            level: Level::Info,
+            ratio: 1.0,
+            timeout: None,
+            retries: None,
+            servers: vec![
+                Server {
+                    port: 80,
+                    hosts: vec!["a".to_string()],
+                    listen: Listen::Tcp(8080)
+                },
+                Server {
+                    port: 81,
+                    hosts: vec![],
+                    listen: Listen::Unix {
+                        path: "/run/api.sock".to_string()
+                    }
+                },
+            ],
+            labels: vec![("team".to_string(), "core".to_string())]
+                .into_iter()
+                .collect(),
+        }
+    );
+    assert_eq!(
+        deserialize::<Level>(&Value::String("debug".into())).unwrap(),
+        Level::Debug
+    );
+
+    let error = |config: &str| {
+        deserialize::<Vec<Server>>(&crate::parse_string(config).unwrap())
+            .unwrap_err()
+            .message
+    };
+    assert_eq!(
+        error(r#"[{port: "80", hosts: [], listen: {tcp: 1}}]"#),
+        r#"Cannot deserialize '[0].port': invalid type: string "80", expected u16"#
+    );
+    assert_eq!(
+        error(r#"[{port: 70000, hosts: [], listen: {tcp: 1}}]"#),
+        "Cannot deserialize '[0].port': invalid value: integer `70000`, expected u16"
+    );
+    assert_eq!(
+        error(r#"[{port: 1, hosts: [1], listen: {tcp: 1}}]"#),
+        "Cannot deserialize '[0].hosts[0]': invalid type: integer `1`, expected a string"
+    );
+    assert_eq!(
+        error(r#"[{port: 1, hosts: [], listen: "udp"}]"#),
+        "Cannot deserialize '[0].listen': unknown variant `udp`, expected `tcp` or `unix`"
+    );
+    assert_eq!(
+        error(r#"[{port: 1, listen: {tcp: 1}}]"#),
+        "Cannot deserialize '[0]': missing field `hosts`"
+    );
+    assert_eq!(
+        error(r#"[{port: 1, hosts: (x) => x, listen: {tcp: 1}}]"#),
+        "Cannot deserialize '[0].hosts': a function cannot be deserialized"
+    );
+    assert_eq!(
+        error("{}"),
+        "Cannot deserialize value: invalid type: map, expected a sequence"
+    );
+}
@@ -0,0 +1,53 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// The files and environment variables an evaluation read,
+/// e.g. to decide whether a cached result of the evaluation is still valid.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Dependencies {
+    /// The config file, its imports, and the files read by `read_file`.
+    pub files: BTreeSet<PathBuf>,
+    /// The environment variables read by `getenv`, `None` if a variable was not set.
+    pub env: BTreeMap<String, Option<String>>,
+}
+
+impl Dependencies {
+    /// A hash of the current content of the files and the current values of the environment variables.
+    /// It changes if any of the dependencies change.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for file in &self.files {
+            file.hash(&mut hasher);
+            fs::read(file).ok().hash(&mut hasher);
+        }
+        for name in self.env.keys() {
+            name.hash(&mut hasher);
+            std::env::var(name).ok().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[test]
+fn record_dependencies() {
+    let file = std::env::temp_dir().join(format!("syconf-deps-{}.txt", std::process::id()));
+    fs::write(&file, "a").unwrap();
+    let compiled = crate::compile_string(&format!(
+        r#"[read_file("{}"), getenv("SYCONF_DEPS_UNSET", "x")]"#,
+        file.display()
+    ))
+    .unwrap();
+    let (value, deps) = compiled.evaluate_with_dependencies().unwrap();
+    assert_eq!(value, crate::parse_string(r#"["a", "x"]"#).unwrap());
+    assert_eq!(deps.files.iter().collect::<Vec<_>>(), vec![&file]);
+    assert_eq!(deps.env.get("SYCONF_DEPS_UNSET"), Some(&None));
+
+    let fingerprint = deps.fingerprint();
+    assert_eq!(deps.fingerprint(), fingerprint);
+    fs::write(&file, "b").unwrap();
+    assert_ne!(deps.fingerprint(), fingerprint);
+    fs::remove_file(&file).unwrap();
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+/// Whether a deprecated name is a builtin function or a method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callable {
+    Function,
+    Method,
+}
+
+/// A builtin or method that still works but has a replacement.
+/// Using it is a warning until the removal version, from then on it fails.
+#[derive(Debug)]
+pub struct Deprecation {
+    pub callable: Callable,
+    pub name: &'static str,
+    pub replacement: &'static str,
+    pub since: &'static str,
+    pub removal: &'static str,
+}
+
+pub const DEPRECATED: &[Deprecation] = &[Deprecation {
+    callable: Callable::Function,
+    name: "merge_deep",
+    replacement: "deep_merge",
+    since: "0.1.1",
+    removal: "0.2.0",
+}];
+
+pub fn lookup(callable: Callable, name: &str) -> Option<&'static Deprecation> {
+    DEPRECATED
+        .iter()
+        .find(|x| x.callable == callable && x.name == name)
+}
+
+impl Deprecation {
+    /// Whether this version of syconf is at or past the removal version.
+    pub fn is_removed(&self) -> bool {
+        let version = |x: &str| semver::Version::parse(x).expect("invalid version");
+        version(env!("CARGO_PKG_VERSION")) >= version(self.removal)
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.callable {
+            Callable::Function => self.name.to_string(),
+            Callable::Method => format!(".{}", self.name),
+        };
+        if self.is_removed() {
+            write!(f, "'{}' was removed in {}", name, self.removal)?;
+        } else {
+            write!(
+                f,
+                "'{}' is deprecated since {} and will be removed in {}",
+                name, self.since, self.removal
+            )?;
+        }
+        write!(f, ", use '{}' instead", self.replacement)
+    }
+}
+
+#[test]
+fn deprecated_names_exist() {
+    use crate::compiler::methods::*;
+    for x in DEPRECATED {
+        let exists = match x.callable {
+            Callable::Function => crate::compiler::functions::lookup(x.name).is_some(),
+            Callable::Method => {
+                hashmap::method(x.name).is_some()
+                    || list::method(x.name).is_some()
+                    || string::method(x.name).is_some()
+                    || number::method(x.name).is_some()
+                    || any::method(x.name).is_some()
+            }
+        };
+        assert!(exists, "{}", x.name);
+        assert!(!x.is_removed(), "{} is past its removal version", x.name);
+    }
+}
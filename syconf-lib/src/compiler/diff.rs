@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::compiler::value::{is_identifier, quote, ValueString};
+use crate::compiler::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The value exists only in the actual value, only reported by `structural_diff`.
+    Added(Value),
+    /// The value exists only in the expected value.
+    Removed(Value),
+    /// Both sides have a value at this path but they differ.
+    Changed(Value, Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Path in the `a.b[2]` notation, empty for the root value.
+    pub path: String,
+    pub change: Change,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "<root>"
+        } else {
+            self.path.as_str()
+        };
+        match &self.change {
+            Change::Added(actual) => write!(f, "{}: unexpected {}", path, actual),
+            Change::Removed(expected) => write!(f, "{}: missing, expected {}", path, expected),
+            Change::Changed(expected, actual) => {
+                write!(f, "{}: expected {}, got {}", path, expected, actual)
+            }
+        }
+    }
+}
+
+/// A list whose length differs between the expected and the actual value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LengthMismatch {
+    pub path: String,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "<root>"
+        } else {
+            self.path.as_str()
+        };
+        write!(
+            f,
+            "{}: lists differ in length {} vs {}",
+            path, self.expected, self.actual
+        )
+    }
+}
+
+/// Returns the differences that prevent `expected` from being a subset of `actual`.
+/// Hashmap keys that exist only in `actual` are ignored at every nesting level.
+pub fn subset_diff(expected: &Value, actual: &Value) -> Vec<Difference> {
+    let mut walker = Walker::default();
+    walker.compare(String::new(), expected, actual);
+    walker.out
+}
+
+pub fn is_subset(
+    expected: &HashMap<ValueString, Value>,
+    actual: &HashMap<ValueString, Value>,
+) -> bool {
+    let mut walker = Walker::default();
+    walker.compare_hashmaps(String::new(), expected, actual);
+    walker.out.is_empty()
+}
+
+/// Returns all differences between `base` as the expected and `head` as the actual value, sorted by path.
+/// Unlike `subset_diff`, keys only in `head` are reported as added, and lists are compared element by element,
+/// so that a changed element is reported by its own path instead of the whole list.
+pub fn structural_diff(base: &Value, head: &Value) -> Vec<Difference> {
+    structural_diff_with_lengths(base, head).0
+}
+
+/// Like `structural_diff`, and also returns the lists whose lengths differ, sorted by path like the differences.
+pub(crate) fn structural_diff_with_lengths(
+    base: &Value,
+    head: &Value,
+) -> (Vec<Difference>, Vec<LengthMismatch>) {
+    let mut walker = Walker {
+        full: true,
+        ..Default::default()
+    };
+    walker.compare(String::new(), base, head);
+    (walker.out, walker.lengths)
+}
+
+#[derive(Default)]
+struct Walker {
+    out: Vec<Difference>,
+    lengths: Vec<LengthMismatch>,
+    full: bool,
+}
+
+impl Walker {
+    fn compare(&mut self, path: String, expected: &Value, actual: &Value) {
+        match (expected, actual) {
+            (Value::HashMap(exp), Value::HashMap(act)) => self.compare_hashmaps(path, exp, act),
+            (Value::List(exp), Value::List(act)) if self.full => {
+                if exp.len() != act.len() {
+                    self.lengths.push(LengthMismatch {
+                        path: path.clone(),
+                        expected: exp.len(),
+                        actual: act.len(),
+                    });
+                }
+                for ix in 0..exp.len().max(act.len()) {
+                    let path = format!("{}[{}]", path, ix);
+                    match (exp.get(ix), act.get(ix)) {
+                        (Some(e), Some(a)) => self.compare(path, e, a),
+                        (Some(e), None) => self.push(path, Change::Removed(e.clone())),
+                        (None, Some(a)) => self.push(path, Change::Added(a.clone())),
+                        (None, None) => unreachable!(),
+                    }
+                }
+            }
+            _ => {
+                if expected != actual {
+                    self.push(path, Change::Changed(expected.clone(), actual.clone()));
+                }
+            }
+        }
+    }
+
+    fn compare_hashmaps(
+        &mut self,
+        path: String,
+        expected: &HashMap<ValueString, Value>,
+        actual: &HashMap<ValueString, Value>,
+    ) {
+        let mut keys: Vec<&ValueString> = expected.keys().collect();
+        if self.full {
+            keys.extend(actual.keys().filter(|k| !expected.contains_key(*k)));
+        }
+        keys.sort();
+        for key in keys {
+            let path = key_path(&path, key);
+            match (expected.get(key), actual.get(key)) {
+                (Some(e), Some(a)) => self.compare(path, e, a),
+                (Some(e), None) => self.push(path, Change::Removed(e.clone())),
+                (None, Some(a)) => self.push(path, Change::Added(a.clone())),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn push(&mut self, path: String, change: Change) {
+        self.out.push(Difference { path, change });
+    }
+}
+
+pub fn key_path(parent: &str, key: &str) -> String {
+    match (parent.is_empty(), is_identifier(key)) {
+        (true, true) => key.to_string(),
+        (false, true) => format!("{}.{}", parent, key),
+        (_, false) => format!("{}[{}]", parent, quote(key)),
+    }
+}
+
+#[test]
+fn subset_diff_nested() {
+    let expected =
+        crate::parse_string(r#"{db: {host: "x", port: 1}, tags: [1, 2], "a b": 1}"#).unwrap();
+    let actual = crate::parse_string(r#"{db: {port: 2, user: "u"}, tags: [1], "a b": 1}"#).unwrap();
+    let out: Vec<String> = subset_diff(&expected, &actual)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        out,
+        vec![
+            r#"db.host: missing, expected "x""#,
+            "db.port: expected 1, got 2",
+            "tags: expected [1, 2], got [1]",
+        ]
+    );
+}
+
+#[test]
+fn structural_diff_nested() {
+    let base = crate::parse_string(
+        r#"{db: {host: "x", port: 1}, tags: ["a", "b", "c"], name: "api", "a b": 1}"#,
+    )
+    .unwrap();
+    let head = crate::parse_string(
+        r#"{db: {port: 2, user: "u"}, tags: ["a", "x"], name: "api", "a b": [1]}"#,
+    )
+    .unwrap();
+    let out: Vec<String> = structural_diff(&base, &head)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        out,
+        vec![
+            r#"["a b"]: expected 1, got [1]"#,
+            r#"db.host: missing, expected "x""#,
+            "db.port: expected 1, got 2",
+            r#"db.user: unexpected "u""#,
+            r#"tags[1]: expected "b", got "x""#,
+            r#"tags[2]: missing, expected "c""#,
+        ]
+    );
+    assert!(structural_diff(&base, &base).is_empty());
+    let lengths: Vec<String> = structural_diff_with_lengths(&base, &head)
+        .1
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(lengths, vec!["tags: lists differ in length 3 vs 2"]);
+}
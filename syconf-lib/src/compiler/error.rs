@@ -8,6 +8,15 @@ pub type Error = ErrorWithLocation;
 pub struct ErrorWithLocation {
     pub location: Option<Location>,
     pub message: String,
+    /// A suggestion how to fix the error, e.g. a similar variable name, shown as a separate `help:` line.
+    pub hint: Option<String>,
+}
+
+impl ErrorWithLocation {
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
 }
 
 impl std::fmt::Display for ErrorWithLocation {
@@ -18,7 +27,11 @@ impl std::fmt::Display for ErrorWithLocation {
             .map(|l| format!("{}", l))
             .unwrap_or_else(|| "somewhere".to_string());
 
-        write!(f, "{}: {}", &s, self.message)
+        write!(f, "{}: {}", &s, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\nhelp: {}", hint)?;
+        }
+        Ok(())
     }
 }
 
@@ -27,6 +40,7 @@ impl From<anyhow::Error> for ErrorWithLocation {
         ErrorWithLocation {
             location: None,
             message: e.to_string(),
+            hint: None,
         }
     }
 }
@@ -36,6 +50,7 @@ impl From<String> for ErrorWithLocation {
         ErrorWithLocation {
             location: None,
             message: e,
+            hint: None,
         }
     }
 }
@@ -45,6 +60,7 @@ impl From<&str> for ErrorWithLocation {
         ErrorWithLocation {
             location: None,
             message: e.to_string(),
+            hint: None,
         }
     }
 }
@@ -54,6 +70,7 @@ impl From<TypeMismatch> for ErrorWithLocation {
         ErrorWithLocation {
             location: None,
             message: e.to_string(),
+            hint: None,
         }
     }
 }
@@ -65,6 +82,7 @@ macro_rules! check {
             return Err($crate::compiler::error::ErrorWithLocation{
                 location: None,
                 message: $msg.to_string(),
+                hint: None,
             });
         }
     };
@@ -73,11 +91,75 @@ macro_rules! check {
             return Err($crate::compiler::error::ErrorWithLocation{
                 location: None,
                 message: format!($fmt, $($arg)*),
+                hint: None,
             });
         }
     };
 }
 
+/// Returns the candidates closest to `name` by edit distance, e.g. for a misspelled variable name.
+pub(crate) fn similar_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut similar: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, c)| *d <= max_distance && *c != name)
+        .collect();
+    similar.sort();
+    similar.dedup();
+    similar.into_iter().take(3).map(|(_, c)| c).collect()
+}
+
+/// The Levenshtein distance counted in characters, where swapping adjacent characters counts as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, x) in d[0].iter_mut().enumerate() {
+        *x = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Formats the names as a hint, e.g. `did you mean 'keys'?`.
+pub(crate) fn did_you_mean(names: &[&str]) -> Option<String> {
+    let quoted: Vec<String> = names.iter().map(|x| format!("'{}'", x)).collect();
+    match quoted.len() {
+        0 => None,
+        1 => Some(format!("did you mean {}?", quoted[0])),
+        _ => Some(format!("did you mean one of {}?", quoted.join(", "))),
+    }
+}
+
+#[test]
+fn similar() {
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "ab"), 2);
+    assert_eq!(edit_distance("prot", "port"), 1);
+    assert_eq!(
+        similar_names("lenght", vec!["length", "len", "lazy", "merge"]),
+        ["length"]
+    );
+    assert_eq!(similar_names("ab", vec!["ab", "ac", "xyz"]), ["ac"]);
+    assert!(similar_names("totally", vec!["map", "filter"]).is_empty());
+}
+
 #[test]
 fn error_location() {
     let result = crate::parse_string(" abc");
@@ -88,3 +170,16 @@ fn error_location() {
     println!("Location: {:?}", loc);
     assert_eq!(loc.unwrap().position, 1);
 }
+
+#[test]
+fn error_hint_display() {
+    let err = crate::parse_string("let name = 1 in nmae").unwrap_err();
+    let shown = err.to_string();
+    assert!(
+        shown.ends_with("Variable 'nmae' is not defined\nhelp: did you mean 'name'?"),
+        "{}",
+        shown
+    );
+    let err = crate::parse_string("1 +").unwrap_err();
+    assert!(!err.to_string().contains("help:"));
+}
@@ -0,0 +1,620 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::compiler::deprecation::Deprecation;
+use crate::compiler::node::{self, Limits};
+use crate::compiler::{Dependencies, Error, Location, Value};
+
+/// Reads the files of `read_file`, can be replaced e.g. to serve files from memory.
+pub trait FileLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Reads files from the file system.
+pub struct FsLoader;
+
+impl FileLoader for FsLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// Reads the environment variables of `getenv`, can be replaced like the `FileLoader`.
+pub trait EnvProvider {
+    fn var(&self, name: &str) -> Option<String>;
+}
+
+/// Reads the environment of the process.
+pub struct ProcessEnv;
+
+impl EnvProvider for ProcessEnv {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+#[derive(Clone)]
+pub struct EvalOptions {
+    /// Caches the results of `read_file` by the resolved path and of `getenv` by the variable name,
+    /// so that repeated calls within one evaluation read every file and variable only once.
+    /// Enabled by default, disable it if files change during the evaluation.
+    pub io_cache: bool,
+    pub file_loader: Rc<dyn FileLoader>,
+    pub env_provider: Rc<dyn EnvProvider>,
+    /// Maximum length in bytes of a hashmap key, 1 KiB by default.
+    /// It catches e.g. a file interpolated into a key by mistake.
+    pub max_key_size: usize,
+    /// Maximum length in bytes of a single value interpolated into a string, 10 MiB by default.
+    pub max_interpolation_size: usize,
+    /// Records every default used by `getenv` and its variants, `hm.get`, `hm.get_ci`, `unwrap_or`, and `unwrap_or_else` in the report
+    /// of `Compiled::evaluate_with_report`. Disabled by default.
+    pub track_defaults: bool,
+    /// Fails on the first warning instead of adding it to the report. Disabled by default.
+    pub deny_warnings: bool,
+    /// Maximum depth of nested expressions being evaluated, 1000 by default.
+    /// It stops infinite recursion before the stack overflows, a higher limit may need a larger stack.
+    pub max_evaluation_depth: usize,
+    /// Maximum number of expressions evaluated, 100 million by default.
+    /// It stops huge computations, e.g. a `fold` over a long `range`, from running for a very long time.
+    /// The elements of lists created by builtins like `range` count as 10 expressions each, which bounds their memory.
+    pub max_nodes_evaluated: u64,
+    /// Maximum number of files imported directly or transitively, unlimited by default.
+    /// It is checked before the evaluation starts, e.g. to keep a growing config tree from slowing down CI.
+    pub max_imports: Option<usize>,
+    /// Writes a `Recording` of the sources, the files, and the environment variables the evaluation read to this file,
+    /// so that it can be replayed elsewhere. Written by `Compiled::evaluate_with_options` and `evaluate_with_report`,
+    /// also if the evaluation fails.
+    pub record: Option<PathBuf>,
+    /// Case-insensitive globs of environment variable names, e.g. `*_TOKEN`, whose values are recorded as `"***"`.
+    /// A replay then sees the redacted values.
+    pub record_redact: Vec<String>,
+}
+
+impl EvalOptions {
+    /// The default options, recording the evaluation to `path`.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            record: Some(path.into()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            io_cache: true,
+            file_loader: Rc::new(FsLoader),
+            env_provider: Rc::new(ProcessEnv),
+            max_key_size: 1024,
+            max_interpolation_size: 10 * 1024 * 1024,
+            track_defaults: false,
+            deny_warnings: false,
+            max_evaluation_depth: Limits::DEFAULT.max_depth,
+            max_nodes_evaluated: Limits::DEFAULT.max_nodes,
+            max_imports: None,
+            record: None,
+            record_redact: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for EvalOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EvalOptions")
+            .field("io_cache", &self.io_cache)
+            .field("max_key_size", &self.max_key_size)
+            .field("max_interpolation_size", &self.max_interpolation_size)
+            .field("track_defaults", &self.track_defaults)
+            .field("deny_warnings", &self.deny_warnings)
+            .field("max_evaluation_depth", &self.max_evaluation_depth)
+            .field("max_nodes_evaluated", &self.max_nodes_evaluated)
+            .field("max_imports", &self.max_imports)
+            .field("record", &self.record)
+            .field("record_redact", &self.record_redact)
+            .finish()
+    }
+}
+
+/// What an evaluation read, which defaults it fell back to, and what it warned about.
+#[derive(Debug, Clone, Default)]
+pub struct EvalReport {
+    pub dependencies: Dependencies,
+    /// In the order the defaults were applied, empty unless `EvalOptions::track_defaults` is enabled.
+    pub defaults_applied: Vec<AppliedDefault>,
+    /// In the order they occurred.
+    pub warnings: Vec<Warning>,
+    /// The content of the files read by `read_file`, only kept for `EvalOptions::record`.
+    pub(crate) files_read: BTreeMap<PathBuf, String>,
+}
+
+/// A problem that does not stop the evaluation, e.g. the use of a deprecated builtin.
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// A default that a builtin returned because the environment variable, hashmap key, or value was missing.
+#[derive(Debug, Clone)]
+pub struct AppliedDefault {
+    /// The builtin that applied the default, e.g. `getenv`.
+    pub function: &'static str,
+    /// The environment variable or hashmap key, if the builtin looked one up.
+    pub name: Option<String>,
+    /// A summary of the default value, e.g. `"8080"`.
+    pub default: String,
+    /// The call that applied the default.
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for AppliedDefault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+        match &self.name {
+            Some(name) => write!(f, "{}('{}')", self.function, name)?,
+            None => write!(f, "{}", self.function)?,
+        }
+        write!(f, " used the default {}", self.default)
+    }
+}
+
+/// The state of the running evaluation that the builtins use to access files and the environment.
+struct Evaluation {
+    options: EvalOptions,
+    dependencies: Dependencies,
+    cache: IoCache,
+    defaults_applied: Vec<AppliedDefault>,
+    warnings: Vec<Warning>,
+    /// The deprecated names that were already warned about.
+    deprecations_used: HashSet<&'static str>,
+    files_read: BTreeMap<PathBuf, String>,
+}
+
+/// The results of `read_file` and `getenv`, which can be kept for further evaluations, e.g. of a workspace.
+#[derive(Debug, Clone, Default)]
+pub struct IoCache {
+    files: HashMap<PathBuf, String>,
+    env: HashMap<String, Option<String>>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Evaluation>> = const { RefCell::new(None) };
+}
+
+/// Runs the evaluation `f` and returns the dependencies recorded by the builtins it called.
+pub fn run<T>(options: &EvalOptions, f: impl FnOnce() -> T) -> (T, Dependencies) {
+    run_with_cache(options, &mut IoCache::default(), f)
+}
+
+/// Like `run`, starts with the cached results of `cache` and adds the new ones to it.
+pub fn run_with_cache<T>(
+    options: &EvalOptions,
+    cache: &mut IoCache,
+    f: impl FnOnce() -> T,
+) -> (T, Dependencies) {
+    let (out, report) = run_with_report(options, cache, f);
+    (out, report.dependencies)
+}
+
+/// Like `run_with_cache`, also returns the applied defaults and the warnings.
+pub fn run_with_report<T>(
+    options: &EvalOptions,
+    cache: &mut IoCache,
+    f: impl FnOnce() -> T,
+) -> (T, EvalReport) {
+    let outer = CURRENT.with(|c| {
+        c.replace(Some(Evaluation {
+            options: options.clone(),
+            dependencies: Dependencies::default(),
+            cache: std::mem::take(cache),
+            defaults_applied: Vec::new(),
+            warnings: Vec::new(),
+            deprecations_used: HashSet::new(),
+            files_read: BTreeMap::new(),
+        }))
+    });
+    let outer_limits = node::set_limits(Limits {
+        max_depth: options.max_evaluation_depth,
+        max_nodes: options.max_nodes_evaluated,
+    });
+    let out = f();
+    node::restore_limits(outer_limits);
+    match CURRENT.with(|c| c.replace(outer)) {
+        Some(evaluation) => {
+            *cache = evaluation.cache;
+            let report = EvalReport {
+                dependencies: evaluation.dependencies,
+                defaults_applied: evaluation.defaults_applied,
+                warnings: evaluation.warnings,
+                files_read: evaluation.files_read,
+            };
+            (out, report)
+        }
+        None => (out, EvalReport::default()),
+    }
+}
+
+/// Called by the builtins when they fall back to a default, if `track_defaults` is enabled it is recorded.
+/// The builtins do not know where they were called, `locate_report` adds the location after the call.
+pub fn default_applied(function: &'static str, name: Option<&str>, default: &Value) {
+    CURRENT.with(|c| {
+        if let Some(e) = c.borrow_mut().as_mut() {
+            if e.options.track_defaults {
+                e.defaults_applied.push(AppliedDefault {
+                    function,
+                    name: name.map(ToString::to_string),
+                    default: default.summary(),
+                    location: None,
+                });
+            }
+        }
+    })
+}
+
+/// Called when a deprecated builtin or method is used, warns once per name and evaluation.
+/// Fails instead if warnings are denied or the removal version is reached.
+/// Without a location, like a default it gets the location of the call by `locate_report`.
+pub fn deprecation_used(
+    deprecation: &'static Deprecation,
+    location: Option<&Location>,
+) -> Result<(), Error> {
+    check!(!deprecation.is_removed(), "{}", deprecation);
+    CURRENT.with(|c| {
+        if let Some(e) = c.borrow_mut().as_mut() {
+            check!(!e.options.deny_warnings, "{}", deprecation);
+            if e.deprecations_used.insert(deprecation.name) {
+                e.warnings.push(Warning {
+                    message: deprecation.to_string(),
+                    location: location.cloned(),
+                });
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Sets the location of the defaults applied and the warnings raised by the call that just returned.
+/// Calls nested in it set the location of their own entries before, so the entries without one are at the end.
+pub fn locate_report(location: Option<&Location>) {
+    CURRENT.with(|c| {
+        if let Some(e) = c.borrow_mut().as_mut() {
+            let unlocated = e.defaults_applied.iter_mut().rev();
+            for applied in unlocated.take_while(|x| x.location.is_none()) {
+                applied.location = location.cloned();
+            }
+            let unlocated = e.warnings.iter_mut().rev();
+            for warning in unlocated.take_while(|x| x.location.is_none()) {
+                warning.location = location.cloned();
+            }
+        }
+    })
+}
+
+/// Outside of an evaluation, e.g. in unit tests of the builtins, the file system is used directly.
+pub fn read_file(path: &Path) -> io::Result<String> {
+    let content = load_file(path)?;
+    CURRENT.with(|c| {
+        if let Some(e) = c.borrow_mut().as_mut() {
+            if e.options.record.is_some() {
+                e.files_read.insert(path.to_path_buf(), content.clone());
+            }
+        }
+    });
+    Ok(content)
+}
+
+fn load_file(path: &Path) -> io::Result<String> {
+    let options = CURRENT.with(|c| {
+        let mut current = c.borrow_mut();
+        current.as_mut().map(|e| {
+            e.dependencies.files.insert(path.to_path_buf());
+            e.options.clone()
+        })
+    });
+    let options = match options {
+        Some(options) => options,
+        None => return fs::read_to_string(path),
+    };
+    if !options.io_cache {
+        return options.file_loader.read_to_string(path);
+    }
+    let key = resolve(path);
+    if let Some(content) = CURRENT.with(|c| {
+        c.borrow()
+            .as_ref()
+            .and_then(|e| e.cache.files.get(&key).cloned())
+    }) {
+        return Ok(content);
+    }
+    let content = options.file_loader.read_to_string(path)?;
+    CURRENT.with(|c| {
+        if let Some(e) = c.borrow_mut().as_mut() {
+            e.cache.files.insert(key, content.clone());
+        }
+    });
+    Ok(content)
+}
+
+/// Makes the path absolute and removes `.` and `..` components without accessing the file system,
+/// so that it also works for files served by a custom `FileLoader`.
+fn resolve(path: &Path) -> PathBuf {
+    let mut out = std::env::current_dir().unwrap_or_default();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            x => out.push(x),
+        }
+    }
+    out
+}
+
+/// The options of the running evaluation, or the defaults outside of an evaluation.
+pub fn options() -> EvalOptions {
+    CURRENT
+        .with(|c| c.borrow().as_ref().map(|e| e.options.clone()))
+        .unwrap_or_default()
+}
+
+pub fn getenv(name: &str) -> Option<String> {
+    CURRENT.with(|c| {
+        let mut current = c.borrow_mut();
+        let e = match current.as_mut() {
+            Some(e) => e,
+            None => return std::env::var(name).ok(),
+        };
+        let value = match e.cache.env.get(name) {
+            Some(value) if e.options.io_cache => value.clone(),
+            _ => e.options.env_provider.var(name),
+        };
+        e.cache.env.insert(name.to_string(), value.clone());
+        e.dependencies.env.insert(name.to_string(), value.clone());
+        value
+    })
+}
+
+#[cfg(test)]
+struct CountingLoader(std::cell::Cell<usize>);
+
+#[cfg(test)]
+impl FileLoader for CountingLoader {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.0.set(self.0.get() + 1);
+        Ok(format!(r#"{{"path": "{}"}}"#, path.display()))
+    }
+}
+
+#[test]
+fn io_cache() {
+    let compiled = crate::compile_string(
+        r#"
+        let constants = () => read_file("shared/constants.json").parse_json()
+        in
+        [
+            constants().path,
+            constants(),
+            read_file("shared/constants.json"),
+            read_file("./shared/constants.json"),
+            read_file("shared/../shared/constants.json"),
+            getenv("SYCONF_IO_CACHE_UNSET", ""),
+        ]
+        "#,
+    )
+    .unwrap();
+    let evaluate = |io_cache: bool| {
+        let loader = Rc::new(CountingLoader(Default::default()));
+        let options = EvalOptions {
+            io_cache,
+            file_loader: loader.clone(),
+            ..Default::default()
+        };
+        let value = compiled.evaluate_with_options(&options).unwrap();
+        (value, loader.0.get())
+    };
+    let (cached, reads) = evaluate(true);
+    assert_eq!(reads, 1);
+    let (uncached, reads) = evaluate(false);
+    assert_eq!(reads, 5);
+    assert_eq!(cached.as_list().unwrap()[0], uncached.as_list().unwrap()[0]);
+}
+
+#[test]
+fn size_limits() {
+    let compiled = crate::compile_string(
+        r#"
+        let name = read_file("name.txt")
+        in
+        {
+            short: "${name}",
+            "${name}": 1,
+        }
+        "#,
+    )
+    .unwrap();
+    struct Loader(usize);
+    impl FileLoader for Loader {
+        fn read_to_string(&self, _path: &Path) -> io::Result<String> {
+            Ok("x".repeat(self.0))
+        }
+    }
+    let evaluate = |len: usize, options: EvalOptions| {
+        compiled.evaluate_with_options(&EvalOptions {
+            file_loader: Rc::new(Loader(len)),
+            ..options
+        })
+    };
+    assert!(evaluate(1024, EvalOptions::default()).is_ok());
+    let err = evaluate(5000, EvalOptions::default()).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Hashmap key is 5000 bytes long, which exceeds the limit of 1024 bytes"
+    );
+    assert_eq!(err.location.unwrap().line_no(), 6);
+    let options = EvalOptions {
+        max_key_size: 10_000,
+        ..Default::default()
+    };
+    assert!(evaluate(5000, options.clone()).is_ok());
+    let err = evaluate(
+        5000,
+        EvalOptions {
+            max_interpolation_size: 4096,
+            ..options
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.message,
+        "Interpolated value is 5000 bytes long, which exceeds the limit of 4096 bytes"
+    );
+}
+
+#[test]
+fn evaluation_limits() {
+    let options = EvalOptions {
+        max_evaluation_depth: 200,
+        max_nodes_evaluated: 10_000,
+        ..Default::default()
+    };
+    let err =
+        crate::parse_string_with_options("let f = (g, n) => g(g, n + 1)\nin\nf(f, 0)", &options)
+            .unwrap_err();
+    assert_eq!(err.message, "Maximum evaluation depth of 200 exceeded");
+    assert_eq!(err.location.unwrap().line_no(), 1);
+
+    let count = "fold(0, (acc, x) => acc + 1, range(100000))";
+    let err = crate::parse_string_with_options(count, &options).unwrap_err();
+    assert_eq!(err.message, "Evaluation budget of 10000 nodes exceeded");
+    assert!(err.location.is_some());
+    // Every evaluation gets the full budget, and the defaults are large enough.
+    let small = "fold(0, (acc, x) => acc + 1, range(100))";
+    assert!(crate::parse_string_with_options(small, &options).is_ok());
+    assert!(crate::parse_string_with_options(small, &options).is_ok());
+    assert_eq!(crate::parse_string(count).unwrap(), Value::Int(100_000));
+}
+
+#[test]
+fn track_defaults() {
+    let compiled = crate::compile_string(
+        r#"
+        let cfg = {port: 80, tls: null}
+        in
+        {
+            home: getenv("SYCONF_TRACK_DEFAULTS_UNSET", "/home"),
+            skipped: if false then getenv("SYCONF_TRACK_DEFAULTS_UNSET", "x") else 1,
+            port: cfg.get("port", 8080),
+            user: cfg.get("user", "root"),
+            tls: cfg.tls.unwrap_or(false),
+            name: "api".unwrap_or("x"),
+            replicas: null.unwrap_or_else(() => {n: 2}.get("replicas", 1)),
+        }
+        "#,
+    )
+    .unwrap();
+    let options = EvalOptions {
+        track_defaults: true,
+        ..Default::default()
+    };
+    let (_, report) = compiled.evaluate_with_report(&options).unwrap();
+    let applied: Vec<String> = report
+        .defaults_applied
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    // The order of the hashmap entries is not defined.
+    let mut expected = vec![
+        r#"<input_string>:5:25: getenv('SYCONF_TRACK_DEFAULTS_UNSET') used the default "/home""#,
+        r#"<input_string>:8:26: get('user') used the default "root""#,
+        "<input_string>:9:35: unwrap_or used the default false",
+        "<input_string>:11:59: get('replicas') used the default 1",
+        "<input_string>:11:42: unwrap_or_else used the default 1",
+    ];
+    expected.sort();
+    let mut sorted = applied.clone();
+    sorted.sort();
+    assert_eq!(sorted, expected);
+
+    let (_, report) = compiled
+        .evaluate_with_report(&EvalOptions::default())
+        .unwrap();
+    assert!(report.defaults_applied.is_empty());
+}
+
+#[test]
+fn deprecation_warnings() {
+    use crate::compiler::deprecation::Callable;
+
+    let compiled = crate::compile_string(
+        r#"[merge_deep({a: 1}, {b: 2}), merge_deep({}, {}), if false then merge_deep else 1]"#,
+    )
+    .unwrap();
+    let warning =
+        "'merge_deep' is deprecated since 0.1.1 and will be removed in 0.2.0, use 'deep_merge' instead";
+    for _ in 0..2 {
+        // Every evaluation warns once.
+        let (_, report) = compiled
+            .evaluate_with_report(&EvalOptions::default())
+            .unwrap();
+        let warnings: Vec<String> = report.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![format!("<input_string>:1:2: {}", warning)]);
+    }
+    let options = EvalOptions {
+        deny_warnings: true,
+        ..Default::default()
+    };
+    let err = compiled.evaluate_with_options(&options).unwrap_err();
+    assert_eq!(err.message, warning);
+    assert_eq!(err.location.unwrap().line_no(), 1);
+
+    let shadowed = crate::compile_string("let merge_deep = (a, b) => a in merge_deep(1, 2)")
+        .unwrap()
+        .evaluate_with_report(&options)
+        .unwrap();
+    assert!(shadowed.1.warnings.is_empty());
+
+    static OLD: Deprecation = Deprecation {
+        callable: Callable::Method,
+        name: "old",
+        replacement: "new",
+        since: "0.1.0",
+        removal: "9.0.0",
+    };
+    static REMOVED: Deprecation = Deprecation {
+        callable: Callable::Method,
+        name: "gone",
+        replacement: "new",
+        since: "0.0.1",
+        removal: "0.1.0",
+    };
+    let (out, report) = run_with_report(&EvalOptions::default(), &mut IoCache::default(), || {
+        deprecation_used(&OLD, None)
+    });
+    assert!(out.is_ok());
+    assert_eq!(
+        report.warnings[0].message,
+        "'.old' is deprecated since 0.1.0 and will be removed in 9.0.0, use 'new' instead"
+    );
+    assert_eq!(
+        deprecation_used(&REMOVED, None).unwrap_err().message,
+        "'.gone' was removed in 0.1.0, use 'new' instead"
+    );
+}
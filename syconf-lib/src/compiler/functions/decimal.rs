@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::compiler::{Error, Value};
+
+/// An arbitrary precision decimal number `digits * 10^-scale`.
+#[derive(Debug, Clone)]
+struct Decimal {
+    negative: bool,
+    /// Little-endian base 10 digits without leading (high) zeros, empty for zero.
+    digits: Vec<u8>,
+    scale: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RoundingMode {
+    /// Ties are rounded away from zero.
+    HalfUp,
+    /// Ties are rounded to the nearest even digit, also known as banker's rounding.
+    HalfEven,
+}
+
+impl Decimal {
+    /// Accepts `-?[0-9]+(\.[0-9]+)?`.
+    fn parse(s: &str) -> Result<Self, Error> {
+        let fail = || -> Error { format!("Invalid decimal '{}'", s).into() };
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (int, frac) = match unsigned.find('.') {
+            Some(ix) => (&unsigned[..ix], &unsigned[ix + 1..]),
+            None => (unsigned, ""),
+        };
+        let is_digits = |x: &str| x.chars().all(|c| c.is_ascii_digit());
+        if int.is_empty() || !is_digits(int) || !is_digits(frac) {
+            return Err(fail());
+        }
+        if unsigned.contains('.') && frac.is_empty() {
+            return Err(fail());
+        }
+        let digits = int
+            .bytes()
+            .chain(frac.bytes())
+            .rev()
+            .map(|b| b - b'0')
+            .collect();
+        Ok(Self::new(negative, digits, frac.len()))
+    }
+
+    fn new(negative: bool, mut digits: Vec<u8>, scale: usize) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Self {
+            negative: negative && !digits.is_empty(),
+            digits,
+            scale,
+        }
+    }
+
+    fn with_scale(&self, scale: usize) -> Self {
+        let mut digits = vec![0; scale - self.scale];
+        digits.extend_from_slice(&self.digits);
+        Self::new(self.negative, digits, scale)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.with_scale(scale), other.with_scale(scale));
+        if a.negative == b.negative {
+            return Self::new(a.negative, add_magnitudes(&a.digits, &b.digits), scale);
+        }
+        match cmp_magnitudes(&a.digits, &b.digits) {
+            Ordering::Less => Self::new(b.negative, sub_magnitudes(&b.digits, &a.digits), scale),
+            _ => Self::new(a.negative, sub_magnitudes(&a.digits, &b.digits), scale),
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut out = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            for (j, b) in other.digits.iter().enumerate() {
+                out[i + j] += (*a as u32) * (*b as u32);
+            }
+        }
+        let mut carry = 0;
+        let digits = out
+            .into_iter()
+            .map(|x| {
+                let x = x + carry;
+                carry = x / 10;
+                (x % 10) as u8
+            })
+            .collect();
+        Self::new(
+            self.negative != other.negative,
+            digits,
+            self.scale + other.scale,
+        )
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.with_scale(scale), other.with_scale(scale));
+        match (a.negative, b.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitudes(&a.digits, &b.digits),
+            (true, true) => cmp_magnitudes(&b.digits, &a.digits),
+        }
+    }
+
+    /// Rounds to exactly `places` fractional digits.
+    fn round(&self, places: usize, mode: RoundingMode) -> Self {
+        if self.scale <= places {
+            return self.with_scale(places);
+        }
+        let dropped = self.scale - places;
+        let digit = |ix: usize| self.digits.get(ix).copied().unwrap_or(0);
+        let kept: Vec<u8> = self.digits.iter().skip(dropped).copied().collect();
+        let round_up = match digit(dropped - 1).cmp(&5) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal if (0..dropped - 1).any(|ix| digit(ix) != 0) => true,
+            Ordering::Equal => match mode {
+                RoundingMode::HalfUp => true,
+                RoundingMode::HalfEven => digit(dropped) % 2 == 1,
+            },
+        };
+        let kept = if round_up {
+            add_magnitudes(&kept, &[1])
+        } else {
+            kept
+        };
+        Self::new(self.negative, kept, places)
+    }
+}
+
+/// Renders the canonical form: no leading zeros, no `-0`, and exactly `scale` fractional digits.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut digits: Vec<u8> = self.digits.clone();
+        digits.resize(digits.len().max(self.scale + 1), 0);
+        let s: String = digits.iter().rev().map(|d| (d + b'0') as char).collect();
+        let (int, frac) = s.split_at(s.len() - self.scale);
+        if self.negative {
+            f.write_str("-")?;
+        }
+        f.write_str(int)?;
+        if !frac.is_empty() {
+            write!(f, ".{}", frac)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitudes(a: &[u8], b: &[u8]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for ix in 0..a.len().max(b.len()) {
+        let x = a.get(ix).unwrap_or(&0) + b.get(ix).unwrap_or(&0) + carry;
+        out.push(x % 10);
+        carry = x / 10;
+    }
+    if carry > 0 {
+        out.push(carry);
+    }
+    out
+}
+
+/// Requires `a >= b`.
+fn sub_magnitudes(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (ix, x) in a.iter().enumerate() {
+        let y = b.get(ix).unwrap_or(&0) + borrow;
+        if *x >= y {
+            out.push(x - y);
+            borrow = 0;
+        } else {
+            out.push(x + 10 - y);
+            borrow = 1;
+        }
+    }
+    out
+}
+
+fn decimal_args(name: &str, args: &[Value], count: usize) -> Result<Vec<Decimal>, Error> {
+    check!(
+        args.len() == count,
+        "'{}' expects {} decimal string arguments",
+        name,
+        count
+    );
+    args.iter()
+        .map(|x| Decimal::parse(x.as_value_string()?))
+        .collect()
+}
+
+pub fn decimal_add(args: &[Value]) -> Result<Value, Error> {
+    let x = decimal_args("decimal_add", args, 2)?;
+    Ok(Value::String(x[0].add(&x[1]).to_string().into()))
+}
+
+pub fn decimal_mul(args: &[Value]) -> Result<Value, Error> {
+    let x = decimal_args("decimal_mul", args, 2)?;
+    Ok(Value::String(x[0].mul(&x[1]).to_string().into()))
+}
+
+pub fn decimal_cmp(args: &[Value]) -> Result<Value, Error> {
+    let x = decimal_args("decimal_cmp", args, 2)?;
+    Ok(Value::Int(match x[0].cmp(&x[1]) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }))
+}
+
+pub fn decimal_round(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3,
+        "'decimal_round' expects 3 arguments (decimal, places, mode)"
+    );
+    let x = Decimal::parse(args[0].as_value_string()?)?;
+    let places = args[1].as_int()?;
+    check!(
+        places >= 0,
+        "'decimal_round' places must not be negative, got {}",
+        places
+    );
+    let mode = match args[2].as_value_string()?.as_ref() {
+        "half_up" => RoundingMode::HalfUp,
+        "half_even" => RoundingMode::HalfEven,
+        x => {
+            return Err(format!(
+                "'decimal_round' mode must be 'half_up' or 'half_even', got '{}'",
+                x
+            )
+            .into())
+        }
+    };
+    Ok(Value::String(
+        x.round(places as usize, mode).to_string().into(),
+    ))
+}
+
+#[test]
+fn decimal_arithmetic() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    let string = |s: &str| Value::String(s.into());
+    assert_eq!(eval(r#"decimal_add("10.05", "0.10")"#), string("10.15"));
+    assert_eq!(eval(r#"decimal_add("0.1", "0.2")"#), string("0.3"));
+    assert_eq!(eval(r#"decimal_add("-10.05", "3")"#), string("-7.05"));
+    assert_eq!(eval(r#"decimal_add("1.5", "-1.50")"#), string("0.00"));
+    assert_eq!(
+        eval(r#"decimal_add("99999999999999999999.99", "0.01")"#),
+        string("100000000000000000000.00")
+    );
+    assert_eq!(eval(r#"decimal_mul("19.99", "3")"#), string("59.97"));
+    assert_eq!(eval(r#"decimal_mul("-0.5", "0.5")"#), string("-0.25"));
+    assert_eq!(eval(r#"decimal_mul("0.0", "-7")"#), string("0.0"));
+    assert_eq!(
+        eval(r#"decimal_mul(decimal_add("1.10", "2"), "1.5")"#),
+        string("4.650")
+    );
+    assert_eq!(eval(r#"decimal_cmp("1.10", "1.1")"#), Value::Int(0));
+    assert_eq!(eval(r#"decimal_cmp("-2", "1")"#), Value::Int(-1));
+    assert_eq!(eval(r#"decimal_cmp("-2", "-10")"#), Value::Int(1));
+    assert_eq!(eval(r#"decimal_cmp("0.05", "0.5")"#), Value::Int(-1));
+}
+
+#[test]
+fn decimal_rounding() {
+    let round = |x: &str, places: i32, mode: &str| {
+        crate::parse_string(&format!(
+            r#"decimal_round("{}", {}, "{}")"#,
+            x, places, mode
+        ))
+        .unwrap()
+        .as_value_string()
+        .unwrap()
+        .to_string()
+    };
+    assert_eq!(round("2.345", 2, "half_up"), "2.35");
+    assert_eq!(round("2.345", 2, "half_even"), "2.34");
+    assert_eq!(round("2.355", 2, "half_even"), "2.36");
+    assert_eq!(round("2.3451", 2, "half_even"), "2.35");
+    assert_eq!(round("-2.345", 2, "half_up"), "-2.35");
+    assert_eq!(round("-2.345", 2, "half_even"), "-2.34");
+    assert_eq!(round("9.995", 2, "half_up"), "10.00");
+    assert_eq!(round("0.5", 0, "half_even"), "0");
+    assert_eq!(round("1.5", 0, "half_even"), "2");
+    assert_eq!(round("-0.004", 2, "half_up"), "0.00");
+    assert_eq!(round("7", 2, "half_up"), "7.00");
+}
+
+#[test]
+fn decimal_invalid() {
+    for x in &["1,5", "1.", ".5", "+1", "1e3", "", "--1", "1.2.3"] {
+        let err = crate::parse_string(&format!(r#"decimal_add("{}", "1")"#, x));
+        match (x.is_empty(), err) {
+            (false, Err(e)) => assert_eq!(e.message, format!("Invalid decimal '{}'", x)),
+            (true, Err(_)) => {}
+            (_, Ok(v)) => panic!("{} parsed as {:?}", x, v),
+        }
+    }
+    assert!(crate::parse_string(r#"decimal_round("1.5", 0, "up")"#).is_err());
+    assert!(crate::parse_string(r#"decimal_round("1.5", 0 - 1, "half_up")"#).is_err());
+}
@@ -0,0 +1,154 @@
+use crate::compiler::{Error, Value};
+
+const SI_BYTES: &[&str] = &["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+const BINARY_BYTES: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const COUNTS: &[&str] = &["", "k", "M", "B", "T"];
+const DURATIONS: &[(i64, &str)] = &[(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+
+/// Returns the option `name` of the optional options hashmap at `args[ix]`.
+fn option<'a>(args: &'a [Value], ix: usize, name: &str) -> Result<Option<&'a Value>, Error> {
+    match args.get(ix) {
+        Some(options) => Ok(options.as_hashmap()?.get(name)),
+        None => Ok(None),
+    }
+}
+
+/// Divides `x` by `base` until it is below `base` after rounding to `decimals` fractional digits,
+/// so that e.g. `999950` becomes `1 MB` and not `1000 kB`. Trailing zeros are removed.
+fn scale(x: f64, base: f64, units: &[&str], decimals: usize, separator: &str) -> String {
+    let factor = 10f64.powi(decimals as i32);
+    let round = |v: f64| (v * factor).round() / factor;
+    let mut value = x.abs();
+    let mut unit = 0;
+    while unit + 1 < units.len() && round(value) >= base {
+        value /= base;
+        unit += 1;
+    }
+    let mut number = format!("{:.*}", decimals, round(value));
+    if number.contains('.') {
+        number = number
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string();
+    }
+    let sign = if x < 0.0 && number != "0" { "-" } else { "" };
+    format!("{}{}{}{}", sign, number, separator, units[unit])
+}
+
+/// SI units are shown with one fractional digit, binary units with two, e.g. `"1.5 MB"` and `"1.46 MiB"`.
+pub fn humanize_bytes(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'humanize_bytes' expects a number of bytes and optional options {{binary: bool}}"
+    );
+    let bytes = args[0].as_float()?;
+    let binary = match option(args, 1, "binary")? {
+        Some(x) => x.as_bool()?,
+        None => false,
+    };
+    let out = if binary {
+        scale(bytes, 1024.0, BINARY_BYTES, 2, " ")
+    } else {
+        scale(bytes, 1000.0, SI_BYTES, 1, " ")
+    };
+    Ok(Value::String(out.into()))
+}
+
+/// Shows the largest non-zero unit and at most `max_units - 1` following units, the rest is truncated.
+pub fn humanize_duration(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'humanize_duration' expects a number of seconds and optional options {{max_units: count}}"
+    );
+    let mut seconds = args[0].as_int()?;
+    check!(
+        seconds >= 0,
+        "'humanize_duration' expects a non-negative number of seconds, got {}",
+        seconds
+    );
+    let max_units = match option(args, 1, "max_units")? {
+        Some(x) => x.as_int()?,
+        None => DURATIONS.len() as i64,
+    };
+    check!(
+        max_units > 0,
+        "'humanize_duration' option 'max_units' must be positive, got {}",
+        max_units
+    );
+    let mut parts = Vec::new();
+    let mut units_left = max_units;
+    for (size, unit) in DURATIONS {
+        let count = seconds / size;
+        seconds %= size;
+        if count > 0 {
+            parts.push(format!("{}{}", count, unit));
+        }
+        if !parts.is_empty() {
+            units_left -= 1;
+            if units_left == 0 {
+                break;
+            }
+        }
+    }
+    if parts.is_empty() {
+        parts.push("0s".to_string());
+    }
+    Ok(Value::String(parts.join(" ").into()))
+}
+
+/// Shows one fractional digit with the suffixes `k`, `M`, `B`, and `T`, e.g. `"12.5k"`.
+pub fn humanize_count(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'humanize_count' expects a single number");
+    let count = args[0].as_float()?;
+    Ok(Value::String(scale(count, 1000.0, COUNTS, 1, "").into()))
+}
+
+#[test]
+fn humanize() {
+    let cases = [
+        ("humanize_bytes(0)", "0 B"),
+        ("humanize_bytes(999)", "999 B"),
+        ("humanize_bytes(1000)", "1 kB"),
+        ("humanize_bytes(1536000)", "1.5 MB"),
+        ("humanize_bytes(1536000, {binary: true})", "1.46 MiB"),
+        ("humanize_bytes(1024, {binary: true})", "1 KiB"),
+        ("humanize_bytes(999950)", "1 MB"),
+        ("humanize_bytes(2147483647)", "2.1 GB"),
+        ("humanize_bytes(0 - 1500)", "-1.5 kB"),
+        ("humanize_bytes(1.5e18)", "1.5 EB"),
+        ("humanize_bytes(1e21)", "1000 EB"),
+        ("humanize_duration(0)", "0s"),
+        ("humanize_duration(59)", "59s"),
+        ("humanize_duration(3600)", "1h"),
+        ("humanize_duration(93784)", "1d 2h 3m 4s"),
+        ("humanize_duration(93784, {max_units: 2})", "1d 2h"),
+        ("humanize_duration(86405, {max_units: 2})", "1d"),
+        ("humanize_duration(3661, {max_units: 1})", "1h"),
+        ("humanize_count(0)", "0"),
+        ("humanize_count(999)", "999"),
+        ("humanize_count(12500)", "12.5k"),
+        ("humanize_count(12000)", "12k"),
+        ("humanize_count(2500000)", "2.5M"),
+        ("humanize_count(0 - 12500)", "-12.5k"),
+        ("humanize_count(0.25)", "0.3"),
+    ];
+    for (expr, expected) in cases.iter() {
+        assert_eq!(
+            crate::parse_string(expr).unwrap(),
+            Value::String((*expected).into()),
+            "{}",
+            expr
+        );
+    }
+    let errors = [
+        ("humanize_duration(0 - 1)", "non-negative"),
+        ("humanize_duration(5, {max_units: 0})", "must be positive"),
+        ("humanize_duration(1.5)", "Type Mismatch"),
+        ("humanize_bytes(\"1\")", "Type Mismatch"),
+        ("humanize_count()", "expects a single number"),
+    ];
+    for (expr, message) in errors.iter() {
+        let err = crate::parse_string(expr).unwrap_err();
+        assert!(err.message.contains(message), "{}: {}", expr, err.message);
+    }
+}
@@ -1,35 +1,184 @@
-use std::fs::File;
-use std::io::Read;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::rc::Rc;
 
-use crate::compiler::{Error, Value};
+use crate::compiler::collation::{try_sort_by, Collation};
+use crate::compiler::methods::hashmap::sorted_entries;
+use crate::compiler::methods::string;
+use crate::compiler::operators::math;
+use crate::compiler::redact::Redaction;
+use crate::compiler::value::{format_float, Func, ValueString};
+use crate::compiler::{diff, evaluation, node, Error, Value};
+use crate::parser::MathOp;
+
+mod decimal;
+mod humanize;
+mod network;
 
 pub type FunctionSig = dyn Fn(&[Value]) -> Result<Value, Error>;
 
+/// The names of all builtin functions, e.g. for suggestions, in the order of `lookup`.
+pub const NAMES: &[&str] = &[
+    "read_file",
+    "getenv",
+    "getenv_any",
+    "getenv_int",
+    "getenv_bool",
+    "env_or_file",
+    "concat",
+    "format",
+    "merge",
+    "deep_merge",
+    "merge_deep",
+    "merge_by",
+    "zip_map",
+    "chunk_by",
+    "fold",
+    "map",
+    "filter",
+    "filter_map",
+    "unique",
+    "sort",
+    "length",
+    "reverse",
+    "sum",
+    "product",
+    "average",
+    "bounds",
+    "lazy",
+    "force",
+    "opt",
+    "cond",
+    "assert_subset",
+    "assert_eq",
+    "assert_ne",
+    "iterate",
+    "range",
+    "iterate_until",
+    "iterate_while",
+    "topo_sort",
+    "redact",
+    "require_data",
+    "canonicalize",
+    "parse_args",
+    "enum_map",
+    "semver_satisfies",
+    "decimal_add",
+    "decimal_mul",
+    "decimal_cmp",
+    "decimal_round",
+    "is_ipv4",
+    "is_ipv6",
+    "is_cidr",
+    "cidr_contains",
+    "humanize_bytes",
+    "humanize_duration",
+    "humanize_count",
+];
+
 pub fn lookup(function_name: &str) -> Option<&'static FunctionSig> {
     Some(match function_name {
         "read_file" => &read_file,
         "getenv" => &getenv,
+        "getenv_any" => &getenv_any,
+        "getenv_int" => &getenv_int,
+        "getenv_bool" => &getenv_bool,
+        "env_or_file" => &env_or_file,
         "concat" => &concat,
+        "format" => &format,
         "merge" => &merge,
+        "deep_merge" => &deep_merge,
+        "merge_deep" => &deep_merge,
+        "merge_by" => &merge_by,
+        "zip_map" => &zip_map,
+        "chunk_by" => &chunk_by,
         "fold" => &fold,
+        "map" => &map,
+        "filter" => &filter,
+        "filter_map" => &filter_map,
+        "unique" => &unique,
+        "sort" => &sort,
+        "length" => &length,
+        "reverse" => &reverse,
+        "sum" => &sum,
+        "product" => &product,
+        "average" => &average,
+        "bounds" => &bounds,
+        "lazy" => &lazy,
+        "force" => &force,
+        "opt" => &opt,
+        "cond" => &cond,
+        "assert_subset" => &assert_subset,
+        "assert_eq" => &assert_eq,
+        "assert_ne" => &assert_ne,
+        "iterate" => &iterate,
+        "range" => &range,
+        "iterate_until" => &iterate_until,
+        "iterate_while" => &iterate_while,
+        "topo_sort" => &topo_sort,
+        "redact" => &redact,
+        "require_data" => &require_data,
+        "canonicalize" => &canonicalize,
+        "parse_args" => &parse_args,
+        "enum_map" => &enum_map,
+        "semver_satisfies" => &semver_satisfies,
+        "decimal_add" => &decimal::decimal_add,
+        "decimal_mul" => &decimal::decimal_mul,
+        "decimal_cmp" => &decimal::decimal_cmp,
+        "decimal_round" => &decimal::decimal_round,
+        "is_ipv4" => &network::is_ipv4,
+        "is_ipv6" => &network::is_ipv6,
+        "is_cidr" => &network::is_cidr,
+        "cidr_contains" => &network::cidr_contains,
+        "humanize_bytes" => &humanize::humanize_bytes,
+        "humanize_duration" => &humanize::humanize_duration,
+        "humanize_count" => &humanize::humanize_count,
         _ => return None,
     })
 }
 
+#[test]
+fn function_names() {
+    for name in NAMES {
+        assert!(lookup(name).is_some(), "{}", name);
+    }
+    let hint = |s: &str| crate::parse_string(s).unwrap_err().hint;
+    assert_eq!(
+        hint("lenght([1])").as_deref(),
+        Some("did you mean 'length'?")
+    );
+    assert_eq!(
+        hint("let port = 1 in prot").as_deref(),
+        Some("did you mean 'port'?")
+    );
+    assert_eq!(hint("completely_unknown"), None);
+    assert!(hint(r#"read_file("does-not-exist.txt")"#)
+        .unwrap()
+        .contains("working directory"));
+    assert!(hint(r#""${[1]}""#).unwrap().contains("to_json()"));
+    assert_eq!(hint(r#""${(x) => x}""#), None);
+}
+
 fn read_file(args: &[Value]) -> Result<Value, Error> {
     check!(
         args.len() == 1,
         "'read_file' expects a single string argument"
     );
     let file_name = args[0].as_value_string()?;
-
-    let mut buf = String::new();
-    let mut f = File::open(file_name.as_ref())
-        .map_err(|e| anyhow!("Cannot open file '{}': {}", file_name, e))?;
-    f.read_to_string(&mut buf)
-        .map_err(|e| anyhow!("Cannot read file '{}': {}", file_name, e))?;
-    Ok(Value::String(buf.into()))
+    let content = evaluation::read_file(Path::new(file_name.as_ref())).map_err(|e| {
+        let error = Error::from(format!("Cannot read file '{}': {}", file_name, e));
+        if e.kind() == std::io::ErrorKind::NotFound {
+            error.with_hint(
+                "'read_file' resolves relative paths against the working directory, not the config file, \
+                 while 'import' paths are relative to the importing config",
+            )
+        } else {
+            error
+        }
+    })?;
+    Ok(Value::String(content.into()))
 }
 
 fn getenv(args: &[Value]) -> Result<Value, Error> {
@@ -38,30 +187,302 @@ fn getenv(args: &[Value]) -> Result<Value, Error> {
         "'getenv' expects a string argument with an optional default value"
     );
     let envname = args[0].as_value_string()?;
-    std::env::var(envname.as_ref())
-        .map(|x| Value::String(x.into()))
-        .or_else(|_| {
-            if args.len() == 2 {
-                Ok(args[1].clone())
-            } else {
-                Err(format!("Environment variable '{}' is not set", envname).into())
-            }
-        })
+    match evaluation::getenv(envname) {
+        Some(x) => Ok(Value::String(x.into())),
+        None if args.len() == 2 => {
+            evaluation::default_applied("getenv", Some(envname), &args[1]);
+            Ok(args[1].clone())
+        }
+        None => Err(format!("Environment variable '{}' is not set", envname).into()),
+    }
+}
+
+/// Returns the first of the environment variables that is set, e.g. to accept a deprecated name as well.
+fn getenv_any(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'getenv_any' expects a list of names with an optional default value"
+    );
+    let names = args[0]
+        .as_list()?
+        .iter()
+        .map(|x| x.as_value_string())
+        .collect::<Result<Vec<_>, _>>()?;
+    check!(!names.is_empty(), "'getenv_any' expects at least one name");
+    if let Some(x) = names.iter().find_map(|name| evaluation::getenv(name)) {
+        return Ok(Value::String(x.into()));
+    }
+    let quoted = names
+        .iter()
+        .map(|x| x.as_ref())
+        .collect::<Vec<&str>>()
+        .join("', '");
+    match args.get(1) {
+        Some(default) => {
+            evaluation::default_applied("getenv_any", Some(&quoted), default);
+            Ok(default.clone())
+        }
+        None => Err(format!("None of the environment variables '{}' is set", quoted).into()),
+    }
+}
+
+#[test]
+fn func_getenv_any() {
+    let eval = |s: &str| crate::parse_string(s);
+    std::env::set_var("SYCONF_TEST_ANY_OLD", "old");
+    std::env::set_var("SYCONF_TEST_ANY_NEW", "new");
+    assert_eq!(
+        eval(
+            r#"[
+                getenv_any(["SYCONF_TEST_ANY_NEW", "SYCONF_TEST_ANY_OLD"]),
+                getenv_any(["SYCONF_TEST_ANY_UNSET", "SYCONF_TEST_ANY_OLD"], "x"),
+                getenv_any(["SYCONF_TEST_ANY_UNSET"], "localhost"),
+            ]"#
+        )
+        .unwrap(),
+        eval(r#"["new", "old", "localhost"]"#).unwrap()
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#"getenv_any(["SYCONF_TEST_ANY_UNSET", "SYCONF_TEST_ANY_UNSET2"])"#),
+        "None of the environment variables 'SYCONF_TEST_ANY_UNSET', 'SYCONF_TEST_ANY_UNSET2' is set"
+    );
+    assert_eq!(
+        error("getenv_any([])"),
+        "'getenv_any' expects at least one name"
+    );
+    assert!(error(r#"getenv_any("SYCONF_TEST_ANY_NEW")"#).starts_with("Type Mismatch"));
+    std::env::remove_var("SYCONF_TEST_ANY_OLD");
+    std::env::remove_var("SYCONF_TEST_ANY_NEW");
+}
+
+fn getenv_int(args: &[Value]) -> Result<Value, Error> {
+    typed_getenv(
+        "getenv_int",
+        args,
+        "an int",
+        None,
+        |x| matches!(x, Value::Int(_)),
+        |x| x.trim().parse::<i64>().ok().map(Value::Int),
+    )
+}
+
+fn getenv_bool(args: &[Value]) -> Result<Value, Error> {
+    typed_getenv(
+        "getenv_bool",
+        args,
+        "a bool",
+        Some(string::BOOL_SPELLINGS),
+        |x| matches!(x, Value::Bool(_)),
+        |x| string::parse_bool(x).map(Value::Bool),
+    )
+}
+
+/// Like `getenv`, but parses the variable with `parse`, failing with the variable name if it is not `kind`,
+/// which is described by its name and optionally its accepted spellings.
+/// The default must be of the kind too, or `null`, and is returned as it is.
+fn typed_getenv(
+    name: &'static str,
+    args: &[Value],
+    kind: &str,
+    spellings: Option<&str>,
+    is_kind: impl Fn(&Value) -> bool,
+    parse: impl Fn(&str) -> Option<Value>,
+) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'{}' expects a string argument with an optional default value",
+        name
+    );
+    if let Some(default) = args.get(1) {
+        check!(
+            is_kind(default) || matches!(default, Value::Null),
+            "'{}' default value must be {} or null, got {}",
+            name,
+            kind,
+            default.summary()
+        );
+    }
+    let envname = args[0].as_value_string()?;
+    match evaluation::getenv(envname) {
+        Some(x) => parse(&x).ok_or_else(|| {
+            let expected = spellings
+                .map(|s| format!(", expected {}", s))
+                .unwrap_or_default();
+            format!(
+                "Environment variable '{}' is not {}, got {:?}{}",
+                envname, kind, x, expected
+            )
+            .into()
+        }),
+        None if args.len() == 2 => {
+            evaluation::default_applied(name, Some(envname), &args[1]);
+            Ok(args[1].clone())
+        }
+        None => Err(format!("Environment variable '{}' is not set", envname).into()),
+    }
+}
+
+/// Reads `$NAME`, or if it is not set the file named by `$NAME_FILE`, the convention of Docker secrets.
+/// Trailing line breaks of the file are removed.
+fn env_or_file(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'env_or_file' expects the name of an environment variable"
+    );
+    let envname = args[0].as_value_string()?;
+    if let Some(x) = evaluation::getenv(envname) {
+        return Ok(Value::String(x.into()));
+    }
+    let file_var = format!("{}_FILE", envname);
+    let file_name = evaluation::getenv(&file_var).ok_or_else(|| {
+        anyhow!(
+            "Neither environment variable '{}' nor '{}' is set",
+            envname,
+            file_var
+        )
+    })?;
+    let content = evaluation::read_file(Path::new(&file_name)).map_err(|e| {
+        anyhow!(
+            "Cannot read file '{}' named by '{}': {}",
+            file_name,
+            file_var,
+            e
+        )
+    })?;
+    Ok(Value::String(
+        content.trim_end_matches(&['\r', '\n'][..]).into(),
+    ))
+}
+
+#[test]
+fn func_typed_getenv() {
+    let eval = |s: &str| crate::parse_string(s);
+    let error = |s: &str| eval(s).unwrap_err().message;
+    std::env::set_var("SYCONF_TEST_TYPED_INT", " 4000000000\n");
+    std::env::set_var("SYCONF_TEST_TYPED_BOOL", "Yes");
+    std::env::set_var("SYCONF_TEST_TYPED_BAD", "80x");
+    assert_eq!(
+        eval(
+            r#"[
+                getenv_int("SYCONF_TEST_TYPED_INT"),
+                getenv_int("SYCONF_TEST_TYPED_UNSET", 8080),
+                getenv_bool("SYCONF_TEST_TYPED_BOOL"),
+                getenv_bool("SYCONF_TEST_TYPED_UNSET", false),
+            ]"#
+        )
+        .unwrap(),
+        eval("[4000000000, 8080, true, false]").unwrap()
+    );
+    assert_eq!(
+        error(r#"getenv_int("SYCONF_TEST_TYPED_BAD", 1)"#),
+        r#"Environment variable 'SYCONF_TEST_TYPED_BAD' is not an int, got "80x""#
+    );
+    assert_eq!(
+        error(r#"getenv_bool("SYCONF_TEST_TYPED_BAD")"#),
+        r#"Environment variable 'SYCONF_TEST_TYPED_BAD' is not a bool, got "80x", expected true, false, yes, no, on, off, 1, or 0"#
+    );
+    assert_eq!(
+        error(r#"getenv_int("SYCONF_TEST_TYPED_UNSET", "x")"#),
+        r#"'getenv_int' default value must be an int or null, got "x""#
+    );
+    assert_eq!(
+        error(r#"getenv_bool("SYCONF_TEST_TYPED_BOOL", "no")"#),
+        r#"'getenv_bool' default value must be a bool or null, got "no""#
+    );
+    assert_eq!(
+        eval(r#"getenv_int("SYCONF_TEST_TYPED_UNSET", null)"#).unwrap(),
+        Value::Null
+    );
+    assert_eq!(
+        error(r#"getenv_int("SYCONF_TEST_TYPED_UNSET")"#),
+        "Environment variable 'SYCONF_TEST_TYPED_UNSET' is not set"
+    );
+    std::env::remove_var("SYCONF_TEST_TYPED_INT");
+    std::env::remove_var("SYCONF_TEST_TYPED_BOOL");
+    std::env::remove_var("SYCONF_TEST_TYPED_BAD");
+}
+
+#[test]
+fn func_env_or_file() {
+    let eval = |s: &str| crate::parse_string(s);
+    let file = std::env::temp_dir().join(format!("syconf-env-or-file-{}", std::process::id()));
+    std::fs::write(&file, "s3cret\r\n").unwrap();
+    std::env::set_var("SYCONF_TEST_SECRET_FILE", &file);
+    assert_eq!(
+        eval(r#"env_or_file("SYCONF_TEST_SECRET")"#).unwrap(),
+        Value::String("s3cret".into())
+    );
+    std::env::set_var("SYCONF_TEST_SECRET", "from env");
+    assert_eq!(
+        eval(r#"env_or_file("SYCONF_TEST_SECRET")"#).unwrap(),
+        Value::String("from env".into())
+    );
+    std::env::remove_var("SYCONF_TEST_SECRET");
+    std::fs::remove_file(&file).unwrap();
+    assert!(eval(r#"env_or_file("SYCONF_TEST_SECRET")"#)
+        .unwrap_err()
+        .message
+        .starts_with("Cannot read file"));
+    std::env::remove_var("SYCONF_TEST_SECRET_FILE");
+    assert_eq!(
+        eval(r#"env_or_file("SYCONF_TEST_SECRET")"#)
+            .unwrap_err()
+            .message,
+        "Neither environment variable 'SYCONF_TEST_SECRET' nor 'SYCONF_TEST_SECRET_FILE' is set"
+    );
 }
 
 pub fn concat_strings(args: &[Value]) -> Result<Value, Error> {
     let mut out = String::new();
-    for s in args {
-        match s {
-            Value::String(s) => out.push_str(s),
-            Value::Int(x) => out.push_str(x.to_string().as_str()),
-            Value::Bool(x) => out.push_str(x.to_string().as_str()),
-            _ => return Err("Cannot format a non-primitive type".into()),
-        }
+    for x in args {
+        out.push_str(&interpolated(x)?);
     }
     Ok(Value::String(out.into()))
 }
 
+/// Converts a value interpolated by `${...}`, the call is located at the interpolated expression.
+pub fn interpolate(args: &[Value]) -> Result<Value, Error> {
+    match &args[0] {
+        x @ Value::String(_) => interpolated(x).map(|_| x.clone()),
+        x => Ok(Value::String(interpolated(x)?.into())),
+    }
+}
+
+/// Numbers and bools are formatted, other values than strings need an explicit conversion.
+fn interpolated(value: &Value) -> Result<String, Error> {
+    let limit = evaluation::options().max_interpolation_size;
+    let kind = match value {
+        Value::String(s) => {
+            check!(
+                s.len() <= limit,
+                "Interpolated value is {} bytes long, which exceeds the limit of {} bytes",
+                s.len(),
+                limit
+            );
+            return Ok(s.to_string());
+        }
+        Value::Int(x) => return Ok(x.to_string()),
+        Value::Float(x) => return Ok(format_float(*x)),
+        Value::Bool(x) => return Ok(x.to_string()),
+        Value::Null => return Err("Cannot interpolate null into a string".into()),
+        Value::HashMap(_) => "a hashmap",
+        Value::List(_) => "a list",
+        Value::Func(_) => "a function",
+    };
+    let err = Error::from(format!(
+        "Cannot interpolate {} into a string, got {}",
+        kind,
+        value.summary()
+    ));
+    Err(match value {
+        Value::Func(_) => err,
+        _ => err.with_hint(
+            "convert it explicitly, e.g. with 'x.to_string()', 'x.to_json()', or 'format(\"{}\", x)'",
+        ),
+    })
+}
+
 #[test]
 fn func_concat_strings() {
     assert_eq!(
@@ -75,9 +496,134 @@ fn func_concat_strings() {
         .unwrap(),
         Value::String("Name: mike".into())
     );
+    assert_eq!(
+        crate::parse_string(r#""${1.5} ${2.0} ${1e20}""#).unwrap(),
+        Value::String("1.5 2.0 100000000000000000000.0".into())
+    );
+    let err = crate::parse_string(r#"let x = null in "x: ${x}""#).unwrap_err();
+    assert_eq!(err.message, "Cannot interpolate null into a string");
+    // The error points to the interpolation that is not a primitive.
+    let err = crate::parse_string("let hosts = ['a', 'b'] in\n\"${1} ${hosts}\"").unwrap_err();
+    assert!(
+        err.to_string().starts_with(
+            "<input_string>:2:9: Cannot interpolate a list into a string, got [\"a\", \"b\"]\n"
+        ),
+        "{}",
+        err
+    );
+    assert!(err.hint.unwrap().contains("x.to_string()"));
+    let err = crate::parse_string("\"a ${{b: 1}}\"").unwrap_err();
+    assert!(
+        err.to_string().starts_with(
+            "<input_string>:1:6: Cannot interpolate a hashmap into a string, got {b: 1}\n"
+        ),
+        "{}",
+        err
+    );
+}
+
+/// Converts any value but a function, hashmaps and lists are written like syconf literals with sorted keys,
+/// so that the result is the same on every run.
+pub(crate) fn stringify(value: &Value) -> Result<String, Error> {
+    fn contains_func(value: &Value) -> bool {
+        match value {
+            Value::Func(_) => true,
+            Value::List(list) => list.iter().any(contains_func),
+            Value::HashMap(hm) => hm.values().any(contains_func),
+            _ => false,
+        }
+    }
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        Value::Func(_) => Err("Cannot convert a function to a string".into()),
+        x if contains_func(x) => Err(format!(
+            "Cannot convert {} to a string, it contains a function",
+            x.summary()
+        )
+        .into()),
+        x => Ok(x.to_string()),
+    }
+}
+
+/// Replaces every `{}` of the format string with the next value converted like by `to_string`,
+/// `{{` and `}}` are literal braces.
+fn format(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty(),
+        "'format' expects a format string and the values of its placeholders"
+    );
+    let fmt = args[0].as_value_string()?;
+    let values = &args[1..];
+    let mut out = String::with_capacity(fmt.len());
+    let mut next = 0;
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => out.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => out.push('}'),
+            '{' if chars.next_if_eq(&'}').is_some() => {
+                let value = values.get(next).ok_or_else(|| {
+                    anyhow!(
+                        "'format' has more placeholders than the {} values given",
+                        values.len()
+                    )
+                })?;
+                next += 1;
+                let s = stringify(value).map_err(|e| {
+                    anyhow!("'format' cannot convert the value {}: {}", next, e.message)
+                })?;
+                out.push_str(&s);
+            }
+            '{' | '}' => {
+                return Err(format!(
+                    "'format' found an unmatched '{}', use '{}{}' for a literal brace",
+                    c, c, c
+                )
+                .into())
+            }
+            c => out.push(c),
+        }
+    }
+    check!(
+        next == values.len(),
+        "'format' got {} values for {} placeholders",
+        values.len(),
+        next
+    );
+    Ok(Value::String(out.into()))
+}
+
+#[test]
+fn func_format() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"format("{}:{} {} {}", "db", 5432, ["a", "b"], {b: null, a: 1.5})"#).unwrap(),
+        Value::String(r#"db:5432 ["a", "b"] {a: 1.5, b: null}"#.into())
+    );
+    assert_eq!(
+        eval(r#"[format("{{}} {}", 1), format("no placeholders"), format("")]"#).unwrap(),
+        eval(r#"["{} 1", "no placeholders", ""]"#).unwrap()
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#"format("{} {}", 1)"#),
+        "'format' has more placeholders than the 1 values given"
+    );
+    assert_eq!(
+        error(r#"format("{}", 1, 2)"#),
+        "'format' got 2 values for 1 placeholders"
+    );
+    assert_eq!(
+        error(r#"format("{} {}", 1, (x) => x)"#),
+        "'format' cannot convert the value 2: Cannot convert a function to a string"
+    );
+    assert_eq!(
+        error(r#"format("a { b", 1)"#),
+        "'format' found an unmatched '{', use '{{' for a literal brace"
+    );
 }
 
-fn concat(args: &[Value]) -> Result<Value, Error> {
+pub fn concat(args: &[Value]) -> Result<Value, Error> {
     check!(
         !args.is_empty(),
         "Concat requires at least one argument as a list"
@@ -98,7 +644,87 @@ fn func_concat() {
     );
 }
 
-fn merge(args: &[Value]) -> Result<Value, Error> {
+/// Checks the value of `...x` in a list literal.
+pub fn spread_list(args: &[Value]) -> Result<Value, Error> {
+    match &args[0] {
+        x @ Value::List(_) => Ok(x.clone()),
+        x => Err(format!("Only lists can be spread into a list, got {}", x.summary()).into()),
+    }
+}
+
+/// Checks the value of `...x` in a hashmap literal.
+pub fn spread_hashmap(args: &[Value]) -> Result<Value, Error> {
+    match &args[0] {
+        x @ Value::HashMap(_) => Ok(x.clone()),
+        x => Err(format!(
+            "Only hashmaps can be spread into a hashmap, got {}",
+            x.summary()
+        )
+        .into()),
+    }
+}
+
+#[test]
+fn spread() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(
+            r#"
+    let base = ["--quiet", "--color"]
+    let defaults = {host: "localhost", port: 80}
+    in
+    [
+        [...base, "--verbose"],
+        [0, ...base, ...[], ...[1, 2], 3],
+        [...[]],
+        {...defaults, port: 8080},
+        {port: 8080, ...defaults},
+        {...defaults, ...{port: 1}, ...{port: 2, tls: true}},
+        {...{}},
+    ]
+    "#
+        )
+        .unwrap(),
+        eval(
+            r#"[
+        ["--quiet", "--color", "--verbose"],
+        [0, "--quiet", "--color", 1, 2, 3],
+        [],
+        {host: "localhost", port: 8080},
+        {host: "localhost", port: 80},
+        {host: "localhost", port: 2, tls: true},
+        {},
+    ]"#
+        )
+        .unwrap()
+    );
+    let err = eval("let x = {a: 1} in\n[1, ...x]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<input_string>:2:8: Only lists can be spread into a list, got {a: 1}"
+    );
+    let err = eval("{a: 1, ...[1]}").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<input_string>:1:11: Only hashmaps can be spread into a hashmap, got [1]"
+    );
+    let err = eval(r#"import "/lib.sy" with {...{a: 1}}"#).unwrap_err();
+    assert_eq!(err.message, "Import bindings cannot be spread");
+}
+
+pub fn merge(args: &[Value]) -> Result<Value, Error> {
+    let hm_list = hashmaps_to_merge(args)?;
+    let mut out = hm_list[0].as_hashmap()?.clone();
+    for x in &hm_list[1..] {
+        let li = x.as_hashmap()?.clone();
+        out.extend(li);
+    }
+    Ok(Value::HashMap(Rc::new(out)))
+}
+
+/// Unwraps the arguments of the merge functions,
+/// which accept either multiple hashmaps or a single list of hashmaps.
+fn hashmaps_to_merge(args: &[Value]) -> Result<&[Value], Error> {
     check!(
         !args.is_empty(),
         "Merge requires at least one argument as a hashmap or a list of hashmaps"
@@ -112,17 +738,13 @@ fn merge(args: &[Value]) -> Result<Value, Error> {
     } else {
         args
     };
-    let mut out = hm_list[0].as_hashmap()?.clone();
-    for x in &hm_list[1..] {
-        let li = x.as_hashmap()?.clone();
-        out.extend(li.into_iter());
-    }
-    Ok(Value::HashMap(Rc::new(out)))
+    check!(!hm_list.is_empty(), "Merge requires at least one hashmap");
+    Ok(hm_list)
 }
 
 #[test]
 fn func_merge() {
-    let mut hm = std::collections::HashMap::new();
+    let mut hm = HashMap::new();
     hm.insert("name".into(), Value::String("alexei".into()));
     hm.insert("age".into(), Value::Int(40));
     assert_eq!(
@@ -149,48 +771,1806 @@ fn func_merge() {
     );
 }
 
-fn fold(args: &[Value]) -> Result<Value, Error> {
+/// `merge_deep` is an alias.
+pub fn deep_merge(args: &[Value]) -> Result<Value, Error> {
+    let hm_list = hashmaps_to_merge(args)?;
+    let mut out = hm_list[0].as_hashmap()?.clone();
+    for x in &hm_list[1..] {
+        deep_merge_into(&mut out, x.as_hashmap()?);
+    }
+    Ok(Value::HashMap(Rc::new(out)))
+}
+
+/// Nested hashmaps are merged recursively, any other value (including lists) replaces the previous one.
+fn deep_merge_into(out: &mut HashMap<ValueString, Value>, hm: &HashMap<ValueString, Value>) {
+    for (key, value) in hm {
+        match (out.get(key), value) {
+            (Some(Value::HashMap(base)), Value::HashMap(overlay)) => {
+                let mut merged = base.as_ref().clone();
+                deep_merge_into(&mut merged, overlay);
+                out.insert(key.clone(), Value::HashMap(Rc::new(merged)));
+            }
+            _ => {
+                out.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[test]
+fn func_deep_merge() {
+    assert_eq!(
+        crate::parse_string(
+            r#"deep_merge([
+        {db: {host: "x", port: 1}, tags: ["a"], name: "base"},
+        {db: {port: 2}, tags: ["b"]},
+        {db: {user: "u"}, name: {first: "n"}},
+    ]) == {db: {host: "x", port: 2, user: "u"}, tags: ["b"], name: {first: "n"}}"#
+        )
+        .unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        crate::parse_string(
+            r#"deep_merge({db: {host: "x"}}, {db: {port: 5432}}) == {db: {host: "x", port: 5432}}"#
+        )
+        .unwrap(),
+        Value::Bool(true)
+    );
+    // Lists are replaced by the right side, even if they contain hashmaps.
+    assert_eq!(
+        crate::parse_string(
+            r#"deep_merge({a: {list: [1, 2], hms: [{x: 1}]}}, {a: {list: [3], hms: [{y: 2}]}})"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"{a: {list: [3], hms: [{y: 2}]}}"#).unwrap()
+    );
+    // A hashmap and a non-hashmap are not merged, the right side wins.
+    assert_eq!(
+        crate::parse_string(r#"deep_merge({a: {b: 1}, c: 1}, {a: 2, c: {d: 1}})"#).unwrap(),
+        crate::parse_string(r#"{a: 2, c: {d: 1}}"#).unwrap()
+    );
+    assert_eq!(
+        crate::parse_string(r#"merge_deep({a: {b: 1}}, {a: {c: 2}}) == {a: {b: 1, c: 2}}"#)
+            .unwrap(),
+        Value::Bool(true)
+    );
+    assert!(crate::parse_string("deep_merge([])").is_err());
+}
+
+/// Groups the elements by `key(value)` and folds every group with `merge(a, b)` into its first element.
+/// The groups are returned in the order of their first elements.
+fn merge_by(args: &[Value]) -> Result<Value, Error> {
     check!(
         args.len() == 3,
-        "Fold requires 3 arguments (initial value, accumulation function, list or hashmap)"
+        "'merge_by' expects a list, a key function, and a merge function"
     );
-    let func = args[1].as_func()?;
-    match &args[2] {
-        Value::List(list) => {
-            let mut out = args[0].clone();
-            for (ix, val) in list.iter().enumerate() {
-                let args = &[out.clone(), Value::Int(ix as i32), val.clone()];
-                out = func.call(args)?;
+    let list = args[0].as_list()?;
+    let key_func = args[1].as_func()?;
+    let merge_func = args[2].as_func()?;
+    // Indices of the groups by the hash of their key, keys with equal hashes are compared for equality.
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut keys: Vec<Value> = Vec::new();
+    let mut out: Vec<Value> = Vec::new();
+    for val in list.iter() {
+        let key = key_func.call(std::slice::from_ref(val))?;
+        check!(
+            !matches!(key, Value::Func(_)),
+            "'merge_by' key function must not return a function"
+        );
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let bucket = seen.entry(hasher.finish()).or_default();
+        match bucket.iter().find(|ix| keys[**ix] == key) {
+            Some(ix) => out[*ix] = merge_func.call(&[out[*ix].clone(), val.clone()])?,
+            None => {
+                bucket.push(out.len());
+                keys.push(key);
+                out.push(val.clone());
             }
-            Ok(out)
         }
-        Value::HashMap(hm) => {
-            let mut out = args[0].clone();
-            for (ix, val) in hm.iter() {
-                let args = &[out.clone(), Value::String(ix.clone()), val.clone()];
-                out = func.call(args)?;
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_merge_by() {
+    assert_eq!(
+        crate::parse_string(
+            r#"merge_by(
+        [
+            {name: "web", port: 80},
+            {name: "db", port: 5432},
+            {name: "web", tls: true},
+            {name: "cache"},
+            {name: "web", port: 8080},
+        ],
+        (x) => x.name,
+        (a, b) => merge(a, b),
+    )"#
+        )
+        .unwrap(),
+        crate::parse_string(
+            r#"[
+        {name: "web", port: 8080, tls: true},
+        {name: "db", port: 5432},
+        {name: "cache"},
+    ]"#
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        crate::parse_string("merge_by([1, 2, 3, 4, 5], (x) => x > 2, (a, b) => a + b)").unwrap(),
+        crate::parse_string("[3, 12]").unwrap()
+    );
+    assert_eq!(
+        crate::parse_string("merge_by([], (x) => x, (a, b) => a)").unwrap(),
+        crate::parse_string("[]").unwrap()
+    );
+    assert!(crate::parse_string("merge_by([1], (x) => (y) => y, (a, b) => a)").is_err());
+}
+
+/// Calls `func(key, a[key], b[key])` for the keys of both hashmaps in sorted order.
+/// Keys of only one hashmap are dropped, or fail with `{unmatched: "error"}`.
+fn zip_map(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3 || args.len() == 4,
+        "'zip_map' expects two hashmaps, a function, and an optional hashmap of options"
+    );
+    let a = args[0].as_hashmap()?;
+    let b = args[1].as_hashmap()?;
+    let func = args[2].as_func()?;
+    let mut strict = false;
+    if let Some(options) = args.get(3) {
+        for (key, val) in options.as_hashmap()? {
+            match (key.as_ref(), val) {
+                ("unmatched", Value::String(x)) if x.as_ref() == "drop" => strict = false,
+                ("unmatched", Value::String(x)) if x.as_ref() == "error" => strict = true,
+                _ => {
+                    return Err(
+                        format!("Unsupported 'zip_map' option {}: {}", key, val.summary()).into(),
+                    )
+                }
             }
-            Ok(out)
         }
-        _ => Err("3rd argument must be either a list or a hashmap".into()),
     }
+    if strict {
+        let only = |x: &HashMap<ValueString, Value>, y: &HashMap<ValueString, Value>| {
+            let mut keys: Vec<&str> = x
+                .keys()
+                .filter(|k| !y.contains_key(*k))
+                .map(|k| k.as_ref())
+                .collect();
+            keys.sort_unstable();
+            keys.join(", ")
+        };
+        for (x, y, which) in [(a, b, "first"), (b, a, "second")] {
+            let keys = only(x, y);
+            check!(
+                keys.is_empty(),
+                "'zip_map' found keys only in the {} hashmap: {}",
+                which,
+                keys
+            );
+        }
+    }
+    let mut keys: Vec<&ValueString> = a.keys().filter(|k| b.contains_key(*k)).collect();
+    keys.sort();
+    let mut out = HashMap::with_capacity(keys.len());
+    for key in keys {
+        let value = func.call(&[Value::String(key.clone()), a[key].clone(), b[key].clone()])?;
+        out.insert(key.clone(), value);
+    }
+    Ok(Value::HashMap(Rc::new(out)))
 }
 
 #[test]
-fn func_fold() {
+fn func_zip_map() {
+    let eval = |s: &str| crate::parse_string(s);
     assert_eq!(
-        crate::parse_string(r#"fold(0, (acc, ix, val) => acc + val, [1,2,3])"#).unwrap(),
-        Value::Int(6)
+        eval(r#"zip_map({a: 2, b: 3, c: 4}, {b: 10, c: 100, d: 1}, (k, w, n) => w * n)"#).unwrap(),
+        eval("{b: 30, c: 400}").unwrap()
     );
     assert_eq!(
-        crate::parse_string(
-            r#"fold(0, (acc, ix, val) => acc + val, {
-        aa: 1,
-        bb: 2,
-        cc: 3
-    })"#
-        )
-        .unwrap(),
+        eval(r#"zip_map({a: 1}, {a: 2}, (k, x, y) => "${k}=${x + y}", {unmatched: "error"})"#)
+            .unwrap(),
+        eval(r#"{a: "a=3"}"#).unwrap()
+    );
+    assert_eq!(
+        eval("zip_map({a: 1}, {b: 2}, (k, x, y) => x)").unwrap(),
+        eval("{}").unwrap()
+    );
+    assert_eq!(
+        eval(r#"zip_map({a: 1, c: 1, b: 1}, {b: 2, d: 2}, (k, x, y) => x, {unmatched: "error"})"#)
+            .unwrap_err()
+            .message,
+        "'zip_map' found keys only in the first hashmap: a, c"
+    );
+    assert_eq!(
+        eval(r#"zip_map({b: 1}, {b: 2, d: 2}, (k, x, y) => x, {unmatched: "error"})"#)
+            .unwrap_err()
+            .message,
+        "'zip_map' found keys only in the second hashmap: d"
+    );
+    assert!(eval(r#"zip_map({}, {}, (k, x, y) => x, {unmatched: "keep"})"#).is_err());
+    assert!(eval("zip_map({a: 1}, [2], (k, x, y) => x)").is_err());
+}
+
+/// Unlike `merge_by`, only adjacent elements with equal keys end up in the same chunk.
+fn chunk_by(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'chunk_by' expects a list and a key function"
+    );
+    let list = args[0].as_list()?;
+    let key_func = args[1].as_func()?;
+    let mut chunks: Vec<Value> = Vec::new();
+    let mut chunk: Vec<Value> = Vec::new();
+    let mut last_key: Option<Value> = None;
+    for val in list.iter() {
+        let key = key_func.call(std::slice::from_ref(val))?;
+        check!(
+            !matches!(key, Value::Func(_)),
+            "'chunk_by' key function must not return a function"
+        );
+        if last_key.as_ref().is_some_and(|x| *x != key) {
+            chunks.push(Value::List(std::mem::take(&mut chunk).into()));
+        }
+        chunk.push(val.clone());
+        last_key = Some(key);
+    }
+    if !chunk.is_empty() {
+        chunks.push(Value::List(chunk.into()));
+    }
+    Ok(Value::List(chunks.into()))
+}
+
+#[test]
+fn func_chunk_by() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("chunk_by([1, 1, 2, 3, 3, 3, 1], (x) => x)").unwrap(),
+        eval("[[1, 1], [2], [3, 3, 3], [1]]").unwrap()
+    );
+    assert_eq!(
+        eval(
+            r#"chunk_by(
+            [{m: "10:01", l: "a"}, {m: "10:01", l: "b"}, {m: "10:02", l: "c"}],
+            (x) => x.m,
+        ).map((c) => c.map((x) => x.l))"#
+        )
+        .unwrap(),
+        eval(r#"[["a", "b"], ["c"]]"#).unwrap()
+    );
+    assert_eq!(eval("chunk_by([], (x) => x)").unwrap(), eval("[]").unwrap());
+    assert!(eval("chunk_by([1], (x) => (y) => y)").is_err());
+}
+
+fn fold(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3,
+        "Fold requires 3 arguments (initial value, accumulation function, list or hashmap)"
+    );
+    let func = args[1].as_func()?;
+    match &args[2] {
+        Value::List(list) => {
+            let mut out = args[0].clone();
+            for (ix, val) in list.iter().enumerate() {
+                let args = &[out.clone(), Value::Int(ix as i64), val.clone()];
+                out = func.call(args)?;
+            }
+            Ok(out)
+        }
+        Value::HashMap(hm) => {
+            let mut out = args[0].clone();
+            for (ix, val) in sorted_entries(hm) {
+                let args = &[out.clone(), Value::String(ix.clone()), val.clone()];
+                out = func.call(args)?;
+            }
+            Ok(out)
+        }
+        _ => Err("3rd argument must be either a list or a hashmap".into()),
+    }
+}
+
+#[test]
+fn func_fold() {
+    assert_eq!(
+        crate::parse_string(r#"fold(0, (acc, ix, val) => acc + val, [1,2,3])"#).unwrap(),
+        Value::Int(6)
+    );
+    assert_eq!(
+        crate::parse_string(
+            r#"fold(0, (acc, ix, val) => acc + val, {
+        aa: 1,
+        bb: 2,
+        cc: 3
+    })"#
+        )
+        .unwrap(),
         Value::Int(6)
     );
+    // Hashmap entries are visited in the order of their keys.
+    assert_eq!(
+        crate::parse_string(r#"fold("", (acc, k, v) => "${acc}${k}${v}", {b: 2, c: 3, a: 1})"#)
+            .unwrap(),
+        Value::String("a1b2c3".into())
+    );
+}
+
+fn map(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'map' expects 2 arguments (function, list or hashmap)"
+    );
+    let func = args[0].as_func()?;
+    match &args[1] {
+        Value::List(list) => {
+            let mut out = Vec::with_capacity(list.len());
+            for (ix, val) in list.iter().enumerate() {
+                out.push(func.call(&[Value::Int(ix as i64), val.clone()])?);
+            }
+            Ok(Value::List(out.into()))
+        }
+        Value::HashMap(hm) => {
+            let mut out = HashMap::with_capacity(hm.len());
+            for (key, val) in sorted_entries(hm) {
+                let mapped = func.call(&[Value::String(key.clone()), val.clone()])?;
+                out.insert(key.clone(), mapped);
+            }
+            Ok(Value::HashMap(Rc::new(out)))
+        }
+        x => Err(format!(
+            "'map' expects a list or a hashmap as the 2nd argument, got {}",
+            x.summary()
+        )
+        .into()),
+    }
+}
+
+#[test]
+fn func_map() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("map((i, v) => v + 1, [1,2,3]) == [2,3,4]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval("map((i, v) => i * v, [5, 6])").unwrap(),
+        eval("[0, 6]").unwrap()
+    );
+    assert_eq!(
+        eval(r#"map((k, v) => "${k}=${v}", {a: 1, b: 2})"#).unwrap(),
+        eval(r#"{a: "a=1", b: "b=2"}"#).unwrap()
+    );
+    assert_eq!(eval("map((i, v) => v, [])").unwrap(), eval("[]").unwrap());
+    assert!(eval(r#"map((i, v) => v, "abc")"#).is_err());
+}
+
+fn filter(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'filter' expects 2 arguments (predicate, list or hashmap)"
+    );
+    let predicate = args[0].as_func()?;
+    let matches = |key: Value, val: &Value| -> Result<bool, Error> {
+        match predicate.call(&[key, val.clone()])? {
+            Value::Bool(x) => Ok(x),
+            x => Err(format!("'filter' predicate must return a bool, got {}", x.summary()).into()),
+        }
+    };
+    match &args[1] {
+        Value::List(list) => {
+            let mut out = Vec::new();
+            for (ix, val) in list.iter().enumerate() {
+                if matches(Value::Int(ix as i64), val)? {
+                    out.push(val.clone());
+                }
+            }
+            Ok(Value::List(out.into()))
+        }
+        Value::HashMap(hm) => {
+            let mut out = HashMap::new();
+            for (key, val) in sorted_entries(hm) {
+                if matches(Value::String(key.clone()), val)? {
+                    out.insert(key.clone(), val.clone());
+                }
+            }
+            Ok(Value::HashMap(Rc::new(out)))
+        }
+        x => Err(format!(
+            "'filter' expects a list or a hashmap as the 2nd argument, got {}",
+            x.summary()
+        )
+        .into()),
+    }
+}
+
+#[test]
+fn func_filter() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("filter((i, v) => v > 1, [1,2,3]) == [2,3]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval(r#"filter((i, v) => i != 1, ["c", "b", "a"])"#).unwrap(),
+        eval(r#"["c", "a"]"#).unwrap()
+    );
+    assert_eq!(
+        eval(r#"filter((k, v) => k != "b" and v > 0, {a: 1, b: 2, c: 0})"#).unwrap(),
+        eval("{a: 1}").unwrap()
+    );
+    let err = eval("filter((i, v) => v, [1])").unwrap_err();
+    assert_eq!(err.message, "'filter' predicate must return a bool, got 1");
+    assert!(eval("filter((i, v) => true, 1)").is_err());
+}
+
+/// Strings are counted in Unicode scalar values, not bytes.
+fn length(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'length' expects a single string, list, or hashmap"
+    );
+    let len = match &args[0] {
+        Value::String(s) => s.chars().count(),
+        Value::List(list) => list.len(),
+        Value::HashMap(hm) => hm.len(),
+        x => {
+            return Err(format!(
+                "'length' expects a string, list, or hashmap, got {}",
+                x.summary()
+            )
+            .into())
+        }
+    };
+    Ok(Value::Int(len as i64))
+}
+
+#[test]
+fn func_length() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"[length("héllo"), length(""), length([1, [2, 3]]), length({a: 1, b: 2})]"#)
+            .unwrap(),
+        eval("[5, 0, 2, 2]").unwrap()
+    );
+    for x in &["1", "true", "(x) => x", "null"] {
+        let err = eval(&format!("length({})", x)).unwrap_err();
+        assert!(err.message.starts_with("'length' expects"), "{}", x);
+    }
+}
+
+/// Strings are reversed by Unicode scalar values, so combining marks end up before their base character.
+fn reverse(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'reverse' expects a single list or string");
+    match &args[0] {
+        Value::List(list) => Ok(Value::List(list.iter().rev().cloned().collect())),
+        Value::String(s) => Ok(Value::String(s.chars().rev().collect::<String>().into())),
+        x => Err(format!("'reverse' expects a list or a string, got {}", x.summary()).into()),
+    }
+}
+
+#[test]
+fn func_reverse() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"[reverse([1,2,3]) == [3,2,1], reverse("abc") == "cba"]"#).unwrap(),
+        eval("[true, true]").unwrap()
+    );
+    assert_eq!(
+        eval(r#"[reverse([]), reverse(""), reverse("añb€"), reverse([[1, 2], 3])]"#).unwrap(),
+        eval(r#"[[], "", "€bña", [3, [1, 2]]]"#).unwrap()
+    );
+    for x in &["{a: 1}", "1", "true"] {
+        let err = eval(&format!("reverse({})", x)).unwrap_err();
+        assert!(
+            err.message
+                .starts_with("'reverse' expects a list or a string"),
+            "{}",
+            x
+        );
+    }
+}
+
+/// Ints are added with overflow checks and stay ints, a single float makes the sum a float.
+fn sum(args: &[Value]) -> Result<Value, Error> {
+    aggregate("sum", args, Value::Int(0), &MathOp::Add)
+}
+
+fn product(args: &[Value]) -> Result<Value, Error> {
+    aggregate("product", args, Value::Int(1), &MathOp::Mul)
+}
+
+/// The mean is always a float, e.g. `average([1, 2]) == 1.5`.
+fn average(args: &[Value]) -> Result<Value, Error> {
+    let total = aggregate("average", args, Value::Float(0.0), &MathOp::Add)?;
+    let len = args[0].as_list()?.len();
+    check!(len > 0, "'average' of an empty list is undefined");
+    Ok(Value::Float(total.as_float()? / len as f64))
+}
+
+fn aggregate(name: &str, args: &[Value], init: Value, op: &MathOp) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'{}' expects a single list of numbers",
+        name
+    );
+    let list = args[0].as_list()?;
+    let mut out = init;
+    for (ix, x) in list.iter().enumerate() {
+        check!(
+            matches!(x, Value::Int(_) | Value::Float(_)),
+            "'{}' expects a list of numbers, but the element at {} is {}",
+            name,
+            ix,
+            x.summary()
+        );
+        out = math(op)(&[out, x.clone()])?;
+    }
+    Ok(out)
+}
+
+#[test]
+fn func_sum_product_average() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("[sum([1, 2, 3]), sum([]), product([2, 3, 4]), product([]), sum([1, 0.5])]").unwrap(),
+        eval("[6, 0, 24, 1, 1.5]").unwrap()
+    );
+    assert_eq!(
+        eval("[average([1, 2]), average([2, 4, 6]), average([0.5])]").unwrap(),
+        eval("[1.5, 4.0, 0.5]").unwrap()
+    );
+    assert_eq!(
+        eval("let w = [1, 3] in w.map((x) => (x * 1.0) / sum(w)) == [0.25, 0.75]").unwrap(),
+        Value::Bool(true)
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#"product([1, "2"])"#),
+        "'product' expects a list of numbers, but the element at 1 is \"2\""
+    );
+    assert_eq!(
+        error("sum([1, null])"),
+        "'sum' expects a list of numbers, but the element at 1 is null"
+    );
+    assert_eq!(
+        error("average([])"),
+        "'average' of an empty list is undefined"
+    );
+    assert!(error("sum([9223372036854775807, 1])").starts_with("Integer overflow"));
+}
+
+/// Returns `[min, max]` in a single pass, the elements keep their types, e.g. `bounds([2, 0.5, 7]) == [0.5, 7]`.
+fn bounds(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'bounds' expects a single list of numbers");
+    let list = args[0].as_list()?;
+    check!(!list.is_empty(), "'bounds' of an empty list is undefined");
+    let (mut min, mut max) = (&list[0], &list[0]);
+    for (ix, x) in list.iter().enumerate() {
+        check!(
+            matches!(x, Value::Int(_) | Value::Float(_)),
+            "'bounds' expects a list of numbers, but the element at {} is {}",
+            ix,
+            x.summary()
+        );
+        if x < min {
+            min = x;
+        }
+        if x > max {
+            max = x;
+        }
+    }
+    Ok(Value::List(vec![min.clone(), max.clone()].into()))
+}
+
+#[test]
+fn func_bounds() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("[bounds([3, 1, 2]), bounds([5]), bounds([2, 0.5, 7]), bounds([1.5, 1, 2.5])]")
+            .unwrap(),
+        eval("[[1, 3], [5, 5], [0.5, 7], [1, 2.5]]").unwrap()
+    );
+    assert_eq!(
+        eval("bounds([1, 1.0])[0].to_json()").unwrap(),
+        Value::String("1".into())
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error("bounds([])"),
+        "'bounds' of an empty list is undefined"
+    );
+    assert_eq!(
+        error(r#"bounds([1, "2"])"#),
+        "'bounds' expects a list of numbers, but the element at 1 is \"2\""
+    );
+}
+
+/// The thunk is a function value, so it cannot be serialized and must be forced first.
+fn lazy(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'lazy' expects a single function without arguments, e.g. 'lazy(() => expr)'"
+    );
+    Ok(Value::Func(Func::new_lazy(args[0].as_func()?)))
+}
+
+/// Values other than thunks are returned unchanged.
+fn force(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'force' expects a single value");
+    match &args[0] {
+        Value::Func(f) if f.is_lazy() => f.call(&[]),
+        x => Ok(x.clone()),
+    }
+}
+
+#[test]
+fn func_lazy() {
+    use std::cell::Cell;
+    thread_local!(static CALLS: Cell<i32> = const { Cell::new(0) });
+    fn expensive(_: &[Value]) -> Result<Value, Error> {
+        CALLS.with(|c| c.set(c.get() + 1));
+        Ok(Value::Int(42))
+    }
+    let thunk = lazy(&[Value::Func(Func::new_builtin(&expensive))]).unwrap();
+    assert_eq!(CALLS.with(Cell::get), 0);
+    for _ in 0..3 {
+        assert_eq!(force(std::slice::from_ref(&thunk)).unwrap(), Value::Int(42));
+    }
+    assert_eq!(CALLS.with(Cell::get), 1);
+
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("let t = lazy(() => 1 + 2) in force(t) * force(t) + force(5)").unwrap(),
+        Value::Int(14)
+    );
+    // Never forced, so the failing read does not run.
+    assert_eq!(
+        eval(r#"let t = lazy(() => read_file("missing.txt")) in "ok""#).unwrap(),
+        Value::String("ok".into())
+    );
+    assert_eq!(
+        eval(r#"{a: lazy(() => 1)}.to_json()"#).unwrap_err().message,
+        "Cannot convert to JSON: Cannot serialize a lazy value, use 'force(x)' to evaluate it"
+    );
+    assert!(eval("lazy(1)").is_err());
+}
+
+/// Marks a value that may be `null` as optional, it is returned unchanged.
+/// The optional methods like `x.map_opt(func)` are available on all values.
+fn opt(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'opt' expects a single value");
+    Ok(args[0].clone())
+}
+
+fn filter_map(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'filter_map' expects 2 arguments (list, function)"
+    );
+    let list = args[0].as_list()?;
+    let func = args[1].as_func()?;
+    let mut out = Vec::with_capacity(list.len());
+    for (ix, val) in list.iter().enumerate() {
+        match func.call(&[Value::Int(ix as i64), val.clone()])? {
+            Value::Null => {}
+            x => out.push(x),
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_filter_map() {
+    assert_eq!(
+        crate::parse_string(
+            r#"filter_map(["a", "tmp", "b"], (ix, x) => if x == "tmp" then null else "${ix}-${x}")"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"["0-a", "2-b"]"#).unwrap()
+    );
+    assert_eq!(
+        crate::parse_string("filter_map([1, null, 2], (ix, x) => x)").unwrap(),
+        crate::parse_string("[1, 2]").unwrap()
+    );
+}
+
+fn unique(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'unique' expects a single list argument");
+    let list = args[0].as_list()?;
+    // Indices of the kept values by their hash, values with equal hashes are compared for equality.
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::with_capacity(list.len());
+    let mut out: Vec<Value> = Vec::with_capacity(list.len());
+    for val in list {
+        check!(
+            !matches!(val, Value::Func(_)),
+            "'unique' cannot compare functions"
+        );
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        let bucket = seen.entry(hasher.finish()).or_default();
+        if !bucket.iter().any(|ix| out[*ix] == *val) {
+            bucket.push(out.len());
+            out.push(val.clone());
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_unique() {
+    assert_eq!(
+        crate::parse_string(
+            r#"unique([3, 1, 3, "3", {a: [1], b: 2}, {b: 2, a: [1]}, {a: [2], b: 2}, 1, true, null, null])"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"[3, 1, "3", {a: [1], b: 2}, {a: [2], b: 2}, true, null]"#).unwrap()
+    );
+    assert!(crate::parse_string("unique([(x) => x])").is_err());
+}
+
+/// Without a comparator, numbers, strings, and bools are sorted ascending, but not mixed, which fails before sorting.
+/// The comparator `cmp(a, b)` returns a negative int if `a` comes first, zero if equal, or a positive int.
+/// The sort is stable, a comparator that is not consistent gives an unspecified order.
+fn sort(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'sort' expects a list and an optional comparator function"
+    );
+    let list = args[0].as_list()?;
+    let sorted = match args.get(1) {
+        None => Collation::default()
+            .sort_keyed(list.iter().map(|x| (x.clone(), x.clone())).collect())?,
+        Some(cmp) => {
+            let cmp = cmp.as_func()?;
+            try_sort_by(list.to_vec(), |a, b| {
+                match cmp.call(&[a.clone(), b.clone()])? {
+                    Value::Int(x) => Ok(x.cmp(&0)),
+                    x => Err(
+                        format!("'sort' comparator must return an int, got {}", x.summary()).into(),
+                    ),
+                }
+            })?
+        }
+    };
+    Ok(Value::List(sorted.into()))
+}
+
+#[test]
+fn func_sort() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("sort([3, 1, 2]) == [1, 2, 3]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval(r#"[sort(["b", "a", "B"]), sort([2.5, 1, 2]), sort([])]"#).unwrap(),
+        eval(r#"[["B", "a", "b"], [1, 2, 2.5], []]"#).unwrap()
+    );
+    assert_eq!(
+        eval("sort([1, 3, 2], (a, b) => b - a)").unwrap(),
+        eval("[3, 2, 1]").unwrap()
+    );
+    // Stable: equal keys keep their order.
+    assert_eq!(
+        eval(r#"sort([{n: "b", v: 1}, {n: "a", v: 2}, {n: "b", v: 3}], (a, b) => if a.n == b.n then 0 else if a.n < b.n then 0 - 1 else 1)"#)
+            .unwrap(),
+        eval(r#"[{n: "a", v: 2}, {n: "b", v: 1}, {n: "b", v: 3}]"#).unwrap()
+    );
+    assert_eq!(
+        eval(r#"sort([1, "a"])"#).unwrap_err().message,
+        r#"Cannot compare 1 and "a""#
+    );
+    assert_eq!(
+        eval(r#"sort(range(0, 30).map((x) => if (x/2)*2 == x then x else "s${x}"))"#)
+            .unwrap_err()
+            .message,
+        r#"Cannot compare 0 and "s1""#
+    );
+    // A comparator that is not a total order gives some order instead of aborting.
+    let sorted = eval(
+        "sort(range(0,500).map((x) => (x*7919) - (x/37)*1000), (a, b) => if a > b*2 then 1 else 0 - 1)",
+    )
+    .unwrap();
+    assert_eq!(sorted.as_list().unwrap().len(), 500);
+    assert!(eval("sort([1, 2], (a, b) => true)")
+        .unwrap_err()
+        .message
+        .contains("must return an int"));
+}
+
+/// Calls with a literal list of branches are compiled into `NodeContent::Cond` and evaluated lazily.
+fn cond(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'cond' expects 2 arguments (list of [test, value] pairs, default)"
+    );
+    for branch in args[0].as_list()? {
+        match branch.as_list()? {
+            [Value::Bool(true), value] => return Ok(value.clone()),
+            [Value::Bool(false), _] => {}
+            [test, _] => return Err(cond_test_error(test).into()),
+            _ => return Err("'cond' branches must be [test, value] pairs".into()),
+        }
+    }
+    Ok(args[1].clone())
+}
+
+pub fn cond_test_error(test: &Value) -> String {
+    format!("'cond' test must be a bool, got {}", test.summary())
+}
+
+#[test]
+fn func_cond() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"let x = 5 in cond([[x < 3, "small"], [x < 10, "medium"]], "large")"#).unwrap(),
+        Value::String("medium".into())
+    );
+    assert_eq!(
+        eval(r#"cond([[false, 1]], "default")"#).unwrap(),
+        Value::String("default".into())
+    );
+    // The literal form evaluates tests and values lazily, so later branches may fail.
+    assert_eq!(
+        eval(r#"let hm = {a: 1} in cond([[true, hm.a], [hm.b, hm.b]], 0)"#).unwrap(),
+        Value::Int(1)
+    );
+    assert_eq!(
+        eval(r#"cond([[1, "x"]], "y")"#).unwrap_err().message,
+        "'cond' test must be a bool, got 1"
+    );
+    // A list given as a variable is handled by the builtin function.
+    assert_eq!(
+        eval(r#"let branches = [[false, 1], [true, 2]] in cond(branches, 3)"#).unwrap(),
+        Value::Int(2)
+    );
+    assert!(eval(r#"let branches = [["x", 1]] in cond(branches, 3)"#).is_err());
+    assert!(eval(r#"let branches = [[true]] in cond(branches, 3)"#).is_err());
+}
+
+fn assert_subset(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'assert_subset' expects 2 arguments (expected, actual)"
+    );
+    let differences = diff::subset_diff(&args[0], &args[1]);
+    check!(
+        differences.is_empty(),
+        "assert_subset failed with {} difference(s):\n{}",
+        differences.len(),
+        differences
+            .iter()
+            .map(|d| format!("  {}", d))
+            .collect::<Vec<String>>()
+            .join("\n")
+    );
+    Ok(args[1].clone())
+}
+
+#[test]
+fn func_assert_subset() {
+    assert_eq!(
+        crate::parse_string(r#"assert_subset({a: {b: 1}}, {a: {b: 1, c: 2}, d: 3}).d"#).unwrap(),
+        Value::Int(3)
+    );
+    let err = crate::parse_string(
+        r#"assert_subset({port: 80, db: {user: "admin", host: "x"}}, {port: 8080, db: {host: "x"}})"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.message,
+        "assert_subset failed with 2 difference(s):\n  db.user: missing, expected \"admin\"\n  port: expected 80, got 8080"
+    );
+}
+
+/// The options of `assert_eq` and `assert_ne`.
+struct AssertOptions {
+    /// Paths like `metadata.timestamp`, differences at these paths or below them are ignored.
+    ignore: Vec<String>,
+    /// The maximum number of differences listed in the error.
+    limit: usize,
+}
+
+impl AssertOptions {
+    fn from_value(name: &str, options: Option<&Value>) -> Result<Self, Error> {
+        let mut out = Self {
+            ignore: Vec::new(),
+            limit: 10,
+        };
+        let options = match options {
+            Some(x) => x.as_hashmap()?,
+            None => return Ok(out),
+        };
+        for (key, val) in options {
+            match (key.as_ref(), val) {
+                ("ignore", Value::List(paths)) => {
+                    out.ignore = paths
+                        .iter()
+                        .map(|x| Ok(x.as_value_string()?.to_string()))
+                        .collect::<Result<_, Error>>()?
+                }
+                ("limit", Value::Int(x)) if *x > 0 => out.limit = *x as usize,
+                _ => {
+                    return Err(
+                        format!("Unsupported '{}' option {}: {}", name, key, val.summary()).into(),
+                    )
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        self.ignore
+            .iter()
+            .any(|x| match path.strip_prefix(x.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('['),
+                None => false,
+            })
+    }
+
+    fn differences(
+        &self,
+        expected: &Value,
+        actual: &Value,
+    ) -> (Vec<diff::Difference>, Vec<diff::LengthMismatch>) {
+        let (mut differences, mut lengths) = diff::structural_diff_with_lengths(expected, actual);
+        differences.retain(|x| !self.is_ignored(&x.path));
+        lengths.retain(|x| !self.is_ignored(&x.path));
+        (differences, lengths)
+    }
+}
+
+/// Returns `actual` if it equals `expected`, otherwise fails listing the lists of different lengths
+/// and the first differing paths with both values.
+fn assert_eq(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2 || args.len() == 3,
+        "'assert_eq' expects 2 arguments (expected, actual) and an optional hashmap of options"
+    );
+    let options = AssertOptions::from_value("assert_eq", args.get(2))?;
+    let (differences, lengths) = options.differences(&args[0], &args[1]);
+    if differences.is_empty() {
+        return Ok(args[1].clone());
+    }
+    let mut lines: Vec<String> = lengths.iter().map(|x| format!("  {}", x)).collect();
+    lines.extend(
+        differences
+            .iter()
+            .take(options.limit)
+            .map(|x| format!("  {}", x)),
+    );
+    if differences.len() > options.limit {
+        lines.push(format!("  and {} more", differences.len() - options.limit));
+    }
+    Err(format!(
+        "assert_eq failed with {} difference(s):\n{}",
+        differences.len(),
+        lines.join("\n")
+    )
+    .into())
+}
+
+fn assert_ne(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2 || args.len() == 3,
+        "'assert_ne' expects 2 arguments (unexpected, actual) and an optional hashmap of options"
+    );
+    let options = AssertOptions::from_value("assert_ne", args.get(2))?;
+    check!(
+        !options.differences(&args[0], &args[1]).0.is_empty(),
+        "assert_ne failed, both values are {}",
+        args[1].summary()
+    );
+    Ok(args[1].clone())
+}
+
+#[test]
+fn func_assert_eq() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("assert_eq({a: [1, 2.0]}, {a: [1, 2]}).a[1]").unwrap(),
+        Value::Int(2)
+    );
+    assert_eq!(
+        eval(
+            r#"assert_eq(
+                {db: {host: "x", port: 1}, tags: ["a", "b", "c"], name: "api"},
+                {db: {port: 2, user: "u"}, tags: ["a", "x", "c", "d"], name: "api"},
+            )"#
+        )
+        .unwrap_err()
+        .message,
+        r#"assert_eq failed with 5 difference(s):
+  tags: lists differ in length 3 vs 4
+  db.host: missing, expected "x"
+  db.port: expected 1, got 2
+  db.user: unexpected "u"
+  tags[1]: expected "b", got "x"
+  tags[3]: unexpected "d""#
+    );
+    assert_eq!(
+        eval("assert_eq(1, 2)").unwrap_err().message,
+        "assert_eq failed with 1 difference(s):\n  <root>: expected 1, got 2"
+    );
+    assert_eq!(
+        eval("assert_eq(range(0, 5), [], {limit: 2})")
+            .unwrap_err()
+            .message,
+        "assert_eq failed with 5 difference(s):\n  <root>: lists differ in length 5 vs 0\n  \
+         [0]: missing, expected 0\n  [1]: missing, expected 1\n  and 3 more"
+    );
+
+    let ignore = r#"{ignore: ["metadata.timestamp", "items[1]", '["a b"]']}"#;
+    let expected = r#"{metadata: {timestamp: 1, name: "x"}, items: [1, 2], "a b": 1}"#;
+    assert!(eval(&format!(
+        r#"assert_eq({}, {{metadata: {{timestamp: 2, name: "x"}}, items: [1, 3], "a b": 2}}, {})"#,
+        expected, ignore
+    ))
+    .is_ok());
+    assert!(eval(&format!(
+        r#"assert_eq({}, {{metadata: {{name: "x"}}, items: [1], "a b": 1}}, {})"#,
+        expected, ignore
+    ))
+    .is_ok());
+    assert_eq!(
+        eval(&format!(
+            r#"assert_eq({}, {{metadata: {{timestamp: 2, name: "y"}}, items: [1, 2], "a b": 1}}, {})"#,
+            expected, ignore
+        ))
+        .unwrap_err()
+        .message,
+        "assert_eq failed with 1 difference(s):\n  metadata.name: expected \"x\", got \"y\""
+    );
+    assert!(eval(r#"assert_eq({ab: 1}, {ab: 2}, {ignore: ["a"]})"#).is_err());
+    assert!(eval(r#"assert_eq(1, 1, {ignore: "a"})"#).is_err());
+    assert!(eval("assert_eq(1, 1, {limit: 0})").is_err());
+
+    assert_eq!(eval("assert_ne({a: 1}, {a: 2}).a").unwrap(), Value::Int(2));
+    assert_eq!(
+        eval("assert_ne({a: 1}, {a: 1.0})").unwrap_err().message,
+        "assert_ne failed, both values are {a: 1.0}"
+    );
+    assert!(eval(r#"assert_ne({a: 1, t: 1}, {a: 1, t: 2}, {ignore: ["t"]})"#).is_err());
+}
+
+fn iterate(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3,
+        "'iterate' requires 3 arguments (count, function, initial value)"
+    );
+    let count = args[0].as_int()?;
+    check!(count >= 0, "'iterate' expects a non-negative count");
+    let func = args[1].as_func()?;
+    let mut acc = args[2].clone();
+    for ix in 0..count {
+        acc = func.call(&[acc, Value::Int(ix)])?;
+    }
+    Ok(acc)
+}
+
+#[test]
+fn func_iterate() {
+    assert_eq!(
+        crate::parse_string(r#"iterate(4, (acc, ix) => acc + ix, 10)"#).unwrap(),
+        Value::Int(16)
+    );
+    assert_eq!(
+        crate::parse_string(r#"iterate(0, (acc, ix) => acc + ix, "x")"#).unwrap(),
+        Value::String("x".into())
+    );
+}
+
+/// `range(end)`, `range(start, end)`, or `range(start, end, step)`, the end is exclusive.
+/// A step that goes away from the end gives an empty list. The elements are charged to the evaluation budget.
+fn range(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 3,
+        "'range' expects 1 to 3 int arguments (start, end, step)"
+    );
+    let ints = args
+        .iter()
+        .map(|x| match x {
+            Value::Int(x) => Ok(*x),
+            x => Err(format!(
+                "'range' expects int arguments, got {}",
+                x.summary()
+            )),
+        })
+        .collect::<Result<Vec<i64>, String>>()?;
+    let (start, end, step) = match ints.as_slice() {
+        [end] => (0, *end, 1),
+        [start, end] => (*start, *end, 1),
+        [start, end, step] => (*start, *end, *step),
+        _ => unreachable!(),
+    };
+    let (distance, step_size) = (i128::from(end) - i128::from(start), i128::from(step));
+    let len = if step != 0 && distance.signum() == step_size.signum() {
+        (distance + step_size - step_size.signum()) / step_size
+    } else {
+        0
+    };
+    node::charge_elements(len as u64)?;
+    let mut out = Vec::new();
+    let mut x = start;
+    while (step > 0 && x < end) || (step < 0 && x > end) {
+        out.push(Value::Int(x));
+        match x.checked_add(step) {
+            Some(next) => x = next,
+            None => break,
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_range() {
+    let eval = |s: &str| crate::parse_string(s);
+    let cases = [
+        ("range(3)", "[0, 1, 2]"),
+        ("range(2, 8, 2)", "[2, 4, 6]"),
+        ("range(2, 5)", "[2, 3, 4]"),
+        ("range(5, 2, 0 - 1)", "[5, 4, 3]"),
+        ("range(0)", "[]"),
+        ("range(0 - 2)", "[]"),
+        ("range(5, 2)", "[]"),
+        ("range(2, 5, 0 - 1)", "[]"),
+        ("range(2, 5, 0)", "[]"),
+        ("range(2147483646, 2147483647, 5)", "[2147483646]"),
+        (
+            "range(9223372036854775806, 9223372036854775807, 5)",
+            "[9223372036854775806]",
+        ),
+    ];
+    for (expr, expected) in cases.iter() {
+        assert_eq!(eval(expr).unwrap(), eval(expected).unwrap(), "{}", expr);
+    }
+    assert!(eval("range(1.5)").is_err());
+    assert!(eval(r#"range("3")"#).is_err());
+    assert!(eval("range(1, 2, 3, 4)").is_err());
+    assert_eq!(
+        eval("range(3000000000).len()").unwrap_err().message,
+        "Evaluation budget of 100000000 nodes exceeded"
+    );
+    // Below the budget in elements, but not in their memory, so it fails before allocating them.
+    assert_eq!(
+        eval("range(99999990).len()").unwrap_err().message,
+        "Evaluation budget of 100000000 nodes exceeded"
+    );
+    assert_eq!(eval("range(1000000).len()").unwrap(), Value::Int(1000000));
+    assert_eq!(
+        eval("range(0 - 9223372036854775807, 9223372036854775807, 4611686018427387904)").unwrap(),
+        eval("[0 - 9223372036854775807, 0 - 4611686018427387903, 1, 4611686018427387905]").unwrap()
+    );
+}
+
+fn iterate_until(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 4,
+        "'iterate_until' requires 4 arguments (predicate, step function, initial value, {{max: count}})"
+    );
+    let predicate = args[0].as_func()?;
+    let step = args[1].as_func()?;
+    let max = args[3]
+        .as_hashmap()?
+        .get("max")
+        .ok_or("'iterate_until' requires the 'max' option")?
+        .as_int()?;
+    repeat("iterate_until", &step, args[2].clone(), max, |acc| {
+        Ok(predicate.call(std::slice::from_ref(acc))?.as_bool()?)
+    })
+}
+
+/// Applies `step` until `done` holds, failing if it does not hold after `max` steps.
+fn repeat(
+    name: &str,
+    step: &Func,
+    initial: Value,
+    max: i64,
+    done: impl Fn(&Value) -> Result<bool, Error>,
+) -> Result<Value, Error> {
+    let mut acc = initial;
+    for _ in 0..max {
+        if done(&acc)? {
+            return Ok(acc);
+        }
+        acc = step.call(&[acc])?;
+    }
+    check!(
+        done(&acc)?,
+        "'{}' did not finish within {} iterations, last value: {}",
+        name,
+        max,
+        acc.summary()
+    );
+    Ok(acc)
+}
+
+#[test]
+fn func_iterate_until() {
+    assert_eq!(
+        crate::parse_string(r#"iterate_until((x) => x > 100, (x) => x * 2, 1, {max: 10})"#)
+            .unwrap(),
+        Value::Int(128)
+    );
+    let err = crate::parse_string(
+        r#"iterate_until((x) => x.n > 100, (x) => {n: x.n + 1}, {n: 0}, {max: 5})"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.message,
+        "'iterate_until' did not finish within 5 iterations, last value: {n: 5}"
+    );
+    assert!(crate::parse_string(r#"iterate_until((x) => true, (x) => x, 1, {})"#).is_err());
+}
+
+/// Iterations of `iterate_while` without the `max` option.
+const DEFAULT_MAX_ITERATIONS: i64 = 10_000;
+
+fn iterate_while(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3 || args.len() == 4,
+        "'iterate_while' requires 3 arguments (initial value, step function, predicate) and optional options"
+    );
+    let step = args[1].as_func()?;
+    let predicate = args[2].as_func()?;
+    let max = match args.get(3) {
+        Some(options) => match options.as_hashmap()?.get("max") {
+            Some(x) => x.as_int()?,
+            None => DEFAULT_MAX_ITERATIONS,
+        },
+        None => DEFAULT_MAX_ITERATIONS,
+    };
+    repeat("iterate_while", &step, args[0].clone(), max, |acc| {
+        Ok(!predicate.call(std::slice::from_ref(acc))?.as_bool()?)
+    })
+}
+
+#[test]
+fn func_iterate_while() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("iterate_while(1, (x) => x * 2, (x) => x < 100)").unwrap(),
+        Value::Int(128)
+    );
+    assert_eq!(
+        eval("iterate_while(128, (x) => x * 2, (x) => x < 100)").unwrap(),
+        Value::Int(128)
+    );
+    assert_eq!(
+        eval("iterate_while(0, (x) => x + 1, (x) => true)")
+            .unwrap_err()
+            .message,
+        "'iterate_while' did not finish within 10000 iterations, last value: 10000"
+    );
+    assert_eq!(
+        eval("iterate_while({n: 0}, (x) => {n: x.n + 1}, (x) => x.n < 10, {max: 3})")
+            .unwrap_err()
+            .message,
+        "'iterate_while' did not finish within 3 iterations, last value: {n: 3}"
+    );
+    assert!(eval("iterate_while(1, (x) => x, (x) => 1)").is_err());
+}
+
+fn topo_sort(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 3 || args.len() == 4,
+        "'topo_sort' requires 3 arguments (list, name function, dependencies function) and optional options"
+    );
+    let items = args[0].as_list()?;
+    let name_func = args[1].as_func()?;
+    let deps_func = args[2].as_func()?;
+    let ignore_unknown = match args.get(3) {
+        Some(options) => match options.as_hashmap()?.get("ignore_unknown") {
+            Some(x) => x.as_bool()?,
+            None => false,
+        },
+        None => false,
+    };
+
+    let mut names = Vec::with_capacity(items.len());
+    let mut indexes = HashMap::with_capacity(items.len());
+    for (ix, item) in items.iter().enumerate() {
+        let name = name_func
+            .call(std::slice::from_ref(item))?
+            .as_value_string()?
+            .clone();
+        check!(
+            indexes.insert(name.clone(), ix).is_none(),
+            "'topo_sort': duplicate name '{}'",
+            name
+        );
+        names.push(name);
+    }
+
+    let mut dependencies: Vec<BTreeSet<usize>> = Vec::with_capacity(items.len());
+    for (ix, item) in items.iter().enumerate() {
+        let mut deps = BTreeSet::new();
+        for dep in deps_func.call(std::slice::from_ref(item))?.as_list()? {
+            let dep = dep.as_value_string()?;
+            match indexes.get(dep) {
+                Some(dep_ix) => {
+                    deps.insert(*dep_ix);
+                }
+                None if ignore_unknown => {}
+                None => {
+                    let mut valid: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+                    valid.sort_unstable();
+                    return Err(format!(
+                        "'topo_sort': '{}' depends on unknown '{}', valid names are: {}",
+                        names[ix],
+                        dep,
+                        valid.join(", ")
+                    )
+                    .into());
+                }
+            }
+        }
+        dependencies.push(deps);
+    }
+
+    let mut dependents = vec![Vec::new(); items.len()];
+    for (ix, deps) in dependencies.iter().enumerate() {
+        for dep in deps {
+            dependents[*dep].push(ix);
+        }
+    }
+    let mut pending: Vec<usize> = dependencies.iter().map(BTreeSet::len).collect();
+    // Ready items are taken in their original order, so the output is deterministic.
+    let mut ready: BTreeSet<usize> = (0..items.len()).filter(|ix| pending[*ix] == 0).collect();
+    let mut out = Vec::with_capacity(items.len());
+    while let Some(ix) = ready.iter().next().cloned() {
+        ready.remove(&ix);
+        out.push(items[ix].clone());
+        for dependent in &dependents[ix] {
+            pending[*dependent] -= 1;
+            if pending[*dependent] == 0 {
+                ready.insert(*dependent);
+            }
+        }
+    }
+
+    if out.len() < items.len() {
+        // Every unsorted item depends on another unsorted item, so following them leads into a cycle.
+        let mut path = vec![(0..items.len()).find(|ix| pending[*ix] > 0).unwrap()];
+        loop {
+            let last = *path.last().unwrap();
+            let next = *dependencies[last]
+                .iter()
+                .find(|dep| pending[**dep] > 0)
+                .unwrap();
+            if let Some(start) = path.iter().position(|x| *x == next) {
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&next))
+                    .map(|ix| names[*ix].as_ref())
+                    .collect();
+                return Err(format!(
+                    "'topo_sort': dependency cycle detected: {}",
+                    cycle.join(" -> ")
+                )
+                .into());
+            }
+            path.push(next);
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_topo_sort() {
+    let sort = |items: &str| {
+        crate::parse_string(&format!(
+            "topo_sort({}, (x) => x.name, (x) => x.deps).map((x) => x.name)",
+            items
+        ))
+    };
+    // diamond
+    assert_eq!(
+        sort(
+            r#"[
+            {name: "app", deps: ["cache", "db"]},
+            {name: "cache", deps: ["net"]},
+            {name: "db", deps: ["net"]},
+            {name: "net", deps: []},
+        ]"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"["net", "cache", "db", "app"]"#).unwrap()
+    );
+    // multiple roots keep their original order
+    assert_eq!(
+        sort(
+            r#"[
+            {name: "b", deps: []},
+            {name: "c", deps: ["a"]},
+            {name: "a", deps: []},
+        ]"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"["b", "a", "c"]"#).unwrap()
+    );
+    assert_eq!(sort("[]").unwrap(), crate::parse_string("[]").unwrap());
+    assert_eq!(
+        sort(
+            r#"[
+            {name: "x", deps: []},
+            {name: "a", deps: ["c"]},
+            {name: "b", deps: ["a", "x"]},
+            {name: "c", deps: ["b"]},
+        ]"#
+        )
+        .unwrap_err()
+        .message,
+        "'topo_sort': dependency cycle detected: a -> c -> b -> a"
+    );
+    assert_eq!(
+        sort(r#"[{name: "a", deps: ["z"]}, {name: "b", deps: []}]"#)
+            .unwrap_err()
+            .message,
+        "'topo_sort': 'a' depends on unknown 'z', valid names are: a, b"
+    );
+    assert_eq!(
+        crate::parse_string(
+            r#"topo_sort([{name: "a", deps: ["z", "b"]}, {name: "b", deps: []}],
+                (x) => x.name, (x) => x.deps, {ignore_unknown: true}).map((x) => x.name)"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"["b", "a"]"#).unwrap()
+    );
+}
+
+fn require_data(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'require_data' expects a single argument");
+    check_data(&args[0], String::new())?;
+    Ok(args[0].clone())
+}
+
+fn check_data(value: &Value, path: String) -> Result<(), Error> {
+    match value {
+        Value::Func(_) => Err(format!(
+            "'require_data' found a function at {}",
+            if path.is_empty() { "<root>" } else { &path }
+        )
+        .into()),
+        Value::List(list) => list
+            .iter()
+            .enumerate()
+            .try_for_each(|(ix, x)| check_data(x, format!("{}[{}]", path, ix))),
+        Value::HashMap(hm) => {
+            let mut keys: Vec<&ValueString> = hm.keys().collect();
+            keys.sort();
+            keys.into_iter()
+                .try_for_each(|k| check_data(&hm[k], diff::key_path(&path, k)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn func_require_data() {
+    assert_eq!(
+        crate::parse_string(r#"require_data({a: [1, {b: "x"}], c: null})"#).unwrap(),
+        crate::parse_string(r#"{a: [1, {b: "x"}], c: null}"#).unwrap()
+    );
+    let err = |s: &str| crate::parse_string(s).unwrap_err().message;
+    assert_eq!(
+        err(r#"require_data({a: [1, {"b c": (x) => x}]})"#),
+        r#"'require_data' found a function at a[1]["b c"]"#
+    );
+    assert_eq!(
+        err(r#"require_data("".trim)"#),
+        "'require_data' found a function at <root>"
+    );
+}
+
+/// Hashmaps have no order of their own, their keys are sorted whenever they are serialized, and the builtins
+/// that call a function for each entry, e.g. `fold`, visit them sorted too, see `hashmap::sorted_entries`.
+/// So the copy only differs from `value` in the normalized floats.
+fn canonicalize(args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'canonicalize' expects a single argument");
+    canonical(&args[0], String::new())
+}
+
+fn canonical(value: &Value, path: String) -> Result<Value, Error> {
+    Ok(match value {
+        Value::Func(_) => {
+            return Err(format!(
+                "'canonicalize' found a function at {}",
+                if path.is_empty() { "<root>" } else { &path }
+            )
+            .into())
+        }
+        Value::Float(x) if *x == 0.0 => Value::Float(0.0),
+        Value::List(list) => Value::List(
+            list.iter()
+                .enumerate()
+                .map(|(ix, x)| canonical(x, format!("{}[{}]", path, ix)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::HashMap(hm) => Value::HashMap(Rc::new(
+            hm.iter()
+                .map(|(k, v)| Ok((k.clone(), canonical(v, diff::key_path(&path, k))?)))
+                .collect::<Result<_, Error>>()?,
+        )),
+        x => x.clone(),
+    })
+}
+
+#[test]
+fn func_canonicalize() {
+    let eval = |s: &str| crate::parse_string(s);
+    let value = eval(r#"canonicalize({z: [{b: 1, a: 0.0.negate()}], a: "x"})"#).unwrap();
+    assert_eq!(value, eval(r#"{a: "x", z: [{a: 0.0, b: 1}]}"#).unwrap());
+    assert_eq!(
+        value.to_json().unwrap(),
+        r#"{"a":"x","z":[{"a":0.0,"b":1}]}"#
+    );
+    // The same data built with other key and merge orders serializes to the same bytes.
+    let json = |s: &str| eval(&format!("canonicalize({}).to_json()", s)).unwrap();
+    assert_eq!(
+        json("{a: [3], b: {c: 0.0, d: 1}}"),
+        Value::String(r#"{"a":[3],"b":{"c":0.0,"d":1}}"#.into())
+    );
+    assert_eq!(
+        eval(
+            "fold([], (acc, k, v) => acc.append(k), canonicalize({b: 1, a: 2, c: 3, d: 4, e: 5}))"
+        )
+        .unwrap(),
+        eval(r#"["a", "b", "c", "d", "e"]"#).unwrap()
+    );
+    for other in &[
+        "{b: {d: 1, c: 0.0.negate()}, a: [3]}",
+        "deep_merge({b: {d: 1}}, {a: [3], b: {c: 0.0}})",
+        "deep_merge({a: [3], b: {c: 0.0.negate()}}, {b: {d: 1}})",
+        "merge({b: {c: 0.0, d: 1}}, {a: [3]})",
+    ] {
+        assert_eq!(
+            json(other),
+            json("{a: [3], b: {c: 0.0, d: 1}}"),
+            "{}",
+            other
+        );
+    }
+    assert!(matches!(
+        eval("canonicalize(0.0.negate())").unwrap(),
+        Value::Float(x) if x.is_sign_positive()
+    ));
+    assert_eq!(
+        eval(r#"canonicalize({a: [1, {"b c": (x) => x}]})"#)
+            .unwrap_err()
+            .message,
+        r#"'canonicalize' found a function at a[1]["b c"]"#
+    );
+}
+
+/// The inverse of `hm.to_args()` for the default style and `{style: "equals"}`. The values stay strings,
+/// a flag without a value is `true`, `--no-x` without a value is `x: false`, and repeated flags collect their values in a list.
+fn parse_args(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'parse_args' expects a single list argument"
+    );
+    let list = args[0].as_list()?;
+    let mut out: HashMap<ValueString, Value> = HashMap::new();
+    let mut ix = 0;
+    while ix < list.len() {
+        let arg = list[ix].as_value_string()?;
+        let flag = match arg.strip_prefix("--") {
+            Some(x) if !x.is_empty() && !x.starts_with('=') => x,
+            _ => {
+                return Err(format!(
+                    "'parse_args' expects a flag starting with '--' at {}, got {:?}",
+                    ix,
+                    arg.as_ref()
+                )
+                .into())
+            }
+        };
+        let next_is_value = list
+            .get(ix + 1)
+            .and_then(|x| x.as_value_string().ok())
+            .is_some_and(|x| !x.starts_with("--"));
+        let (key, value) = match flag.split_once('=') {
+            Some((key, value)) => (key, Value::String(value.into())),
+            None if next_is_value => {
+                ix += 1;
+                (flag, list[ix].clone())
+            }
+            None => match flag.strip_prefix("no-") {
+                Some(key) if !key.is_empty() => (key, Value::Bool(false)),
+                _ => (flag, Value::Bool(true)),
+            },
+        };
+        // The values are strings and bools, so a list is always from a repeated flag.
+        let key = ValueString::from(key);
+        let value = match out.remove(&key) {
+            Some(Value::List(prev)) => {
+                Value::List(prev.iter().cloned().chain(Some(value)).collect())
+            }
+            Some(prev) => Value::List(vec![prev, value].into()),
+            None => value,
+        };
+        out.insert(key, value);
+        ix += 1;
+    }
+    Ok(Value::HashMap(Rc::new(out)))
+}
+
+#[test]
+fn func_parse_args() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(
+        eval(
+            r#"parse_args(["--verbose", "--count", "3", "--name=x", "--tags", "a", "--tags=b", "--no-cache"])"#
+        ),
+        eval(r#"{verbose: true, count: "3", name: "x", tags: ["a", "b"], cache: false}"#)
+    );
+    assert_eq!(
+        eval(r#"parse_args(["--x", "--x", "--empty=", "--no-", "--list", "[1]"])"#),
+        eval(r#"{x: [true, true], empty: "", "no-": true, list: "[1]"}"#)
+    );
+    assert_eq!(eval("parse_args([])"), eval("{}"));
+    let config = r#"{verbose: true, name: "x", tags: ["a", "b"], debug: false}"#;
+    assert_eq!(
+        eval(&format!("parse_args({}.to_args())", config)),
+        eval(r#"{verbose: true, name: "x", tags: ["a", "b"]}"#)
+    );
+    assert_eq!(
+        eval(&format!(
+            r#"parse_args({}.to_args({{style: "equals", negate_false: true}}))"#,
+            config
+        )),
+        eval(config)
+    );
+    let err = |s: &str| crate::parse_string(s).unwrap_err().message;
+    assert_eq!(
+        err(r#"parse_args(["--a", "1", "2"])"#),
+        r#"'parse_args' expects a flag starting with '--' at 2, got "2""#
+    );
+    assert!(err(r#"parse_args(["--"])"#).contains("at 0"));
+    assert!(err(r#"parse_args(["-v"])"#).contains("at 0"));
+    assert!(crate::parse_string(r#"parse_args(["--a", 1])"#).is_err());
+}
+
+fn enum_map(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'enum_map' expects 2 arguments (allowed values, mapping)"
+    );
+    let allowed = args[0]
+        .as_list()?
+        .iter()
+        .map(|x| match x {
+            Value::String(s) => Ok(s.as_ref()),
+            x => Err(format!(
+                "'enum_map' allowed values must be strings, got {}",
+                x.summary()
+            )),
+        })
+        .collect::<Result<BTreeSet<&str>, String>>()?;
+    let mapping = args[1].as_hashmap()?;
+    let keys: BTreeSet<&str> = mapping.keys().map(|k| k.as_ref()).collect();
+    let missing: Vec<&str> = allowed.difference(&keys).copied().collect();
+    let extra: Vec<&str> = keys.difference(&allowed).copied().collect();
+    check!(
+        missing.is_empty() && extra.is_empty(),
+        "'enum_map' mapping does not match the allowed values, missing keys: [{}], extra keys: [{}]",
+        missing.join(", "),
+        extra.join(", ")
+    );
+    Ok(args[1].clone())
+}
+
+#[test]
+fn func_enum_map() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"enum_map(["info", "error"], {info: "log", error: "pager"})"#).unwrap(),
+        eval(r#"{info: "log", error: "pager"}"#).unwrap()
+    );
+    assert_eq!(
+        eval(r#"enum_map(["debug", "info", "warn"], {info: 1, error: 2})"#)
+            .unwrap_err()
+            .message,
+        "'enum_map' mapping does not match the allowed values, missing keys: [debug, warn], extra keys: [error]"
+    );
+    assert_eq!(
+        eval(r#"enum_map(["info"], {})"#).unwrap_err().message,
+        "'enum_map' mapping does not match the allowed values, missing keys: [info], extra keys: []"
+    );
+    assert_eq!(
+        eval(r#"enum_map(["info", 3], {info: 1})"#)
+            .unwrap_err()
+            .message,
+        "'enum_map' allowed values must be strings, got 3"
+    );
+}
+
+fn semver_satisfies(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'semver_satisfies' expects 2 arguments (version, constraint)"
+    );
+    let version = args[0].as_value_string()?;
+    let constraint = args[1].as_value_string()?;
+    let version = semver::Version::parse(version)
+        .map_err(|e| format!("Invalid version '{}': {}", version, e))?;
+    let constraint = semver::VersionReq::parse(constraint)
+        .map_err(|e| format!("Invalid version constraint '{}': {}", constraint, e))?;
+    Ok(Value::Bool(constraint.matches(&version)))
+}
+
+#[test]
+fn func_semver_satisfies() {
+    let satisfies = |version: &str, constraint: &str| {
+        crate::parse_string(&format!(
+            r#"semver_satisfies("{}", "{}")"#,
+            version, constraint
+        ))
+        .unwrap()
+        .as_bool()
+        .unwrap()
+    };
+    assert!(satisfies("1.4.0", ">=1.2, <2.0"));
+    assert!(!satisfies("2.0.0", ">=1.2, <2.0"));
+    assert!(satisfies("1.9.3", "^1.2"));
+    assert!(!satisfies("2.0.0", "^1.2"));
+    assert!(satisfies("0.2.5", "^0.2.3"));
+    assert!(!satisfies("0.3.0", "^0.2.3"));
+    assert!(satisfies("1.2.9", "~1.2.3"));
+    assert!(!satisfies("1.3.0", "~1.2.3"));
+    assert!(!satisfies("1.3.0-beta.1", ">=1.2"));
+
+    let err = |s: &str| crate::parse_string(s).unwrap_err().message;
+    assert!(err(r#"semver_satisfies("1.2", "^1")"#).starts_with("Invalid version '1.2'"));
+    assert!(
+        err(r#"semver_satisfies("1.2.0", ">>1")"#).starts_with("Invalid version constraint '>>1'")
+    );
+}
+
+fn redact(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'redact' expects 2 arguments (value, key patterns or options)"
+    );
+    Ok(Redaction::from_value(&args[1])?.apply(&args[0]))
+}
+
+#[test]
+fn func_redact() {
+    assert_eq!(
+        crate::parse_string(
+            r#"redact({db: {password: "p", db_secret: "s", user: "u"}, token: "t"},
+                ["password", "token", "*_secret"])"#
+        )
+        .unwrap(),
+        crate::parse_string(
+            r#"{db: {password: "***", db_secret: "***", user: "u"}, token: "***"}"#
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        crate::parse_string(
+            r#"redact({db: {password: "p"}, services: [{key: "k", name: "n"}], key: "x"},
+                {keys: ["password"], paths: ["services[0].key"]})"#
+        )
+        .unwrap(),
+        crate::parse_string(
+            r#"{db: {password: "***"}, services: [{key: "***", name: "n"}], key: "x"}"#
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        crate::parse_string(
+            r#"redact({"key[0]": "a", key0: "b", "key[1": "c"}, ["key\\[0]", "key[1"])"#
+        )
+        .unwrap(),
+        crate::parse_string(r#"{"key[0]": "***", key0: "b", "key[1": "***"}"#).unwrap()
+    );
+    assert!(crate::parse_string(r#"redact({}, {key: ["x"]})"#).is_err());
+    assert!(crate::parse_string(r#"redact({}, {paths: ["a..b"]})"#).is_err());
 }
@@ -0,0 +1,133 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::compiler::{Error, Value};
+
+/// A CIDR block like `10.0.0.0/8`, the address may have host bits set.
+struct Cidr {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl Cidr {
+    fn parse(s: &str) -> Option<Self> {
+        let ix = s.find('/')?;
+        let address: IpAddr = s[..ix].parse().ok()?;
+        let prefix = &s[ix + 1..];
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let prefix: u32 = prefix.parse().ok()?;
+        let max = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return None;
+        }
+        Some(Self { address, prefix })
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the string argument of the predicates, other values are never valid addresses.
+fn predicate_arg<'a>(name: &str, args: &'a [Value]) -> Result<Option<&'a str>, Error> {
+    check!(args.len() == 1, "'{}' expects a single argument", name);
+    Ok(match &args[0] {
+        Value::String(s) => Some(s),
+        _ => None,
+    })
+}
+
+pub fn is_ipv4(args: &[Value]) -> Result<Value, Error> {
+    let s = predicate_arg("is_ipv4", args)?;
+    Ok(Value::Bool(
+        s.is_some_and(|s| s.parse::<Ipv4Addr>().is_ok()),
+    ))
+}
+
+pub fn is_ipv6(args: &[Value]) -> Result<Value, Error> {
+    let s = predicate_arg("is_ipv6", args)?;
+    Ok(Value::Bool(
+        s.is_some_and(|s| s.parse::<Ipv6Addr>().is_ok()),
+    ))
+}
+
+pub fn is_cidr(args: &[Value]) -> Result<Value, Error> {
+    let s = predicate_arg("is_cidr", args)?;
+    Ok(Value::Bool(s.and_then(Cidr::parse).is_some()))
+}
+
+/// Addresses of the other IP version are not contained.
+pub fn cidr_contains(args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'cidr_contains' expects 2 arguments (cidr, ip)"
+    );
+    let cidr = args[0].as_value_string()?;
+    let cidr = Cidr::parse(cidr).ok_or_else(|| format!("Invalid CIDR block '{}'", cidr))?;
+    let ip = args[1].as_value_string()?;
+    let ip: IpAddr = ip
+        .parse()
+        .map_err(|_| format!("Invalid IP address '{}'", ip))?;
+    Ok(Value::Bool(cidr.contains(&ip)))
+}
+
+#[test]
+fn network_predicates() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(
+        eval(r#"[is_ipv4("10.0.0.1"), is_ipv4("10.0.0.256"), is_ipv4("::1"), is_ipv4(1)]"#),
+        eval("[true, false, false, false]")
+    );
+    assert_eq!(
+        eval(r#"[is_ipv6("::1"), is_ipv6("fe80::1:2"), is_ipv6("10.0.0.1"), is_ipv6("::g")]"#),
+        eval("[true, true, false, false]")
+    );
+    assert_eq!(
+        eval(
+            r#"[is_cidr("10.0.0.0/8"), is_cidr("::/0"), is_cidr("10.0.0.0/33"),
+                is_cidr("10.0.0.0"), is_cidr("10.0.0.0/"), is_cidr("10.0.0.0/+8")]"#
+        ),
+        eval("[true, true, false, false, false, false]")
+    );
+}
+
+#[test]
+fn network_cidr_contains() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(
+            r#"[cidr_contains("10.0.0.0/8", "10.1.2.3"), cidr_contains("10.0.0.0/8", "11.0.0.1"),
+                cidr_contains("192.168.1.7/24", "192.168.1.200"), cidr_contains("0.0.0.0/0", "1.2.3.4"),
+                cidr_contains("10.0.0.1/32", "10.0.0.2"), cidr_contains("2001:db8::/32", "2001:db8::1"),
+                cidr_contains("2001:db8::/32", "2001:db9::1"), cidr_contains("10.0.0.0/8", "::1")]"#
+        )
+        .unwrap(),
+        eval("[true, false, true, true, false, true, false, false]").unwrap()
+    );
+    assert_eq!(
+        eval(r#"cidr_contains("10.0.0.0/40", "10.0.0.1")"#)
+            .unwrap_err()
+            .message,
+        "Invalid CIDR block '10.0.0.0/40'"
+    );
+    assert_eq!(
+        eval(r#"cidr_contains("10.0.0.0/8", "10.0.0")"#)
+            .unwrap_err()
+            .message,
+        "Invalid IP address '10.0.0'"
+    );
+}
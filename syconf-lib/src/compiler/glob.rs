@@ -0,0 +1,174 @@
+/// A shell-like pattern, where `*` matches any sequence of characters, `?` matches a single character,
+/// and `[abc]`, `[a-z]`, or `[!abc]` match a single character of a class. A backslash matches the next character
+/// literally, e.g. `items\[0]` matches `items[0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob(Vec<Token>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Any,
+    One,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Char(char),
+}
+
+impl Glob {
+    /// Fails on unclosed or empty character classes, on reversed ranges like `[z-a]`, and on a trailing backslash.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        parse(pattern, true)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let pattern = &self.0;
+        let text: Vec<char> = text.chars().collect();
+        let (mut p, mut t) = (0, 0);
+        // Position of the last `*` in the pattern and the text position it started matching at.
+        let mut backtrack: Option<(usize, usize)> = None;
+        while t < text.len() {
+            match pattern.get(p) {
+                Some(Token::Any) => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                }
+                Some(token) if token.matches(text[t]) => {
+                    p += 1;
+                    t += 1;
+                }
+                _ => match backtrack {
+                    Some((star, start)) => {
+                        p = star + 1;
+                        t = start + 1;
+                        backtrack = Some((star, start + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        pattern[p..].iter().all(|x| *x == Token::Any)
+    }
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Any | Token::One => true,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) != *negated
+            }
+            Token::Char(x) => *x == c,
+        }
+    }
+}
+
+/// Unless `strict`, a `[` that does not start a valid class and a trailing backslash are matched literally.
+fn parse(pattern: &str, strict: bool) -> Result<Glob, String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut ix = 0;
+    while ix < chars.len() {
+        let token = match chars[ix] {
+            '*' => Token::Any,
+            '?' => Token::One,
+            '\\' => match chars.get(ix + 1) {
+                Some(c) => {
+                    ix += 1;
+                    Token::Char(*c)
+                }
+                None if strict => {
+                    return Err(format!(
+                        "Invalid glob pattern '{}': trailing backslash",
+                        pattern
+                    ))
+                }
+                None => Token::Char('\\'),
+            },
+            '[' => match parse_class(&chars[ix + 1..]) {
+                Ok((token, len)) => {
+                    ix += len;
+                    token
+                }
+                Err(e) if strict => {
+                    return Err(format!("Invalid glob pattern '{}': {}", pattern, e))
+                }
+                Err(_) => Token::Char('['),
+            },
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+        ix += 1;
+    }
+    Ok(Glob(tokens))
+}
+
+/// Parses the class after its `[`, returns the token and the number of characters including the closing `]`.
+fn parse_class(chars: &[char]) -> Result<(Token, usize), String> {
+    let negated = chars.first() == Some(&'!');
+    let start = usize::from(negated);
+    let end = chars[start..]
+        .iter()
+        .position(|c| *c == ']')
+        .map(|x| x + start)
+        .ok_or("unclosed character class")?;
+    let members = &chars[start..end];
+    if members.is_empty() {
+        return Err("empty character class".to_string());
+    }
+    let mut ranges = Vec::new();
+    let mut ix = 0;
+    while ix < members.len() {
+        if ix + 2 < members.len() && members[ix + 1] == '-' {
+            let (from, to) = (members[ix], members[ix + 2]);
+            if from > to {
+                return Err(format!("reversed range '{}-{}'", from, to));
+            }
+            ranges.push((from, to));
+            ix += 3;
+        } else {
+            ranges.push((members[ix], members[ix]));
+            ix += 1;
+        }
+    }
+    Ok((Token::Class { negated, ranges }, end + 1))
+}
+
+/// Like `Glob::new(pattern)?.matches(text)`, but a `[` that does not start a valid class and a trailing backslash
+/// are matched literally.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    parse(pattern, false)
+        .map(|glob| glob.matches(text))
+        .unwrap_or(false)
+}
+
+#[test]
+fn glob_matches() {
+    assert!(matches("*_secret", "db_secret"));
+    assert!(matches("*_secret", "_secret"));
+    assert!(!matches("*_secret", "db_secrets"));
+    assert!(matches("pass?ord", "password"));
+    assert!(matches("a*b*c", "aXbYbZc"));
+    assert!(matches("*", ""));
+    assert!(!matches("?", ""));
+    assert!(matches("tök?n", "tökén"));
+    assert!(matches("host[0-9][0-9]", "host42"));
+    assert!(!matches("host[0-9]", "hostx"));
+    assert!(matches("[!.]*.sy", "app.sy"));
+    assert!(!matches("[!.]*.sy", ".hidden.sy"));
+    assert!(matches("[abc-]", "-"));
+    assert!(matches("a[b", "a[b"));
+    assert!(!matches("a[]", "a"));
+    assert!(matches(r"items\[0]", "items[0]"));
+    assert!(!matches(r"items\[0]", "items0"));
+    assert!(matches(r"\*\?\\", r"*?\"));
+    assert!(!matches(r"\*", "a"));
+    assert!(matches("a\\", "a\\"));
+}
+
+#[test]
+fn glob_invalid() {
+    for pattern in &["a[b", "[]", "[!]", "[z-a]", "a\\"] {
+        assert!(Glob::new(pattern).is_err(), "{}", pattern);
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::compiler::{Location, Source};
+
+/// The files of a compiled config and the import statements between them.
+/// The paths are normalized like the paths of the imports, i.e. without `.` and `..` but not resolving symlinks.
+#[derive(Debug, Clone, Default)]
+pub struct ImportGraph {
+    /// The root first, then the imported files in the order they were first imported.
+    pub files: Vec<GraphFile>,
+    /// Every import statement in the order of compilation, a file imported twice by the same file has two edges.
+    pub edges: Vec<ImportEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphFile {
+    pub path: PathBuf,
+    /// The size of the content in bytes.
+    pub size: usize,
+    /// The number of distinct files importing this file.
+    pub importers: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportEdge {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// The location of the `import` statement.
+    pub location: Location,
+}
+
+impl ImportGraph {
+    pub(crate) fn new(sources: &[Source], edges: Vec<ImportEdge>) -> Self {
+        let mut importers: HashMap<&Path, HashSet<&Path>> = HashMap::new();
+        for edge in &edges {
+            importers
+                .entry(&edge.to)
+                .or_default()
+                .insert(edge.from.as_path());
+        }
+        // A file imported with different bindings is compiled, and listed in the sources, once per set of bindings.
+        let mut seen = HashSet::new();
+        let files = sources
+            .iter()
+            .map(|src| (super::compile::normalize(src.file()), src))
+            .filter(|(path, _)| seen.insert(path.clone()))
+            .map(|(path, src)| GraphFile {
+                importers: importers.get(path.as_path()).map_or(0, HashSet::len),
+                size: src.as_str().len(),
+                path,
+            })
+            .collect();
+        Self { files, edges }
+    }
+
+    /// The first import of the file that exceeds `max_imports`, if there are more imported files.
+    pub(crate) fn exceeding_import(&self, max_imports: usize) -> Option<&ImportEdge> {
+        let file = self.files.get(max_imports + 1)?;
+        self.edges.iter().find(|edge| edge.to == file.path)
+    }
+}
+
+#[test]
+fn import_graph() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("syconf-import-graph-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("main.sy"),
+        r#"{
+    db: import "./lib/db.sy",
+    web: import "lib/web.sy",
+}"#,
+    )
+    .unwrap();
+    fs::write(dir.join("lib/db.sy"), r#"{common: import "common.sy"}"#).unwrap();
+    fs::write(
+        dir.join("lib/web.sy"),
+        r#"[import "common.sy", import "../lib/common.sy"]"#,
+    )
+    .unwrap();
+    fs::write(dir.join("lib/common.sy"), "{}").unwrap();
+
+    let compiled = crate::compile_file(dir.join("main.sy").to_str().unwrap()).unwrap();
+    let graph = compiled.import_graph();
+    let files: Vec<(PathBuf, usize, usize)> = graph
+        .files
+        .iter()
+        .map(|f| {
+            (
+                f.path.strip_prefix(&dir).unwrap().into(),
+                f.size,
+                f.importers,
+            )
+        })
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            ("main.sy".into(), 63, 0),
+            ("lib/db.sy".into(), 28, 1),
+            ("lib/common.sy".into(), 2, 2),
+            ("lib/web.sy".into(), 47, 1),
+        ]
+    );
+    let edges: Vec<(&Path, &Path, String)> = graph
+        .edges
+        .iter()
+        .map(|e| {
+            (
+                e.from.strip_prefix(&dir).unwrap(),
+                e.to.strip_prefix(&dir).unwrap(),
+                format!("{}:{}", e.location.line_no(), e.location.column_no()),
+            )
+        })
+        .collect();
+    let path = Path::new;
+    assert_eq!(
+        edges,
+        vec![
+            (path("main.sy"), path("lib/db.sy"), "2:9".to_string()),
+            (path("lib/db.sy"), path("lib/common.sy"), "1:10".to_string()),
+            (path("main.sy"), path("lib/web.sy"), "3:10".to_string()),
+            (path("lib/web.sy"), path("lib/common.sy"), "1:2".to_string()),
+            (
+                path("lib/web.sy"),
+                path("lib/common.sy"),
+                "1:22".to_string()
+            ),
+        ]
+    );
+
+    let limit = |max_imports| crate::EvalOptions {
+        max_imports: Some(max_imports),
+        ..Default::default()
+    };
+    assert!(compiled.evaluate_with_options(&limit(3)).is_ok());
+    let err = compiled.evaluate_with_options(&limit(2)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}:3:10: 3 files are imported, more than the limit of 2",
+            dir.join("main.sy").display()
+        )
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
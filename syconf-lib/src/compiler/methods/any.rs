@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::compiler::methods::hashmap::sorted_entries;
+use crate::compiler::value::ValueString;
+use crate::compiler::{evaluation, functions, Error, Value, YamlOptions};
+
+/// Methods available on values of every type, unless a type has a method or hashmap field of the same name.
+pub type AnyMethod = dyn Fn(&Value, &[Value]) -> Result<Value, Error>;
+
+/// The names of all methods, in the order of `method`.
+pub const NAMES: &[&str] = &[
+    "is_null",
+    "is_present",
+    "map_opt",
+    "and_then_opt",
+    "unwrap_or",
+    "unwrap_or_else",
+    "to_json",
+    "to_json_pretty",
+    "to_flat_json",
+    "to_yaml",
+    "to_toml",
+    "to_string",
+];
+
+pub fn method(name: &str) -> Option<&'static AnyMethod> {
+    Some(match name {
+        "is_null" => &is_null,
+        "is_present" => &is_present,
+        "map_opt" => &map_opt,
+        "and_then_opt" => &map_opt,
+        "unwrap_or" => &unwrap_or,
+        "unwrap_or_else" => &unwrap_or_else,
+        "to_json" => &to_json,
+        "to_json_pretty" => &to_json_pretty,
+        "to_flat_json" => &to_flat_json,
+        "to_yaml" => &to_yaml,
+        "to_toml" => &to_toml,
+        "to_string" => &to_string,
+        _ => return None,
+    })
+}
+
+fn is_null(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'is_null' does not take any arguments");
+    Ok(Value::Bool(matches!(value, Value::Null)))
+}
+
+#[test]
+fn func_is_null() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(
+        eval("[null.is_null(), 1.is_null(), 'a'.is_null(), [].is_null(), {}.is_null(), true.is_null()]"),
+        eval("[true, false, false, false, false, false]")
+    );
+    assert_eq!(
+        eval(r#"'{"a": null}'.parse_json().a.is_null()"#),
+        Value::Bool(true)
+    );
+    assert_eq!(eval("{is_null: 3}.is_null"), Value::Int(3));
+}
+
+/// Without `indent` the JSON is compact. Hashmap keys are sorted.
+fn to_json(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() <= 1, "'to_json' expects an optional indentation");
+    let json = match args.first() {
+        Some(indent) => {
+            let indent = indent.as_int()?;
+            check!(
+                indent >= 0,
+                "indentation must not be negative, got {}",
+                indent
+            );
+            value.to_json_indented(indent as usize)?
+        }
+        None => value.to_json()?,
+    };
+    Ok(Value::String(json.into()))
+}
+
+fn to_json_pretty(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.is_empty(),
+        "'to_json_pretty' does not take any arguments"
+    );
+    Ok(Value::String(value.to_json_pretty()?.into()))
+}
+
+#[test]
+fn func_to_json() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"[1.to_json(), (1.5).to_json(), 'a"b'.to_json(), null.to_json(), true.to_json_pretty()]"#)
+            .unwrap(),
+        eval(r#"["1", "1.5", '"a\"b"', "null", "true"]"#).unwrap()
+    );
+    assert_eq!(
+        eval("{b: [1], a: {c: null}}.to_json_pretty()").unwrap(),
+        Value::String("{\n  \"a\": {\n    \"c\": null\n  },\n  \"b\": [\n    1\n  ]\n}".into())
+    );
+    assert_eq!(
+        eval("{a: 1}.to_json().parse_json() == {a: 1}").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval("((x) => x).to_json()").unwrap_err().message,
+        "Cannot convert to JSON: Cannot serialize a function"
+    );
+    assert_eq!(
+        eval("{f: (x) => x}.to_json_pretty()").unwrap_err().message,
+        "Cannot convert to JSON: Cannot serialize a function"
+    );
+}
+
+/// Nested hashmaps and lists are flattened into a single hashmap, e.g. `{a: {b: [1]}}` into `{"a.b.0": 1}`.
+/// Empty hashmaps and lists are kept as values, as there is nothing to flatten.
+fn to_flat_json(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'to_flat_json' expects an optional separator"
+    );
+    let separator = match args.first() {
+        Some(x) => x.as_value_string()?.as_ref(),
+        None => ".",
+    };
+    check!(
+        !separator.is_empty(),
+        "'to_flat_json' expects a non-empty separator"
+    );
+    check!(
+        matches!(value, Value::HashMap(_) | Value::List(_)),
+        "'to_flat_json' expects a hashmap or a list, got {}",
+        value.summary()
+    );
+    let mut out = HashMap::new();
+    flatten_into(value, None, separator, &mut out)?;
+    Ok(Value::String(
+        Value::HashMap(Rc::new(out)).to_json()?.into(),
+    ))
+}
+
+fn flatten_into(
+    value: &Value,
+    prefix: Option<&str>,
+    separator: &str,
+    out: &mut HashMap<ValueString, Value>,
+) -> Result<(), Error> {
+    let key = |segment: &str| match prefix {
+        Some(prefix) => format!("{}{}{}", prefix, separator, segment),
+        None => segment.to_string(),
+    };
+    match value {
+        Value::HashMap(hm) if !hm.is_empty() => {
+            for (k, v) in sorted_entries(hm) {
+                flatten_into(v, Some(&key(k)), separator, out)?;
+            }
+        }
+        Value::List(list) if !list.is_empty() => {
+            for (ix, v) in list.iter().enumerate() {
+                flatten_into(v, Some(&key(&ix.to_string())), separator, out)?;
+            }
+        }
+        x => match prefix {
+            // An empty root gives an empty object.
+            None => {}
+            Some(key) => check!(
+                out.insert(key.into(), x.clone()).is_none(),
+                "'to_flat_json' produces the key '{}' more than once",
+                key
+            ),
+        },
+    }
+    Ok(())
+}
+
+#[test]
+fn func_to_flat_json() {
+    let eval = |s: &str| crate::parse_string(s);
+    let flat = |s: &str| match eval(s).unwrap() {
+        Value::String(x) => x.to_string(),
+        x => panic!("{}", x),
+    };
+    assert_eq!(
+        flat(
+            r#"{db: {host: "h", ports: [80, 443]}, debug: true, tags: [], extra: {}}.to_flat_json()"#
+        ),
+        r#"{"db.host":"h","db.ports.0":80,"db.ports.1":443,"debug":true,"extra":{},"tags":[]}"#
+    );
+    assert_eq!(
+        flat(r#"[{a: 1}, null].to_flat_json("/")"#),
+        r#"{"0/a":1,"1":null}"#
+    );
+    assert_eq!(flat("{}.to_flat_json()"), "{}");
+    assert_eq!(
+        eval(r#"{"a.b": 1, a: {b: 2}}.to_flat_json()"#)
+            .unwrap_err()
+            .message,
+        "'to_flat_json' produces the key 'a.b' more than once"
+    );
+    assert_eq!(
+        eval("1.to_flat_json()").unwrap_err().message,
+        "'to_flat_json' expects a hashmap or a list, got 1"
+    );
+    assert!(eval(r#"{a: 1}.to_flat_json("")"#).is_err());
+}
+
+fn to_yaml(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'to_yaml' expects an optional hashmap of options"
+    );
+    let options = args
+        .first()
+        .map_or_else(|| Ok(YamlOptions::default()), YamlOptions::from_value)?;
+    Ok(Value::String(value.to_yaml(&options)?.into()))
+}
+
+#[test]
+fn func_to_yaml() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"[1.to_yaml(), "yes".to_yaml(), null.to_yaml(), true.to_yaml()]"#).unwrap(),
+        Value::List(
+            ["1\n", "\"yes\"\n", "null\n", "true\n"]
+                .iter()
+                .map(|x| Value::String((*x).into()))
+                .collect()
+        )
+    );
+    let config =
+        r#"{name: "api", ports: [80, 443], db: {host: "db1", ratio: 0.5, tls: null}, tags: []}"#;
+    assert_eq!(
+        eval(&format!("{0}.to_yaml().parse_yaml() == {0}", config)).unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval(&format!(
+            "{0}.to_yaml({{style: \"flow\"}}).parse_yaml() == {0}",
+            config
+        ))
+        .unwrap(),
+        Value::Bool(true)
+    );
+    assert!(eval("((x) => x).to_yaml()").is_err());
+}
+
+/// Only hashmaps can be converted, because a TOML document is a table.
+fn to_toml(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'to_toml' does not take any arguments");
+    Ok(Value::String(value.to_toml()?.into()))
+}
+
+#[test]
+fn func_to_toml() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"{name: "x", port: 8080}.to_toml().parse_toml() == {name: "x", port: 8080}"#)
+            .unwrap(),
+        Value::Bool(true)
+    );
+    for (expr, summary) in &[
+        ("1", "1"),
+        ("'a'", "\"a\""),
+        ("null", "null"),
+        ("[1]", "[1]"),
+    ] {
+        assert_eq!(
+            eval(&format!("({}).to_toml()", expr)).unwrap_err().message,
+            format!(
+                "Cannot convert to TOML: the top level must be a hashmap, got {}",
+                summary
+            )
+        );
+    }
+    assert_eq!(
+        eval("{f: (x) => x}.to_toml()").unwrap_err().message,
+        "Cannot convert to TOML: 'f' is a function"
+    );
+    assert!(eval("{}.to_toml(1)").is_err());
+}
+
+fn to_string(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'to_string' does not take any arguments");
+    match value {
+        Value::String(_) => Ok(value.clone()),
+        x => Ok(Value::String(functions::stringify(x)?.into())),
+    }
+}
+
+#[test]
+fn func_to_string() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"["a", 1, 1.5, true, null].map((x) => x.to_string())"#).unwrap(),
+        eval(r#"["a", "1", "1.5", "true", "null"]"#).unwrap()
+    );
+    assert_eq!(
+        eval(
+            r#"let servers = ["a", "b"]
+            let limits = {mem: 2, cpu: 1, "max-conn": [1]}
+            in "servers: ${servers.to_string()}, limits: ${limits.to_string()}""#
+        )
+        .unwrap(),
+        Value::String(r#"servers: ["a", "b"], limits: {cpu: 1, "max-conn": [1], mem: 2}"#.into())
+    );
+    assert_eq!(eval("{to_string: 1}.to_string").unwrap(), Value::Int(1));
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error("((x) => x).to_string()"),
+        "Cannot convert a function to a string"
+    );
+    assert_eq!(
+        error("[1, (x) => x].to_string()"),
+        "Cannot convert [1, <function>] to a string, it contains a function"
+    );
+}
+
+fn is_present(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'is_present' does not take any arguments");
+    Ok(Value::Bool(!matches!(value, Value::Null)))
+}
+
+thread_local! {
+    /// The hashmaps passed to the running `map_opt` functions, their missing fields read as `null`.
+    static OPTIONAL: RefCell<Vec<Rc<HashMap<ValueString, Value>>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Whether `hm` is the value of a running `map_opt`, so that `t.cert_path` is `null` rather than an error.
+pub fn is_optional(hm: &Rc<HashMap<ValueString, Value>>) -> bool {
+    OPTIONAL.with(|x| x.borrow().iter().any(|o| Rc::ptr_eq(o, hm)))
+}
+
+/// Optional values are values that may be `null`, the function is only applied to other values.
+/// `and_then_opt` is the same, its name says that the function itself may return `null`.
+fn map_opt(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'map_opt' expects a single function argument"
+    );
+    let func = args[0].as_func()?;
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::HashMap(hm) => {
+            OPTIONAL.with(|x| x.borrow_mut().push(hm.clone()));
+            let result = func.call(std::slice::from_ref(value));
+            OPTIONAL.with(|x| x.borrow_mut().pop());
+            result
+        }
+        x => func.call(std::slice::from_ref(x)),
+    }
+}
+
+fn unwrap_or(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'unwrap_or' expects a single default value"
+    );
+    match value {
+        Value::Null => {
+            evaluation::default_applied("unwrap_or", None, &args[0]);
+            Ok(args[0].clone())
+        }
+        x => Ok(x.clone()),
+    }
+}
+
+/// The default is only computed if the value is `null`.
+fn unwrap_or_else(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'unwrap_or_else' expects a single function returning the default"
+    );
+    let func = args[0].as_func()?;
+    match value {
+        Value::Null => {
+            let default = func.call(&[])?;
+            evaluation::default_applied("unwrap_or_else", None, &default);
+            Ok(default)
+        }
+        x => Ok(x.clone()),
+    }
+}
+
+#[test]
+fn func_optional() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    let cert = |cfg: &str| {
+        eval(&format!(
+            r#"let cfg = {} in cfg.get("tls").map_opt((t) => t.cert_path).unwrap_or("/etc/ssl/default.pem")"#,
+            cfg
+        ))
+    };
+    assert_eq!(cert("{}"), Value::String("/etc/ssl/default.pem".into()));
+    assert_eq!(
+        cert(r#"{tls: {cert_path: "/run/tls.pem"}}"#),
+        Value::String("/run/tls.pem".into())
+    );
+    assert_eq!(
+        cert("{tls: {cert_path: null}}"),
+        Value::String("/etc/ssl/default.pem".into())
+    );
+    assert_eq!(
+        cert("{tls: {}}"),
+        Value::String("/etc/ssl/default.pem".into())
+    );
+    assert_eq!(
+        eval(
+            r#"let cfg = {tls: {}} in
+            opt(cfg.get("tls")).and_then_opt((t) => t.get("cert_path")).unwrap_or("/etc/ssl/default.pem")"#
+        ),
+        Value::String("/etc/ssl/default.pem".into())
+    );
+    // Only the fields of the mapped value are optional, and only inside the function.
+    let err = |s: &str| crate::parse_string(s).unwrap_err().message;
+    assert_eq!(
+        err("{a: {}}.map_opt((t) => t.a.b)"),
+        "no such field or method: b"
+    );
+    assert_eq!(
+        err("let f = {}.map_opt((t) => () => t.x) in f()"),
+        "no such field or method: x"
+    );
+    assert_eq!(
+        eval("[null.is_present(), 0.is_present(), null.unwrap_or_else(() => 1), 2.unwrap_or_else(() => 1 / 0)]"),
+        eval("[false, true, 1, 2]")
+    );
+    assert!(crate::parse_string("null.map_opt(1)").is_err());
+}
@@ -1,18 +1,49 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use crate::compiler::diff;
 use crate::compiler::value::ValueString;
 use crate::compiler::value_extraction::ValueExtractor;
-use crate::compiler::{Error, Value};
+use crate::compiler::{evaluation, Error, Value};
 
 pub type HashmapMethod = dyn Fn(&HashMap<ValueString, Value>, &[Value]) -> Result<Value, Error>;
 
+/// The names of all methods, in the order of `method`.
+pub const NAMES: &[&str] = &[
+    "map",
+    "filter",
+    "len",
+    "insert",
+    "keys",
+    "values",
+    "entries",
+    "map_values",
+    "contains_key",
+    "get",
+    "get_ci",
+    "is_subset_of",
+    "is_superset_of",
+    "matches",
+    "to_args",
+];
+
 pub fn method(name: &str) -> Option<&'static HashmapMethod> {
     Some(match name {
         "map" => &map,
         "filter" => &filter,
         "len" => &len,
         "insert" => &insert,
+        "keys" => &keys,
+        "values" => &values,
+        "entries" => &entries,
+        "map_values" => &map_values,
+        "contains_key" => &contains_key,
+        "get" => &get,
+        "get_ci" => &get_ci,
+        "is_subset_of" => &is_subset_of,
+        "is_superset_of" => &is_superset_of,
+        "matches" => &is_superset_of,
+        "to_args" => &to_args,
         _ => return None,
     })
 }
@@ -22,7 +53,7 @@ fn map(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error>
     let func = extractor.extract_func(0)?;
 
     let mut new_hm = HashMap::new();
-    for (k, v) in hm {
+    for (k, v) in sorted_entries(hm) {
         let v = func.call(&[Value::String(k.clone()), v.clone()])?;
         match v {
             Value::List(list) => {
@@ -51,7 +82,7 @@ fn func_map() {
 fn filter(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
     let func = ValueExtractor::new(args, 1)?.extract_func(0)?;
     let mut filtered = HashMap::with_capacity(hm.len());
-    for (ix, val) in hm {
+    for (ix, val) in sorted_entries(hm) {
         let out = func
             .call(&[Value::String(ix.clone()), val.clone()])?
             .as_bool()?;
@@ -77,7 +108,7 @@ fn func_filter() {
 
 fn len(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "expects no arguments");
-    Ok(Value::Int(hm.len() as i32))
+    Ok(Value::Int(hm.len() as i64))
 }
 
 #[test]
@@ -113,6 +144,201 @@ fn func_insert() {
     )
 }
 
+fn keys(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "expects no arguments");
+    let mut keys: Vec<&ValueString> = hm.keys().collect();
+    keys.sort();
+    Ok(Value::List(
+        keys.into_iter().map(|k| Value::String(k.clone())).collect(),
+    ))
+}
+
+#[test]
+fn func_keys() {
+    assert_eq!(
+        crate::parse_string(r#"{bb: 1, aa: 2}.keys() == ["aa", "bb"]"#).unwrap(),
+        Value::Bool(true)
+    )
+}
+
+/// Hashmaps have no order of their own, everything that calls a function for each entry visits them in this order,
+/// so that e.g. `fold` accumulates and the first error is reported the same way in every run.
+pub fn sorted_entries(hm: &HashMap<ValueString, Value>) -> Vec<(&ValueString, &Value)> {
+    let mut entries: Vec<(&ValueString, &Value)> = hm.iter().collect();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+/// The values are ordered by their keys, like `keys()`.
+fn values(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "expects no arguments");
+    Ok(Value::List(
+        sorted_entries(hm)
+            .into_iter()
+            .map(|(_, v)| v.clone())
+            .collect(),
+    ))
+}
+
+fn entries(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "expects no arguments");
+    Ok(Value::List(
+        sorted_entries(hm)
+            .into_iter()
+            .map(|(k, v)| Value::List(vec![Value::String(k.clone()), v.clone()].into()))
+            .collect(),
+    ))
+}
+
+#[test]
+fn func_values_entries() {
+    assert_eq!(
+        crate::parse_string(
+            r#"
+        let hm = {bb: 1, aa: 2, cc: {x: 3}}
+        in
+        [
+            hm.values(),
+            hm.entries(),
+            fold(0, (acc, ix, e) => acc + e[1], {bb: 1, aa: 2}.entries()),
+            {}.values(),
+            {}.entries(),
+        ]
+    "#
+        )
+        .unwrap(),
+        crate::parse_string(
+            r#"[[2, 1, {x: 3}], [["aa", 2], ["bb", 1], ["cc", {x: 3}]], 3, [], []]"#
+        )
+        .unwrap()
+    )
+}
+
+fn map_values(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    let func = ValueExtractor::new(args, 1)?.extract_func(0)?;
+    let mut out = HashMap::with_capacity(hm.len());
+    for (k, v) in sorted_entries(hm) {
+        out.insert(
+            k.clone(),
+            func.call(&[Value::String(k.clone()), v.clone()])?,
+        );
+    }
+    Ok(Value::HashMap(Rc::new(out)))
+}
+
+#[test]
+fn func_map_values() {
+    assert_eq!(
+        crate::parse_string(
+            r#"
+        {aa: 3, bb: 4}.map_values((k, v) => "${k}=${v}") == {aa: "aa=3", bb: "bb=4"}
+    "#
+        )
+        .unwrap(),
+        Value::Bool(true)
+    )
+}
+
+fn contains_key(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "expects a single key argument");
+    Ok(Value::Bool(hm.contains_key(args[0].as_value_string()?)))
+}
+
+#[test]
+fn func_contains_key() {
+    assert_eq!(
+        crate::parse_string(
+            r#"
+        [{port: null}.contains_key("port"), {}.contains_key("port")]
+    "#
+        )
+        .unwrap(),
+        crate::parse_string("[true, false]").unwrap()
+    );
+    assert!(crate::parse_string("{a: 1}.contains_key(1)").is_err());
+}
+
+/// Unlike `hm.key` or `hm["key"]`, a missing key is not an error.
+fn get(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1 || args.len() == 2,
+        "expects a key and an optional default value"
+    );
+    let key = args[0].as_value_string()?;
+    match (hm.get(key), args.get(1)) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, Some(default)) => {
+            evaluation::default_applied("get", Some(key), default);
+            Ok(default.clone())
+        }
+        (None, None) => Ok(Value::Null),
+    }
+}
+
+#[test]
+fn func_get() {
+    assert_eq!(
+        crate::parse_string(
+            r#"[{a: 1}.get("a"), {a: 1}.get("b"), {a: 1}.get("b", 2), {a: null}.get("a", 2)]"#
+        )
+        .unwrap(),
+        crate::parse_string("[1, null, 2, null]").unwrap()
+    );
+}
+
+/// Like `get`, but keys match ignoring ASCII case. Fails if several keys match.
+fn get_ci(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1 || args.len() == 2,
+        "'get_ci' expects a key and an optional default value"
+    );
+    let key = args[0].as_value_string()?;
+    let mut matches: Vec<&ValueString> =
+        hm.keys().filter(|k| k.eq_ignore_ascii_case(key)).collect();
+    matches.sort();
+    match (matches.as_slice(), args.get(1)) {
+        ([found], _) => Ok(hm[*found].clone()),
+        ([], Some(default)) => {
+            evaluation::default_applied("get_ci", Some(key), default);
+            Ok(default.clone())
+        }
+        ([], None) => Ok(Value::Null),
+        (found, _) => Err(format!(
+            "'get_ci' key '{}' is ambiguous, it matches the keys {}",
+            key,
+            found
+                .iter()
+                .map(|k| format!("'{}'", k))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+#[test]
+fn func_get_ci() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(
+            r#"let headers = {"Content-Type": "text/plain", Accept: "*/*"} in [
+                headers.get_ci("content-type", ""),
+                headers.get_ci("ACCEPT"),
+                headers.get_ci("x-missing"),
+                headers.get_ci("x-missing", "none"),
+            ]"#
+        )
+        .unwrap(),
+        eval(r#"["text/plain", "*/*", null, "none"]"#).unwrap()
+    );
+    assert_eq!(
+        eval(r#"{Host: "a", host: "b"}.get_ci("HOST")"#)
+            .unwrap_err()
+            .message,
+        "'get_ci' key 'HOST' is ambiguous, it matches the keys 'Host', 'host'"
+    );
+}
+
 #[test]
 fn key_expr() {
     assert_eq!(
@@ -127,3 +353,329 @@ fn key_expr() {
         Value::Bool(true)
     )
 }
+
+fn is_subset_of(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "expects a single hashmap argument");
+    Ok(Value::Bool(diff::is_subset(hm, args[0].as_hashmap()?)))
+}
+
+fn is_superset_of(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "expects a single hashmap argument");
+    Ok(Value::Bool(diff::is_subset(args[0].as_hashmap()?, hm)))
+}
+
+#[test]
+fn func_is_subset_of() {
+    assert_eq!(
+        crate::parse_string(
+            r#"
+        let actual = {name: "api", db: {host: "x", port: 5432}, replicas: 3}
+        in
+        [
+            {db: {port: 5432}}.is_subset_of(actual),
+            {db: {port: 5433}}.is_subset_of(actual),
+            {user: "x"}.is_subset_of(actual),
+            actual.is_superset_of({name: "api", db: {}}),
+            actual.matches({replicas: 3}),
+            actual.matches({replicas: [3]}),
+        ]
+    "#
+        )
+        .unwrap(),
+        crate::parse_string("[true, false, false, true, true, false]").unwrap()
+    )
+}
+
+struct ArgsOptions {
+    equals: bool,
+    negate_false: bool,
+    case: Option<char>,
+    separator: Option<String>,
+}
+
+impl ArgsOptions {
+    fn from_value(options: Option<&Value>) -> Result<Self, Error> {
+        let mut out = Self {
+            equals: false,
+            negate_false: false,
+            case: None,
+            separator: None,
+        };
+        let options = match options {
+            Some(x) => x.as_hashmap()?,
+            None => return Ok(out),
+        };
+        // The separator depends on `lists`, so it is resolved after all options are read.
+        let mut join = false;
+        let mut separator = None;
+        for (key, val) in options {
+            match (key.as_ref(), val) {
+                ("style", Value::String(x)) if x.as_ref() == "gnu" => out.equals = false,
+                ("style", Value::String(x)) if x.as_ref() == "equals" => out.equals = true,
+                ("negate_false", Value::Bool(x)) => out.negate_false = *x,
+                ("case", Value::String(x)) if x.as_ref() == "keep" => out.case = None,
+                ("case", Value::String(x)) if x.as_ref() == "kebab" => out.case = Some('-'),
+                ("case", Value::String(x)) if x.as_ref() == "snake" => out.case = Some('_'),
+                ("lists", Value::String(x)) if x.as_ref() == "repeat" => join = false,
+                ("lists", Value::String(x)) if x.as_ref() == "join" => join = true,
+                ("separator", Value::String(x)) => separator = Some(x.to_string()),
+                _ => {
+                    return Err(
+                        format!("Unsupported 'to_args' option {}: {}", key, val.summary()).into(),
+                    )
+                }
+            }
+        }
+        check!(
+            join || separator.is_none(),
+            "'to_args' option separator requires {lists: \"join\"}"
+        );
+        if join {
+            out.separator = Some(separator.unwrap_or_else(|| ",".to_string()));
+        }
+        Ok(out)
+    }
+
+    /// Splits the key into words at `-`, `_` and lowercase to uppercase changes, and joins them lowercased.
+    fn name(&self, key: &str) -> String {
+        let sep = match self.case {
+            Some(sep) => sep,
+            None => return key.to_string(),
+        };
+        let mut out = String::with_capacity(key.len() + 4);
+        let mut prev_lower = false;
+        for c in key.chars() {
+            if c == '-' || c == '_' {
+                out.push(sep);
+                prev_lower = false;
+                continue;
+            }
+            if c.is_uppercase() && prev_lower {
+                out.push(sep);
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            out.extend(c.to_lowercase());
+        }
+        out
+    }
+
+    fn push(&self, out: &mut Vec<Value>, name: &str, value: String) {
+        if self.equals {
+            out.push(Value::String(format!("--{}={}", name, value).into()));
+        } else {
+            out.push(Value::String(format!("--{}", name).into()));
+            out.push(Value::String(value.into()));
+        }
+    }
+}
+
+/// The string of a single option value, which must be a string or a number.
+fn arg_value(value: &Value, path: &str) -> Result<String, Error> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        Value::Int(_) | Value::Float(_) => Ok(value.to_string()),
+        x => Err(format!(
+            "'to_args' cannot convert the value at {} to an argument: {}",
+            path,
+            x.summary()
+        )
+        .into()),
+    }
+}
+
+/// Flags for the entries ordered by their keys: `true` is a bare flag, `null` is left out,
+/// and lists repeat the flag or are joined into a single value.
+fn to_args(hm: &HashMap<ValueString, Value>, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'to_args' expects an optional hashmap of options, e.g. {{style: \"equals\"}}"
+    );
+    let options = ArgsOptions::from_value(args.first())?;
+    let mut out = Vec::new();
+    for (key, value) in sorted_entries(hm) {
+        let name = options.name(key);
+        let path = diff::key_path("", key);
+        match value {
+            Value::Null => (),
+            Value::Bool(true) => out.push(Value::String(format!("--{}", name).into())),
+            Value::Bool(false) if options.negate_false => {
+                out.push(Value::String(format!("--no-{}", name).into()))
+            }
+            Value::Bool(false) => (),
+            Value::List(list) => {
+                let values = list
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, x)| arg_value(x, &format!("{}[{}]", path, ix)))
+                    .collect::<Result<Vec<String>, Error>>()?;
+                match &options.separator {
+                    Some(sep) if !values.is_empty() => {
+                        options.push(&mut out, &name, values.join(sep))
+                    }
+                    Some(_) => (),
+                    None => {
+                        for x in values {
+                            options.push(&mut out, &name, x);
+                        }
+                    }
+                }
+            }
+            x => options.push(&mut out, &name, arg_value(x, &path)?),
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_to_args() {
+    let eval = |s: &str| crate::parse_string(s);
+    let args = |s: &str| -> Vec<String> {
+        eval(s)
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|x| x.as_value_string().unwrap().to_string())
+            .collect()
+    };
+    let config =
+        r#"{verbose: true, count: 3, name: "x", tags: ["a", "b"], debug: false, region: null}"#;
+    assert_eq!(
+        args(&format!(r#"{}.to_args({{style: "gnu"}})"#, config)),
+        [
+            "--count",
+            "3",
+            "--name",
+            "x",
+            "--tags",
+            "a",
+            "--tags",
+            "b",
+            "--verbose"
+        ]
+    );
+    assert_eq!(
+        args(&format!(r#"{}.to_args({{style: "equals"}})"#, config)),
+        ["--count=3", "--name=x", "--tags=a", "--tags=b", "--verbose"]
+    );
+    assert_eq!(
+        args(&format!("{}.to_args({{negate_false: true}})", config)),
+        [
+            "--count",
+            "3",
+            "--no-debug",
+            "--name",
+            "x",
+            "--tags",
+            "a",
+            "--tags",
+            "b",
+            "--verbose"
+        ]
+    );
+    assert_eq!(
+        args(&format!(r#"{}.to_args({{lists: "join"}})"#, config)),
+        ["--count", "3", "--name", "x", "--tags", "a,b", "--verbose"]
+    );
+    assert_eq!(
+        args(
+            r#"{tags: ["a", "b"], none: []}.to_args({lists: "join", separator: " ", style: "equals"})"#
+        ),
+        ["--tags=a b"]
+    );
+    assert_eq!(
+        args(r#"{none: [], off: false}.to_args()"#),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        args(
+            r#"{maxRetries: 1, dry_run: true, "log-level": "info", ratio: 0.5}.to_args({case: "kebab"})"#
+        ),
+        [
+            "--dry-run",
+            "--log-level",
+            "info",
+            "--max-retries",
+            "1",
+            "--ratio",
+            "0.5"
+        ]
+    );
+    assert_eq!(
+        args(r#"{maxRetries: 1, "log-level": "info"}.to_args({case: "snake", style: "equals"})"#),
+        ["--log_level=info", "--max_retries=1"]
+    );
+    assert_eq!(
+        args(r#"{maxRetries: 1}.to_args({case: "keep"})"#),
+        ["--maxRetries", "1"]
+    );
+    assert_eq!(
+        eval(r#"{db: {host: "x"}}.to_args()"#).unwrap_err().message,
+        r#"'to_args' cannot convert the value at db to an argument: {host: "x"}"#
+    );
+    assert_eq!(
+        eval(r#"{"a b": [1, [2]]}.to_args()"#).unwrap_err().message,
+        r#"'to_args' cannot convert the value at ["a b"][1] to an argument: [2]"#
+    );
+    assert!(eval(r#"{flags: [true]}.to_args()"#).is_err());
+    assert!(eval(r#"{a: 1}.to_args({style: "posix"})"#).is_err());
+    assert!(eval(r#"{a: 1}.to_args({lists: "join", separator: 1})"#).is_err());
+    for options in &[
+        r#"{lists: "repeat", separator: ";"}"#,
+        r#"{separator: ";"}"#,
+    ] {
+        assert_eq!(
+            eval(&format!(r#"{{tags: ["a", "b"]}}.to_args({})"#, options))
+                .unwrap_err()
+                .message,
+            r#"'to_args' option separator requires {lists: "join"}"#
+        );
+    }
+    assert_eq!(
+        args(r#"{tags: ["a", "b"]}.to_args({separator: ";", lists: "join"})"#),
+        ["--tags", "a;b"]
+    );
+}
+
+#[test]
+fn func_to_yaml() {
+    assert_eq!(
+        crate::parse_string(r#"{b: "1.0", a: [1, 2]}.to_yaml({style: "flow"})"#).unwrap(),
+        Value::String("{a: [1, 2], b: \"1.0\"}\n".into())
+    );
+    assert_eq!(
+        crate::parse_string(r#"{a: "x"}.to_yaml({quoting: "always"})"#).unwrap(),
+        Value::String("\"a\": \"x\"\n".into())
+    );
+    assert!(crate::parse_string(r#"{a: "x"}.to_yaml({quoting: "never"})"#).is_err());
+}
+
+#[test]
+fn func_to_toml_and_json() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"{name: "api", db: {port: 5432, hosts: ["a", "b"]}, ratio: 0.5}.to_toml()"#)
+            .unwrap(),
+        Value::String(
+            "name = \"api\"\nratio = 0.5\n\n[db]\nhosts = [\"a\", \"b\"]\nport = 5432\n".into()
+        )
+    );
+    assert_eq!(
+        eval(r#"{a: [{b: null}]}.to_toml()"#).unwrap_err().message,
+        "Cannot convert to TOML: 'a[0].b' is null, which TOML cannot express"
+    );
+    let config = r#"{b: [1], a: {}}"#;
+    assert_eq!(
+        eval(&format!("{}.to_json()", config)).unwrap(),
+        Value::String(r#"{"a":{},"b":[1]}"#.into())
+    );
+    assert_eq!(
+        eval(&format!("{}.to_json(4)", config)).unwrap(),
+        Value::String("{\n    \"a\": {},\n    \"b\": [\n        1\n    ]\n}".into())
+    );
+    assert!(eval(&format!("{}.to_json(0 - 1)", config)).is_err());
+    assert_eq!(
+        eval(r#"{a: [1, 2]}.to_json(2).parse_json() == {a: [1, 2]}"#).unwrap(),
+        Value::Bool(true)
+    );
+}
@@ -1,44 +1,145 @@
-use crate::compiler::value_extraction::ValueExtractor;
+use crate::compiler::collation::Collation;
+use crate::compiler::functions::concat_strings;
+use crate::compiler::value::Func;
 use crate::compiler::{Error, Value};
 
 pub type ListMethod = dyn Fn(&[Value], &[Value]) -> Result<Value, Error>;
 
+/// The names of all methods, in the order of `method`.
+pub const NAMES: &[&str] = &[
+    "map", "filter", "len", "flatten", "append", "join", "sort", "sort_by",
+];
+
 pub fn method(name: &str) -> Option<&'static ListMethod> {
     Some(match name {
         "map" => &map,
         "filter" => &filter,
         "len" => &len,
+        "flatten" => &flatten,
         "append" => &append,
+        "join" => &join,
+        "sort" => &sort,
+        "sort_by" => &sort_by,
         _ => return None,
     })
 }
 
+/// Like `fold`, a function with two arguments gets the index and the value, otherwise just the value.
+fn with_index(method: &str, func: &Func) -> Result<bool, Error> {
+    match func.arity() {
+        Some(1) | None => Ok(false),
+        Some(2) => Ok(true),
+        Some(n) => Err(format!(
+            "'{}' expects a function with the arguments (val) or (ix, val), got a function with {} arguments",
+            method, n
+        )
+        .into()),
+    }
+}
+
+fn call_indexed(func: &Func, with_index: bool, ix: usize, val: &Value) -> Result<Value, Error> {
+    if with_index {
+        func.call(&[Value::Int(ix as i64), val.clone()])
+    } else {
+        func.call(std::slice::from_ref(val))
+    }
+}
+
 fn map(list: &[Value], args: &[Value]) -> Result<Value, Error> {
-    let func = ValueExtractor::new(args, 1)?.extract_func(0)?;
+    check!(args.len() == 1, "'map' expects a single function argument");
+    let func = args[0].as_func()?;
+    let with_index = with_index("map", &func)?;
     let mapped = list
         .iter()
-        .map(|x| func.call(&[x.clone()]))
+        .enumerate()
+        .map(|(ix, x)| call_indexed(&func, with_index, ix, x))
         .collect::<Result<Vec<Value>, Error>>()?;
     Ok(Value::List(mapped.into()))
 }
 
 fn filter(list: &[Value], args: &[Value]) -> Result<Value, Error> {
-    let func = ValueExtractor::new(args, 1)?.extract_func(0)?;
+    check!(
+        args.len() == 1,
+        "'filter' expects a single function argument"
+    );
+    let func = args[0].as_func()?;
+    let with_index = with_index("filter", &func)?;
     let mut filtered = Vec::with_capacity(list.len());
     for (ix, val) in list.iter().enumerate() {
-        let out = func
-            .call(&[Value::Int(ix as i32), val.clone()])?
-            .as_bool()?;
-        if out {
-            filtered.push(val.clone());
+        match call_indexed(&func, with_index, ix, val)? {
+            Value::Bool(true) => filtered.push(val.clone()),
+            Value::Bool(false) => {}
+            x => {
+                return Err(
+                    format!("'filter' function must return a bool, got {}", x.summary()).into(),
+                )
+            }
         }
     }
     Ok(Value::List(filtered.into()))
 }
 
+#[test]
+fn func_map_filter() {
+    let eval = |s: &str| crate::parse_string(s);
+    let cases = [
+        ("[1, 2, 3].map((x) => x * 2)", "[2, 4, 6]"),
+        ("[1, 2, 3].map((ix, x) => ix * x)", "[0, 2, 6]"),
+        (
+            "[{n: 1, enabled: true}, {n: 2, enabled: false}].filter((s) => s.enabled)",
+            "[{n: 1, enabled: true}]",
+        ),
+        (
+            r#"["a", "b", "c"].filter((ix, x) => ix != 1)"#,
+            r#"["a", "c"]"#,
+        ),
+        ("[[1], [2, 3]].map(length)", "[1, 2]"),
+        ("[1, 2, 3].len()", "3"),
+    ];
+    for (expr, expected) in cases.iter() {
+        assert_eq!(eval(expr).unwrap(), eval(expected).unwrap(), "{}", expr);
+    }
+    assert_eq!(
+        eval("[1].map((a, b, c) => a)").unwrap_err().message,
+        "'map' expects a function with the arguments (val) or (ix, val), got a function with 3 arguments"
+    );
+    assert_eq!(
+        eval("[1].filter((x) => x)").unwrap_err().message,
+        "'filter' function must return a bool, got 1"
+    );
+    assert_eq!(
+        eval("[1].filter(1)").unwrap_err().message,
+        "Type Mismatch: expects function but was Int(1)"
+    );
+}
+
 fn len(list: &[Value], args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "expects no arguments");
-    Ok(Value::Int(list.len() as i32))
+    Ok(Value::Int(list.len() as i64))
+}
+
+/// Flattens one level of nesting, values that are not lists are kept.
+fn flatten(list: &[Value], args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'flatten' does not take any arguments");
+    let mut out = Vec::with_capacity(list.len());
+    for x in list {
+        match x {
+            Value::List(inner) => out.extend(inner.iter().cloned()),
+            x => out.push(x.clone()),
+        }
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_flatten() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(eval("[[1], [2, 3]].flatten()"), eval("[1, 2, 3]"));
+    assert_eq!(eval("[[1, [2]], 3, []].flatten()"), eval("[1, [2], 3]"));
+    assert!(crate::parse_string("{a: [1]}.flatten()")
+        .unwrap_err()
+        .message
+        .contains("no such field or method"));
 }
 
 fn append(list: &[Value], args: &[Value]) -> Result<Value, Error> {
@@ -48,3 +149,137 @@ fn append(list: &[Value], args: &[Value]) -> Result<Value, Error> {
     }
     Ok(Value::List(a.into()))
 }
+
+/// Numbers and bools are formatted like in string interpolation.
+fn join(list: &[Value], args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'join' expects a single separator argument"
+    );
+    let separator = Value::String(args[0].as_value_string()?.clone());
+    let mut parts = Vec::with_capacity(list.len() * 2);
+    for (ix, x) in list.iter().enumerate() {
+        if ix > 0 {
+            parts.push(separator.clone());
+        }
+        parts.push(x.clone());
+    }
+    concat_strings(&parts)
+}
+
+#[test]
+fn func_join() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"["a","b","c"].join("-")"#).unwrap(),
+        Value::String("a-b-c".into())
+    );
+    assert_eq!(
+        eval(r#"[["a", 1, true].join(", "), [].join("-"), "a b".split(" ").join("")]"#).unwrap(),
+        eval(r#"["a, 1, true", "", "ab"]"#).unwrap()
+    );
+    assert_eq!(
+        eval(r#"["a", null].join("-")"#).unwrap_err().message,
+        "Cannot interpolate null into a string"
+    );
+    assert!(eval(r#"["a"].join(1)"#).is_err());
+}
+
+#[test]
+fn func_to_yaml() {
+    assert_eq!(
+        crate::parse_string(r#"["yes", {a: 1}].to_yaml()"#).unwrap(),
+        Value::String("- \"yes\"\n- a: 1\n".into())
+    );
+}
+
+#[test]
+fn func_to_toml_and_json() {
+    assert_eq!(
+        crate::parse_string("[1, {a: true}].to_json()").unwrap(),
+        Value::String(r#"[1,{"a":true}]"#.into())
+    );
+    assert_eq!(
+        crate::parse_string("[{a: 1}].to_toml()")
+            .unwrap_err()
+            .message,
+        "Cannot convert to TOML: the top level must be a hashmap, got [{a: 1}]"
+    );
+}
+
+fn sort(list: &[Value], args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'sort' expects an optional hashmap of options"
+    );
+    let collation = args
+        .first()
+        .map_or_else(|| Ok(Collation::default()), Collation::from_value)?;
+    let keyed = list.iter().map(|x| (x.clone(), x.clone())).collect();
+    sort_keyed(keyed, &collation)
+}
+
+/// Sorts by the keys that `func(element)` returns.
+fn sort_by(list: &[Value], args: &[Value]) -> Result<Value, Error> {
+    check!(
+        !args.is_empty() && args.len() <= 2,
+        "'sort_by' expects a key function and an optional hashmap of options"
+    );
+    let func = args[0].as_func()?;
+    let collation = args
+        .get(1)
+        .map_or_else(|| Ok(Collation::default()), Collation::from_value)?;
+    let keyed = list
+        .iter()
+        .map(|x| Ok((func.call(std::slice::from_ref(x))?, x.clone())))
+        .collect::<Result<Vec<(Value, Value)>, Error>>()?;
+    sort_keyed(keyed, &collation)
+}
+
+/// The sort is stable, so elements with equal keys keep their order.
+fn sort_keyed(keyed: Vec<(Value, Value)>, collation: &Collation) -> Result<Value, Error> {
+    Ok(Value::List(collation.sort_keyed(keyed)?.into()))
+}
+
+#[test]
+fn func_sort() {
+    let eval = |s: &str| crate::parse_string(s);
+    let cases = [
+        ("[3, 1.5, 2].sort()", "[1.5, 2, 3]"),
+        (r#"["b", "Ä", "a", "Z"].sort()"#, r#"["Z", "a", "b", "Ä"]"#),
+        (
+            r#"["b", "Ä", "a", "Z"].sort({collate: "unicode"})"#,
+            r#"["a", "Ä", "b", "Z"]"#,
+        ),
+        (
+            r#"["host10", "host2", "host1"].sort({numeric: true})"#,
+            r#"["host1", "host2", "host10"]"#,
+        ),
+        (
+            r#"[{n: "B", v: 1}, {n: "a", v: 2}, {n: "b", v: 3}].sort_by((x) => x.n, {case_insensitive: true})"#,
+            r#"[{n: "a", v: 2}, {n: "B", v: 1}, {n: "b", v: 3}]"#,
+        ),
+        ("[[2], [1]].sort_by((x) => x[0])", "[[1], [2]]"),
+    ];
+    for (expr, expected) in cases.iter() {
+        assert_eq!(eval(expr).unwrap(), eval(expected).unwrap(), "{}", expr);
+    }
+    assert_eq!(
+        eval(r#"[1, "a"].sort()"#).unwrap_err().message,
+        r#"Cannot compare 1 and "a""#
+    );
+    // Mixed keys fail before sorting, so the order of the comparisons cannot lead to an inconsistent order.
+    assert_eq!(
+        eval(r#"reverse(range(0, 30).map((x) => if (x*7919) - (x/3)*23757 > 0 then "s${x}" else x)).sort()"#)
+            .unwrap_err()
+            .message,
+        r#"Cannot compare "s29" and 27"#
+    );
+    assert_eq!(
+        eval(r#"[{a: 1}, {a: "b"}].sort_by((x) => x.a)"#)
+            .unwrap_err()
+            .message,
+        r#"Cannot compare 1 and "b""#
+    );
+    assert!(eval(r#"["a"].sort({collate: "icu"})"#).is_err());
+}
@@ -1,12 +1,30 @@
+use crate::compiler::deprecation::{self, Callable};
+use crate::compiler::error::{did_you_mean, similar_names};
+use crate::compiler::evaluation;
 use crate::compiler::value::{Func, Method};
 use crate::compiler::{Error, Value};
 
+pub mod any;
 pub mod hashmap;
 pub mod list;
+pub mod number;
 pub mod string;
 
 pub fn index(args: &[Value]) -> Result<Value, Error> {
     debug!(?args, "index");
+    check!(args.len() == 2, "expects 2 arguments");
+    let value = lookup(args)?;
+    if let (Value::Func(_), Value::String(name)) = (&value, &args[1]) {
+        let is_field = matches!(&args[0], Value::HashMap(hm) if hm.contains_key(name));
+        match deprecation::lookup(Callable::Method, name) {
+            Some(deprecation) if !is_field => evaluation::deprecation_used(deprecation, None)?,
+            _ => {}
+        }
+    }
+    Ok(value)
+}
+
+fn lookup(args: &[Value]) -> Result<Value, Error> {
     match &args[0] {
         Value::HashMap(hm) => {
             let key = args[1].as_value_string()?;
@@ -14,29 +32,110 @@ pub fn index(args: &[Value]) -> Result<Value, Error> {
                 Some(v) => Ok(v.clone()),
                 None => hashmap::method(key)
                     .map(|func| Value::Func(Func::new_method(Method::HashMap(hm.clone(), func))))
-                    .ok_or_else(|| format!("no such field or method: {}", key).into()),
+                    .or_else(|| any_method(&args[0], key))
+                    .or_else(|| any::is_optional(hm).then_some(Value::Null))
+                    .ok_or_else(|| {
+                        no_such_method(
+                            key,
+                            "hashmap",
+                            hashmap::NAMES,
+                            hm.keys().map(|k| k.as_ref()),
+                        )
+                    }),
             }
         }
         Value::List(list) => match &args[1] {
             Value::Int(key) => list
                 .get(*key as usize)
-                .map(Clone::clone)
+                .cloned()
                 .ok_or_else(|| "No such element".into()),
             Value::String(key) => list::method(key)
                 .map(|func| Value::Func(Func::new_method(Method::List(list.clone(), func))))
-                .ok_or_else(|| format!("no such field or method: {}", key).into()),
-            _ => unreachable!(),
+                .or_else(|| any_method(&args[0], key))
+                .ok_or_else(|| no_such_method(key, "list", list::NAMES, None)),
+            x => Err(format!("List index must be an int, got {}", x.summary()).into()),
         },
         Value::String(string) => match &args[1] {
             Value::String(method) => string::method(method)
                 .map(|func| Value::Func(Func::new_method(Method::String(string.clone(), func))))
-                .ok_or_else(|| format!("no such field or method: {}", string).into()),
-            _ => unreachable!(),
+                .or_else(|| any_method(&args[0], method))
+                .ok_or_else(|| no_such_method(method, "string", string::NAMES, None)),
+            x => Err(format!("String method name must be a string, got {}", x.summary()).into()),
+        },
+        Value::Int(_) | Value::Float(_) => match &args[1] {
+            Value::String(method) => number::method(method)
+                .map(|func| {
+                    Value::Func(Func::new_method(Method::Number(
+                        Box::new(args[0].clone()),
+                        func,
+                    )))
+                })
+                .or_else(|| any_method(&args[0], method))
+                .ok_or_else(|| no_such_method(method, "number", number::NAMES, None)),
+            x => Err(format!("Number method name must be a string, got {}", x.summary()).into()),
+        },
+        x => match &args[1] {
+            Value::String(method) => {
+                any_method(x, method).ok_or_else(|| format!("Cannot index {}", x.summary()).into())
+            }
+            _ => Err(format!("Cannot index {}", x.summary()).into()),
         },
-        _ => unreachable!(),
     }
 }
 
+fn any_method(value: &Value, name: &str) -> Option<Value> {
+    any::method(name)
+        .map(|func| Value::Func(Func::new_method(Method::Any(Box::new(value.clone()), func))))
+}
+
+/// Suggests similar names of methods and hashmap keys, otherwise lists the methods of the type.
+fn no_such_method<'a>(
+    name: &str,
+    type_name: &str,
+    methods: &[&'a str],
+    keys: impl IntoIterator<Item = &'a str>,
+) -> Error {
+    let candidates = methods.iter().chain(any::NAMES).copied().chain(keys);
+    let hint = did_you_mean(&similar_names(name, candidates)).unwrap_or_else(|| {
+        let all: Vec<&str> = methods.iter().chain(any::NAMES).copied().collect();
+        format!("{} methods are {}", type_name, all.join(", "))
+    });
+    Error::from(format!("no such field or method: {}", name)).with_hint(hint)
+}
+
+#[test]
+fn method_names() {
+    for name in hashmap::NAMES {
+        assert!(hashmap::method(name).is_some(), "{}", name);
+    }
+    for name in list::NAMES {
+        assert!(list::method(name).is_some(), "{}", name);
+    }
+    for name in string::NAMES {
+        assert!(string::method(name).is_some(), "{}", name);
+    }
+    for name in number::NAMES {
+        assert!(number::method(name).is_some(), "{}", name);
+    }
+    for name in any::NAMES {
+        assert!(any::method(name).is_some(), "{}", name);
+    }
+    let hint = |s: &str| crate::parse_string(s).unwrap_err().hint;
+    assert_eq!(
+        hint("{port: 1}.prot").as_deref(),
+        Some("did you mean 'port'?")
+    );
+    assert_eq!(hint("[1].lenn()").as_deref(), Some("did you mean 'len'?"));
+    assert_eq!(
+        hint("1.xyz()").as_deref(),
+        Some(
+            "number methods are negate, abs, sign, is_null, is_present, map_opt, and_then_opt, \
+             unwrap_or, unwrap_or_else, to_json, to_json_pretty, to_flat_json, to_yaml, to_toml, to_string"
+        )
+    );
+    assert_eq!(hint("[1][5]"), None);
+}
+
 #[test]
 fn method_index() {
     use crate::parse_string;
@@ -0,0 +1,66 @@
+use crate::compiler::{operators, Error, Value};
+
+pub type NumberMethod = dyn Fn(&Value, &[Value]) -> Result<Value, Error>;
+
+/// The names of all methods, in the order of `method`.
+pub const NAMES: &[&str] = &["negate", "abs", "sign"];
+
+pub fn method(name: &str) -> Option<&'static NumberMethod> {
+    Some(match name {
+        "negate" => &negate,
+        "abs" => &abs,
+        "sign" => &sign,
+        _ => return None,
+    })
+}
+
+fn negate(number: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'negate' does not take any arguments");
+    operators::negate(std::slice::from_ref(number))
+}
+
+fn abs(number: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'abs' does not take any arguments");
+    if let Value::Float(x) = number {
+        return Ok(Value::Float(x.abs()));
+    }
+    let x = number.as_int()?;
+    x.checked_abs()
+        .map(Value::Int)
+        .ok_or_else(|| format!("Integer overflow in absolute value of {}", x).into())
+}
+
+/// Returns `-1`, `0`, or `1`.
+fn sign(number: &Value, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'sign' does not take any arguments");
+    let x = number.as_float()?;
+    Ok(Value::Int(if x > 0.0 {
+        1
+    } else if x < 0.0 {
+        -1
+    } else {
+        0
+    }))
+}
+
+#[test]
+fn number_methods() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(eval("5.negate()"), Value::Int(-5));
+    assert_eq!(eval("(0 - 5).negate()"), Value::Int(5));
+    assert_eq!(eval("0.negate()"), Value::Int(0));
+    assert_eq!(eval("(3 - 10).abs()"), Value::Int(7));
+    assert_eq!(eval("0.abs()"), Value::Int(0));
+    assert_eq!(
+        eval("[(0 - 2).sign(), 0.sign(), 7.sign()]"),
+        eval("[0 - 1, 0, 1]")
+    );
+    assert_eq!(eval("let a = 2 in (a - 9).abs().negate()"), Value::Int(-7));
+    assert!(crate::parse_string("((0 - 9223372036854775807) - 1).abs()").is_err());
+    assert!(crate::parse_string("1.sign(2)").is_err());
+    assert_eq!(eval("1.5.negate().abs()"), Value::Float(1.5));
+    assert_eq!(
+        eval("[(0 - 0.5).sign(), 0.0.sign(), 2.5.sign()]"),
+        eval("[0 - 1, 0, 1]")
+    );
+}
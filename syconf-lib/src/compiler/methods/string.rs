@@ -1,37 +1,188 @@
 use std::rc::Rc;
 
-use crate::compiler::{Error, Value};
+use crate::compiler::glob::Glob;
+use crate::compiler::value::check_finite;
+use crate::compiler::{evaluation, Error, Value};
 use std::cmp::min;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
 pub type StringMethod = dyn Fn(&str, &[Value]) -> Result<Value, Error>;
 
+/// The names of all methods, in the order of `method`.
+pub const NAMES: &[&str] = &[
+    "parse_json",
+    "parse_json5",
+    "parse_yaml",
+    "parse_toml",
+    "trim",
+    "normalize_whitespace",
+    "unindent",
+    "reindent",
+    "embed_yaml_block",
+    "split",
+    "replace",
+    "replace_all_map",
+    "to_upper",
+    "to_lower",
+    "substring",
+    "repeat",
+    "lines",
+    "hex_encode",
+    "hex_decode",
+    "base32_encode",
+    "base32_decode",
+    "matches_glob",
+    "to_bool",
+    "strip_ansi",
+    "sanitize",
+    "to_dns_label",
+    "to_env_var_name",
+];
+
 pub fn method(method_name: &str) -> Option<&'static StringMethod> {
     Some(match method_name {
         "parse_json" => &parse_json,
+        "parse_json5" => &parse_json5,
         "parse_yaml" => &parse_yaml,
         "parse_toml" => &parse_toml,
         "trim" => &trim,
+        "normalize_whitespace" => &normalize_whitespace,
         "unindent" => &unindent,
+        "reindent" => &reindent,
+        "embed_yaml_block" => &embed_yaml_block,
+        "split" => &split,
+        "replace" => &replace,
+        "replace_all_map" => &replace_all_map,
+        "to_upper" => &to_upper,
+        "to_lower" => &to_lower,
+        "substring" => &substring,
+        "repeat" => &repeat,
+        "lines" => &lines,
+        "hex_encode" => &hex_encode,
+        "hex_decode" => &hex_decode,
+        "base32_encode" => &base32_encode,
+        "base32_decode" => &base32_decode,
+        "matches_glob" => &matches_glob,
+        "to_bool" => &to_bool,
+        "strip_ansi" => &strip_ansi,
+        "sanitize" => &sanitize,
+        "to_dns_label" => &to_dns_label,
+        "to_env_var_name" => &to_env_var_name,
         _ => return None,
     })
 }
 
+/// Parses the data with the `parse_<format>` method of the format.
+pub fn parse_data(input: &str, format: &str) -> Result<Value, Error> {
+    let parse = match format {
+        "json" => parse_json,
+        "json5" => parse_json5,
+        "yaml" => parse_yaml,
+        "toml" => parse_toml,
+        _ => {
+            return Err(format!(
+                "Unknown data format '{}', expected json, json5, yaml, or toml",
+                format
+            )
+            .into())
+        }
+    };
+    parse(input, &[])
+}
+
+#[test]
+fn data_formats() {
+    let expected = crate::parse_string("{a: {b: [1]}}").unwrap();
+    for (format, input) in &[
+        ("json", r#"{"a": {"b": [1]}}"#),
+        ("json5", "{a: {b: [1,]}}"),
+        ("yaml", "a:\n  b: [1]"),
+        ("toml", "[a]\nb = [1]"),
+    ] {
+        assert_eq!(parse_data(input, format).unwrap(), expected, "{}", format);
+    }
+    assert!(parse_data("a: 1", "json").is_err());
+    assert!(parse_data("a: 1", "ini").is_err());
+}
+
 fn parse_json(string: &str, args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "'parse_json' does not take any arguments");
     let x = serde_json::from_str(string).map_err(|e| anyhow!("cannot parse JSON: {}", e))?;
     Ok(Value::HashMap(Rc::new(x)))
 }
 
+#[test]
+fn parse_json_floats() {
+    let value = crate::parse_string(r#"'{"a": 1.5, "b": 2, "c": 3.0e2}'.parse_json()"#).unwrap();
+    let hm = value.as_hashmap().unwrap();
+    assert!(matches!(hm["a"], Value::Float(x) if x == 1.5));
+    assert!(matches!(hm["b"], Value::Int(2)));
+    assert!(matches!(hm["c"], Value::Float(x) if x == 300.0));
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        r#"{"a":1.5,"b":2,"c":300.0}"#
+    );
+}
+
+/// Accepts JSON5, i.e. JSON with comments, trailing commas, single-quoted strings, and unquoted keys.
+fn parse_json5(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'parse_json5' does not take any arguments");
+    // The message of the parser starts with the line and column, followed by a snippet.
+    let x = json5::from_str(string)
+        .map_err(|e| anyhow!("cannot parse JSON5: {}", e.to_string().trim_start()))?;
+    let value = Value::HashMap(Rc::new(x));
+    check_finite(&value, "").map_err(|e| anyhow!("cannot parse JSON5: {}", e.message))?;
+    Ok(value)
+}
+
+#[test]
+fn func_parse_json5() {
+    let eval = |s: &str| crate::parse_string(s);
+    let value = eval(
+        r##"#"
+        // The service
+        {
+            name: "api", /* inline */
+            'quoted': 'single',
+            ports: [80, 443,],
+            ratio: .5,
+            big: 3000000000,
+        }"#.parse_json5()"##,
+    )
+    .unwrap();
+    assert_eq!(
+        value,
+        eval(r#"{name: "api", quoted: "single", ports: [80, 443], ratio: 0.5, big: 3000000000.0}"#)
+            .unwrap()
+    );
+    assert!(matches!(
+        value.as_hashmap().unwrap()["ports"].as_list().unwrap()[0],
+        Value::Int(80)
+    ));
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert!(error(r#"'{a: 1,, }'.parse_json5()"#).starts_with("cannot parse JSON5: --> 1:7\n"));
+    assert!(error(r#"'{a: 1, }'.parse_json()"#).starts_with("cannot parse JSON: "));
+    assert_eq!(
+        error(r#"'{a: NaN}'.parse_json5()"#),
+        "cannot parse JSON5: 'a' is NaN, but only finite numbers are supported"
+    );
+}
+
 fn parse_yaml(string: &str, args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "'parse_yaml' does not take any arguments");
     let x = serde_yaml::from_str(string).map_err(|e| anyhow!("cannot parse YAML: {}", e))?;
-    Ok(Value::HashMap(Rc::new(x)))
+    let value = Value::HashMap(Rc::new(x));
+    check_finite(&value, "").map_err(|e| anyhow!("cannot parse YAML: {}", e.message))?;
+    Ok(value)
 }
 
 fn parse_toml(string: &str, args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "'parse_toml' does not take any arguments");
     let x = toml::de::from_str(string).map_err(|e| anyhow!("cannot parse TOML: {}", e))?;
-    Ok(Value::HashMap(Rc::new(x)))
+    let value = Value::HashMap(Rc::new(x));
+    check_finite(&value, "").map_err(|e| anyhow!("cannot parse TOML: {}", e.message))?;
+    Ok(value)
 }
 
 fn trim(string: &str, args: &[Value]) -> Result<Value, Error> {
@@ -54,6 +205,35 @@ fn trim_string() {
     )
 }
 
+fn normalize_whitespace(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.is_empty(),
+        "'normalize_whitespace' does not take any arguments"
+    );
+    Ok(Value::String(
+        string
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .into(),
+    ))
+}
+
+#[test]
+fn func_normalize_whitespace() {
+    assert_eq!(
+        crate::parse_string(
+            "\"\n  SELECT *\n\tFROM  users\r\n  WHERE id = 1\n\".normalize_whitespace()"
+        )
+        .unwrap(),
+        Value::String("SELECT * FROM users WHERE id = 1".into())
+    );
+    assert_eq!(
+        crate::parse_string("' \t\n '.normalize_whitespace()").unwrap(),
+        Value::String("".into())
+    );
+}
+
 fn unindent(string: &str, args: &[Value]) -> Result<Value, Error> {
     check!(args.is_empty(), "'unindent' does not take any arguments");
     let mut prefixed_whitespaces: Vec<&str> = string
@@ -111,3 +291,967 @@ fn func_unindent() {
         Value::String("\n\n    abc\ndef\n            ghk\n".into())
     )
 }
+
+/// Removes the common indentation and indents every line by `spaces` spaces.
+/// Blank lines become empty and trailing whitespace is removed, a trailing newline is kept.
+fn reindent(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'reindent' expects the number of spaces to indent by"
+    );
+    let spaces = args[0].as_int()?;
+    check!(
+        spaces >= 0,
+        "'reindent' expects a non-negative number of spaces, got {}",
+        spaces
+    );
+    let indentation = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+    let common = string
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(indentation)
+        .min()
+        .unwrap_or(0);
+    let prefix = " ".repeat(spaces as usize);
+    let mut out = string
+        .lines()
+        .map(|line| match line.trim_end() {
+            "" => String::new(),
+            line => {
+                let start = line
+                    .char_indices()
+                    .nth(common)
+                    .map_or(line.len(), |(ix, _)| ix);
+                format!("{}{}", prefix, &line[start..])
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    if string.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(Value::String(out.into()))
+}
+
+#[test]
+fn func_reindent() {
+    let reindent = |s: &str, spaces: i64| {
+        reindent(s, &[Value::Int(spaces)])
+            .unwrap()
+            .as_value_string()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        reindent("\n    a:\n      b: 1  \n   \n    c\n", 2),
+        "\n  a:\n    b: 1\n\n  c\n"
+    );
+    assert_eq!(reindent("x\n  y", 0), "x\n  y");
+    assert_eq!(reindent("", 4), "");
+    assert!(crate::parse_string("'a'.reindent(0 - 1)").is_err());
+}
+
+/// Renders a YAML literal block scalar that reads back as exactly the string, starting with `|`,
+/// to be inserted after a `key: ` whose line is indented by `indent` spaces.
+/// The content lines are indented by two more spaces, the chomping indicator keeps the trailing newlines.
+fn embed_yaml_block(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'embed_yaml_block' expects the indentation of the key line"
+    );
+    let indent = args[0].as_int()?;
+    check!(
+        indent >= 0,
+        "'embed_yaml_block' expects a non-negative indentation, got {}",
+        indent
+    );
+    check!(
+        !string.contains('\r'),
+        "'embed_yaml_block' cannot embed carriage returns, YAML normalizes line breaks"
+    );
+    let content = string.trim_end_matches('\n');
+    // Clipping drops the final newline of an empty content, so only newlines need keeping.
+    let chomping = match string.len() - content.len() {
+        0 => "-",
+        1 if !content.is_empty() => "",
+        _ => "+",
+    };
+    let lines: Vec<&str> = string.split('\n').collect();
+    // The indentation is detected from the first line with content, which fails if that line starts with a space
+    // or if a preceding line consists of spaces only, so the indentation is given explicitly then.
+    let first = lines
+        .iter()
+        .position(|x| !x.trim_start_matches(' ').is_empty());
+    let explicit = match first {
+        Some(ix) => lines[ix].starts_with(' ') || lines[..ix].iter().any(|x| !x.is_empty()),
+        None => lines.iter().any(|x| !x.is_empty()),
+    };
+    let mut out = format!("|{}{}\n", if explicit { "2" } else { "" }, chomping);
+    let prefix = " ".repeat(indent as usize + 2);
+    // The last element is the part after the final newline, which is empty unless there is no trailing newline.
+    for (ix, line) in lines.iter().enumerate() {
+        if ix == lines.len() - 1 && line.is_empty() {
+            break;
+        }
+        if !line.is_empty() {
+            out.push_str(&prefix);
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    Ok(Value::String(out.into()))
+}
+
+#[test]
+fn func_embed_yaml_block() {
+    let cases = [
+        "",
+        "a",
+        "a\n",
+        "a\n\n",
+        "\n",
+        "\n\na",
+        "  indented\nb\n",
+        "line: 1\n  nested: [x]\n\n# not a comment\n",
+        "   \nspaces only line above",
+        "trailing space  \n\tx",
+        "é\n\n\nend\n\n\n",
+    ];
+    for s in cases.iter() {
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("s".to_string(), Value::String((*s).into()));
+        let parse = |expr: &str| {
+            crate::parse_string_with_vars(expr, &vars).unwrap_or_else(|e| panic!("{:?}: {}", s, e))
+        };
+        assert_eq!(
+            parse(r#""key: ${s.embed_yaml_block(0)}".parse_yaml().key"#),
+            Value::String((*s).into()),
+            "{:?}",
+            s
+        );
+        assert_eq!(
+            parse(
+                r#""outer:\n  key: ${s.embed_yaml_block(2)}  after: 1".parse_yaml().outer.key"#
+                    .replace("\\n", "\n")
+                    .as_str()
+            ),
+            Value::String((*s).into()),
+            "{:?}",
+            s
+        );
+    }
+    assert!(embed_yaml_block("a\r\nb", &[Value::Int(0)]).is_err());
+}
+
+fn split(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'split' expects a single separator argument"
+    );
+    let separator = args[0].as_value_string()?;
+    check!(!separator.is_empty(), "'split' separator must not be empty");
+    Ok(Value::List(
+        string
+            .split(separator.as_ref())
+            .map(|x| Value::String(x.into()))
+            .collect(),
+    ))
+}
+
+#[test]
+fn func_split() {
+    assert_eq!(
+        crate::parse_string(r#""a,b,,c".split(",")"#).unwrap(),
+        Value::List(
+            vec!["a", "b", "", "c"]
+                .into_iter()
+                .map(|x| Value::String(x.into()))
+                .collect()
+        )
+    )
+}
+
+/// Replaces all occurrences.
+fn replace(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 2,
+        "'replace' expects the string to replace and its replacement"
+    );
+    let from = args[0].as_value_string()?;
+    check!(
+        !from.is_empty(),
+        "'replace' expects a non-empty string to replace"
+    );
+    Ok(Value::String(
+        string
+            .replace(from.as_ref(), args[1].as_value_string()?)
+            .into(),
+    ))
+}
+
+/// Scans the string once from left to right and replaces the longest key matching at each position,
+/// so replaced text is never replaced again, e.g. `{a: "b", b: "a"}` swaps `a` and `b`.
+fn replace_all_map(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'replace_all_map' expects a hashmap of replacements"
+    );
+    let mut pairs = Vec::new();
+    for (from, to) in args[0].as_hashmap()?.iter() {
+        check!(
+            !from.is_empty(),
+            "'replace_all_map' expects non-empty strings to replace"
+        );
+        pairs.push((from.as_ref(), to.as_value_string()?));
+    }
+    pairs.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+    let mut out = String::with_capacity(string.len());
+    let mut rest = string;
+    while let Some(c) = rest.chars().next() {
+        match pairs.iter().find(|(from, _)| rest.starts_with(from)) {
+            Some((from, to)) => {
+                out.push_str(to);
+                rest = &rest[from.len()..];
+            }
+            None => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Ok(Value::String(out.into()))
+}
+
+#[test]
+fn func_replace_all_map() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(
+            r#"[
+            "a-b".replace_all_map({a: "b", b: "a"}),
+            "<a & b>".replace_all_map({"<": "&lt;", ">": "&gt;", "&": "&amp;"}),
+            "aaa".replace_all_map({a: "x", aa: "y"}),
+            "héllo".replace_all_map({"é": "e"}),
+            '{name}, {greeting}'.replace_all_map({"{name}": "Bob", "{greeting}": "hi"}),
+        ]"#
+        )
+        .unwrap(),
+        eval(r#"["b-a", "&lt;a &amp; b&gt;", "yx", "hello", "Bob, hi"]"#).unwrap()
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#""abc".replace_all_map({"": "x"})"#),
+        "'replace_all_map' expects non-empty strings to replace"
+    );
+    assert!(error(r#""abc".replace_all_map({a: 1})"#).contains("string"));
+}
+
+fn to_upper(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'to_upper' does not take any arguments");
+    Ok(Value::String(string.to_uppercase().into()))
+}
+
+fn to_lower(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'to_lower' does not take any arguments");
+    Ok(Value::String(string.to_lowercase().into()))
+}
+
+/// The indices count characters, `end` is exclusive. Both are clamped to the length of the string.
+fn substring(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1 || args.len() == 2,
+        "'substring' expects a start index and an optional end index"
+    );
+    let index = |value: &Value| -> Result<usize, Error> {
+        let ix = value.as_int()?;
+        check!(
+            ix >= 0,
+            "'substring' index must not be negative, got {}",
+            ix
+        );
+        Ok(ix as usize)
+    };
+    let start = index(&args[0])?;
+    let end = args.get(1).map(index).transpose()?.unwrap_or(usize::MAX);
+    Ok(Value::String(
+        string
+            .chars()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect::<String>()
+            .into(),
+    ))
+}
+
+/// Fails if the result would be longer than the `max_interpolation_size` of the evaluation options.
+fn repeat(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.len() == 1, "'repeat' expects a single count argument");
+    let n = args[0].as_int()?;
+    check!(n >= 0, "'repeat' count must not be negative, got {}", n);
+    let limit = evaluation::options().max_interpolation_size;
+    let size = string.len().saturating_mul(n as usize);
+    check!(
+        size <= limit,
+        "'repeat' would create a string of {} bytes, which exceeds the limit of {} bytes",
+        size,
+        limit
+    );
+    Ok(Value::String(string.repeat(n as usize).into()))
+}
+
+#[test]
+fn func_string_basics() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(
+            r#"[
+            "a-b-c".replace("-", "_"),
+            "aaa".replace("aa", "b"),
+            "Héllo".to_upper(),
+            "HÉLLO".to_lower(),
+            "-".repeat(3),
+            "ab".repeat(0),
+        ]"#
+        )
+        .unwrap(),
+        eval(r#"["a_b_c", "ba", "HÉLLO", "héllo", "---", ""]"#).unwrap()
+    );
+    assert_eq!(
+        eval(
+            r#"[
+            "héllo".substring(1, 3),
+            "héllo".substring(2),
+            "héllo".substring(3, 100),
+            "héllo".substring(10),
+            "héllo".substring(4, 2),
+        ]"#
+        )
+        .unwrap(),
+        eval(r#"["él", "llo", "lo", "", ""]"#).unwrap()
+    );
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#""abc".substring(0 - 1)"#),
+        "'substring' index must not be negative, got -1"
+    );
+    assert_eq!(
+        error(r#""abc".split("")"#),
+        "'split' separator must not be empty"
+    );
+    assert_eq!(
+        error(r#""abc".repeat(0 - 2)"#),
+        "'repeat' count must not be negative, got -2"
+    );
+    assert_eq!(
+        error(r#""abc".repeat(2147483647)"#),
+        "'repeat' would create a string of 6442450941 bytes, which exceeds the limit of 10485760 bytes"
+    );
+}
+
+fn lines(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'lines' expects an optional keep_trailing argument"
+    );
+    let keep_trailing = match args.first() {
+        Some(x) => x.as_bool()?,
+        None => false,
+    };
+    let mut out: Vec<Value> = string.lines().map(|x| Value::String(x.into())).collect();
+    if keep_trailing && string.ends_with('\n') {
+        out.push(Value::String("".into()));
+    }
+    Ok(Value::List(out.into()))
+}
+
+#[test]
+fn func_lines() {
+    let lines = |s: &str| {
+        crate::parse_string(s)
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|x| x.as_value_string().unwrap().to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(lines("'a\r\nb\n'.lines()"), vec!["a", "b"]);
+    assert_eq!(lines("'a\nb\n'.lines(false)"), vec!["a", "b"]);
+    assert_eq!(lines("'a\nb\n'.lines(true)"), vec!["a", "b", ""]);
+    assert_eq!(lines("'a\n\n'.lines(true)"), vec!["a", "", ""]);
+    assert_eq!(lines("'a'.lines(true)"), vec!["a"]);
+    assert!(crate::parse_string("'a'.lines(1)").is_err());
+}
+
+fn hex_encode(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'hex_encode' does not take any arguments");
+    let mut out = String::with_capacity(string.len() * 2);
+    for b in string.bytes() {
+        out.push_str(&format!("{:02x}", b));
+    }
+    Ok(Value::String(out.into()))
+}
+
+/// Accepts both lower and upper case digits.
+fn hex_decode(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'hex_decode' does not take any arguments");
+    check!(
+        string.len().is_multiple_of(2),
+        "'hex_decode' expects an even number of hex digits, got {}",
+        string.len()
+    );
+    let bytes = string
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .ok_or_else(|| format!("'hex_decode' found an invalid hex digit in '{}'", string))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    decoded_string("hex_decode", bytes)
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Uses the RFC 4648 alphabet with `=` padding.
+fn base32_encode(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.is_empty(),
+        "'base32_encode' does not take any arguments"
+    );
+    let mut out = String::new();
+    for chunk in string.as_bytes().chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for ix in 0..8 {
+            if ix < chars {
+                out.push(BASE32_ALPHABET[((bits >> (35 - ix * 5)) & 31) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    Ok(Value::String(out.into()))
+}
+
+/// Accepts lower case letters and missing padding.
+fn base32_decode(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.is_empty(),
+        "'base32_decode' does not take any arguments"
+    );
+    let invalid = || format!("'base32_decode' got invalid base32 '{}'", string);
+    let data = string.trim_end_matches('=');
+    check!(
+        !matches!(data.len() % 8, 1 | 3 | 6),
+        "'base32_decode' got invalid base32 '{}'",
+        string
+    );
+    let mut bytes = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in data.chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|x| *x as char == c.to_ascii_uppercase())
+            .ok_or_else(invalid)?;
+        bits = (bits << 5) | value as u32;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    check!(bits == 0, "'base32_decode' got invalid base32 '{}'", string);
+    decoded_string("base32_decode", bytes)
+}
+
+fn decoded_string(name: &str, bytes: Vec<u8>) -> Result<Value, Error> {
+    String::from_utf8(bytes)
+        .map(|x| Value::String(x.into()))
+        .map_err(|_| format!("'{}' decoded bytes that are not valid UTF-8", name).into())
+}
+
+#[test]
+fn func_hex_base32() {
+    let eval = |s: &str| crate::parse_string(s).map(|x| x.as_value_string().unwrap().to_string());
+    assert_eq!(eval("'hi!'.hex_encode()").unwrap(), "686921");
+    assert_eq!(eval("'686921'.hex_decode()").unwrap(), "hi!");
+    assert_eq!(eval("'C3A9'.hex_decode()").unwrap(), "é");
+    assert!(eval("'abc'.hex_decode()").is_err());
+    assert!(eval("'zz'.hex_decode()").is_err());
+    assert!(eval("'ff'.hex_decode()").is_err());
+    for (plain, encoded) in [
+        ("", ""),
+        ("f", "MY======"),
+        ("fo", "MZXQ===="),
+        ("foo", "MZXW6==="),
+        ("foob", "MZXW6YQ="),
+        ("fooba", "MZXW6YTB"),
+        ("foobar", "MZXW6YTBOI======"),
+    ] {
+        assert_eq!(
+            eval(&format!("'{}'.base32_encode()", plain)).unwrap(),
+            encoded
+        );
+        assert_eq!(
+            eval(&format!("'{}'.base32_decode()", encoded)).unwrap(),
+            plain
+        );
+    }
+    assert_eq!(eval("'mzxw6'.base32_decode()").unwrap(), "foo");
+    assert!(eval("'MZXW1==='.base32_decode()").is_err());
+    assert!(eval("'M'.base32_decode()").is_err());
+    assert!(eval("'74======'.base32_decode()").is_err());
+}
+
+fn matches_glob(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() == 1,
+        "'matches_glob' expects a single pattern, e.g. \"*.sy\""
+    );
+    let glob = Glob::new(args[0].as_value_string()?)?;
+    Ok(Value::Bool(glob.matches(string)))
+}
+
+#[test]
+fn func_matches_glob() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"["app.syconf", "app.yaml", "db1.syconf", "x.syconf.bak"].map((x) => x.matches_glob("*.syconf"))"#)
+            .unwrap(),
+        eval("[true, false, true, false]").unwrap()
+    );
+    assert_eq!(
+        eval(r#"["db1", "db12", "dbx"].map((x) => x.matches_glob("db[0-9]?"))"#).unwrap(),
+        eval("[false, true, false]").unwrap()
+    );
+    assert_eq!(
+        eval(r#""a.sy".matches_glob("[abc")"#).unwrap_err().message,
+        "Invalid glob pattern '[abc': unclosed character class"
+    );
+}
+
+/// Accepts the usual spellings of environment variables and flags, ignoring case and surrounding whitespace.
+fn to_bool(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(args.is_empty(), "'to_bool' does not take any arguments");
+    parse_bool(string).map(Value::Bool).ok_or_else(|| {
+        format!(
+            "Cannot convert {:?} to a bool, expected {}",
+            string, BOOL_SPELLINGS
+        )
+        .into()
+    })
+}
+
+pub(crate) const BOOL_SPELLINGS: &str = "true, false, yes, no, on, off, 1, or 0";
+
+/// Accepts the spellings of `BOOL_SPELLINGS` ignoring case and surrounding whitespace, like `getenv_bool`.
+pub(crate) fn parse_bool(string: &str) -> Option<bool> {
+    match string.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[test]
+fn func_to_bool() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"["true", "False", " yes ", "NO", "on", "off", "1", "0"].map((x) => x.to_bool())"#)
+            .unwrap(),
+        eval("[true, false, true, false, true, false, true, false]").unwrap()
+    );
+    assert_eq!(
+        eval(r#""".to_bool()"#).unwrap_err().message,
+        r#"Cannot convert "" to a bool, expected true, false, yes, no, on, off, 1, or 0"#
+    );
+}
+
+/// Removes ANSI escape sequences, e.g. colors and cursor movements, and all other control characters.
+/// Newlines and tabs are kept unless the option `keep_whitespace` is `false`.
+/// An escape sequence that is cut off at the end of the string is removed up to the end.
+fn strip_ansi(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'strip_ansi' expects an optional hashmap of options, e.g. {{keep_whitespace: false}}"
+    );
+    let mut keep_whitespace = true;
+    if let Some(options) = args.first() {
+        for (key, val) in options.as_hashmap()? {
+            match (key.as_ref(), val) {
+                ("keep_whitespace", Value::Bool(x)) => keep_whitespace = *x,
+                _ => {
+                    return Err(format!(
+                        "Unsupported 'strip_ansi' option {}: {}",
+                        key,
+                        val.summary()
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+    let mut out = String::with_capacity(string.len());
+    let mut chars = string.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                // CSI, e.g. `ESC [ 31 m`, ends with a byte in `@` to `~`.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC, e.g. a window title or a hyperlink, and the other string commands end with BEL or `ESC \`.
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Other sequences have intermediate bytes and a final byte, e.g. `ESC ( B`.
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\u{9b}' => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            '\n' | '\t' if keep_whitespace => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    Ok(Value::String(out.into()))
+}
+
+#[test]
+fn func_strip_ansi() {
+    let strip = |s: &str| {
+        strip_ansi(s, &[])
+            .unwrap()
+            .as_value_string()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(strip("\u{1b}[1;31mred\u{1b}[0m text"), "red text");
+    assert_eq!(strip("\u{1b}[2K\rprogress 100%\n"), "progress 100%\n");
+    assert_eq!(strip("a\tb\u{7}\u{0}c\u{7f}"), "a\tbc");
+    assert_eq!(
+        strip("\u{1b}]8;;https://example.com\u{1b}\\link\u{1b}]8;;\u{1b}\\ and \u{1b}]0;title\u{7}done"),
+        "link and done"
+    );
+    assert_eq!(strip("\u{1b}(Bplain\u{1b}7 \u{9b}32mtext"), "plain text");
+    assert_eq!(strip("héllo \u{1b}[3"), "héllo ");
+    assert_eq!(strip("\u{1b}"), "");
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#""1b5b33326d6f6b1b5b306d0a09".hex_decode().strip_ansi({keep_whitespace: false})"#)
+            .unwrap(),
+        Value::String("ok".into())
+    );
+    assert_eq!(
+        eval(r#""1b5b33326d6f6b1b5b306d0a".hex_decode().strip_ansi()"#).unwrap(),
+        Value::String("ok\n".into())
+    );
+    assert_eq!(
+        eval(r#""a".strip_ansi({newlines: true})"#)
+            .unwrap_err()
+            .message,
+        "Unsupported 'strip_ansi' option newlines: true"
+    );
+}
+
+/// The options of `sanitize`, the presets start from other defaults.
+struct Sanitizer {
+    /// A character class like in glob patterns, e.g. `a-z0-9-`.
+    allowed: Glob,
+    replace: String,
+    max_len: Option<usize>,
+    lower: bool,
+    upper: bool,
+    collapse: bool,
+    trim_edges: String,
+    suffix_hash: Option<usize>,
+}
+
+impl Sanitizer {
+    fn new(allowed: &str, replace: &str) -> Self {
+        Self {
+            allowed: Glob::new(&format!("[{}]", allowed)).expect("valid preset"),
+            replace: replace.to_string(),
+            max_len: None,
+            lower: false,
+            upper: false,
+            collapse: false,
+            trim_edges: String::new(),
+            suffix_hash: None,
+        }
+    }
+
+    fn with_options(mut self, name: &str, options: &Value) -> Result<Self, Error> {
+        for (key, val) in options.as_hashmap()? {
+            match (key.as_ref(), val) {
+                ("allowed", Value::String(x)) => self.allowed = Glob::new(&format!("[{}]", x))?,
+                ("replace", Value::String(x)) => self.replace = x.to_string(),
+                ("max_len", Value::Int(x)) if *x > 0 => self.max_len = Some(*x as usize),
+                ("lower", Value::Bool(x)) => self.lower = *x,
+                ("upper", Value::Bool(x)) => self.upper = *x,
+                ("collapse", Value::Bool(x)) => self.collapse = *x,
+                ("trim_edges", Value::String(x)) => self.trim_edges = x.to_string(),
+                ("suffix_hash", Value::Int(x)) if (1..=16).contains(x) => {
+                    self.suffix_hash = Some(*x as usize)
+                }
+                _ => {
+                    return Err(
+                        format!("Unsupported '{}' option {}: {}", name, key, val.summary()).into(),
+                    )
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn is_allowed(&self, c: char) -> bool {
+        self.allowed.matches(c.encode_utf8(&mut [0; 4]))
+    }
+
+    fn trim<'a>(&self, s: &'a str) -> &'a str {
+        s.trim_matches(|c| self.trim_edges.contains(c))
+    }
+
+    fn apply(&self, name: &str, input: &str) -> Result<String, Error> {
+        check!(
+            self.replace.chars().all(|c| self.is_allowed(c)),
+            "'{}' replaces with {:?}, which is not allowed itself",
+            name,
+            self.replace
+        );
+        let mut out = String::with_capacity(input.len());
+        let mut kept = 0;
+        // Accents are dropped, so that e.g. `é` becomes an allowed `e`.
+        for c in input.nfd().filter(|c| !is_combining_mark(*c)) {
+            let converted: String = if self.lower {
+                c.to_lowercase().collect()
+            } else if self.upper {
+                c.to_uppercase().collect()
+            } else {
+                c.to_string()
+            };
+            for c in converted.chars() {
+                if self.is_allowed(c) {
+                    out.push(c);
+                    kept += 1;
+                } else {
+                    out.push_str(&self.replace);
+                }
+            }
+        }
+        if self.collapse && !self.replace.is_empty() {
+            let double = self.replace.repeat(2);
+            while out.contains(&double) {
+                out = out.replace(&double, &self.replace);
+            }
+        }
+        let suffix = match self.suffix_hash {
+            Some(len) => {
+                let hash = format!("{:016x}", fnv1a(input));
+                let mut hash = hash[..len].to_string();
+                if !hash.chars().all(|c| self.is_allowed(c)) {
+                    hash = hash.to_uppercase();
+                }
+                check!(
+                    hash.chars().all(|c| self.is_allowed(c)),
+                    "'{}' with 'suffix_hash' needs hex digits to be allowed",
+                    name
+                );
+                format!("{}{}", self.replace, hash)
+            }
+            None => String::new(),
+        };
+        let mut base = self.trim(&out).to_string();
+        if let Some(max_len) = self.max_len {
+            let suffix_len = suffix.chars().count();
+            check!(
+                max_len > suffix_len,
+                "'{}' cannot fit the hash suffix into a max_len of {}",
+                name,
+                max_len
+            );
+            base = base.chars().take(max_len - suffix_len).collect();
+            base = self.trim(&base).to_string();
+        }
+        check!(
+            kept > 0 && !base.is_empty(),
+            "'{}' of {:?} is empty, as none of its characters are allowed",
+            name,
+            input
+        );
+        Ok(base + &suffix)
+    }
+}
+
+/// A stable 64 bit hash, so that a name with a hash suffix is the same on every platform and with every version.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn sanitize(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'sanitize' expects an optional hashmap of options, e.g. {{allowed: \"a-z0-9-\", max_len: 63}}"
+    );
+    let mut sanitizer = Sanitizer::new("a-zA-Z0-9_-", "-");
+    if let Some(options) = args.first() {
+        sanitizer = sanitizer.with_options("sanitize", options)?;
+    }
+    Ok(Value::String(sanitizer.apply("sanitize", string)?.into()))
+}
+
+/// A label of a DNS name as in RFC 1123, which Kubernetes requires for most names.
+fn to_dns_label(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'to_dns_label' expects an optional hashmap of options, e.g. {{suffix_hash: 4}}"
+    );
+    let mut sanitizer = Sanitizer::new("a-z0-9-", "-");
+    sanitizer.max_len = Some(63);
+    sanitizer.lower = true;
+    sanitizer.collapse = true;
+    sanitizer.trim_edges = "-".to_string();
+    if let Some(options) = args.first() {
+        sanitizer = sanitizer.with_options("to_dns_label", options)?;
+    }
+    Ok(Value::String(
+        sanitizer.apply("to_dns_label", string)?.into(),
+    ))
+}
+
+/// A name of an environment variable that shells accept, which does not start with a digit.
+fn to_env_var_name(string: &str, args: &[Value]) -> Result<Value, Error> {
+    check!(
+        args.len() <= 1,
+        "'to_env_var_name' expects an optional hashmap of options, e.g. {{suffix_hash: 4}}"
+    );
+    let mut sanitizer = Sanitizer::new("A-Z0-9_", "_");
+    sanitizer.upper = true;
+    sanitizer.collapse = true;
+    sanitizer.trim_edges = "_".to_string();
+    if let Some(options) = args.first() {
+        sanitizer = sanitizer.with_options("to_env_var_name", options)?;
+    }
+    let name = sanitizer.apply("to_env_var_name", string)?;
+    Ok(Value::String(
+        match name.starts_with(|c: char| c.is_ascii_digit()) {
+            true => format!("_{}", name).into(),
+            false => name.into(),
+        },
+    ))
+}
+
+#[test]
+fn func_sanitize() {
+    let eval = |s: &str| crate::parse_string(s);
+    let cases = [
+        (r#""My App (prod)".to_dns_label()"#, "my-app-prod"),
+        (r#""--Café Crème--".to_dns_label()"#, "cafe-creme"),
+        (r#""Ünïcödé 名前 name".to_dns_label()"#, "unicode-name"),
+        (r#""a.b_c".sanitize()"#, "a-b_c"),
+        (
+            r#""a  b!!c".sanitize({allowed: "a-z_", replace: "_", collapse: true})"#,
+            "a_b_c",
+        ),
+        (r#""a  b".sanitize({allowed: "a-z", replace: ""})"#, "ab"),
+        (
+            r#""Hello World".sanitize({lower: true, max_len: 7, trim_edges: "-"})"#,
+            "hello-w",
+        ),
+        (
+            r#""Hello World".sanitize({max_len: 6, trim_edges: "-"})"#,
+            "Hello",
+        ),
+        (r#""api.server-port".to_env_var_name()"#, "API_SERVER_PORT"),
+        (r#""2fa secret".to_env_var_name()"#, "_2FA_SECRET"),
+        (r#"length("x".repeat(100).to_dns_label())"#, "63"),
+    ];
+    for (src, expected) in &cases {
+        assert_eq!(
+            eval(src).unwrap().to_string().trim_matches('"'),
+            *expected,
+            "{}",
+            src
+        );
+    }
+
+    // Names that would collide differ in their hash suffixes, which are deterministic.
+    let hashed =
+        eval(r#"["My App", "my-app", "my app"].map((x) => x.to_dns_label({suffix_hash: 4}))"#)
+            .unwrap();
+    let hashed: Vec<String> = hashed
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|x| x.as_value_string().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        hashed[0],
+        format!("my-app-{}", &format!("{:016x}", fnv1a("My App"))[..4])
+    );
+    assert!(hashed
+        .iter()
+        .all(|x| x.starts_with("my-app-") && x.len() == 11));
+    assert_ne!(hashed[0], hashed[1]);
+    assert_ne!(hashed[1], hashed[2]);
+    let long = eval(r#""x".repeat(100).to_dns_label({suffix_hash: 8})"#).unwrap();
+    assert_eq!(long.as_value_string().unwrap().len(), 63);
+    let env = eval(r#""db url".to_env_var_name({suffix_hash: 6})"#).unwrap();
+    assert!(env.as_value_string().unwrap().starts_with("DB_URL_"));
+    assert_eq!(
+        env.to_string().trim_matches('"').to_uppercase(),
+        env.to_string().trim_matches('"')
+    );
+
+    let error = |s: &str| eval(s).unwrap_err().message;
+    assert_eq!(
+        error(r#""!!! ???".to_dns_label()"#),
+        r#"'to_dns_label' of "!!! ???" is empty, as none of its characters are allowed"#
+    );
+    assert_eq!(
+        error(r#""名前".sanitize({allowed: "a-z", replace: ""})"#),
+        r#"'sanitize' of "名前" is empty, as none of its characters are allowed"#
+    );
+    assert_eq!(
+        error(r#""名前".sanitize({allowed: "a-z"})"#),
+        r#"'sanitize' replaces with "-", which is not allowed itself"#
+    );
+    assert_eq!(
+        error(r#""xyz".sanitize({allowed: "x-z", replace: "", suffix_hash: 4})"#),
+        "'sanitize' with 'suffix_hash' needs hex digits to be allowed"
+    );
+    assert_eq!(
+        error(r#""abc".to_dns_label({max_len: 5, suffix_hash: 4})"#),
+        "'to_dns_label' cannot fit the hash suffix into a max_len of 5"
+    );
+    assert_eq!(
+        error(r#""abc".sanitize({max_len: 0})"#),
+        "Unsupported 'sanitize' option max_len: 0"
+    );
+}
@@ -1,27 +1,140 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 
+pub use compiled::Compiled;
 use context::Context;
+pub use dependencies::Dependencies;
+pub use diff::{structural_diff, Change, Difference};
+pub use evaluation::{
+    AppliedDefault, EnvProvider, EvalOptions, EvalReport, FileLoader, FsLoader, ProcessEnv, Warning,
+};
+pub use import_graph::{GraphFile, ImportEdge, ImportGraph};
+pub use methods::string::parse_data;
 pub use source::{Location, Source};
+pub use tree::{write_tree, WriteTreeOptions, WriteTreeReport};
 pub use value::Value;
+pub use workspace::{
+    evaluate_workspace, expand_entries, load_checks, WorkspaceCheck, WorkspaceOptions,
+    WorkspaceResult, WorkspaceViolation,
+};
+pub use yaml::{YamlOptions, YamlQuoting, YamlStyle};
 
-use crate::parser::ExprWithLocation;
+use crate::parser::{
+    furthest_position, parse_unit, requirements, reset_furthest, ExprWithLocation, Span,
+};
 pub use error::{Error, ErrorWithLocation};
+pub use node::EXPLAIN_TARGET;
+pub use replay::Recording;
+pub use requirements::LANGUAGE_FEATURES;
 
 #[macro_use]
 mod error;
+mod binary;
+mod collation;
 mod compile;
+mod compiled;
 mod context;
+mod de;
+mod dependencies;
+mod deprecation;
+mod diff;
+mod evaluation;
 mod functions;
+mod glob;
+mod import_graph;
 mod methods;
 mod node;
 mod operators;
+mod path;
+mod redact;
+mod replay;
+mod requirements;
 mod source;
+mod tree;
 mod value;
 mod value_extraction;
+mod workspace;
+mod yaml;
 
-pub fn compile(expr: &ExprWithLocation, source: Source) -> Result<Value, Error> {
-    let node = compile::Compiler::new(source).compile(&Context::empty(), expr)?;
+/// Parses the whole source, errors point to the position where parsing failed.
+/// The requirements declared at the beginning are checked before the rest is parsed.
+pub fn parse(source: &Source) -> Result<ExprWithLocation<'_>, Error> {
+    let input = source.as_str();
+    // Parse errors show the line with a caret, as a message like "Cannot parse '}'" is hard to find in a long file.
+    let fail = |position: usize, message: String| {
+        let location = Location {
+            source: source.clone(),
+            position,
+        };
+        ErrorWithLocation {
+            message: format!("{}\n{}", message, location.snippet()),
+            location: Some(location),
+            hint: None,
+        }
+    };
+    let unexpected = |rest: &str| match rest.lines().next() {
+        Some(line) => {
+            let snippet: String = line.chars().take(30).collect();
+            format!("Cannot parse '{}'", snippet)
+        }
+        None => "Unexpected end of input".to_string(),
+    };
+    let (body, declared) = requirements(Span::new(input))
+        .map_err(|_| fail(0, "Cannot parse the requirements".to_string()))?;
+    requirements::check(&declared, |position| Location {
+        source: source.clone(),
+        position,
+    })?;
+    // Errors are reported at the furthest position the parser reached, not where `alt` backtracked to.
+    let furthest = |rest: &Span| rest.location_offset().max(furthest_position());
+    reset_furthest();
+    match parse_unit(body) {
+        Ok((rest, expr)) if rest.fragment().is_empty() => Ok(expr),
+        Ok((rest, _)) => {
+            let position = furthest(&rest);
+            Err(fail(position, unexpected(&input[position..])))
+        }
+        Err(nom::Err::Error((rest, kind))) | Err(nom::Err::Failure((rest, kind))) => Err(fail(
+            match kind {
+                nom::error::ErrorKind::TooLarge | nom::error::ErrorKind::Escaped => {
+                    rest.location_offset()
+                }
+                _ => furthest(&rest),
+            },
+            match kind {
+                nom::error::ErrorKind::TooLarge => "Expression is nested too deeply".to_string(),
+                nom::error::ErrorKind::Escaped => format!(
+                    "Unknown escape sequence '{}', use '\\\\' for a backslash",
+                    rest.fragment().chars().take(2).collect::<String>()
+                ),
+                _ => unexpected(&input[furthest(&rest)..]),
+            },
+        )),
+        Err(nom::Err::Incomplete(_)) => {
+            Err(fail(input.len(), "Unexpected end of input".to_string()))
+        }
+    }
+}
+
+/// Compiles the expression, `vars` are available as predefined variables.
+pub fn compile(
+    expr: &ExprWithLocation,
+    source: Source,
+    vars: &HashMap<String, Value>,
+    loader: Rc<dyn FileLoader>,
+) -> Result<Compiled, Error> {
+    let ctx = Context::empty();
+    for (name, value) in vars {
+        ctx.bind(
+            name.clone(),
+            node::CodeNode::new(node::NodeContent::Resolved(value.clone()), None),
+        );
+    }
+    let compiler = compile::Compiler::new(source.clone(), loader);
+    let node = compiler.compile(&ctx, expr)?;
     debug!(?node, "compiled node");
-    node.resolve(&Context::empty())
+    let mut sources = vec![source];
+    sources.extend(compiler.imports());
+    let graph = import_graph::ImportGraph::new(&sources, compiler.import_edges());
+    Ok(Compiled::new(node, &sources, graph))
 }
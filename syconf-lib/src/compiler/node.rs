@@ -1,9 +1,72 @@
+use std::cell::Cell;
+use std::fmt;
+
+use crate::compiler::deprecation::Deprecation;
 use crate::compiler::value::{Func, ValueString};
 use crate::compiler::*;
+use crate::depth::DepthGuard;
 
 use super::context::Context;
 use super::value::Value;
 
+/// Target of the tracing events that explain the evaluation.
+/// Every evaluated function call, including operators and methods, is emitted as an INFO event
+/// with a human readable message like `config.sy:3: add(1, 2) = 3`.
+pub const EXPLAIN_TARGET: &str = "syconf::explain";
+
+thread_local! {
+    static EVALUATION_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static LIMITS: Cell<Limits> = const { Cell::new(Limits::DEFAULT) };
+    static NODES_EVALUATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The limits of `EvalOptions`, which stop e.g. infinite recursion and huge computations.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_nodes: u64,
+}
+
+impl Limits {
+    pub const DEFAULT: Limits = Limits {
+        max_depth: 1000,
+        max_nodes: 100_000_000,
+    };
+}
+
+/// Sets the limits of an evaluation that starts, returns the state of the outer one to restore it by `restore_limits`.
+pub fn set_limits(limits: Limits) -> (Limits, u64) {
+    (
+        LIMITS.with(|x| x.replace(limits)),
+        NODES_EVALUATED.with(|x| x.replace(0)),
+    )
+}
+
+pub fn restore_limits((limits, nodes_evaluated): (Limits, u64)) {
+    LIMITS.with(|x| x.set(limits));
+    NODES_EVALUATED.with(|x| x.set(nodes_evaluated));
+}
+
+/// The budget cost of a list element that a builtin creates without evaluating nodes. An element takes more memory
+/// than an evaluated node leaves behind, so the default budget allows lists of 10 million elements, a few hundred MB.
+const ELEMENT_COST: u64 = 10;
+
+/// Charges list elements that a builtin creates without evaluating nodes, e.g. the elements of `range(n)`, to the budget.
+/// Call it before allocating them.
+pub fn charge_elements(count: u64) -> Result<(), Error> {
+    let max_nodes = LIMITS.with(Cell::get).max_nodes;
+    let evaluated = NODES_EVALUATED
+        .with(|x| x.get())
+        .saturating_add(count.saturating_mul(ELEMENT_COST));
+    NODES_EVALUATED.with(|x| x.set(evaluated));
+    check!(
+        evaluated < max_nodes,
+        "Evaluation budget of {} nodes exceeded",
+        max_nodes
+    );
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct FunctionDefinition {
     pub argument_names: Option<Vec<String>>,
@@ -27,6 +90,26 @@ pub enum NodeContent {
         // If arguments is None, it is just a variable, i.e. the value as it is.
         arguments: Option<Vec<CodeNode>>,
     },
+    /// `if condition then a else b`, only the selected branch is evaluated.
+    If {
+        condition: CodeNode,
+        then_branch: CodeNode,
+        else_branch: CodeNode,
+    },
+    /// `a and b`, `b` is evaluated only if `a` is `true`.
+    And(CodeNode, CodeNode),
+    /// `a or b`, `b` is evaluated only if `a` is `false`.
+    Or(CodeNode, CodeNode),
+    /// A deprecated builtin, which is reported by `evaluation::deprecation_used` whenever it is used.
+    Deprecated {
+        deprecation: &'static Deprecation,
+        node: CodeNode,
+    },
+    /// `cond([[test, value], ...], default)` with literal branches, the tests are evaluated lazily in order.
+    Cond {
+        branches: Vec<(CodeNode, CodeNode)>,
+        default: CodeNode,
+    },
 }
 
 #[derive(Debug)]
@@ -53,6 +136,20 @@ impl CodeNode {
     }
 
     pub fn resolve(&self, ctx: &Context) -> Result<Value, ErrorWithLocation> {
+        let limits = LIMITS.with(Cell::get);
+        let _guard = DepthGuard::enter(&EVALUATION_DEPTH, limits.max_depth).ok_or_else(|| {
+            self.err(format!(
+                "Maximum evaluation depth of {} exceeded",
+                limits.max_depth
+            ))
+        })?;
+        let evaluated = NODES_EVALUATED.with(|x| x.replace(x.get() + 1));
+        if evaluated >= limits.max_nodes {
+            return Err(self.err(format!(
+                "Evaluation budget of {} nodes exceeded",
+                limits.max_nodes
+            )));
+        }
         match &self.0.content {
             NodeContent::Resolved(v) => Ok(v.clone()),
             NodeContent::FunctionInputArgument(name) => ctx
@@ -68,22 +165,30 @@ impl CodeNode {
                 .collect::<Result<Vec<Value>, ErrorWithLocation>>()
                 .map(Into::into)
                 .map(Value::List),
-            NodeContent::HashMap(hm) => hm
-                .iter()
-                .map(|HmEntry { key, value }| {
-                    Ok((
-                        key.resolve(ctx)?
+            NodeContent::HashMap(hm) => {
+                let max_key_size = evaluation::options().max_key_size;
+                hm.iter()
+                    .map(|HmEntry { key, value }| {
+                        let k = key
+                            .resolve(ctx)?
                             .as_value_string()
                             .map_err(|e| self.err(e.to_string()))?
-                            .clone(),
-                        value.resolve(ctx)?,
-                    ))
-                })
-                .collect::<Result<HashMap<ValueString, Value>, ErrorWithLocation>>()
-                .map(Rc::new)
-                .map(Value::HashMap),
+                            .clone();
+                        if k.len() > max_key_size {
+                            return Err(self.add_location(key.err(format!(
+                                "Hashmap key is {} bytes long, which exceeds the limit of {} bytes",
+                                k.len(),
+                                max_key_size
+                            ))));
+                        }
+                        Ok((k, value.resolve(ctx)?))
+                    })
+                    .collect::<Result<HashMap<ValueString, Value>, ErrorWithLocation>>()
+                    .map(Rc::new)
+                    .map(Value::HashMap)
+            }
             NodeContent::FunctionCall {
-                name: _,
+                name,
                 function,
                 arguments,
             } => {
@@ -97,19 +202,124 @@ impl CodeNode {
                     .map_or(Ok(None), |v| v.map(Some))?;
                 match (&function.resolve(ctx)?, &opt_args) {
                     (Value::Func(func), Some(args)) => {
-                        func.call(args.as_slice()) //.map_err(|e| self.add_location(e))
+                        let result = func.call(args.as_slice());
+                        evaluation::locate_report(self.0.location.as_ref());
+                        let result = result.map_err(|e| self.add_location(e))?;
+                        info!(
+                            target: EXPLAIN_TARGET,
+                            "{}{}({}) = {}",
+                            ExplainLocation(self.0.location.as_ref()),
+                            name,
+                            ExplainArgs(args),
+                            ExplainValue(&result)
+                        );
+                        Ok(result)
                     }
                     (_, Some(_)) => Err(self.err("value is not a function".to_string())),
                     (x, None) => Ok(x.clone()),
                 }
             }
+            NodeContent::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match condition.resolve(ctx)? {
+                Value::Bool(true) => then_branch.resolve(ctx),
+                Value::Bool(false) => else_branch.resolve(ctx),
+                x => Err(condition.err(operators::if_condition_error(&x))),
+            },
+            NodeContent::And(left, right) => self.resolve_logical(ctx, "and", left, right, false),
+            NodeContent::Or(left, right) => self.resolve_logical(ctx, "or", left, right, true),
+            NodeContent::Cond { branches, default } => self.resolve_cond(ctx, branches, default),
+            NodeContent::Deprecated { deprecation, node } => {
+                evaluation::deprecation_used(deprecation, self.0.location.as_ref())
+                    .map_err(|e| self.add_location(e))?;
+                node.resolve(ctx)
+            }
+        }
+    }
+
+    /// Returns `decisive` without evaluating `right` if `left` is `decisive`.
+    fn resolve_logical(
+        &self,
+        ctx: &Context,
+        op: &str,
+        left: &CodeNode,
+        right: &CodeNode,
+        decisive: bool,
+    ) -> Result<Value, ErrorWithLocation> {
+        let operand = |node: &CodeNode| {
+            let value = node.resolve(ctx)?;
+            operators::operand(op, &value).map_err(|e| self.add_location(e))
+        };
+        if operand(left)? == decisive {
+            return Ok(Value::Bool(decisive));
+        }
+        Ok(Value::Bool(operand(right)?))
+    }
+
+    fn resolve_cond(
+        &self,
+        ctx: &Context,
+        branches: &[(CodeNode, CodeNode)],
+        default: &CodeNode,
+    ) -> Result<Value, ErrorWithLocation> {
+        for (test, value) in branches {
+            match test.resolve(ctx)? {
+                Value::Bool(true) => return value.resolve(ctx),
+                Value::Bool(false) => {}
+                x => return Err(test.err(functions::cond_test_error(&x))),
+            }
         }
+        default.resolve(ctx)
+    }
+
+    /// Errors of builtin functions have no location, the location of the call is used instead.
+    fn add_location(&self, mut err: ErrorWithLocation) -> ErrorWithLocation {
+        if err.location.is_none() {
+            err.location = self.0.location.clone();
+        }
+        err
     }
 
     fn err(&self, message: String) -> ErrorWithLocation {
         ErrorWithLocation {
             message,
             location: self.0.location.clone(),
+            hint: None,
+        }
+    }
+}
+
+struct ExplainLocation<'a>(Option<&'a Location>);
+
+impl<'a> fmt::Display for ExplainLocation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(location) => write!(f, "{}: ", location),
+            None => Ok(()),
+        }
+    }
+}
+
+struct ExplainArgs<'a>(&'a [Value]);
+
+impl<'a> fmt::Display for ExplainArgs<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (ix, arg) in self.0.iter().enumerate() {
+            if ix > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&arg.summary())?;
         }
+        Ok(())
+    }
+}
+
+struct ExplainValue<'a>(&'a Value);
+
+impl<'a> fmt::Display for ExplainValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.summary())
     }
 }
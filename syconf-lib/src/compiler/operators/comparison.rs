@@ -38,6 +38,17 @@ fn op_equal() {
         .unwrap(),
         Value::Bool(true)
     );
+    assert_eq!(parse_string("1 == 1.0").unwrap(), Value::Bool(true));
+    assert_eq!(
+        parse_string("[1, 2.5] == [1.0, 2.5]").unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(parse_string("1 < 1.5").unwrap(), Value::Bool(true));
+    assert_eq!(parse_string("2.5 > 3").unwrap(), Value::Bool(false));
+    assert_eq!(
+        parse_string("unique([1, 1.0, 1.5])").unwrap(),
+        parse_string("[1, 1.5]").unwrap()
+    );
 }
 
 fn not_equal(args: &[Value]) -> Result<Value, Error> {
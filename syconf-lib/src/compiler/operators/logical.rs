@@ -1,13 +1,50 @@
 use crate::compiler::{Error, Value};
 
-pub fn and(args: &[Value]) -> Result<Value, Error> {
-    Ok(Value::Bool(args[0].as_bool()? && args[1].as_bool()?))
+pub fn not(args: &[Value]) -> Result<Value, Error> {
+    Ok(Value::Bool(!operand("not", &args[0])?))
 }
 
-pub fn or(args: &[Value]) -> Result<Value, Error> {
-    Ok(Value::Bool(args[0].as_bool()? || args[1].as_bool()?))
+/// There is no truthiness, other values fail instead of silently turning into `true` or `false`.
+/// `and` and `or` are compiled into `NodeContent::And` and `NodeContent::Or` to short-circuit.
+pub fn operand(op: &str, value: &Value) -> Result<bool, Error> {
+    match value {
+        Value::Bool(x) => Ok(*x),
+        Value::String(_) => Err(Error::from(format!(
+            "'{}' expects bools, got the string {}",
+            op,
+            value.summary()
+        ))
+        .with_hint("use 'x.to_bool()' to read a bool from a string, e.g. from 'getenv'")),
+        Value::Null => Err(Error::from(format!("'{}' expects bools, got null", op))
+            .with_hint("use 'x.is_null()' to test for null")),
+        x => Err(format!("'{}' expects bools, got {}", op, x.summary()).into()),
+    }
 }
 
-pub fn not(args: &[Value]) -> Result<Value, Error> {
-    Ok(Value::Bool(!args[0].as_bool()?))
+#[test]
+fn logical_operands() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("[true and false, true or false, not false, not (1 > 2)]").unwrap(),
+        eval("[false, true, true, true]").unwrap()
+    );
+    let err = eval(r#"not "false""#).unwrap_err();
+    assert_eq!(
+        err.message,
+        r#"'not' expects bools, got the string "false""#
+    );
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("use 'x.to_bool()' to read a bool from a string, e.g. from 'getenv'")
+    );
+    assert_eq!(
+        eval(r#"true and "yes""#).unwrap_err().message,
+        r#"'and' expects bools, got the string "yes""#
+    );
+    assert_eq!(
+        eval("false or 1").unwrap_err().message,
+        "'or' expects bools, got 1"
+    );
+    assert!(eval("null and true").unwrap_err().hint.is_some());
+    assert_eq!(eval(r#"not "false".to_bool()"#).unwrap(), Value::Bool(true));
 }
@@ -11,26 +11,86 @@ pub fn math(op: &MathOp) -> &'static dyn Fn(&[Value]) -> Result<Value, Error> {
 }
 
 fn op_add(oargs: &[Value]) -> Result<Value, Error> {
-    math_bi_op(|a, b| a + b, oargs)
+    math_bi_op(i64::checked_add, |a, b| a + b, oargs)
 }
 fn op_sub(oargs: &[Value]) -> Result<Value, Error> {
-    math_bi_op(|a, b| a - b, oargs)
+    math_bi_op(i64::checked_sub, |a, b| a - b, oargs)
 }
 fn op_mul(oargs: &[Value]) -> Result<Value, Error> {
-    math_bi_op(|a, b| a * b, oargs)
+    math_bi_op(i64::checked_mul, |a, b| a * b, oargs)
 }
+/// Divides ints with truncation, `1 / 2 == 0`, but `1.0 / 2 == 0.5`. Also `0.0` is equal to `0`.
 fn op_div(oargs: &[Value]) -> Result<Value, Error> {
-    math_bi_op(|a, b| a / b, oargs)
+    check!(oargs.get(1) != Some(&Value::Int(0)), "Division by zero");
+    math_bi_op(i64::checked_div, |a, b| a / b, oargs)
 }
 
-fn math_bi_op<F: Fn(i32, i32) -> i32>(f: F, args: &[Value]) -> Result<Value, Error> {
+/// The unary minus, `-x` is the same as `x.negate()`.
+pub fn negate(args: &[Value]) -> Result<Value, Error> {
+    match &args[0] {
+        Value::Int(x) => x
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| format!("Integer overflow in negation of {}", x).into()),
+        Value::Float(x) => Ok(Value::Float(-x)),
+        x => Err(format!("'-' expects a number, got {}", x.summary()).into()),
+    }
+}
+
+/// Applies `int_op` if both arguments are ints, it returns `None` on overflow.
+/// Otherwise the ints are converted to floats and `float_op` is applied.
+fn math_bi_op<F, G>(int_op: F, float_op: G, args: &[Value]) -> Result<Value, Error>
+where
+    F: Fn(i64, i64) -> Option<i64>,
+    G: Fn(f64, f64) -> f64,
+{
     check!(args.len() == 2, "expects 2 arguments");
     match (&args[0], &args[1]) {
-        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(f(*a, *b))),
-        _ => Err(format!(
-            "Expects INT and INT, but was {:?} and {:?}",
-            &args[0], &args[1]
-        )
-        .into()),
+        (Value::Int(a), Value::Int(b)) => int_op(*a, *b)
+            .map(Value::Int)
+            .ok_or_else(|| format!("Integer overflow in operation on {} and {}", a, b).into()),
+        (a, b) => match (a.as_float(), b.as_float()) {
+            (Ok(x), Ok(y)) => {
+                let out = float_op(x, y);
+                check!(
+                    out.is_finite(),
+                    "Float overflow in operation on {} and {}",
+                    a,
+                    b
+                );
+                Ok(Value::Float(out))
+            }
+            _ => {
+                let error = Error::from(format!("Expects numbers, but was {:?} and {:?}", a, b));
+                if matches!(a, Value::String(_)) || matches!(b, Value::String(_)) {
+                    Err(error.with_hint(
+                        "strings are joined by interpolation, e.g. \"${a}${b}\", \
+                         and 'x.parse_json()' reads a number from a string",
+                    ))
+                } else {
+                    Err(error)
+                }
+            }
+        },
     }
 }
+
+#[test]
+fn float_arithmetic() {
+    let eval = |s: &str| crate::parse_string(s).unwrap();
+    assert_eq!(eval("1 / 2"), Value::Int(0));
+    assert_eq!(eval("1.0 / 2"), Value::Float(0.5));
+    assert_eq!(eval("3 * 0.5"), Value::Float(1.5));
+    assert_eq!(eval("0.1 + 0.2 - 0.3 < 1e-9"), Value::Bool(true));
+    assert_eq!(eval("2.5e2 + 1"), Value::Float(251.0));
+    assert!(crate::parse_string("1 / 0.0").is_err());
+    assert!(crate::parse_string("1e300 * 1e300").is_err());
+    assert!(crate::parse_string("1.5 + \"a\"").is_err());
+}
+
+#[test]
+fn string_math_hint() {
+    let err = crate::parse_string(r#""port: " + 80"#).unwrap_err();
+    assert!(err.hint.unwrap().contains("interpolation"));
+    assert_eq!(crate::parse_string("[1] + 80").unwrap_err().hint, None);
+}
@@ -1,17 +1,20 @@
 pub use comparison::comparison;
 pub use logical::*;
-pub use math::math;
+pub use math::{math, negate};
 
-use crate::compiler::{Error, Value};
+use crate::compiler::Value;
 
 mod comparison;
 mod logical;
 mod math;
 
-pub fn conditional(args: &[Value]) -> Result<Value, Error> {
-    Ok(if args[0].as_bool()? {
-        args[1].clone()
-    } else {
-        args[2].clone()
-    })
+/// `if` is compiled into `NodeContent::If` to evaluate only the selected branch.
+pub fn if_condition_error(condition: &Value) -> String {
+    match condition {
+        Value::Null => {
+            "'if' condition must be a bool, got null, use 'x.is_null()' to test for null"
+                .to_string()
+        }
+        x => format!("'if' condition must be a bool, got {}", x.summary()),
+    }
 }
@@ -0,0 +1,86 @@
+use crate::compiler::Error;
+
+/// A step of a path like `services[0].name` addressing a nested value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parses paths like `db.password`, `services[0].token`, or `labels["app.kubernetes.io/name"]`.
+pub fn parse(path: &str) -> Result<Vec<PathSegment>, Error> {
+    let fail = || -> Error { format!("Invalid path '{}'", path).into() };
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix("[\"") {
+            let end = r.find("\"]").ok_or_else(fail)?;
+            segments.push(PathSegment::Key(r[..end].to_string()));
+            rest = &r[end + 2..];
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']').ok_or_else(fail)?;
+            segments.push(PathSegment::Index(r[..end].parse().map_err(|_| fail())?));
+            rest = &r[end + 1..];
+        } else {
+            let r = if segments.is_empty() {
+                rest
+            } else {
+                rest.strip_prefix('.').ok_or_else(fail)?
+            };
+            let end = r.find(['.', '[']).unwrap_or(r.len());
+            if end == 0 {
+                return Err(fail());
+            }
+            segments.push(PathSegment::Key(r[..end].to_string()));
+            rest = &r[end..];
+        }
+    }
+    Ok(segments)
+}
+
+/// Writes the segments back as a path, quoting keys that are not plain identifiers.
+pub fn render(segments: &[PathSegment]) -> String {
+    let mut out = String::new();
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) if key.is_empty() || key.contains(['.', '[', ']', '"']) => {
+                out.push_str(&format!("[\"{}\"]", key))
+            }
+            PathSegment::Key(key) if out.is_empty() => out.push_str(key),
+            PathSegment::Key(key) => out.push_str(&format!(".{}", key)),
+            PathSegment::Index(ix) => out.push_str(&format!("[{}]", ix)),
+        }
+    }
+    out
+}
+
+#[test]
+fn parse_path() {
+    use PathSegment::*;
+    assert_eq!(
+        parse(r#"services[0].db["a.b"][2]"#).unwrap(),
+        vec![
+            Key("services".into()),
+            Index(0),
+            Key("db".into()),
+            Key("a.b".into()),
+            Index(2)
+        ]
+    );
+    assert_eq!(parse("").unwrap(), vec![]);
+    assert!(parse("a..b").is_err());
+    assert!(parse("a[x]").is_err());
+    assert!(parse("a[1").is_err());
+}
+
+#[test]
+fn render_path() {
+    for path in &[
+        "a",
+        r#"services[0].db["a.b"][2]"#,
+        "[1].x",
+        r#"["k8s/a.yaml"]"#,
+    ] {
+        assert_eq!(render(&parse(path).unwrap()), *path);
+    }
+}
@@ -0,0 +1,158 @@
+use std::rc::Rc;
+
+use crate::compiler::path::{self, PathSegment};
+use crate::compiler::{glob, Error, Value};
+
+const REDACTED: &str = "***";
+
+/// Describes which values of a config are replaced with `"***"`.
+#[derive(Debug, Default)]
+pub struct Redaction {
+    /// Lowercase glob patterns matched against hashmap keys at every nesting level.
+    key_patterns: Vec<String>,
+    /// Explicit paths, e.g. `db.password` or `services[0].token`.
+    paths: Vec<Vec<PathSegment>>,
+}
+
+impl Redaction {
+    pub fn from_patterns<S: AsRef<str>>(patterns: &[S]) -> Self {
+        Self {
+            key_patterns: patterns.iter().map(|p| p.as_ref().to_lowercase()).collect(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Reads either a list of key patterns or a hashmap `{keys: [...], paths: [...]}`.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let strings = |value: &Value| -> Result<Vec<String>, Error> {
+            value
+                .as_list()?
+                .iter()
+                .map(|x| Ok(x.as_value_string()?.to_string()))
+                .collect()
+        };
+        match value {
+            Value::List(_) => Ok(Self::from_patterns(&strings(value)?)),
+            Value::HashMap(hm) => {
+                let mut out = Self::default();
+                for (key, val) in hm.iter() {
+                    match key.as_ref() {
+                        "keys" => {
+                            out.key_patterns = Self::from_patterns(&strings(val)?).key_patterns
+                        }
+                        "paths" => {
+                            out.paths = strings(val)?
+                                .iter()
+                                .map(|p| path::parse(p))
+                                .collect::<Result<_, _>>()?
+                        }
+                        _ => return Err(format!("Unsupported redact option '{}'", key).into()),
+                    }
+                }
+                Ok(out)
+            }
+            _ => Err("redaction must be a list of key patterns or a hashmap of options".into()),
+        }
+    }
+
+    /// Returns a redacted copy of the value. Subtrees without redacted values are shared with the original.
+    pub fn apply(&self, value: &Value) -> Value {
+        let mut path = Vec::new();
+        if self.is_redacted_path(&path) {
+            return Value::String(REDACTED.into());
+        }
+        self.walk(value, &mut path).unwrap_or_else(|| value.clone())
+    }
+
+    /// Returns `None` if nothing inside the value is redacted.
+    fn walk(&self, value: &Value, path: &mut Vec<PathSegment>) -> Option<Value> {
+        match value {
+            Value::HashMap(hm) => {
+                let mut changed = Vec::new();
+                for (key, val) in hm.iter() {
+                    path.push(PathSegment::Key(key.to_string()));
+                    if self.is_redacted_key(key) || self.is_redacted_path(path) {
+                        changed.push((key.clone(), Value::String(REDACTED.into())));
+                    } else if let Some(x) = self.walk(val, path) {
+                        changed.push((key.clone(), x));
+                    }
+                    path.pop();
+                }
+                if changed.is_empty() {
+                    return None;
+                }
+                let mut out = hm.as_ref().clone();
+                out.extend(changed);
+                Some(Value::HashMap(Rc::new(out)))
+            }
+            Value::List(list) => {
+                let mut changed = Vec::new();
+                for (ix, val) in list.iter().enumerate() {
+                    path.push(PathSegment::Index(ix));
+                    if self.is_redacted_path(path) {
+                        changed.push((ix, Value::String(REDACTED.into())));
+                    } else if let Some(x) = self.walk(val, path) {
+                        changed.push((ix, x));
+                    }
+                    path.pop();
+                }
+                if changed.is_empty() {
+                    return None;
+                }
+                let mut out = list.to_vec();
+                for (ix, x) in changed {
+                    out[ix] = x;
+                }
+                Some(Value::List(out.into()))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_redacted_key(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.key_patterns.iter().any(|p| glob::matches(p, &key))
+    }
+
+    fn is_redacted_path(&self, path: &[PathSegment]) -> bool {
+        self.paths.iter().any(|p| p.as_slice() == path)
+    }
+}
+
+#[test]
+fn redact_shares_untouched_values() {
+    let original = crate::parse_string(
+        r#"{
+            db: {host: "h", PassWord: "p"},
+            services: [{name: "a", api_token: "t"}, {name: "b"}],
+            public: {tags: ["x"]},
+        }"#,
+    )
+    .unwrap();
+    let redacted = original.redacted(&["password", "*_TOKEN"]);
+    assert_eq!(
+        redacted,
+        crate::parse_string(
+            r#"{
+            db: {host: "h", PassWord: "***"},
+            services: [{name: "a", api_token: "***"}, {name: "b"}],
+            public: {tags: ["x"]},
+        }"#
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        original.as_hashmap().unwrap()["db"].as_hashmap().unwrap()["PassWord"],
+        Value::String("p".into())
+    );
+    let shared = |v: &Value| match &v.as_hashmap().unwrap()["public"] {
+        Value::HashMap(hm) => hm.clone(),
+        _ => unreachable!(),
+    };
+    assert!(Rc::ptr_eq(&shared(&original), &shared(&redacted)));
+    let services = |v: &Value| v.as_hashmap().unwrap()["services"].as_list().unwrap()[1].clone();
+    match (services(&original), services(&redacted)) {
+        (Value::HashMap(a), Value::HashMap(b)) => assert!(Rc::ptr_eq(&a, &b)),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::compiler::evaluation::{EnvProvider, EvalOptions, EvalReport, FileLoader};
+use crate::compiler::{glob, Compiled, Error, ErrorWithLocation, Source};
+
+/// The version of the recording format, incremented on incompatible changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// Everything an evaluation consumed, written by `EvalOptions::record`, so that the evaluation can be replayed
+/// on another machine or after the files changed. It is stored as a single JSON file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    /// The path of the config file as it was compiled, the imports are resolved relative to it again.
+    pub entry: PathBuf,
+    /// The config file, its imports, and the files read by `read_file`, by the paths they were read with.
+    pub files: BTreeMap<PathBuf, String>,
+    /// The environment variables read by `getenv` and the like, `None` if a variable was not set.
+    pub env: BTreeMap<String, Option<String>>,
+}
+
+impl Recording {
+    pub(crate) fn new(
+        sources: &[Source],
+        report: &EvalReport,
+        redact: &[String],
+    ) -> Result<Self, Error> {
+        let entry = match sources.first() {
+            Some(src) if src.is_file() => src.file().clone(),
+            _ => return Err("Only a config compiled from a file can be recorded".into()),
+        };
+        let mut files = report.files_read.clone();
+        files.extend(
+            sources
+                .iter()
+                .map(|src| (src.file().clone(), src.as_str().to_string())),
+        );
+        let redacted = |name: &str| {
+            redact
+                .iter()
+                .any(|p| glob::matches(&p.to_lowercase(), &name.to_lowercase()))
+        };
+        let env = report
+            .dependencies
+            .env
+            .iter()
+            .map(|(name, value)| match value {
+                Some(_) if redacted(name) => (name.clone(), Some("***".to_string())),
+                _ => (name.clone(), value.clone()),
+            })
+            .collect();
+        Ok(Self {
+            version: FORMAT_VERSION,
+            entry,
+            files,
+            env,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read recording '{}': {}", path.display(), e))?;
+        let recording: Self = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Cannot parse recording '{}': {}", path.display(), e))?;
+        check!(
+            recording.version == FORMAT_VERSION,
+            "Unsupported recording version {} in '{}'",
+            recording.version,
+            path.display()
+        );
+        Ok(recording)
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, json)
+            .map_err(|e| anyhow!("Cannot write recording '{}': {}", path.display(), e).into())
+    }
+
+    /// Compiles the recorded config and returns the options to evaluate it with,
+    /// which read the files and environment variables only from the recording.
+    pub fn replay(self) -> Result<(Compiled, EvalOptions), ErrorWithLocation> {
+        let entry = self.entry.to_string_lossy().to_string();
+        let recording = Rc::new(self);
+        let compiled = crate::compile_file_with_loader(&entry, recording.clone())?;
+        let options = EvalOptions {
+            file_loader: recording.clone(),
+            env_provider: recording,
+            ..Default::default()
+        };
+        Ok((compiled, options))
+    }
+}
+
+impl FileLoader for Recording {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in the recording"))
+    }
+}
+
+/// Variables that were not read by the recorded evaluation are not set.
+impl EnvProvider for Recording {
+    fn var(&self, name: &str) -> Option<String> {
+        self.env.get(name).cloned().flatten()
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    let dir = std::env::temp_dir().join(format!("syconf-replay-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("main.sy");
+    fs::write(
+        &main,
+        r#"{
+    db: import "db.sy",
+    motd: read_file("DIR/motd.txt"),
+    user: getenv("SYCONF_REPLAY_USER"),
+    token: getenv("SYCONF_REPLAY_TOKEN", "none"),
+    home: getenv("SYCONF_REPLAY_UNSET", "/home"),
+}"#
+        .replace("DIR", &dir.display().to_string()),
+    )
+    .unwrap();
+    fs::write(dir.join("db.sy"), "{port: 5432}").unwrap();
+    fs::write(dir.join("motd.txt"), "hello").unwrap();
+    std::env::set_var("SYCONF_REPLAY_USER", "alice");
+    std::env::set_var("SYCONF_REPLAY_TOKEN", "t0ken");
+
+    let archive = dir.join("recording.json");
+    let options = EvalOptions {
+        record_redact: vec!["*_token".to_string()],
+        ..EvalOptions::record(&archive)
+    };
+    let compiled = crate::compile_file(main.to_str().unwrap()).unwrap();
+    let original = compiled.evaluate_with_options(&options).unwrap();
+
+    fs::write(dir.join("db.sy"), "{port: 1}").unwrap();
+    fs::remove_file(dir.join("motd.txt")).unwrap();
+    std::env::set_var("SYCONF_REPLAY_USER", "bob");
+    std::env::set_var("SYCONF_REPLAY_UNSET", "/root");
+
+    let recording = Recording::load(&archive).unwrap();
+    assert_eq!(recording.entry, main);
+    assert_eq!(recording.files.len(), 3);
+    assert_eq!(recording.env["SYCONF_REPLAY_TOKEN"].as_deref(), Some("***"));
+    assert_eq!(recording.env["SYCONF_REPLAY_UNSET"], None);
+    let (replayed, options) = recording.replay().unwrap();
+    let replay = replayed.evaluate_with_options(&options).unwrap();
+    assert_eq!(
+        replay,
+        crate::parse_string(
+            r#"{db: {port: 5432}, motd: "hello", user: "alice", token: "***", home: "/home"}"#
+        )
+        .unwrap()
+    );
+    assert_eq!(replay.to_json().unwrap(), {
+        let redacted = crate::parse_string(r#"{token: "***"}"#).unwrap();
+        original.deep_merge(&redacted).unwrap().to_json().unwrap()
+    });
+
+    let err = crate::compile_string("1")
+        .unwrap()
+        .evaluate_with_options(&EvalOptions::record(&archive))
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "Only a config compiled from a file can be recorded"
+    );
+    std::env::remove_var("SYCONF_REPLAY_USER");
+    std::env::remove_var("SYCONF_REPLAY_TOKEN");
+    std::env::remove_var("SYCONF_REPLAY_UNSET");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,174 @@
+use crate::compiler::{Error, ErrorWithLocation, Location};
+use crate::parser::{Requirement, RequirementWithLocation};
+
+/// The language features of this build that files can require with `required_features [...]`.
+pub const LANGUAGE_FEATURES: &[&str] = &[
+    "null",
+    "import_with",
+    "cond",
+    "decimal",
+    "redact",
+    "floats",
+    "spread",
+    "comments",
+    "escapes",
+    "unary_minus",
+    "with",
+];
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+type Version = (u32, u32, u32);
+
+/// Parses `MAJOR[.MINOR[.PATCH]]`, missing parts are zero.
+fn parse_version(s: &str) -> Option<Version> {
+    let mut parts = s.split('.').map(|x| {
+        if !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()) {
+            x.parse().ok()
+        } else {
+            None
+        }
+    });
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some((major, minor, patch)),
+    }
+}
+
+/// Checks a comma separated list of comparisons like `>=0.2, <1.0`, a version without an operator means `>=`.
+fn version_matches(requirement: &str, version: Version) -> Result<bool, Error> {
+    let fail = || -> Error {
+        format!(
+            "Invalid syconf_version requirement '{}', expected e.g. '>=0.2, <1.0'",
+            requirement
+        )
+        .into()
+    };
+    let mut matches = true;
+    for comparison in requirement.split(',') {
+        let comparison = comparison.trim();
+        let split = comparison
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(fail)?;
+        let (op, required) = comparison.split_at(split);
+        let required = parse_version(required).ok_or_else(fail)?;
+        matches &= match op.trim() {
+            ">=" | "" => version >= required,
+            ">" => version > required,
+            "<=" => version <= required,
+            "<" => version < required,
+            "=" => version == required,
+            _ => return Err(fail()),
+        };
+    }
+    Ok(matches)
+}
+
+fn check_requirement(requirement: &Requirement) -> Result<(), Error> {
+    match requirement {
+        Requirement::Version(req) => {
+            check!(
+                version_matches(req, parse_version(VERSION).unwrap())?,
+                "This file requires syconf {}, you are running {}",
+                req,
+                VERSION
+            );
+        }
+        Requirement::Features(features) => {
+            for feature in features {
+                check!(
+                    LANGUAGE_FEATURES.contains(feature),
+                    "This file requires the language feature '{}', which syconf {} does not support",
+                    feature,
+                    VERSION
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails on the first requirement this build does not satisfy.
+pub fn check(
+    requirements: &[RequirementWithLocation],
+    location: impl Fn(usize) -> Location,
+) -> Result<(), Error> {
+    for r in requirements {
+        check_requirement(&r.inner).map_err(|e| ErrorWithLocation {
+            location: Some(location(r.location.location_offset())),
+            message: e.message,
+            hint: e.hint,
+        })?;
+    }
+    Ok(())
+}
+
+#[test]
+fn version_requirements() {
+    assert_eq!(parse_version("0.3"), Some((0, 3, 0)));
+    assert_eq!(parse_version("1.2.3"), Some((1, 2, 3)));
+    assert_eq!(parse_version("1.2.3.4"), None);
+    assert_eq!(parse_version("1.x"), None);
+    assert!(version_matches(">=0.1, <1.0", (0, 1, 1)).unwrap());
+    assert!(version_matches("0.1", (0, 1, 1)).unwrap());
+    assert!(version_matches("=0.1.1", (0, 1, 1)).unwrap());
+    assert!(!version_matches(">=0.3", (0, 1, 1)).unwrap());
+    assert!(!version_matches(">0.1, <0.1.1", (0, 1, 1)).unwrap());
+    for x in &["", ">=", "~0.1", ">=0.1,", "0.1.x", "=>0.1"] {
+        assert!(version_matches(x, (0, 1, 1)).is_err(), "{}", x);
+    }
+}
+
+#[test]
+fn file_requirements() {
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval("syconf_version \">=0.1\"\nrequired_features [\"null\"]\n[null]").unwrap(),
+        eval("[null]").unwrap()
+    );
+    let err = eval("syconf_version \">=99.0\"\n{a: ...b}").unwrap_err();
+    assert_eq!(
+        err.message,
+        format!(
+            "This file requires syconf >=99.0, you are running {}",
+            VERSION
+        )
+    );
+    assert_eq!(err.location.unwrap().position, 0);
+    assert_eq!(
+        eval("required_features [\"spread\"]\nlet b = {x: 1} in {a: 2, ...b}").unwrap(),
+        eval("{a: 2, x: 1}").unwrap()
+    );
+    assert_eq!(
+        eval("required_features [\"comments\"]\n# a\n[1, /* b */ 2] // c").unwrap(),
+        eval("[1, 2]").unwrap()
+    );
+    assert_eq!(
+        eval("required_features [\"escapes\"]\n\"a\\tb\"").unwrap(),
+        eval("#\"a\tb\"#").unwrap()
+    );
+    assert_eq!(
+        eval("required_features [\"unary_minus\"]\n[-1, -(2)]").unwrap(),
+        eval("[0 - 1, 0 - 2]").unwrap()
+    );
+    assert_eq!(
+        eval("required_features [\"with\"]\nlet a = {x: 1} in with a {x: 2} => a").unwrap(),
+        eval("{x: 2}").unwrap()
+    );
+    let err = eval("// header\nrequired_features [\"null\", \"macros\"]\n{a: ...b}").unwrap_err();
+    assert_eq!(
+        err.message,
+        format!(
+            "This file requires the language feature 'macros', which syconf {} does not support",
+            VERSION
+        )
+    );
+    assert_eq!(err.location.unwrap().position, 10);
+    assert!(eval("syconf_version \"latest\"\n1")
+        .unwrap_err()
+        .message
+        .starts_with("Invalid syconf_version requirement 'latest'"));
+}
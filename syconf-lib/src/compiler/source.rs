@@ -1,6 +1,4 @@
-use crate::compiler::Error;
-use std::fs::File;
-use std::io::Read;
+use crate::compiler::{Error, FileLoader, FsLoader};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
@@ -11,19 +9,26 @@ pub struct Source(Rc<SourceRef>);
 struct SourceRef {
     file: PathBuf,
     content: String,
+    is_file: bool,
+    /// The directory relative imports are resolved against, none for strings without a base directory.
+    base_dir: Option<PathBuf>,
 }
 
 impl Source {
     pub fn from_file(file_name: &Path) -> Result<Self, Error> {
-        let mut content = String::new();
-        let mut f = File::open(file_name)
-            .map_err(|e| format!("Cannot open file '{}: {}", file_name.to_str().unwrap(), e))?;
-        f.read_to_string(&mut content)
-            .map_err(|e| format!("Cannot read file '{}': {}", file_name.to_str().unwrap(), e))?;
+        Self::from_loader(file_name, &FsLoader)
+    }
 
+    /// Like `from_file`, reads the file with the loader, e.g. from another directory or a git revision.
+    pub fn from_loader(file_name: &Path, loader: &dyn FileLoader) -> Result<Self, Error> {
+        let content = loader
+            .read_to_string(file_name)
+            .map_err(|e| format!("Cannot open file '{}': {}", file_name.display(), e))?;
         Ok(Self(Rc::new(SourceRef {
             file: file_name.into(),
             content,
+            is_file: true,
+            base_dir: Some(file_name.parent().unwrap_or_else(|| Path::new("")).into()),
         })))
     }
 
@@ -31,6 +36,18 @@ impl Source {
         Self(Rc::new(SourceRef {
             content,
             file: "<input_string>".into(),
+            is_file: false,
+            base_dir: None,
+        }))
+    }
+
+    /// Like `from_string`, but relative imports are resolved against `base_dir`.
+    pub fn from_string_with_base(content: String, base_dir: &Path) -> Self {
+        Self(Rc::new(SourceRef {
+            content,
+            file: "<input_string>".into(),
+            is_file: false,
+            base_dir: Some(base_dir.into()),
         }))
     }
 
@@ -38,6 +55,16 @@ impl Source {
         &self.0.file
     }
 
+    /// Returns false for sources created from a string.
+    pub fn is_file(&self) -> bool {
+        self.0.is_file
+    }
+
+    /// The directory of the file, or the base directory given for a string.
+    pub fn base_dir(&self) -> Option<&Path> {
+        self.0.base_dir.as_deref()
+    }
+
     pub fn path(&self) -> &str {
         self.0.file.to_str().unwrap()
     }
@@ -56,9 +83,43 @@ pub struct Location {
 }
 
 impl Location {
+    /// Starts at 1.
     pub fn line_no(&self) -> usize {
-        let before = &self.source.as_str()[..self.position];
-        before.lines().count()
+        self.before().matches('\n').count() + 1
+    }
+
+    /// Starts at 1 and counts characters, not bytes.
+    pub fn column_no(&self) -> usize {
+        let before = self.before();
+        let line_start = before.rfind('\n').map_or(0, |ix| ix + 1);
+        before[line_start..].chars().count() + 1
+    }
+
+    /// The source line with a caret below the position, prefixed by the line number, e.g.
+    /// ```text
+    ///   |
+    /// 3 | {a: 1} }
+    ///   |        ^
+    /// ```
+    pub fn snippet(&self) -> String {
+        let content = self.source.as_str();
+        let line_start = self.before().rfind('\n').map_or(0, |ix| ix + 1);
+        let line = content[line_start..].lines().next().unwrap_or("");
+        let line_no = self.line_no().to_string();
+        let margin = " ".repeat(line_no.len());
+        format!(
+            "{} |\n{} | {}\n{} | {}^",
+            margin,
+            line_no,
+            line,
+            margin,
+            " ".repeat(self.column_no() - 1)
+        )
+    }
+
+    fn before(&self) -> &str {
+        let content = self.source.as_str();
+        &content[..self.position.min(content.len())]
     }
 }
 
@@ -66,9 +127,26 @@ impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{}:{}",
+            "{}:{}:{}",
             &self.source.0.file.to_str().unwrap_or("somewhere"),
             self.line_no(),
+            self.column_no(),
         )
     }
 }
+
+#[test]
+fn location_line_and_column() {
+    let source = Source::from_string("{\n  a: 1,\n  bé: x }".to_string());
+    let at = |position: usize| Location {
+        source: source.clone(),
+        position,
+    };
+    assert_eq!((at(0).line_no(), at(0).column_no()), (1, 1));
+    assert_eq!((at(2).line_no(), at(2).column_no()), (2, 1));
+    // `x` follows the two-byte `é`.
+    let x = at(source.as_str().find('x').unwrap());
+    assert_eq!((x.line_no(), x.column_no()), (3, 7));
+    assert_eq!(x.to_string(), "<input_string>:3:7");
+    assert_eq!(x.snippet(), "  |\n3 |   bé: x }\n  |       ^");
+}
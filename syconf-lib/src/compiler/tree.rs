@@ -0,0 +1,237 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::compiler::{Error, Value, YamlOptions};
+
+/// Lists the files generated by the previous run, relative to the output directory.
+const MANIFEST: &str = ".syconf-tree.json";
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteTreeOptions {
+    /// Deletes the files generated by previous runs that are not part of the value anymore.
+    pub delete_stale: bool,
+    pub yaml: YamlOptions,
+}
+
+/// The paths relative to the output directory, sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteTreeReport {
+    pub written: Vec<PathBuf>,
+    pub unchanged: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+/// Writes every entry of the hashmap `value` into a file below `out_dir`, the keys are relative paths like `"k8s/service.yaml"`.
+/// Strings are written verbatim, other values are serialized by the file extension: `json`, `yaml`, `yml`, or `toml`.
+///
+/// All files are rendered before anything is written, and every file is written to a temporary file first and renamed,
+/// so that a failing run does not leave half-written files. Files with the same content are not touched.
+/// The generated paths are recorded in a manifest file in `out_dir`, which `delete_stale` uses to find files of previous runs.
+pub fn write_tree(
+    value: &Value,
+    out_dir: &Path,
+    options: &WriteTreeOptions,
+) -> Result<WriteTreeReport, Error> {
+    let mut files = Vec::new();
+    for (key, value) in value.as_hashmap()? {
+        let path = relative_path(key)?;
+        let content = render(&path, value, options)?;
+        files.push((path, content));
+    }
+    files.sort();
+
+    fs::create_dir_all(out_dir).map_err(|e| io_error(out_dir, e))?;
+    let previous = read_manifest(out_dir)?;
+    let mut report = WriteTreeReport::default();
+    for (path, content) in &files {
+        let full_path = out_dir.join(path);
+        if fs::read(&full_path).ok().as_ref() == Some(content) {
+            report.unchanged.push(path.clone());
+        } else {
+            write_atomically(&full_path, content)?;
+            report.written.push(path.clone());
+        }
+    }
+
+    let mut generated: BTreeSet<PathBuf> = files.into_iter().map(|(path, _)| path).collect();
+    for path in previous {
+        if generated.contains(&path) {
+            continue;
+        }
+        if !options.delete_stale {
+            generated.insert(path);
+            continue;
+        }
+        let full_path = out_dir.join(&path);
+        match fs::remove_file(&full_path) {
+            Ok(()) => report.deleted.push(path.clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(io_error(&full_path, e)),
+        }
+        remove_empty_parents(out_dir, &path);
+    }
+    write_manifest(out_dir, &generated)?;
+    Ok(report)
+}
+
+/// Rejects absolute paths and paths with `.` or `..` components, so that every file stays within the output directory.
+fn relative_path(key: &str) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(key);
+    let valid = !key.is_empty()
+        && key != MANIFEST
+        && path.components().all(|c| matches!(c, Component::Normal(_)));
+    check!(
+        valid,
+        "'write_tree' path '{}' must be a relative path within the output directory",
+        key
+    );
+    Ok(path)
+}
+
+fn render(path: &Path, value: &Value, options: &WriteTreeOptions) -> Result<Vec<u8>, Error> {
+    if let Value::String(s) = value {
+        return Ok(s.as_bytes().to_vec());
+    }
+    let extension = path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    let content = match extension {
+        "json" => serde_json::to_string_pretty(value)
+            .map(|x| x + "\n")
+            .map_err(|e| e.to_string()),
+        "yaml" | "yml" => value.to_yaml(&options.yaml).map_err(|e| e.message),
+        "toml" => value.to_toml().map_err(|e| e.message),
+        _ => Err(
+            "only strings can be written to files other than json, yaml, yml, or toml".to_string(),
+        ),
+    };
+    content
+        .map(String::into_bytes)
+        .map_err(|e| format!("'write_tree' cannot render '{}': {}", path.display(), e).into())
+}
+
+fn write_atomically(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = parent.join(format!(".{}.tmp", file_name));
+    fs::write(&tmp, content).map_err(|e| io_error(&tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| io_error(path, e))
+}
+
+/// Stops at the first directory that is not empty.
+fn remove_empty_parents(out_dir: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(d) = dir.filter(|d| !d.as_os_str().is_empty()) {
+        if fs::remove_dir(out_dir.join(d)).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// Paths are stored with `/` separators.
+fn read_manifest(out_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let path = out_dir.join(MANIFEST);
+    let content = match fs::read_to_string(&path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(&path, e)),
+    };
+    let paths: Vec<String> = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Invalid manifest '{}': {}", path.display(), e))?;
+    paths.iter().map(|x| relative_path(x)).collect()
+}
+
+fn write_manifest(out_dir: &Path, paths: &BTreeSet<PathBuf>) -> Result<(), Error> {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|x| {
+            x.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect();
+    let content = serde_json::to_string_pretty(&paths).unwrap() + "\n";
+    write_atomically(&out_dir.join(MANIFEST), content.as_bytes())
+}
+
+fn io_error(path: &Path, e: io::Error) -> Error {
+    format!("Cannot write '{}': {}", path.display(), e).into()
+}
+
+#[test]
+fn write_tree_with_cleanup() {
+    let dir = std::env::temp_dir().join(format!("syconf-tree-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let write = |config: &str, delete_stale: bool| {
+        let options = WriteTreeOptions {
+            delete_stale,
+            ..Default::default()
+        };
+        write_tree(&crate::parse_string(config).unwrap(), &dir, &options)
+    };
+    let paths = |x: &[&str]| x.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    let report = write(
+        r#"{
+            "k8s/deployment.yaml": {replicas: 2, name: "api"},
+            "k8s/service.json": {port: 80},
+            "app.toml": {db: {port: 5432}, name: "api"},
+            "README": "generated",
+        }"#,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        report.written,
+        paths(&[
+            "README",
+            "app.toml",
+            "k8s/deployment.yaml",
+            "k8s/service.json"
+        ])
+    );
+    let read = |x: &str| fs::read_to_string(dir.join(x)).unwrap();
+    assert_eq!(read("README"), "generated");
+    assert_eq!(read("k8s/deployment.yaml"), "name: api\nreplicas: 2\n");
+    assert_eq!(read("k8s/service.json"), "{\n  \"port\": 80\n}\n");
+    assert_eq!(read("app.toml"), "name = \"api\"\n\n[db]\nport = 5432\n");
+
+    // Without delete_stale, the files of the previous run are kept and stay in the manifest.
+    let report = write(r#"{"README": "generated", "other.txt": "x"}"#, false).unwrap();
+    assert_eq!(report.written, paths(&["other.txt"]));
+    assert_eq!(report.unchanged, paths(&["README"]));
+    assert!(report.deleted.is_empty());
+    assert!(dir.join("k8s/service.json").exists());
+
+    let report = write(r#"{"README": "changed"}"#, true).unwrap();
+    assert_eq!(report.written, paths(&["README"]));
+    assert_eq!(
+        report.deleted,
+        paths(&[
+            "app.toml",
+            "k8s/deployment.yaml",
+            "k8s/service.json",
+            "other.txt"
+        ])
+    );
+    assert!(!dir.join("k8s").exists());
+    assert_eq!(read(MANIFEST), "[\n  \"README\"\n]\n");
+
+    for key in &[
+        "../x.txt",
+        "/tmp/x.txt",
+        "a/../../x.txt",
+        "./x.txt",
+        "",
+        MANIFEST,
+    ] {
+        let err = write(&format!("{{{:?}: \"x\"}}", key), true).unwrap_err();
+        assert!(err.message.contains("must be a relative path"), "{}", key);
+    }
+    assert!(write(r#"{"a.txt": {b: 1}}"#, true).is_err());
+    assert!(write(r#"[1]"#, true).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,24 +1,38 @@
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
+use crate::compiler::binary;
 use crate::compiler::context::Context;
-use crate::compiler::methods::hashmap::HashmapMethod;
+use crate::compiler::de;
+use crate::compiler::diff;
+use crate::compiler::functions;
+use crate::compiler::methods::any::AnyMethod;
+use crate::compiler::methods::hashmap::{sorted_entries, HashmapMethod};
 use crate::compiler::methods::list::ListMethod;
+use crate::compiler::methods::number::NumberMethod;
 use crate::compiler::methods::string::StringMethod;
 use crate::compiler::node::{FunctionDefinition, NodeContent};
+use crate::compiler::path::{self, PathSegment};
+use crate::compiler::redact::Redaction;
+use crate::compiler::yaml::{self, YamlOptions};
 use crate::compiler::{Error, ErrorWithLocation};
 
 use super::node::CodeNode;
 
 pub type ValueString = Rc<str>;
 
-#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 #[serde(untagged)]
 pub enum Value {
+    Null,
     Bool(bool),
-    Int(i32),
+    Int(i64),
+    /// Always finite, arithmetic that would produce infinity or NaN fails.
+    Float(f64),
     String(ValueString),
     HashMap(Rc<HashMap<ValueString, Value>>),
     List(Rc<[Value]>),
@@ -41,13 +55,28 @@ impl Value {
         }
     }
 
-    pub fn as_int(&self) -> Result<i32, TypeMismatch> {
+    pub fn as_int(&self) -> Result<i64, TypeMismatch> {
         if let Value::Int(x) = self {
             Ok(*x)
         } else {
             Err(self.fail("int"))
         }
     }
+    /// Returns ints converted to floats as well.
+    pub fn as_float(&self) -> Result<f64, TypeMismatch> {
+        match self {
+            Value::Int(x) => Ok(*x as f64),
+            Value::Float(x) => Ok(*x),
+            _ => Err(self.fail("float")),
+        }
+    }
+    /// The same as `as_int`, kept from when ints were 32 bit.
+    pub fn as_i64(&self) -> Result<i64, TypeMismatch> {
+        self.as_int()
+    }
+    pub fn as_str(&self) -> Result<&str, TypeMismatch> {
+        self.as_value_string().map(|x| x.as_ref())
+    }
     pub fn as_value_string(&self) -> Result<&ValueString, TypeMismatch> {
         if let Value::String(x) = self {
             Ok(x)
@@ -83,12 +112,235 @@ impl Value {
             Err(self.fail("function"))
         }
     }
+
+    /// Returns the element of a list, or `None` for other values and indexes out of range.
+    pub fn index(&self, ix: usize) -> Option<&Value> {
+        match self {
+            Value::List(list) => list.get(ix),
+            _ => None,
+        }
+    }
+
+    /// Returns the nested value at a path like `server.port`, `services[0].name`, or `labels["app.kubernetes.io/name"]`,
+    /// or `None` if the path is invalid or does not exist.
+    ///
+    /// ```
+    /// let config = syconf_lib::parse_string("{server: {port: 8080, hosts: ['a', 'b']}}").unwrap();
+    /// assert_eq!(config.get("server.port").unwrap().as_i64().unwrap(), 8080);
+    /// assert_eq!(config.get("server.hosts[1]").unwrap().as_str().unwrap(), "b");
+    /// assert!(config.get("server.user").is_none());
+    /// ```
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        self.get_or_err(path).ok()
+    }
+
+    /// Like `get`, but the error names the missing key or index and what is there instead.
+    ///
+    /// ```
+    /// let config = syconf_lib::parse_string("{server: {port: 8080, host: 'localhost'}}").unwrap();
+    /// let error = config.get_or_err("server.user").unwrap_err();
+    /// assert_eq!(error.message, "'server.user' does not exist, 'server' has the keys 'host', 'port'");
+    /// ```
+    pub fn get_or_err(&self, path: &str) -> Result<&Value, Error> {
+        let segments = path::parse(path)?;
+        let mut value = self;
+        for (ix, segment) in segments.iter().enumerate() {
+            let parent = || match ix {
+                0 => "the root".to_string(),
+                _ => format!("'{}'", path::render(&segments[..ix])),
+            };
+            let missing = || format!("'{}' does not exist", path::render(&segments[..=ix]));
+            value = match (segment, value) {
+                (PathSegment::Key(key), Value::HashMap(hm)) => match hm.get(key.as_str()) {
+                    Some(x) => x,
+                    None => {
+                        let mut keys: Vec<String> = hm.keys().map(|k| format!("'{}'", k)).collect();
+                        keys.sort();
+                        let message = if keys.is_empty() {
+                            format!("{}, {} is an empty hashmap", missing(), parent())
+                        } else {
+                            format!(
+                                "{}, {} has the keys {}",
+                                missing(),
+                                parent(),
+                                keys.join(", ")
+                            )
+                        };
+                        return Err(message.into());
+                    }
+                },
+                (PathSegment::Index(i), Value::List(list)) => match list.get(*i) {
+                    Some(x) => x,
+                    None => {
+                        return Err(format!(
+                            "{}, {} has {} elements",
+                            missing(),
+                            parent(),
+                            list.len()
+                        )
+                        .into())
+                    }
+                },
+                (_, x) => {
+                    return Err(format!("{}, {} is {}", missing(), parent(), x.summary()).into())
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    /// Renders the value for messages, truncating large values.
+    pub(crate) fn summary(&self) -> String {
+        const MAX_LEN: usize = 80;
+        let s = self.to_string();
+        match s.char_indices().nth(MAX_LEN) {
+            Some((ix, _)) => format!("{}...", &s[..ix]),
+            None => s,
+        }
+    }
+
+    /// Merges `overlay` over the hashmap like the `deep_merge` builtin: nested hashmaps are merged recursively
+    /// and any other value of `overlay` replaces the previous one.
+    pub fn deep_merge(&self, overlay: &Value) -> Result<Value, Error> {
+        functions::deep_merge(&[self.clone(), overlay.clone()])
+    }
+
+    /// Returns a copy with the values of matching hashmap keys replaced by `"***"`, e.g. for logging.
+    /// Patterns are case-insensitive globs like `"*_secret"` applied at every nesting level.
+    pub fn redacted(&self, patterns: &[&str]) -> Value {
+        Redaction::from_patterns(patterns).apply(self)
+    }
+
+    pub fn to_yaml(&self, options: &YamlOptions) -> Result<String, Error> {
+        yaml::to_yaml(self, options)
+    }
+
+    /// Serializes the value into compact JSON with sorted hashmap keys. Functions cannot be serialized.
+    pub fn to_json(&self) -> Result<String, Error> {
+        self.write_json(serde_json::ser::CompactFormatter)
+    }
+
+    /// Like `to_json`, but indented with two spaces.
+    pub fn to_json_pretty(&self) -> Result<String, Error> {
+        self.to_json_indented(2)
+    }
+
+    /// Like `to_json`, but indented with `indent` spaces.
+    pub fn to_json_indented(&self, indent: usize) -> Result<String, Error> {
+        let indent = " ".repeat(indent);
+        self.write_json(serde_json::ser::PrettyFormatter::with_indent(
+            indent.as_bytes(),
+        ))
+    }
+
+    fn write_json(&self, formatter: impl serde_json::ser::Formatter) -> Result<String, Error> {
+        use serde::Serialize;
+        let mut out = Vec::new();
+        self.serialize(&mut serde_json::Serializer::with_formatter(
+            &mut out,
+            CanonicalFloats(formatter),
+        ))
+        .map_err(|e| anyhow!("Cannot convert to JSON: {}", e))?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    /// Serializes a hashmap into a TOML document with sorted keys. Fails on other values, on null, and on functions,
+    /// naming the key path of the offending value.
+    /// The value is converted to a `toml::Value` first, because serializing a hashmap directly fails
+    /// when a table comes before a plain value in its iteration order.
+    pub fn to_toml(&self) -> Result<String, Error> {
+        check!(
+            matches!(self, Value::HashMap(_)),
+            "Cannot convert to TOML: the top level must be a hashmap, got {}",
+            self.summary()
+        );
+        let value = to_toml_value(self, "")?;
+        toml::to_string(&value).map_err(|e| anyhow!("Cannot convert to TOML: {}", e).into())
+    }
+
+    /// Deserializes the value into a Rust type deriving `serde::Deserialize`, e.g. `value.deserialize::<MyConfig>()`.
+    /// Errors name the key path of the failing value. Enum variants are read from strings or from single-key hashmaps.
+    pub fn deserialize<'a, T: serde::Deserialize<'a>>(&'a self) -> Result<T, Error> {
+        de::deserialize(self)
+    }
+
+    /// Encodes the value into a compact binary form, e.g. to cache it. Functions cannot be encoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        binary::to_bytes(self)
+    }
+
+    /// Decodes a value encoded by `to_bytes`, fails on corrupted data or data of another format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Value, Error> {
+        binary::from_bytes(bytes)
+    }
+}
+
+/// Numbers are compared by their values, so `1 == 1.0`.
+fn to_toml_value(value: &Value, path: &str) -> Result<toml::Value, Error> {
+    Ok(match value {
+        Value::Bool(x) => toml::Value::Boolean(*x),
+        Value::Int(x) => toml::Value::Integer(*x),
+        // `toml` writes floats like `format_float`, except for the sign of zero and that it never uses an exponent.
+        Value::Float(x) if *x == 0.0 => toml::Value::Float(0.0),
+        Value::Float(x) => toml::Value::Float(*x),
+        Value::String(x) => toml::Value::String(x.to_string()),
+        Value::List(list) => toml::Value::Array(
+            list.iter()
+                .enumerate()
+                .map(|(ix, x)| to_toml_value(x, &format!("{}[{}]", path, ix)))
+                .collect::<Result<_, Error>>()?,
+        ),
+        Value::HashMap(hm) => toml::Value::Table(
+            hm.iter()
+                .map(|(k, v)| {
+                    let path = if path.is_empty() {
+                        k.to_string()
+                    } else {
+                        format!("{}.{}", path, k)
+                    };
+                    Ok((k.to_string(), to_toml_value(v, &path)?))
+                })
+                .collect::<Result<_, Error>>()?,
+        ),
+        Value::Null => {
+            return Err(format!(
+                "Cannot convert to TOML: '{}' is null, which TOML cannot express",
+                path
+            )
+            .into())
+        }
+        Value::Func(_) => {
+            return Err(format!("Cannot convert to TOML: '{}' is a function", path).into())
+        }
+    })
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => {
+                cmp_int_float(*a, *b) == Ordering::Equal
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::HashMap(a), Value::HashMap(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Func(a), Value::Func(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (self, other) {
             (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Float(b)) => Some(cmp_int_float(*a, *b)),
+            (Value::Float(a), Value::Int(b)) => Some(cmp_int_float(*b, *a).reverse()),
             (Value::String(a), Value::String(b)) => a.partial_cmp(b),
             (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
             _ => None,
@@ -96,6 +348,439 @@ impl PartialOrd for Value {
     }
 }
 
+/// 2^63, the first float above `i64::MAX`.
+const INT_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Compares exactly, converting the int to a float would round ints above 2^53.
+fn cmp_int_float(a: i64, b: f64) -> Ordering {
+    if b >= INT_LIMIT {
+        Ordering::Less
+    } else if b < -INT_LIMIT {
+        Ordering::Greater
+    } else {
+        a.cmp(&(b.trunc() as i64))
+            .then_with(|| 0.0.partial_cmp(&b.fract()).unwrap())
+    }
+}
+
+/// The int equal to the float, if there is one.
+fn float_to_int(x: f64) -> Option<i64> {
+    (x.fract() == 0.0 && (-INT_LIMIT..INT_LIMIT).contains(&x)).then_some(x as i64)
+}
+
+/// Consistent with `PartialEq`: equal values have equal hashes. Hashmaps are hashed in the order of sorted keys.
+/// Functions are never equal to anything, so they all share the same hash.
+/// Floats with an integer value are hashed like the equal int.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Value::Float(x) = self {
+            if let Some(int) = float_to_int(*x) {
+                return Value::Int(int).hash(state);
+            }
+        }
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Null | Value::Func(_) => {}
+            Value::Bool(x) => x.hash(state),
+            Value::Int(x) => x.hash(state),
+            Value::Float(x) => x.to_bits().hash(state),
+            Value::String(x) => x.hash(state),
+            Value::HashMap(hm) => {
+                let mut keys: Vec<&ValueString> = hm.keys().collect();
+                keys.sort();
+                keys.len().hash(state);
+                for key in keys {
+                    key.hash(state);
+                    hm[key].hash(state);
+                }
+            }
+            Value::List(list) => list.hash(state),
+        }
+    }
+}
+
+/// Hashmaps are serialized with sorted keys. Functions cannot be serialized.
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeMap};
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(x) => serializer.serialize_bool(*x),
+            Value::Int(x) => serializer.serialize_i64(*x),
+            Value::Float(x) => serializer.serialize_f64(*x),
+            Value::String(x) => serializer.serialize_str(x),
+            Value::HashMap(hm) => {
+                let mut keys: Vec<&ValueString> = hm.keys().collect();
+                keys.sort();
+                let mut map = serializer.serialize_map(Some(keys.len()))?;
+                for key in keys {
+                    map.serialize_entry(key.as_ref(), &hm[key])?;
+                }
+                map.end()
+            }
+            Value::List(list) => serializer.collect_seq(list.iter()),
+            Value::Func(f) if f.is_lazy() => Err(S::Error::custom(
+                "Cannot serialize a lazy value, use 'force(x)' to evaluate it",
+            )),
+            Value::Func(_) => Err(S::Error::custom("Cannot serialize a function")),
+        }
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let json = r#"{"a":[1,2.5,"x",null,true],"b":{"c":{}},"d":[]}"#;
+    let value = crate::parse_string(&format!("'{}'.parse_json()", json)).unwrap();
+    assert_eq!(value.to_json().unwrap(), json);
+    assert_eq!(
+        value
+            .to_json_pretty()
+            .unwrap()
+            .parse::<serde_json::Value>()
+            .unwrap(),
+        json.parse::<serde_json::Value>().unwrap()
+    );
+    let err = crate::parse_string("{f: (x) => x}")
+        .unwrap()
+        .to_json()
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "Cannot convert to JSON: Cannot serialize a function"
+    );
+}
+
+#[test]
+fn value_accessors() {
+    let value = crate::parse_string(
+        r#"{
+            server: {port: 8080, hosts: ["a", "b"], tls: true},
+            labels: {"app.kubernetes.io/name": "api"},
+            empty: {},
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(value.get("server.port").unwrap().as_i64().unwrap(), 8080);
+    assert!(value.get("server.tls").unwrap().as_bool().unwrap());
+    assert_eq!(
+        value
+            .get(r#"labels["app.kubernetes.io/name"]"#)
+            .unwrap()
+            .as_str()
+            .unwrap(),
+        "api"
+    );
+    let hosts = value.get("server.hosts").unwrap();
+    assert_eq!(hosts.index(1).unwrap().as_str().unwrap(), "b");
+    assert!(hosts.index(2).is_none());
+    assert!(value.index(0).is_none());
+    assert!(value.get("server..port").is_none());
+    assert!(value.get("server.port").unwrap().as_str().is_err());
+
+    let error = |path: &str| value.get_or_err(path).unwrap_err().message;
+    assert_eq!(
+        error("server.user"),
+        "'server.user' does not exist, 'server' has the keys 'hosts', 'port', 'tls'"
+    );
+    assert_eq!(
+        error("db"),
+        "'db' does not exist, the root has the keys 'empty', 'labels', 'server'"
+    );
+    assert_eq!(
+        error("empty.x"),
+        "'empty.x' does not exist, 'empty' is an empty hashmap"
+    );
+    assert_eq!(
+        error("server.hosts[5]"),
+        "'server.hosts[5]' does not exist, 'server.hosts' has 2 elements"
+    );
+    assert_eq!(
+        error("server.port.x"),
+        "'server.port.x' does not exist, 'server.port' is 8080"
+    );
+    assert_eq!(
+        error("server[0]"),
+        "'server[0]' does not exist, 'server' is {hosts: [\"a\", \"b\"], port: 8080, tls: true}"
+    );
+    assert_eq!(error("a[x]"), "Invalid path 'a[x]'");
+}
+
+/// Renders the value in syconf literal syntax. Hashmap keys are sorted to keep the output stable.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", format_float(*x)),
+            Value::String(x) => f.write_str(&quote(x)),
+            Value::HashMap(hm) => {
+                let mut keys: Vec<&ValueString> = hm.keys().collect();
+                keys.sort();
+                f.write_str("{")?;
+                for (ix, key) in keys.into_iter().enumerate() {
+                    if ix > 0 {
+                        f.write_str(", ")?;
+                    }
+                    if is_identifier(key) {
+                        write!(f, "{}: {}", key, &hm[key])?;
+                    } else {
+                        write!(f, "{}: {}", quote(key), &hm[key])?;
+                    }
+                }
+                f.write_str("}")
+            }
+            Value::List(list) => {
+                f.write_str("[")?;
+                for (ix, x) in list.iter().enumerate() {
+                    if ix > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                f.write_str("]")
+            }
+            Value::Func(x) if x.is_lazy() => f.write_str("<lazy>"),
+            Value::Func(_) => f.write_str("<function>"),
+        }
+    }
+}
+
+/// The only conversion of floats to text, used by the interpolation, `Display`, and all serializers,
+/// so that the same value is written identically everywhere. The rules are pinned:
+/// the shortest digits that read back as the same float, at least one fractional digit,
+/// e.g. `1.0`, `0.1`, `1e20` as `100000000000000000000.0`, and `-0.0` as `0.0`.
+/// Like in JavaScript, magnitudes from `1e21` and below `1e-6` have an exponent, which is always signed,
+/// e.g. `1e21` as `1.0e+21` and `5e-324` as `5.0e-324`, so that YAML 1.1 reads them as floats too.
+pub(crate) fn format_float(x: f64) -> String {
+    if x == 0.0 {
+        return "0.0".to_string();
+    }
+    if !(1e-6..1e21).contains(&x.abs()) {
+        let s = format!("{:e}", x);
+        let (mantissa, exponent) = s.split_at(s.find('e').unwrap());
+        let fraction = if mantissa.contains('.') { "" } else { ".0" };
+        let sign = if exponent.starts_with("e-") { "" } else { "+" };
+        return format!("{}{}e{}{}", mantissa, fraction, sign, &exponent[1..]);
+    }
+    let s = x.to_string();
+    if s.contains('.') || !x.is_finite() {
+        s
+    } else {
+        s + ".0"
+    }
+}
+
+/// Fails on NaN and infinite floats, e.g. `.nan` in YAML, which syconf values cannot hold.
+pub(crate) fn check_finite(value: &Value, path: &str) -> Result<(), Error> {
+    match value {
+        Value::Float(x) => check!(
+            x.is_finite(),
+            "'{}' is {}, but only finite numbers are supported",
+            path,
+            x
+        ),
+        Value::List(list) => {
+            for (ix, x) in list.iter().enumerate() {
+                check_finite(x, &format!("{}[{}]", path, ix))?;
+            }
+        }
+        Value::HashMap(hm) => {
+            for (k, v) in sorted_entries(hm) {
+                check_finite(v, &diff::key_path(path, k))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Writes the JSON floats with `format_float` instead of the formatting of `serde_json`.
+struct CanonicalFloats<F>(F);
+
+impl<F: serde_json::ser::Formatter> serde_json::ser::Formatter for CanonicalFloats<F> {
+    fn write_f64<W: ?Sized + std::io::Write>(&mut self, w: &mut W, x: f64) -> std::io::Result<()> {
+        w.write_all(format_float(x).as_bytes())
+    }
+
+    fn begin_array<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.begin_array(w)
+    }
+
+    fn end_array<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.end_array(w)
+    }
+
+    fn begin_array_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        w: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        self.0.begin_array_value(w, first)
+    }
+
+    fn end_array_value<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.end_array_value(w)
+    }
+
+    fn begin_object<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.begin_object(w)
+    }
+
+    fn end_object<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.end_object(w)
+    }
+
+    fn begin_object_key<W: ?Sized + std::io::Write>(
+        &mut self,
+        w: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        self.0.begin_object_key(w, first)
+    }
+
+    fn end_object_key<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.end_object_key(w)
+    }
+
+    fn begin_object_value<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.begin_object_value(w)
+    }
+
+    fn end_object_value<W: ?Sized + std::io::Write>(&mut self, w: &mut W) -> std::io::Result<()> {
+        self.0.end_object_value(w)
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn float_formatting_is_canonical(x in proptest::num::f64::NORMAL | proptest::num::f64::SUBNORMAL | proptest::num::f64::ZERO) {
+        use proptest::prop_assert_eq;
+        let text = format_float(x);
+        prop_assert_eq!(text.parse::<f64>().unwrap(), x);
+        let parsed = crate::parse_string(text.trim_start_matches('-')).unwrap();
+        prop_assert_eq!(parsed, Value::Float(x.abs()));
+        let value = Value::HashMap(Rc::new(
+            vec![("a".into(), Value::Float(x))].into_iter().collect(),
+        ));
+        prop_assert_eq!(value.to_json().unwrap(), format!("{{\"a\":{}}}", text));
+        prop_assert_eq!(value.to_json_pretty().unwrap(), format!("{{\n  \"a\": {}\n}}", text));
+        if !text.contains('e') {
+            prop_assert_eq!(value.to_toml().unwrap(), format!("a = {}\n", text));
+        }
+        prop_assert_eq!(value.to_yaml(&YamlOptions::default()).unwrap(), format!("a: {}\n", text));
+        prop_assert_eq!(value.to_string(), format!("{{a: {}}}", text));
+        let interpolated = crate::compiler::functions::concat_strings(&[Value::Float(x)]).unwrap();
+        prop_assert_eq!(interpolated, Value::String(text.into()));
+    }
+}
+
+#[test]
+fn float_formatting() {
+    for (x, expected) in &[
+        (1.0, "1.0"),
+        (0.1, "0.1"),
+        (-0.0, "0.0"),
+        (1e-6, "0.000001"),
+        (9.999999999999997e-7, "9.999999999999997e-7"),
+        (1.5e-7, "1.5e-7"),
+        (5e-324, "5.0e-324"),
+        (999999999999999900000.0, "999999999999999900000.0"),
+        (1e21, "1.0e+21"),
+        (-1e21, "-1.0e+21"),
+        (1e300, "1.0e+300"),
+        (f64::MAX, "1.7976931348623157e+308"),
+        (123456789.125, "123456789.125"),
+        (-2.5, "-2.5"),
+    ] {
+        assert_eq!(format_float(*x), *expected);
+    }
+    let eval = |s: &str| crate::parse_string(s);
+    assert_eq!(
+        eval(r#"[(0 - 0.0).negate()].to_json()"#).unwrap(),
+        Value::String("[0.0]".into())
+    );
+    assert_eq!(
+        eval(r#"'{"a": [1e16, 0.00001]}'.parse_json().to_json()"#).unwrap(),
+        Value::String(r#"{"a":[10000000000000000.0,0.00001]}"#.into())
+    );
+    assert_eq!(
+        eval(r#"{a: [1e300, 5e-324]}.to_yaml().parse_yaml() == {a: [1e300, 5e-324]}"#).unwrap(),
+        Value::Bool(true)
+    );
+    assert_eq!(
+        eval(r#"'a: [1, .nan]'.parse_yaml()"#).unwrap_err().message,
+        "cannot parse YAML: 'a[1]' is NaN, but only finite numbers are supported"
+    );
+    assert_eq!(
+        eval(r#"'{"a.b": {"c d": [.inf]}}'.parse_yaml()"#)
+            .unwrap_err()
+            .message,
+        r#"cannot parse YAML: '["a.b"]["c d"][0]' is inf, but only finite numbers are supported"#
+    );
+    assert_eq!(
+        eval(r#"'a = -inf'.parse_toml()"#).unwrap_err().message,
+        "cannot parse TOML: 'a' is -inf, but only finite numbers are supported"
+    );
+}
+
+/// A `"` string literal that parses back to `s`, with the escape sequences of the parser
+/// for quotes, backslashes, `${`, and control characters.
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn quote_round_trip() {
+    let strings = [
+        "",
+        "plain",
+        "say \"hi\"",
+        "C:\\dir\\",
+        "a\tb\r\n",
+        "${x} and $5 and \\${y}",
+        "\u{1b}[31mred\u{1b}[0m\u{0}\u{7f}",
+        "héllo 名前 😀",
+    ];
+    for x in &strings {
+        let value = Value::String((*x).into());
+        assert_eq!(
+            crate::parse_string(&value.to_string()).unwrap(),
+            value,
+            "{}",
+            value
+        );
+        let hm = Value::HashMap(std::rc::Rc::new(
+            std::iter::once(((*x).into(), value.clone())).collect(),
+        ));
+        assert_eq!(crate::parse_string(&hm.to_string()).unwrap(), hm, "{}", hm);
+    }
+    assert_eq!(quote("${x}\u{1b}"), r#""\${x}\u{1b}""#);
+}
+
+pub(crate) fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
 #[derive(Clone)]
 pub struct Func(FuncInner);
 
@@ -110,6 +795,7 @@ impl fmt::Debug for Func {
         match &self.0 {
             FuncInner::BuiltInFunction(_) => f.write_str("<func>"),
             FuncInner::BuiltInMethod(_) => f.write_str("<func>"),
+            FuncInner::Lazy(_) => f.write_str("<lazy>"),
             FuncInner::UserDefined(ud) => {
                 f.write_str(format!("user_func:{:?}", ud.definition.as_ref()).as_str())
             }
@@ -118,7 +804,7 @@ impl fmt::Debug for Func {
 }
 
 impl Func {
-    pub fn new_builtin(func: &'static (dyn Fn(&[Value]) -> Result<Value, Error>)) -> Self {
+    pub fn new_builtin(func: &'static dyn Fn(&[Value]) -> Result<Value, Error>) -> Self {
         Self(FuncInner::BuiltInFunction(func))
     }
 
@@ -133,11 +819,35 @@ impl Func {
         }))
     }
 
+    /// Wraps a function without arguments into a thunk, which calls it at most once when it is forced.
+    pub fn new_lazy(func: Func) -> Self {
+        Self(FuncInner::Lazy(Rc::new(Thunk {
+            func,
+            result: RefCell::new(None),
+        })))
+    }
+
+    /// The number of arguments of user-defined functions, builtins take a variable number of arguments.
+    pub fn arity(&self) -> Option<usize> {
+        match &self.0 {
+            FuncInner::UserDefined(ud) => {
+                Some(ud.definition.argument_names.as_ref().map_or(0, Vec::len))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        matches!(self.0, FuncInner::Lazy(_))
+    }
+
+    /// Calling a thunk forces it, the arguments are ignored.
     pub fn call(&self, args: &[Value]) -> Result<Value, ErrorWithLocation> {
         match &self.0 {
             FuncInner::BuiltInFunction(func) => func(args),
             FuncInner::BuiltInMethod(method) => method.call(args),
             FuncInner::UserDefined(ud) => ud.call(args),
+            FuncInner::Lazy(thunk) => thunk.force(),
         }
     }
 }
@@ -147,6 +857,24 @@ enum FuncInner {
     BuiltInFunction(&'static dyn Fn(&[Value]) -> Result<Value, Error>),
     BuiltInMethod(Method),
     UserDefined(UserDefinedFunction),
+    Lazy(Rc<Thunk>),
+}
+
+/// Errors are not memoized, forcing a failed thunk again calls the function again.
+struct Thunk {
+    func: Func,
+    result: RefCell<Option<Value>>,
+}
+
+impl Thunk {
+    fn force(&self) -> Result<Value, Error> {
+        if let Some(x) = self.result.borrow().as_ref() {
+            return Ok(x.clone());
+        }
+        let value = self.func.call(&[])?;
+        self.result.replace(Some(value.clone()));
+        Ok(value)
+    }
 }
 
 #[derive(Clone)]
@@ -154,6 +882,8 @@ pub enum Method {
     HashMap(Rc<HashMap<ValueString, Value>>, &'static HashmapMethod),
     List(Rc<[Value]>, &'static ListMethod),
     String(ValueString, &'static StringMethod),
+    Number(Box<Value>, &'static NumberMethod),
+    Any(Box<Value>, &'static AnyMethod),
 }
 
 impl Method {
@@ -162,6 +892,8 @@ impl Method {
             Method::HashMap(hm, func) => func(hm, args),
             Method::List(list, func) => func(list, args),
             Method::String(string, func) => func(string, args),
+            Method::Number(number, func) => func(number, args),
+            Method::Any(value, func) => func(value, args),
         }
     }
 }
@@ -177,12 +909,21 @@ impl UserDefinedFunction {
         debug!(arg_names=?self.definition.argument_names, input=?args, "applying user defined function");
         debug!(node=?self.definition.node, "user defined");
         let nctx = self.context.new_child();
-        // TODO: check args
-        let arg_names = self.definition.argument_names.as_ref().unwrap();
-        for ix in 0..arg_names.len() {
+        let arg_names = self
+            .definition
+            .argument_names
+            .as_deref()
+            .unwrap_or_default();
+        check!(
+            args.len() >= arg_names.len(),
+            "Function expects {} arguments, but got {}",
+            arg_names.len(),
+            args.len()
+        );
+        for (name, arg) in arg_names.iter().zip(args) {
             nctx.bind(
-                arg_names[ix].clone(),
-                CodeNode::new(NodeContent::Resolved(args[ix].clone()), None),
+                name.clone(),
+                CodeNode::new(NodeContent::Resolved(arg.clone()), None),
             );
         }
         self.definition.node.resolve(&nctx)
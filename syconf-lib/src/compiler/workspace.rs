@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::compiler::evaluation::IoCache;
+use crate::compiler::glob::Glob;
+use crate::compiler::value::Func;
+use crate::compiler::{Dependencies, Error, EvalOptions, Value};
+
+/// A cross-file check gets the values of all evaluated entry points by their paths.
+pub type WorkspaceCheck = dyn Fn(&HashMap<PathBuf, Value>) -> Vec<WorkspaceViolation>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceViolation {
+    pub entry: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for WorkspaceViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.entry.display(), self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct WorkspaceOptions {
+    pub eval: EvalOptions,
+    pub checks: Vec<Box<WorkspaceCheck>>,
+}
+
+#[derive(Debug, Default)]
+pub struct WorkspaceResult {
+    pub values: HashMap<PathBuf, Value>,
+    pub dependencies: HashMap<PathBuf, Dependencies>,
+    /// The entry points that failed to compile or evaluate, in the order of the entries.
+    pub errors: Vec<(PathBuf, Error)>,
+    pub violations: Vec<WorkspaceViolation>,
+}
+
+impl WorkspaceResult {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.violations.is_empty()
+    }
+}
+
+/// Evaluates every entry point and runs the checks over the values of the entries that evaluated successfully.
+/// The entries share the results of `read_file` and `getenv`, so that files used by many entries are read once,
+/// unless the `io_cache` of the options is disabled.
+pub fn evaluate_workspace(entries: &[PathBuf], options: &WorkspaceOptions) -> WorkspaceResult {
+    let mut result = WorkspaceResult::default();
+    let mut cache = IoCache::default();
+    for entry in entries {
+        let evaluated = crate::compile_file(&entry.to_string_lossy())
+            .and_then(|compiled| compiled.evaluate_with_cache(&options.eval, &mut cache));
+        match evaluated {
+            Ok((value, deps)) => {
+                result.values.insert(entry.clone(), value);
+                result.dependencies.insert(entry.clone(), deps);
+            }
+            Err(e) => result.errors.push((entry.clone(), e)),
+        }
+    }
+    for check in &options.checks {
+        result.violations.extend(check(&result.values));
+    }
+    result
+}
+
+/// Reads checks written in syconf: the file evaluates to a function or to a hashmap of named functions.
+/// Every function gets a hashmap of the values by the entry paths and returns a list of violations,
+/// which are hashmaps like `{entry: "services/api.sy", message: "port 80 is used twice"}`.
+/// Failing checks are reported as violations of the checks file.
+pub fn load_checks(path: &Path) -> Result<Vec<Box<WorkspaceCheck>>, Error> {
+    let checks = match crate::parse_file(&path.to_string_lossy())? {
+        Value::Func(func) => vec![("checks".to_string(), func)],
+        Value::HashMap(hm) => {
+            let mut checks = Vec::with_capacity(hm.len());
+            for (name, func) in hm.iter() {
+                checks.push((name.to_string(), func.as_func()?));
+            }
+            checks.sort_by(|a, b| a.0.cmp(&b.0));
+            checks
+        }
+        x => {
+            return Err(format!(
+                "Checks must be a function or a hashmap of functions, got {}",
+                x.summary()
+            )
+            .into())
+        }
+    };
+    Ok(checks
+        .into_iter()
+        .map(|(name, func)| {
+            let origin = path.to_path_buf();
+            Box::new(move |values: &HashMap<PathBuf, Value>| {
+                run_check(&name, &func, values).unwrap_or_else(|message| {
+                    vec![WorkspaceViolation {
+                        entry: origin.clone(),
+                        message,
+                    }]
+                })
+            }) as Box<WorkspaceCheck>
+        })
+        .collect())
+}
+
+fn run_check(
+    name: &str,
+    func: &Func,
+    values: &HashMap<PathBuf, Value>,
+) -> Result<Vec<WorkspaceViolation>, String> {
+    let results = values
+        .iter()
+        .map(|(path, value)| (Rc::from(path.to_string_lossy().as_ref()), value.clone()))
+        .collect();
+    let out = func
+        .call(&[Value::HashMap(Rc::new(results))])
+        .map_err(|e| format!("check '{}' failed: {}", name, e.message))?;
+    let invalid = |x: &Value| {
+        format!(
+            "check '{}' must return a list of {{entry, message}} hashmaps, got {}",
+            name,
+            x.summary()
+        )
+    };
+    let violations = out.as_list().map_err(|_| invalid(&out))?;
+    violations
+        .iter()
+        .map(|x| match x {
+            Value::HashMap(hm) => match (hm.get("entry"), hm.get("message")) {
+                (Some(Value::String(entry)), Some(Value::String(message))) => {
+                    Ok(WorkspaceViolation {
+                        entry: PathBuf::from(entry.as_ref()),
+                        message: message.to_string(),
+                    })
+                }
+                _ => Err(invalid(x)),
+            },
+            _ => Err(invalid(x)),
+        })
+        .collect()
+}
+
+/// Expands the glob patterns into the sorted paths of the matching files, other paths are kept as they are.
+/// The directories below the part of a pattern without wildcards are searched recursively,
+/// and `*` also matches `/`, e.g. `services/*.sy` matches `services/a.sy` and `services/db/b.sy`.
+pub fn expand_entries(patterns: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let mut entries = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[']) {
+            entries.push(PathBuf::from(pattern));
+            continue;
+        }
+        let glob = Glob::new(pattern)?;
+        let base: PathBuf = Path::new(pattern)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        let mut files = Vec::new();
+        list_files(&base, &mut files)?;
+        let before = entries.len();
+        entries.extend(
+            files
+                .into_iter()
+                .filter(|x| glob.matches(&x.to_string_lossy())),
+        );
+        check!(
+            entries.len() > before,
+            "'{}' does not match any file",
+            pattern
+        );
+    }
+    entries.sort();
+    entries.dedup();
+    Ok(entries)
+}
+
+fn list_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let read_dir = if dir.as_os_str().is_empty() {
+        fs::read_dir(".")
+    } else {
+        fs::read_dir(dir)
+    };
+    let read_dir = read_dir.map_err(|e| format!("Cannot list '{}': {}", dir.display(), e))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|e| format!("Cannot list '{}': {}", dir.display(), e))?;
+        let path = dir.join(entry.file_name());
+        if entry.file_type().map(|x| x.is_dir()).unwrap_or(false) {
+            list_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn workspace_duplicate_ports() {
+    use crate::compiler::FileLoader;
+    use std::cell::Cell;
+    use std::io;
+
+    struct CountingLoader(Cell<usize>);
+    impl FileLoader for CountingLoader {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.0.set(self.0.get() + 1);
+            fs::read_to_string(path)
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("syconf-workspace-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("services")).unwrap();
+    let shared = dir.join("shared.txt");
+    fs::write(&shared, "core").unwrap();
+    let service = |name: &str, port: i64| {
+        let config = format!(
+            r#"{{name: "{}", port: {}, team: read_file("{}")}}"#,
+            name,
+            port,
+            shared.display()
+        );
+        fs::write(dir.join("services").join(format!("{}.sy", name)), config).unwrap();
+    };
+    service("api", 8080);
+    service("web", 80);
+    service("worker", 8080);
+    fs::write(dir.join("services/broken.sy.bak"), "{").unwrap();
+
+    let pattern = format!("{}/services/*.sy", dir.display());
+    let entries = expand_entries(&[&pattern]).unwrap();
+    assert_eq!(
+        entries,
+        ["api", "web", "worker"]
+            .iter()
+            .map(|x| dir.join("services").join(format!("{}.sy", x)))
+            .collect::<Vec<_>>()
+    );
+
+    let loader = Rc::new(CountingLoader(Cell::new(0)));
+    let duplicate_ports = |values: &HashMap<PathBuf, Value>| {
+        let mut by_port: HashMap<i64, Vec<&PathBuf>> = HashMap::new();
+        for (path, value) in values {
+            let port = value.as_hashmap().unwrap()["port"].as_int().unwrap();
+            by_port.entry(port).or_default().push(path);
+        }
+        let mut violations: Vec<WorkspaceViolation> = by_port
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .flat_map(|(port, paths)| {
+                paths.into_iter().map(move |path| WorkspaceViolation {
+                    entry: path.clone(),
+                    message: format!("port {} is used by more than one service", port),
+                })
+            })
+            .collect();
+        violations.sort_by(|a, b| a.entry.cmp(&b.entry));
+        violations
+    };
+    let options = WorkspaceOptions {
+        eval: EvalOptions {
+            file_loader: loader.clone(),
+            ..Default::default()
+        },
+        checks: vec![Box::new(duplicate_ports)],
+    };
+    let result = evaluate_workspace(&entries, &options);
+    assert!(result.errors.is_empty());
+    assert_eq!(loader.0.get(), 1, "the shared file is read once");
+    assert!(result.dependencies[&entries[2]].files.contains(&shared));
+    let expected = vec![
+        WorkspaceViolation {
+            entry: entries[0].clone(),
+            message: "port 8080 is used by more than one service".to_string(),
+        },
+        WorkspaceViolation {
+            entry: entries[2].clone(),
+            message: "port 8080 is used by more than one service".to_string(),
+        },
+    ];
+    assert_eq!(result.violations, expected);
+
+    // The same check written in syconf.
+    let checks = dir.join("checks.sy");
+    fs::write(
+        &checks,
+        r#"{
+            duplicate_ports: (results) =>
+                results.keys()
+                    .filter((p) => results.keys().filter((q) => q != p and results[q].port == results[p].port).len() > 0)
+                    .map((p) => {entry: p, message: "port ${results[p].port} is used by more than one service"}),
+            invalid: (results) => 1,
+        }"#,
+    )
+    .unwrap();
+    let options = WorkspaceOptions {
+        checks: load_checks(&checks).unwrap(),
+        ..Default::default()
+    };
+    let result = evaluate_workspace(&entries, &options);
+    assert_eq!(result.violations[..2], expected[..]);
+    assert_eq!(
+        result.violations[2],
+        WorkspaceViolation {
+            entry: checks.clone(),
+            message: "check 'invalid' must return a list of {entry, message} hashmaps, got 1"
+                .to_string(),
+        }
+    );
+
+    let result = evaluate_workspace(&[dir.join("services/broken.sy.bak")], &options);
+    assert_eq!(result.errors.len(), 1);
+    assert!(expand_entries(&[&format!("{}/*.none", dir.display())]).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
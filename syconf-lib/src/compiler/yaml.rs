@@ -0,0 +1,304 @@
+use std::fmt::Write;
+
+use crate::compiler::value::{format_float, ValueString};
+use crate::compiler::{Error, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YamlStyle {
+    /// Every non-empty hashmap and list is emitted in the indented block style.
+    Block,
+    /// The whole value is emitted in the JSON-like flow style on a single line.
+    Flow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YamlQuoting {
+    /// Strings are quoted only if YAML would read them as something else,
+    /// e.g. `"yes"`, `"1.0"`, `"null"`, or if they contain special characters.
+    Minimal,
+    /// All strings are double-quoted.
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YamlOptions {
+    pub style: YamlStyle,
+    pub quoting: YamlQuoting,
+}
+
+impl Default for YamlOptions {
+    fn default() -> Self {
+        Self {
+            style: YamlStyle::Block,
+            quoting: YamlQuoting::Minimal,
+        }
+    }
+}
+
+impl YamlOptions {
+    /// Reads options given as a hashmap in a config, e.g. `{style: "flow", quoting: "always"}`.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let mut options = Self::default();
+        for (key, val) in value.as_hashmap()? {
+            let val = val.as_value_string()?;
+            match (key.as_ref(), val.as_ref()) {
+                ("style", "block") => options.style = YamlStyle::Block,
+                ("style", "flow") => options.style = YamlStyle::Flow,
+                ("quoting", "minimal") => options.quoting = YamlQuoting::Minimal,
+                ("quoting", "always") => options.quoting = YamlQuoting::Always,
+                _ => return Err(format!("Unsupported YAML option {}: '{}'", key, val).into()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Serializes the value into a YAML document. Hashmap keys are sorted.
+pub fn to_yaml(value: &Value, options: &YamlOptions) -> Result<String, Error> {
+    let mut emitter = Emitter {
+        options,
+        out: String::new(),
+    };
+    match options.style {
+        YamlStyle::Block => emitter.block(value, 0)?,
+        YamlStyle::Flow => emitter.flow(value)?,
+    }
+    emitter.out.push('\n');
+    Ok(emitter.out)
+}
+
+struct Emitter<'a> {
+    options: &'a YamlOptions,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn block(&mut self, value: &Value, indent: usize) -> Result<(), Error> {
+        match value {
+            Value::HashMap(hm) if !hm.is_empty() => {
+                let mut keys: Vec<&ValueString> = hm.keys().collect();
+                keys.sort();
+                for (ix, key) in keys.into_iter().enumerate() {
+                    if ix > 0 {
+                        self.new_line(indent);
+                    }
+                    self.string(key, false);
+                    self.out.push(':');
+                    self.block_child(&hm[key], indent + 2)?;
+                }
+                Ok(())
+            }
+            Value::List(list) if !list.is_empty() => {
+                for (ix, x) in list.iter().enumerate() {
+                    if ix > 0 {
+                        self.new_line(indent);
+                    }
+                    self.out.push_str("- ");
+                    self.block(x, indent + 2)?;
+                }
+                Ok(())
+            }
+            _ => self.flow(value),
+        }
+    }
+
+    fn block_child(&mut self, value: &Value, indent: usize) -> Result<(), Error> {
+        if is_block_collection(value) {
+            self.new_line(indent);
+            self.block(value, indent)
+        } else {
+            self.out.push(' ');
+            self.flow(value)
+        }
+    }
+
+    fn flow(&mut self, value: &Value) -> Result<(), Error> {
+        match value {
+            Value::Null => self.out.push_str("null"),
+            Value::Bool(x) => write!(self.out, "{}", x).unwrap(),
+            Value::Int(x) => write!(self.out, "{}", x).unwrap(),
+            Value::Float(x) => self.out.push_str(&format_float(*x)),
+            Value::String(x) => self.string(x, self.options.style == YamlStyle::Flow),
+            Value::HashMap(hm) => {
+                let mut keys: Vec<&ValueString> = hm.keys().collect();
+                keys.sort();
+                self.out.push('{');
+                for (ix, key) in keys.into_iter().enumerate() {
+                    if ix > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.string(key, true);
+                    self.out.push_str(": ");
+                    self.flow(&hm[key])?;
+                }
+                self.out.push('}');
+            }
+            Value::List(list) => {
+                self.out.push('[');
+                for (ix, x) in list.iter().enumerate() {
+                    if ix > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.flow(x)?;
+                }
+                self.out.push(']');
+            }
+            Value::Func(_) => return Err("Functions cannot be serialized into YAML".into()),
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str, in_flow: bool) {
+        if self.options.quoting == YamlQuoting::Minimal && is_safe_plain(s, in_flow) {
+            self.out.push_str(s);
+        } else {
+            self.out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => self.out.push_str("\\\""),
+                    '\\' => self.out.push_str("\\\\"),
+                    '\n' => self.out.push_str("\\n"),
+                    '\t' => self.out.push_str("\\t"),
+                    '\r' => self.out.push_str("\\r"),
+                    c if c.is_control() => write!(self.out, "\\u{:04X}", c as u32).unwrap(),
+                    c => self.out.push(c),
+                }
+            }
+            self.out.push('"');
+        }
+    }
+
+    fn new_line(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+}
+
+fn is_block_collection(value: &Value) -> bool {
+    match value {
+        Value::HashMap(hm) => !hm.is_empty(),
+        Value::List(list) => !list.is_empty(),
+        _ => false,
+    }
+}
+
+/// Words that YAML 1.1 or 1.2 parsers resolve to booleans or null.
+const RESERVED_WORDS: &[&str] = &[
+    "y", "yes", "n", "no", "true", "false", "on", "off", "null", "~",
+];
+
+/// Returns true if the string can be emitted unquoted and is read back as the same string.
+fn is_safe_plain(s: &str, in_flow: bool) -> bool {
+    let first = match s.chars().next() {
+        Some(c) => c,
+        None => return false,
+    };
+    if RESERVED_WORDS.contains(&s.to_lowercase().as_str()) || s == "<<" || s == "=" {
+        return false;
+    }
+    if s.trim() != s || "-?:,[]{}#&*!|>'\"%@`".contains(first) {
+        return false;
+    }
+    // Numbers, timestamps, `.inf`, `.nan`, etc.
+    if first.is_ascii_digit() || first == '.' || first == '+' {
+        return false;
+    }
+    if s.contains(": ") || s.contains(" #") || s.ends_with(':') {
+        return false;
+    }
+    if in_flow && s.contains(|c| ",[]{}".contains(c)) {
+        return false;
+    }
+    !s.contains(char::is_control)
+}
+
+#[test]
+fn yaml_block() {
+    let value = crate::parse_string(
+        r#"{
+            name: "api",
+            ports: [80, 443],
+            enabled: "yes",
+            empty: {},
+            version: "1.0",
+            nested: {list: [{a: 1, b: [true]}, [1, 2]], text: "a: b
+c"},
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        to_yaml(&value, &YamlOptions::default()).unwrap(),
+        r#"empty: {}
+enabled: "yes"
+name: api
+nested:
+  list:
+    - a: 1
+      b:
+        - true
+    - - 1
+      - 2
+  text: "a: b\nc"
+ports:
+  - 80
+  - 443
+version: "1.0"
+"#
+    );
+}
+
+#[test]
+fn yaml_flow_and_quoting() {
+    let value = crate::parse_string(r#"{name: "api", list: ["a,b", "c"], n: "no"}"#).unwrap();
+    assert_eq!(
+        to_yaml(
+            &value,
+            &YamlOptions {
+                style: YamlStyle::Flow,
+                quoting: YamlQuoting::Minimal
+            }
+        )
+        .unwrap(),
+        "{list: [\"a,b\", c], \"n\": \"no\", name: api}\n"
+    );
+    assert_eq!(
+        to_yaml(
+            &value,
+            &YamlOptions {
+                style: YamlStyle::Block,
+                quoting: YamlQuoting::Always
+            }
+        )
+        .unwrap(),
+        "\"list\":\n  - \"a,b\"\n  - \"c\"\n\"n\": \"no\"\n\"name\": \"api\"\n"
+    );
+}
+
+#[test]
+fn yaml_round_trip() {
+    let value = crate::parse_string(
+        r##"{
+            a: ["yes", "No", "ON", "null", "~", "1.0", "0x1F", "1_000", ".inf", "2001-12-14", "-1"],
+            b: [" x", "x ", "#x", "a #b", "- x", "a:", "*x", "@x", "<<", "=", "tab\tx"],
+            c: {"key: x": 'a\b"c', "multi": "a
+  b"},
+        }"##,
+    )
+    .unwrap();
+    for style in &[YamlStyle::Block, YamlStyle::Flow] {
+        let yaml = to_yaml(
+            &value,
+            &YamlOptions {
+                style: *style,
+                quoting: YamlQuoting::Minimal,
+            },
+        )
+        .unwrap();
+        let parsed: Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed, value, "{}", yaml);
+    }
+    assert_eq!(
+        to_yaml(&Value::String("".into()), &YamlOptions::default()).unwrap(),
+        "\"\"\n"
+    );
+}
@@ -0,0 +1,24 @@
+use std::cell::Cell;
+use std::thread::LocalKey;
+
+/// Tracks the depth of a recursion, e.g. of parsing nested expressions, to fail with an error
+/// instead of overflowing the stack on deeply nested input.
+pub struct DepthGuard(&'static LocalKey<Cell<usize>>);
+
+impl DepthGuard {
+    /// Returns `None` if the recursion is already `max` levels deep.
+    pub fn enter(depth: &'static LocalKey<Cell<usize>>, max: usize) -> Option<Self> {
+        let current = depth.with(Cell::get);
+        if current >= max {
+            return None;
+        }
+        depth.with(|x| x.set(current + 1));
+        Some(Self(depth))
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        self.0.with(|x| x.set(x.get() - 1));
+    }
+}
@@ -0,0 +1,401 @@
+//! Property tests feeding generated, nearly valid sources into `parse_string`.
+//! Malformed input must be rejected with an error pointing to a location, never with a panic.
+//! The runner uses a fixed seed, so failures are reproducible.
+//! Inputs that used to crash are kept in `CORPUS`.
+
+use std::panic;
+
+use proptest::prelude::*;
+use proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+
+/// Builtins used by the generator. `iterate`, `iterate_until`, and `iterate_while` are left out,
+/// because a generated iteration count like `2147483647` would run for a very long time.
+const FUNCTIONS: &[&str] = &[
+    "read_file",
+    "getenv",
+    "getenv_any",
+    "getenv_int",
+    "getenv_bool",
+    "env_or_file",
+    "concat",
+    "format",
+    "merge",
+    "deep_merge",
+    "merge_deep",
+    "merge_by",
+    "zip_map",
+    "chunk_by",
+    "fold",
+    "map",
+    "filter",
+    "filter_map",
+    "unique",
+    "length",
+    "reverse",
+    "sum",
+    "product",
+    "average",
+    "bounds",
+    "sort",
+    "lazy",
+    "force",
+    "opt",
+    "cond",
+    "assert_subset",
+    "assert_eq",
+    "assert_ne",
+    "topo_sort",
+    "redact",
+    "decimal_add",
+    "decimal_mul",
+    "decimal_cmp",
+    "decimal_round",
+    "require_data",
+    "canonicalize",
+    "parse_args",
+    "is_ipv4",
+    "is_cidr",
+    "cidr_contains",
+    "humanize_bytes",
+    "humanize_duration",
+    "humanize_count",
+    "enum_map",
+    "semver_satisfies",
+];
+
+const METHODS: &[&str] = &[
+    "map",
+    "filter",
+    "len",
+    "insert",
+    "keys",
+    "values",
+    "entries",
+    "map_values",
+    "contains_key",
+    "append",
+    "join",
+    "is_subset_of",
+    "matches",
+    "to_args",
+    "to_yaml",
+    "flatten",
+    "matches_glob",
+    "to_bool",
+    "strip_ansi",
+    "sanitize",
+    "to_dns_label",
+    "to_env_var_name",
+    "to_json",
+    "to_json_pretty",
+    "to_flat_json",
+    "to_toml",
+    "to_string",
+    "sort",
+    "sort_by",
+    "parse_json",
+    "parse_json5",
+    "parse_yaml",
+    "parse_toml",
+    "trim",
+    "normalize_whitespace",
+    "unindent",
+    "reindent",
+    "embed_yaml_block",
+    "split",
+    "replace",
+    "replace_all_map",
+    "to_upper",
+    "to_lower",
+    "substring",
+    "repeat",
+    "hex_encode",
+    "hex_decode",
+    "base32_encode",
+    "base32_decode",
+    "negate",
+    "abs",
+    "sign",
+    "is_null",
+    "is_present",
+    "map_opt",
+    "and_then_opt",
+    "unwrap_or",
+    "unwrap_or_else",
+    "get",
+    "get_ci",
+];
+
+const TOKENS: &[&str] = &[
+    "(", ")", "[", "]", "{", "}", ",", ":", "\"", "'", "${", "#", "=>", "+", "-", "*", "/", "==",
+    ">=", ".", "...", "let", "in", "if", "then", "else", "not", "and", "import", "with", "//",
+    "/*", "*/", "\\", "\n", " ",
+];
+
+/// Inputs that crashed the parser or the evaluation before, with a part of the expected error message.
+const CORPUS: &[(&str, &str)] = &[
+    ("1 / 0", "Division by zero"),
+    ("9223372036854775807 + 1", "Integer overflow"),
+    ("(0 - 9223372036854775807) - 2", "Integer overflow"),
+    ("5 * 9223372036854775807", "Integer overflow"),
+    ("9223372036854775808", "Cannot parse"),
+    ("-(-9223372036854775808)", "Integer overflow in negation"),
+    ("-\"a\"", "'-' expects a number"),
+    ("1e300 * 1e300", "Float overflow"),
+    ("1 / 0.0", "Division by zero"),
+    ("((x) => x)()", "Function expects 1 arguments, but got 0"),
+    (
+        "[1, 2].map((a, b, c) => b)",
+        "'map' expects a function with the arguments (val) or (ix, val)",
+    ),
+    ("[1][true]", "List index must be an int"),
+    ("1.x", "no such field or method: x"),
+    ("1[0]", "Number method name must be a string"),
+    ("null[1]", "Cannot index null"),
+    ("\"abc\"[1]", "String method name must be a string"),
+    (
+        "let f = (g, x) => g(g, x) in f(f, 1)",
+        "Maximum evaluation depth",
+    ),
+    ("\"${", "Unexpected end of input"),
+    ("\"${x", "Unexpected end of input"),
+    ("\"a\\", "Unexpected end of input"),
+    ("\"\\q\"", "Unknown escape sequence"),
+    ("{a: 1} }", "Cannot parse '}'"),
+    ("[1,", "Unexpected end of input"),
+    (
+        "\"abc\" + import \"missing.cfg\"",
+        "Imports are not allowed when parsing from a string",
+    ),
+    ("import \"/missing.cfg\"", "Cannot open file"),
+];
+
+enum Outcome {
+    Ok,
+    Err { message: String, has_location: bool },
+    Panic(String),
+}
+
+/// Runs `tokenize` and `parse_string` on a thread with a large stack,
+/// because unoptimized builds need a lot of stack for deeply nested input.
+fn check(src: &str) -> Outcome {
+    let src = src.to_string();
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(move || {
+            let prev_hook = panic::take_hook();
+            panic::set_hook(Box::new(|_| {}));
+            let out = panic::catch_unwind(|| {
+                crate::tokenize(&src);
+                crate::parse_string(&src)
+            });
+            panic::set_hook(prev_hook);
+            match out {
+                Ok(Ok(_)) => Outcome::Ok,
+                Ok(Err(e)) => Outcome::Err {
+                    has_location: e.location.is_some(),
+                    message: e.message,
+                },
+                Err(p) => Outcome::Panic(
+                    p.downcast_ref::<&str>()
+                        .map(ToString::to_string)
+                        .or_else(|| p.downcast_ref::<String>().cloned())
+                        .unwrap_or_default(),
+                ),
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+fn assert_no_panic(src: &str) -> Result<(), TestCaseError> {
+    match check(src) {
+        Outcome::Ok => Ok(()),
+        Outcome::Err { has_location, .. } if has_location => Ok(()),
+        Outcome::Err { message, .. } => Err(TestCaseError::fail(format!(
+            "error without location for {:?}: {}",
+            src, message
+        ))),
+        Outcome::Panic(p) => Err(TestCaseError::fail(format!("panic for {:?}: {}", src, p))),
+    }
+}
+
+fn identifier() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("x".to_string()),
+        Just("y".to_string()),
+        "[a-z_][a-z0-9_]{0,4}",
+    ]
+}
+
+fn leaf() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("0"),
+        Just("1"),
+        Just("7"),
+        Just("2147483647"),
+        Just("9223372036854775807"),
+        Just("-1"),
+        Just("-9223372036854775808"),
+        Just("0.5"),
+        Just("1e300"),
+        Just("true"),
+        Just("false"),
+        Just("null"),
+        Just("\"\""),
+        Just("\"abc\""),
+        Just("\"1.50\""),
+        Just("\"a${x}b\""),
+        Just("\"a\\tb\\${x}\""),
+        Just("'raw'"),
+        Just("#\"a\"b\"#"),
+        Just("{}"),
+        Just("[]"),
+        Just("x"),
+    ]
+    .prop_map(String::from)
+}
+
+fn expression() -> impl Strategy<Value = String> {
+    leaf().prop_recursive(5, 48, 4, |inner| {
+        let args = prop::collection::vec(inner.clone(), 0..4).prop_map(|x| x.join(", "));
+        prop_oneof![
+            args.clone().prop_map(|x| format!("[{}]", x)),
+            prop::collection::vec((identifier(), inner.clone()), 0..3).prop_map(|x| {
+                let entries: Vec<String> = x.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                format!("{{{}}}", entries.join(", "))
+            }),
+            (
+                inner.clone(),
+                prop::sample::select(vec!["+", "-", "*", "/", "==", "!=", "<", ">", "and", "or"]),
+                inner.clone()
+            )
+                .prop_map(|(a, op, b)| format!("{} {} {}", a, op, b)),
+            (prop::sample::select(FUNCTIONS), args.clone())
+                .prop_map(|(f, x)| format!("{}({})", f, x)),
+            (inner.clone(), prop::sample::select(METHODS), args.clone())
+                .prop_map(|(a, m, x)| format!("{}.{}({})", a, m, x)),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("{}[{}]", a, b)),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("[...{}, {{...{}}}]", a, b)),
+            inner.clone().prop_map(|x| format!("({})", x)),
+            inner.clone().prop_map(|x| format!("not {}", x)),
+            inner.clone().prop_map(|x| format!("\"s${{{}}}\"", x)),
+            (inner.clone(), inner.clone(), inner.clone())
+                .prop_map(|(c, a, b)| format!("if {} then {} else {}", c, a, b)),
+            (identifier(), inner.clone(), inner.clone())
+                .prop_map(|(id, v, body)| format!("let {} = {}\nin\n{}", id, v, body)),
+            (
+                prop::collection::vec(identifier(), 0..3),
+                inner.clone(),
+                args
+            )
+                .prop_map(|(ids, body, x)| format!(
+                    "(({}) => {})({})",
+                    ids.join(", "),
+                    body,
+                    x
+                )),
+        ]
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    Truncate(usize),
+    Insert(usize, &'static str),
+    Delete(usize, usize),
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        any::<usize>().prop_map(Mutation::Truncate),
+        (any::<usize>(), prop::sample::select(TOKENS)).prop_map(|(ix, t)| Mutation::Insert(ix, t)),
+        (any::<usize>(), 1..4usize).prop_map(|(ix, len)| Mutation::Delete(ix, len)),
+    ]
+}
+
+/// Applies the mutations at char boundaries, positions are taken modulo the source length.
+fn mutate(src: &str, mutations: &[Mutation]) -> String {
+    let mut chars: Vec<char> = src.chars().collect();
+    for m in mutations {
+        let len = chars.len() + 1;
+        match m {
+            Mutation::Truncate(ix) => chars.truncate(ix % len),
+            Mutation::Insert(ix, token) => {
+                let ix = ix % len;
+                chars.splice(ix..ix, token.chars());
+            }
+            Mutation::Delete(ix, n) => {
+                let ix = ix % len;
+                let end = (ix + n).min(chars.len());
+                chars.drain(ix.min(end)..end);
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+fn run(cases: u32, strategy: impl Strategy<Value = String>) {
+    let config = Config {
+        cases,
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let mut runner =
+        TestRunner::new_with_rng(config, TestRng::deterministic_rng(RngAlgorithm::ChaCha));
+    if let Err(e) = runner.run(&strategy, |src| assert_no_panic(&src)) {
+        panic!("{}", e);
+    }
+}
+
+#[test]
+fn fuzz_valid_expressions() {
+    run(1000, expression());
+}
+
+#[test]
+fn fuzz_mutated_expressions() {
+    run(
+        2000,
+        (expression(), prop::collection::vec(mutation(), 1..4))
+            .prop_map(|(src, mutations)| mutate(&src, &mutations)),
+    );
+}
+
+#[test]
+fn fuzz_deep_nesting() {
+    for n in &[100, 1000, 10000] {
+        for (open, close) in &[
+            ("(", ")"),
+            ("[", "]"),
+            ("{a: ", "}"),
+            ("\"${", "}\""),
+            ("f(", ")"),
+        ] {
+            let src = format!("{}1{}", open.repeat(*n), close.repeat(*n));
+            assert_no_panic(&src).unwrap();
+        }
+        assert_no_panic(&vec!["1"; *n].join(" + ")).unwrap();
+        assert_no_panic(&format!("[1]{}", ".len".repeat(*n))).unwrap();
+        assert_no_panic(&format!("{}true", "not ".repeat(*n))).unwrap();
+        assert_no_panic(&format!("{}1", "-".repeat(*n))).unwrap();
+    }
+}
+
+#[test]
+fn regression_corpus() {
+    for (src, expected) in CORPUS {
+        match check(src) {
+            Outcome::Err {
+                message,
+                has_location,
+            } => {
+                assert!(message.contains(expected), "{:?}: {}", src, message);
+                assert!(has_location, "{:?}: {}", src, message);
+            }
+            Outcome::Ok => panic!("{:?} did not fail", src),
+            Outcome::Panic(p) => panic!("{:?} panicked: {}", src, p),
+        }
+    }
+}
@@ -5,34 +5,130 @@ extern crate derivative;
 #[macro_use]
 extern crate tracing;
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::rc::Rc;
 
-use parser::*;
-
-pub use crate::compiler::Value;
+pub use crate::compiler::{
+    evaluate_workspace, expand_entries, load_checks, structural_diff, write_tree, AppliedDefault,
+    Change, Compiled, Dependencies, Difference, EnvProvider, EvalOptions, EvalReport, FileLoader,
+    FsLoader, GraphFile, ImportEdge, ImportGraph, ProcessEnv, Recording, Value, Warning,
+    WorkspaceCheck, WorkspaceOptions, WorkspaceResult, WorkspaceViolation, WriteTreeOptions,
+    WriteTreeReport, YamlOptions, YamlQuoting, YamlStyle, EXPLAIN_TARGET, LANGUAGE_FEATURES,
+};
 use crate::compiler::{ErrorWithLocation, Source};
-use nom_locate::LocatedSpan;
+pub use crate::parser::{tokenize, Token, TokenKind};
 
 mod compiler;
+mod depth;
 mod parser;
 
+#[cfg(test)]
+mod fuzz_tests;
 #[cfg(test)]
 mod tests;
 
 pub fn parse_string(input: &str) -> Result<Value, ErrorWithLocation> {
-    parse_source(Source::from_string(input.to_string()))
+    compile_string(input)?.evaluate()
+}
+
+/// Like `parse_string`, the `vars` can be used in the input as predefined variables.
+pub fn parse_string_with_vars(
+    input: &str,
+    vars: &HashMap<String, Value>,
+) -> Result<Value, ErrorWithLocation> {
+    compile_source(Source::from_string(input.to_string()), vars)?.evaluate()
+}
+
+/// Like `parse_string`, relative imports are resolved against `base_dir`.
+/// Without a base directory, only absolute paths can be imported from a string.
+pub fn parse_string_with_base(input: &str, base_dir: &Path) -> Result<Value, ErrorWithLocation> {
+    compile_string_with_base(input, base_dir)?.evaluate()
 }
 
 pub fn parse_file(file_name: &str) -> Result<Value, ErrorWithLocation> {
-    parse_source(Source::from_file(Path::new(file_name))?)
+    compile_file(file_name)?.evaluate()
+}
+
+/// Like `parse_string`, e.g. with other limits of the evaluation depth and the number of evaluated expressions.
+pub fn parse_string_with_options(
+    input: &str,
+    options: &EvalOptions,
+) -> Result<Value, ErrorWithLocation> {
+    compile_string(input)?.evaluate_with_options(options)
+}
+
+/// Like `parse_file`, with the options of `parse_string_with_options`.
+pub fn parse_file_with_options(
+    file_name: &str,
+    options: &EvalOptions,
+) -> Result<Value, ErrorWithLocation> {
+    compile_file(file_name)?.evaluate_with_options(options)
+}
+
+/// Parses and compiles the config once, so that it can be evaluated many times.
+pub fn compile_string(input: &str) -> Result<Compiled, ErrorWithLocation> {
+    compile_source(Source::from_string(input.to_string()), &HashMap::new())
+}
+
+/// Like `compile_string`, relative imports are resolved against `base_dir`.
+pub fn compile_string_with_base(
+    input: &str,
+    base_dir: &Path,
+) -> Result<Compiled, ErrorWithLocation> {
+    compile_source(
+        Source::from_string_with_base(input.to_string(), base_dir),
+        &HashMap::new(),
+    )
+}
+
+/// Like `compile_string`, use `Compiled::is_stale` to check whether the file or its imports changed.
+pub fn compile_file(file_name: &str) -> Result<Compiled, ErrorWithLocation> {
+    compile_source(Source::from_file(Path::new(file_name))?, &HashMap::new())
+}
+
+/// Like `compile_file`, the `vars` can be used in the file as predefined variables, but not in its imports.
+pub fn compile_file_with_vars(
+    file_name: &str,
+    vars: &HashMap<String, Value>,
+) -> Result<Compiled, ErrorWithLocation> {
+    compile_source(Source::from_file(Path::new(file_name))?, vars)
+}
+
+/// Like `compile_string_with_base`, with the predefined `vars` of `compile_file_with_vars`.
+pub fn compile_string_with_base_and_vars(
+    input: &str,
+    base_dir: &Path,
+    vars: &HashMap<String, Value>,
+) -> Result<Compiled, ErrorWithLocation> {
+    compile_source(
+        Source::from_string_with_base(input.to_string(), base_dir),
+        vars,
+    )
+}
+
+/// Like `compile_file`, reads the file and its imports with the loader,
+/// e.g. to compile a config as of another revision without checking it out.
+/// Pass the same loader in the `EvalOptions` to also use it for `read_file`.
+pub fn compile_file_with_loader(
+    file_name: &str,
+    loader: Rc<dyn FileLoader>,
+) -> Result<Compiled, ErrorWithLocation> {
+    let source = Source::from_loader(Path::new(file_name), loader.as_ref())?;
+    let expr = compiler::parse(&source)?;
+    compiler::compile(&expr, source.clone(), &HashMap::new(), loader)
+}
+
+/// Parses `json`, `json5`, `yaml`, or `toml` data into a hashmap, like the `parse_json` etc. string methods,
+/// e.g. to merge data files into an evaluated config with `Value::deep_merge`.
+pub fn parse_data(input: &str, format: &str) -> Result<Value, ErrorWithLocation> {
+    compiler::parse_data(input, format)
 }
 
-fn parse_source(source: Source) -> Result<Value, ErrorWithLocation> {
-    let input = source.as_str();
-    let (rest, expr) =
-        parse_unit(LocatedSpan::new(input)).map_err(|e| anyhow!("Cannot parse {}", e))?;
-    if !rest.fragment().is_empty() {
-        return Err(anyhow!("Cannot parse: '{}'", rest.fragment()).into());
-    }
-    compiler::compile(&expr, source.clone())
+fn compile_source(
+    source: Source,
+    vars: &HashMap<String, Value>,
+) -> Result<Compiled, ErrorWithLocation> {
+    let expr = compiler::parse(&source)?;
+    compiler::compile(&expr, source.clone(), vars, Rc::new(FsLoader))
 }
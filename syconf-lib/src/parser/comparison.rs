@@ -4,7 +4,7 @@ use nom::combinator::{map, opt};
 use nom::sequence::{pair, tuple};
 use nom::IResult;
 
-use crate::parser::{expr_sum, ml_space0, Expr, ExprWithLocation, Span};
+use crate::parser::{enter_nested, expr_sum, ml_space0, Expr, ExprWithLocation, Span};
 use nom_locate::position;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -25,6 +25,7 @@ pub enum ComparisonOperator {
 }
 
 pub fn expr_comparison(input: Span) -> IResult<Span, ExprWithLocation> {
+    let _guard = enter_nested(input)?;
     map(
         tuple((
             expr_sum,
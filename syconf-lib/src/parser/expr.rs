@@ -25,11 +25,14 @@ pub enum Expr<'a> {
     Identifier(&'a str),
     FuncDefinition(Rc<FuncDefinition<'a>>),
     Math(Box<MathOperation<'a>>),
+    /// The unary minus of an operand that is not a number literal, e.g. `-x`.
+    Negation(Box<ExprWithLocation<'a>>),
     Comparison(Box<Comparison<'a>>),
     Conditional(Box<Conditional<'a>>),
     Logical(Box<Logical<'a>>),
     Suffix(Box<SuffixExpr<'a>>),
-    Import(&'a str),
+    Import(Import<'a>),
+    With(Box<WithExpr<'a>>),
 }
 
 impl<'a> Expr<'a> {
@@ -45,7 +48,7 @@ pub fn expr(input: Span) -> IResult<Span, ExprWithLocation> {
     expr_logical(input)
 }
 
-pub fn identifier(input: Span) -> IResult<Span, &str> {
+pub fn identifier(input: Span<'_>) -> IResult<Span<'_>, &str> {
     let (next_input, (a, b)) = pair(
         take_while1(|x: char| x.is_alpha() || x == '_'),
         take_while(|x: char| x.is_alphanumeric() || x == '_'),
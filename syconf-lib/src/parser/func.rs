@@ -12,7 +12,7 @@ pub struct FuncDefinition<'a> {
     pub expression: Box<ExprWithLocation<'a>>,
 }
 
-fn func_arguments(input: Span) -> IResult<Span, Vec<&str>> {
+fn func_arguments(input: Span<'_>) -> IResult<Span<'_>, Vec<&str>> {
     delimited(
         pair(tag("("), ml_space0),
         separated_list(tuple((ml_space0, tag(","), ml_space0)), identifier),
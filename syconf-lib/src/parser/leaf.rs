@@ -2,12 +2,13 @@ use std::rc::Rc;
 
 use nom::branch::alt;
 use nom::bytes::complete::*;
-use nom::combinator::map;
-use nom::sequence::{delimited, pair, tuple};
+use nom::combinator::{map, opt};
+use nom::sequence::{delimited, pair, preceded, tuple};
 use nom::IResult;
 
 use crate::parser::block::block_expr;
 use crate::parser::conditional::*;
+use crate::parser::with_expr::with_expr;
 
 use super::*;
 use nom_locate::position;
@@ -18,6 +19,9 @@ pub fn expr_leaf(input: Span) -> IResult<Span, ExprWithLocation> {
         map(pair(position, conditional), |(pos, x)| {
             Expr::Conditional(Box::new(x)).with_location(pos)
         }),
+        map(pair(position, with_expr), |(pos, x)| {
+            Expr::With(Box::new(x)).with_location(pos)
+        }),
         map(pair(position, config_value), |(pos, x)| {
             Expr::Value(x).with_location(pos)
         }),
@@ -35,6 +39,13 @@ pub fn expr_leaf(input: Span) -> IResult<Span, ExprWithLocation> {
     ))(input)
 }
 
+#[derive(Debug, Eq, PartialEq)]
+pub struct Import<'a> {
+    pub path: &'a str,
+    /// Variables given by `import "file.sy" with {name: value}` that are bound in the imported file.
+    pub bindings: Vec<HashMapElement<'a>>,
+}
+
 fn import(input: Span) -> IResult<Span, ExprWithLocation> {
     map(
         tuple((
@@ -42,7 +53,17 @@ fn import(input: Span) -> IResult<Span, ExprWithLocation> {
             tuple((tag("import"), ml_space1, tag("\""))),
             is_not("\""),
             tag("\""),
+            opt(preceded(
+                tuple((ml_space1, tag("with"), ml_space1)),
+                hashmap,
+            )),
         )),
-        |(pos, _, path, _)| Expr::Import(path.fragment()).with_location(pos),
+        |(pos, _, path, _, bindings)| {
+            Expr::Import(Import {
+                path: path.fragment(),
+                bindings: bindings.unwrap_or_default(),
+            })
+            .with_location(pos)
+        },
     )(input)
 }
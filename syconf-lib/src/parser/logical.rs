@@ -4,7 +4,9 @@ use nom::combinator::{map, opt};
 use nom::sequence::{pair, tuple};
 use nom::IResult;
 
-use crate::parser::{expr_comparison, ml_space0, ml_space1, Expr, ExprWithLocation, Span};
+use crate::parser::{
+    enter_nested, expr_comparison, ml_space0, ml_space1, Expr, ExprWithLocation, Span,
+};
 use nom_locate::position;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -15,6 +17,7 @@ pub enum Logical<'a> {
 }
 
 pub fn expr_logical(input: Span) -> IResult<Span, ExprWithLocation> {
+    let _guard = enter_nested(input)?;
     alt((negation, binary))(input)
 }
 
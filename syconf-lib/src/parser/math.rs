@@ -1,7 +1,7 @@
 use nom::branch::alt;
 use nom::bytes::complete::*;
 use nom::combinator::{map, opt};
-use nom::sequence::{delimited, pair, tuple};
+use nom::sequence::{delimited, pair, preceded, tuple};
 use nom::IResult;
 
 use super::*;
@@ -23,6 +23,7 @@ pub enum MathOp {
 }
 
 pub fn expr_sum(input: Span) -> IResult<Span, ExprWithLocation> {
+    let _guard = enter_nested(input)?;
     map(
         tuple((
             expr_prod,
@@ -52,9 +53,10 @@ pub fn expr_sum(input: Span) -> IResult<Span, ExprWithLocation> {
 }
 
 pub fn expr_prod(input: Span) -> IResult<Span, ExprWithLocation> {
+    let _guard = enter_nested(input)?;
     map(
         tuple((
-            expr_suffix,
+            expr_unary,
             // expr_dot_chain,
             opt(pair(
                 delimited(
@@ -81,6 +83,19 @@ pub fn expr_prod(input: Span) -> IResult<Span, ExprWithLocation> {
     )(input)
 }
 
+/// A minus before an operand, which binds weaker than method calls, i.e. `-x.abs()` is `-(x.abs())`.
+/// A minus in front of a binary operand is the operator, so `a - 5` is a subtraction and `a - -5` subtracts `-5`.
+fn expr_unary(input: Span) -> IResult<Span, ExprWithLocation> {
+    let _guard = enter_nested(input)?;
+    alt((
+        expr_suffix,
+        map(
+            pair(position, preceded(pair(tag("-"), ml_space0), expr_unary)),
+            |(pos, x)| Expr::Negation(Box::new(x)).with_location(pos),
+        ),
+    ))(input)
+}
+
 fn map_math_op<'a>(
     expr1: ExprWithLocation<'a>,
     x: Option<((Span<'a>, MathOp), ExprWithLocation<'a>)>,
@@ -1,5 +1,8 @@
+use std::cell::Cell;
+
 use nom::branch::alt;
 use nom::combinator::{all_consuming, map};
+use nom::error::ErrorKind;
 use nom::sequence::delimited;
 use nom::IResult;
 
@@ -8,12 +11,17 @@ pub use comparison::*;
 pub use conditional::*;
 pub use expr::*;
 pub use func::*;
+pub use leaf::Import;
 pub use logical::*;
 pub use math::*;
+pub use requirements::*;
 pub use spaces::*;
 pub use suffix_operators::*;
+pub use tokens::{tokenize, Token, TokenKind};
 pub use value::*;
+pub use with_expr::WithExpr;
 
+use crate::depth::DepthGuard;
 use crate::parser::block::block_body;
 
 mod block;
@@ -24,12 +32,44 @@ mod func;
 mod leaf;
 mod logical;
 mod math;
+mod requirements;
 mod spaces;
 mod suffix_operators;
+mod tokens;
 mod value;
+mod with_expr;
 
 pub type Span<'a> = nom_locate::LocatedSpan<&'a str>;
 
+/// Maximum depth of the recursive parser functions, a parenthesis level takes 4 of them.
+const MAX_NESTING: usize = 400;
+
+thread_local! {
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+    static FURTHEST: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Called by the recursive parser functions, fails with `ErrorKind::TooLarge` on too deeply nested input.
+fn enter_nested(input: Span) -> Result<DepthGuard, nom::Err<(Span, ErrorKind)>> {
+    reach(&input);
+    DepthGuard::enter(&NESTING, MAX_NESTING).ok_or(nom::Err::Failure((input, ErrorKind::TooLarge)))
+}
+
+/// Records the position as reached. The alternatives of `alt` backtrack to the start of the outermost construct
+/// on an error, so the furthest position is where a syntax error inside a nested construct most likely is.
+fn reach(input: &Span) {
+    FURTHEST.with(|x| x.set(x.get().max(input.location_offset())));
+}
+
+/// The furthest position reached since the last `reset_furthest`.
+pub fn furthest_position() -> usize {
+    FURTHEST.with(Cell::get)
+}
+
+pub fn reset_furthest() {
+    FURTHEST.with(|x| x.set(0));
+}
+
 pub fn parse_unit(input: Span) -> IResult<Span, ExprWithLocation> {
     alt((
         map(all_consuming(block_body), |x| {
@@ -0,0 +1,81 @@
+use nom::branch::alt;
+use nom::bytes::complete::*;
+use nom::combinator::map;
+use nom::multi::{many0, separated_list};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use nom::IResult;
+
+use super::*;
+use nom_locate::position;
+
+/// A declaration at the beginning of a file, checked before the rest of the file is parsed.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Requirement<'a> {
+    /// `syconf_version ">=0.2, <1.0"`
+    Version(&'a str),
+    /// `required_features ["null", "import_with"]`
+    Features(Vec<&'a str>),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct RequirementWithLocation<'a> {
+    pub inner: Requirement<'a>,
+    pub location: Span<'a>,
+}
+
+fn quoted(input: Span<'_>) -> IResult<Span<'_>, &str> {
+    map(
+        delimited(tag("\""), take_till(|c| c == '"'), tag("\"")),
+        |x: Span| *x.fragment(),
+    )(input)
+}
+
+fn requirement(input: Span) -> IResult<Span, Requirement> {
+    alt((
+        map(
+            preceded(pair(tag("syconf_version"), ml_space1), quoted),
+            Requirement::Version,
+        ),
+        map(
+            preceded(
+                tuple((tag("required_features"), ml_space1, tag("["), ml_space0)),
+                terminated(
+                    separated_list(tuple((ml_space0, tag(","), ml_space0)), quoted),
+                    pair(ml_space0, tag("]")),
+                ),
+            ),
+            Requirement::Features,
+        ),
+    ))(input)
+}
+
+/// Parses the leading requirement declarations, the rest of the input is not touched.
+pub fn requirements(input: Span) -> IResult<Span, Vec<RequirementWithLocation>> {
+    preceded(
+        ml_space0,
+        many0(terminated(
+            map(pair(position, requirement), |(location, inner)| {
+                RequirementWithLocation { inner, location }
+            }),
+            ml_space1,
+        )),
+    )(input)
+}
+
+#[test]
+fn parse_requirements() {
+    let (rest, x) = requirements(Span::new(
+        "// shared\nsyconf_version \">=0.1\"\nrequired_features [\"null\", \"cond\"]\n{a: 1}",
+    ))
+    .unwrap();
+    let x: Vec<Requirement> = x.into_iter().map(|r| r.inner).collect();
+    assert_eq!(
+        x,
+        vec![
+            Requirement::Version(">=0.1"),
+            Requirement::Features(vec!["null", "cond"])
+        ]
+    );
+    assert_eq!(rest.fragment(), &"{a: 1}");
+    assert_eq!(requirements(Span::new("syconf_version")).unwrap().1, vec![]);
+}
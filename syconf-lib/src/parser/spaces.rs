@@ -1,17 +1,19 @@
 use crate::parser::Span;
+use nom::branch::alt;
 use nom::bytes::complete::*;
-use nom::character::complete::{line_ending, not_line_ending};
-use nom::combinator::{map, opt, recognize, verify};
-use nom::sequence::{pair, preceded, terminated};
+use nom::character::complete::{line_ending, not_line_ending, one_of};
+use nom::combinator::{map, not, opt, recognize, verify};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated};
 use nom::IResult;
 
 const SPACES: &str = " \t\r\n";
 
-fn is_space_or_newline(c: char) -> bool {
+pub fn is_space_or_newline(c: char) -> bool {
     SPACES.contains(c)
 }
 
-pub fn ml_space1(input: Span) -> IResult<Span, &str> {
+pub fn ml_space1(input: Span<'_>) -> IResult<Span<'_>, &str> {
     verify(ml_space0, |x: &str| !x.is_empty())(input)
 }
 
@@ -32,14 +34,17 @@ fn test_ml_space1() {
     );
 }
 
-pub fn ml_space0(input: Span) -> IResult<Span, &str> {
-    map(
+/// Whitespace and comments, a loop rather than recursion, so that many comments do not overflow the stack.
+pub fn ml_space0(input: Span<'_>) -> IResult<Span<'_>, &str> {
+    let (rest, spaces) = map(
         recognize(pair(
             take_while(is_space_or_newline),
-            opt(pair(line_comment, ml_space0)),
+            many0(pair(comment, take_while(is_space_or_newline))),
         )),
         |x| *x.fragment(),
-    )(input)
+    )(input)?;
+    super::reach(&rest);
+    Ok((rest, spaces))
 }
 
 #[test]
@@ -54,9 +59,51 @@ fn test_ml_space0() {
     );
 }
 
-fn line_comment(input: Span) -> IResult<Span, &str> {
+/// A `//` or `#` line comment, or a `/* */` block comment, which does not nest.
+pub fn comment(input: Span<'_>) -> IResult<Span<'_>, &str> {
+    alt((line_comment, hash_comment, block_comment))(input)
+}
+
+/// Returns the text of the comment, the line break is consumed as well. The last line may end without one.
+pub fn line_comment(input: Span<'_>) -> IResult<Span<'_>, &str> {
     map(
-        preceded(tag("//"), opt(terminated(not_line_ending, line_ending))),
-        |x| x.map(|a: Span| *a.fragment()).unwrap_or(""),
+        preceded(tag("//"), terminated(not_line_ending, opt(line_ending))),
+        |x: Span| *x.fragment(),
     )(input)
 }
+
+/// Like `line_comment`, but a `#` followed by more `#` and a quote starts a raw string like `#"..."#` instead.
+fn hash_comment(input: Span<'_>) -> IResult<Span<'_>, &str> {
+    map(
+        preceded(
+            pair(tag("#"), not(pair(take_while(|c| c == '#'), one_of("\"'")))),
+            terminated(not_line_ending, opt(line_ending)),
+        ),
+        |x: Span| *x.fragment(),
+    )(input)
+}
+
+fn block_comment(input: Span<'_>) -> IResult<Span<'_>, &str> {
+    map(
+        delimited(tag("/*"), take_until("*/"), tag("*/")),
+        |x: Span| *x.fragment(),
+    )(input)
+}
+
+#[test]
+fn comments() {
+    let comment = |s| comment(Span::new(s)).map(|(rest, x)| (x, *rest.fragment()));
+    assert_eq!(comment("// a\nb"), Ok((" a", "b")));
+    assert_eq!(comment("// a"), Ok((" a", "")));
+    assert_eq!(comment("# a \"b\"\r\nc"), Ok((" a \"b\"", "c")));
+    assert_eq!(comment("#"), Ok(("", "")));
+    assert_eq!(comment("/* a\n * b */c"), Ok((" a\n * b ", "c")));
+    assert_eq!(comment("/* a /* b */ c */"), Ok((" a /* b ", " c */")));
+    assert!(comment("#\"raw\"#").is_err());
+    assert!(comment("##'raw'##").is_err());
+    assert!(comment("/* open").is_err());
+    assert_eq!(
+        ml_space0(Span::new("# a\n  /* b */ // c\n\t1")).unwrap().1,
+        "# a\n  /* b */ // c\n\t"
+    );
+}
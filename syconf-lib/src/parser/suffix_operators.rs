@@ -49,7 +49,7 @@ fn suffix_operator(input: Span) -> IResult<Span, SuffixOperator> {
     ))(input)
 }
 
-fn dot(input: Span) -> IResult<Span, &str> {
+fn dot(input: Span<'_>) -> IResult<Span<'_>, &str> {
     preceded(pair(tag("."), ml_space0), identifier)(input)
 }
 
@@ -0,0 +1,286 @@
+use std::ops::Range;
+
+use nom::character::complete::digit1;
+use nom::error::ErrorKind;
+use nom::Slice;
+
+use crate::parser::string::{delimited_string, find_interpolation, has_escapes};
+use crate::parser::value::float;
+use crate::parser::{comment, identifier, is_space_or_newline, Span};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    Identifier,
+    /// A string literal or the part of an interpolated string between the interpolations.
+    String,
+    /// `${` and `}` around an interpolated expression.
+    InterpolationDelimiter,
+    Number,
+    Operator,
+    Comment,
+    Punctuation,
+    /// The rest of the input from the position where tokenizing failed.
+    Error,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte offsets in the input.
+    pub span: Range<usize>,
+}
+
+const KEYWORDS: &[&str] = &[
+    "let", "in", "if", "then", "else", "and", "or", "not", "import", "with", "null", "true",
+    "false",
+];
+
+/// Longer operators come first, so that `>=` is not split into `>` and `=`.
+const OPERATORS: &[&str] = &[
+    "...", "=>", "==", "!=", ">=", "<=", "+", "-", "*", "/", ">", "<", "=", ".",
+];
+
+const PUNCTUATION: &[&str] = &["(", ")", "[", "]", "{", "}", ",", ":", ";"];
+
+/// Splits the input into tokens for syntax highlighting using the lexical rules of the parser.
+/// Never fails, the input from the first position that cannot be tokenized is an `Error` token.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokenizer = Tokenizer(Vec::new());
+    if let Err(position) = tokenizer.tokens(Span::new(input), false) {
+        tokenizer.push(TokenKind::Error, position..input.len());
+    }
+    tokenizer.0
+}
+
+struct Tokenizer(Vec<Token>);
+
+impl Tokenizer {
+    fn push(&mut self, kind: TokenKind, span: Range<usize>) {
+        if !span.is_empty() {
+            self.0.push(Token { kind, span });
+        }
+    }
+
+    /// Consumes the input until its end or, in an interpolation, until the closing `}`.
+    /// Returns the position of the first character that cannot be tokenized as an error.
+    fn tokens<'a>(&mut self, mut input: Span<'a>, interpolation: bool) -> Result<Span<'a>, usize> {
+        let mut braces = 0;
+        loop {
+            let spaces = input.fragment().len()
+                - input
+                    .fragment()
+                    .trim_start_matches(is_space_or_newline)
+                    .len();
+            input = input.slice(spaces..);
+            let fragment = *input.fragment();
+            if fragment.is_empty() || (interpolation && braces == 0 && fragment.starts_with('}')) {
+                return Ok(input);
+            }
+            let start = input.location_offset();
+            let (rest, kind) = if let Ok((rest, _)) = comment(input) {
+                (rest, TokenKind::Comment)
+            } else if let Ok((rest, (quote, content))) = delimited_string(input) {
+                input = self.string(start, quote, content, rest)?;
+                continue;
+            } else if let Ok((rest, word)) = identifier(input) {
+                if KEYWORDS.contains(&word) {
+                    (rest, TokenKind::Keyword)
+                } else {
+                    (rest, TokenKind::Identifier)
+                }
+            } else if let Ok((rest, _)) = float(input) {
+                (rest, TokenKind::Number)
+            } else if let Ok((rest, _)) = digit1::<_, (Span, ErrorKind)>(input) {
+                (rest, TokenKind::Number)
+            } else if let Some(op) = OPERATORS.iter().find(|x| fragment.starts_with(*x)) {
+                (input.slice(op.len()..), TokenKind::Operator)
+            } else if let Some(p) = PUNCTUATION.iter().find(|x| fragment.starts_with(*x)) {
+                match *p {
+                    "{" => braces += 1,
+                    "}" => braces -= 1,
+                    _ => {}
+                }
+                (input.slice(p.len()..), TokenKind::Punctuation)
+            } else {
+                return Err(start);
+            };
+            self.push(kind, start..rest.location_offset());
+            input = rest;
+        }
+    }
+
+    /// Splits interpolated strings into the string parts and the tokens of the interpolated expressions.
+    fn string<'a>(
+        &mut self,
+        start: usize,
+        quote: Span<'a>,
+        mut content: Span<'a>,
+        rest: Span<'a>,
+    ) -> Result<Span<'a>, usize> {
+        let mut part_start = start;
+        if !quote.fragment().ends_with('\'') {
+            let escapes = has_escapes(quote.fragment());
+            while let Some(ix) = find_interpolation(content.fragment(), escapes) {
+                let delimiter = content.slice(ix..).location_offset();
+                self.push(TokenKind::String, part_start..delimiter);
+                self.push(TokenKind::InterpolationDelimiter, delimiter..delimiter + 2);
+                let end = self.tokens(content.slice(ix + 2..), true)?;
+                if end.fragment().is_empty() {
+                    return Err(end.location_offset());
+                }
+                let end = end.location_offset();
+                self.push(TokenKind::InterpolationDelimiter, end..end + 1);
+                content = content.slice(end + 1 - content.location_offset()..);
+                part_start = end + 1;
+            }
+        }
+        self.push(TokenKind::String, part_start..rest.location_offset());
+        Ok(rest)
+    }
+}
+
+#[test]
+fn tokenize_all_kinds() {
+    use TokenKind::*;
+    let input = r##"let x = 1 // one
+in {a: [x, null], "b": if x >= 2 then "v${ x.len() }" else #"o${ "${x}" }"#}"##;
+    let tokens = tokenize(input);
+    let texts: Vec<(TokenKind, &str)> = tokens
+        .iter()
+        .map(|t| (t.kind, &input[t.span.clone()]))
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            (Keyword, "let"),
+            (Identifier, "x"),
+            (Operator, "="),
+            (Number, "1"),
+            (Comment, "// one\n"),
+            (Keyword, "in"),
+            (Punctuation, "{"),
+            (Identifier, "a"),
+            (Punctuation, ":"),
+            (Punctuation, "["),
+            (Identifier, "x"),
+            (Punctuation, ","),
+            (Keyword, "null"),
+            (Punctuation, "]"),
+            (Punctuation, ","),
+            (String, "\"b\""),
+            (Punctuation, ":"),
+            (Keyword, "if"),
+            (Identifier, "x"),
+            (Operator, ">="),
+            (Number, "2"),
+            (Keyword, "then"),
+            (String, "\"v"),
+            (InterpolationDelimiter, "${"),
+            (Identifier, "x"),
+            (Operator, "."),
+            (Identifier, "len"),
+            (Punctuation, "("),
+            (Punctuation, ")"),
+            (InterpolationDelimiter, "}"),
+            (String, "\""),
+            (Keyword, "else"),
+            (String, "#\"o"),
+            (InterpolationDelimiter, "${"),
+            (String, "\""),
+            (InterpolationDelimiter, "${"),
+            (Identifier, "x"),
+            (InterpolationDelimiter, "}"),
+            (String, "\""),
+            (InterpolationDelimiter, "}"),
+            (String, "\"#"),
+            (Punctuation, "}"),
+        ]
+    );
+    assert_eq!(tokens[0].span, 0..3);
+    assert_eq!(tokens[4].span, 10..17);
+    assert_eq!(tokens[22].span, 55..57);
+    assert_eq!(tokens.last().unwrap().span, 92..93);
+}
+
+#[test]
+fn tokenize_comments() {
+    use TokenKind::*;
+    let input = "# a\n[1 /* b */, #\"c\"#] // d";
+    let texts: Vec<(TokenKind, &str)> = tokenize(input)
+        .iter()
+        .map(|t| (t.kind, &input[t.span.clone()]))
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            (Comment, "# a\n"),
+            (Punctuation, "["),
+            (Number, "1"),
+            (Comment, "/* b */"),
+            (Punctuation, ","),
+            (String, "#\"c\"#"),
+            (Punctuation, "]"),
+            (Comment, "// d"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_escapes() {
+    use TokenKind::*;
+    let input = r#"["a\"${x}\${y}", 'b\'"#;
+    let texts: Vec<(TokenKind, &str)> = tokenize(input)
+        .iter()
+        .map(|t| (t.kind, &input[t.span.clone()]))
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            (Punctuation, "["),
+            (String, r#""a\""#),
+            (InterpolationDelimiter, "${"),
+            (Identifier, "x"),
+            (InterpolationDelimiter, "}"),
+            (String, r#"\${y}""#),
+            (Punctuation, ","),
+            (String, r#"'b\'"#),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_invalid_input() {
+    use TokenKind::*;
+    let kinds = |input: &str| -> Vec<(TokenKind, Range<usize>)> {
+        tokenize(input)
+            .into_iter()
+            .map(|t| (t.kind, t.span))
+            .collect()
+    };
+    assert_eq!(
+        kinds("a + $b c"),
+        vec![(Identifier, 0..1), (Operator, 2..3), (Error, 4..8)]
+    );
+    assert_eq!(kinds("[\"abc"), vec![(Punctuation, 0..1), (Error, 1..5)]);
+    assert_eq!(
+        kinds("\"a${x\""),
+        vec![
+            (String, 0..2),
+            (InterpolationDelimiter, 2..4),
+            (Identifier, 4..5),
+            (Error, 5..6)
+        ]
+    );
+    assert_eq!(kinds("'${x}'"), vec![(String, 0..6)]);
+    assert_eq!(
+        kinds("[...a]"),
+        vec![
+            (Punctuation, 0..1),
+            (Operator, 1..4),
+            (Identifier, 4..5),
+            (Punctuation, 5..6)
+        ]
+    );
+    assert_eq!(kinds(""), vec![]);
+}
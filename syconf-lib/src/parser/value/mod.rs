@@ -1,9 +1,9 @@
 use nom::branch::alt;
 use nom::bytes::complete::*;
 use nom::character::complete::*;
-use nom::combinator::{map, map_res};
+use nom::combinator::{map, map_res, not, opt, recognize, verify};
 use nom::multi::separated_list;
-use nom::sequence::{delimited, pair, separated_pair, tuple};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 use nom::{IResult, InputLength, InputTake};
 
 use string::ConfigString;
@@ -15,11 +15,14 @@ pub mod string;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum ConfigValue<'a> {
+    Null,
     Bool(bool),
-    Int(i32),
+    Int(i64),
+    /// The literal as written, it is a valid finite `f64`.
+    Float(&'a str),
     String(Vec<ConfigString<'a>>),
-    HashMap(Vec<HashMapEntry<'a>>),
-    List(Vec<ExprWithLocation<'a>>),
+    HashMap(Vec<HashMapElement<'a>>),
+    List(Vec<ListElement<'a>>),
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -28,11 +31,27 @@ pub struct HashMapEntry<'a> {
     pub value: ExprWithLocation<'a>,
 }
 
+/// An entry or `...expr`, which inserts all entries of a hashmap.
+#[derive(Debug, Eq, PartialEq)]
+pub enum HashMapElement<'a> {
+    Entry(HashMapEntry<'a>),
+    Spread(ExprWithLocation<'a>),
+}
+
+/// An element or `...expr`, which inserts all elements of a list.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ListElement<'a> {
+    Single(ExprWithLocation<'a>),
+    Spread(ExprWithLocation<'a>),
+}
+
 pub fn config_value(input: Span) -> IResult<Span, ConfigValue> {
     alt((
+        map(keyword("null"), |_| ConfigValue::Null),
         map(boolean, ConfigValue::Bool),
-        map_res(digit1, |s: Span| {
-            s.fragment().parse::<i32>().map(ConfigValue::Int)
+        map(signed(float), |s: Span| ConfigValue::Float(s.fragment())),
+        map_res(signed(digit1), |s: Span| {
+            s.fragment().parse::<i64>().map(ConfigValue::Int)
         }),
         map(hashmap, ConfigValue::HashMap),
         map(list, ConfigValue::List),
@@ -40,12 +59,65 @@ pub fn config_value(input: Span) -> IResult<Span, ConfigValue> {
     ))(input)
 }
 
+/// Matches `1.5`, `1.5e-3`, and `1e3`, but not `1.` so that `1.abs()` remains a method call.
+pub(crate) fn float(input: Span) -> IResult<Span, Span> {
+    let exponent = || recognize(tuple((one_of("eE"), opt(one_of("+-")), digit1)));
+    verify(
+        recognize(pair(
+            digit1,
+            alt((
+                recognize(tuple((char('.'), digit1, opt(exponent())))),
+                exponent(),
+            )),
+        )),
+        |s: &Span| s.fragment().parse::<f64>().is_ok_and(f64::is_finite),
+    )(input)
+}
+
+/// Includes a minus directly before the number unless a method call follows, so that `-5` is a literal,
+/// and so is `-9223372036854775808`, whose digits alone do not fit an int, but `-5.abs()` is `-(5.abs())`.
+fn signed<'a, F>(number: F) -> impl Fn(Span<'a>) -> IResult<Span<'a>, Span<'a>>
+where
+    F: Fn(Span<'a>) -> IResult<Span<'a>, Span<'a>>,
+{
+    move |input| {
+        alt((
+            terminated(
+                recognize(pair(char('-'), &number)),
+                // Not `ml_space0`, a lookahead must not record the position as reached.
+                not(pair(take_while(is_space_or_newline), char('.'))),
+            ),
+            &number,
+        ))(input)
+    }
+}
+
 fn boolean(input: Span) -> IResult<Span, bool> {
-    map(alt((tag("true"), tag("false"))), |x: Span| {
+    map(alt((keyword("true"), keyword("false"))), |x: Span| {
         x.fragment() == &"true"
     })(input)
 }
 
+/// Matches the word only if it is not a prefix of an identifier like `nullable`.
+fn keyword<'a>(word: &'static str) -> impl Fn(Span<'a>) -> IResult<Span<'a>, Span<'a>> {
+    move |input| {
+        let (rest, x) = tag(word)(input)?;
+        let (rest, _) = not(take_while1(|c: char| c.is_alphanumeric() || c == '_'))(rest)?;
+        Ok((rest, x))
+    }
+}
+
+fn spread(input: Span) -> IResult<Span, ExprWithLocation> {
+    preceded(tag("..."), expr)(input)
+}
+
+fn hashmap_element(input: Span) -> IResult<Span, HashMapElement> {
+    alt((
+        map(spread, HashMapElement::Spread),
+        map(hashmap_entry, HashMapElement::Entry),
+    ))(input)
+}
+
 fn hashmap_entry(input: Span) -> IResult<Span, HashMapEntry> {
     map(
         separated_pair(
@@ -62,11 +134,11 @@ fn hashmap_entry(input: Span) -> IResult<Span, HashMapEntry> {
     )(input)
 }
 
-fn raw_string(s: &str) -> Expr {
+fn raw_string(s: &str) -> Expr<'_> {
     Expr::Value(ConfigValue::String(vec![ConfigString::Raw(s)]))
 }
 
-fn sep(input: Span) -> IResult<Span, &str> {
+fn sep(input: Span<'_>) -> IResult<Span<'_>, &str> {
     let orig = input;
     let (input, _) = ml_space0(input)?;
     let (input, _) = tag(",")(input)?;
@@ -79,20 +151,84 @@ fn sep(input: Span) -> IResult<Span, &str> {
     ))
 }
 
-fn list(input: Span) -> IResult<Span, Vec<ExprWithLocation>> {
+fn list(input: Span) -> IResult<Span, Vec<ListElement>> {
     delimited(
         pair(tag("["), ml_space0),
-        separated_list(sep, expr),
+        separated_list(
+            sep,
+            alt((
+                map(spread, ListElement::Spread),
+                map(expr, ListElement::Single),
+            )),
+        ),
         pair(alt((sep, ml_space0)), tag("]")),
     )(input)
 }
 
-fn hashmap(input: Span) -> IResult<Span, Vec<HashMapEntry>> {
+pub fn hashmap(input: Span) -> IResult<Span, Vec<HashMapElement>> {
     delimited(
         pair(tag("{"), ml_space0),
-        map(separated_list(alt((sep, ml_space1)), hashmap_entry), |x| {
-            x.into_iter().collect()
-        }),
+        map(
+            separated_list(alt((sep, ml_space1)), hashmap_element),
+            |x| x.into_iter().collect(),
+        ),
         pair(alt((sep, ml_space0)), tag("}")),
     )(input)
 }
+
+#[test]
+fn null_keyword() {
+    use nom_locate::LocatedSpan;
+    assert_eq!(
+        config_value(LocatedSpan::new("null")).unwrap().1,
+        ConfigValue::Null
+    );
+    assert!(config_value(LocatedSpan::new("nullable")).is_err());
+    assert!(config_value(LocatedSpan::new("trueish")).is_err());
+}
+
+#[test]
+fn float_literal() {
+    use nom_locate::LocatedSpan;
+    let parse = |s| config_value(LocatedSpan::new(s)).map(|(rest, v)| (*rest.fragment(), v));
+    assert_eq!(parse("1.5").unwrap(), ("", ConfigValue::Float("1.5")));
+    assert_eq!(parse("2.5E-3").unwrap(), ("", ConfigValue::Float("2.5E-3")));
+    assert_eq!(parse("1e3").unwrap(), ("", ConfigValue::Float("1e3")));
+    assert_eq!(parse("1.abs").unwrap(), (".abs", ConfigValue::Int(1)));
+    assert_eq!(parse("1e").unwrap(), ("e", ConfigValue::Int(1)));
+    // Out of the `f64` range, so the literal ends at the integer and the rest fails to parse.
+    assert_eq!(parse("1e999").unwrap(), ("e999", ConfigValue::Int(1)));
+}
+
+#[test]
+fn spread_elements() {
+    use nom_locate::LocatedSpan;
+    let parse = |s| config_value(LocatedSpan::new(s)).map(|(rest, v)| (*rest.fragment(), v));
+    match parse("[...a, 1, ...[2]]").unwrap() {
+        ("", ConfigValue::List(list)) => {
+            assert!(matches!(
+                list.as_slice(),
+                [
+                    ListElement::Spread(_),
+                    ListElement::Single(_),
+                    ListElement::Spread(_)
+                ]
+            ));
+        }
+        x => panic!("{:?}", x),
+    }
+    match parse("{...a, b: 1 ...c}").unwrap() {
+        ("", ConfigValue::HashMap(hm)) => {
+            assert!(matches!(
+                hm.as_slice(),
+                [
+                    HashMapElement::Spread(_),
+                    HashMapElement::Entry(_),
+                    HashMapElement::Spread(_)
+                ]
+            ));
+        }
+        x => panic!("{:?}", x),
+    }
+    assert!(parse("[..a]").is_err());
+}
@@ -1,6 +1,6 @@
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_while};
-use nom::combinator::{all_consuming, map};
+use nom::combinator::{all_consuming, map, recognize};
 use nom::error::ErrorKind;
 use nom::multi::many1;
 use nom::sequence::{delimited, pair};
@@ -12,32 +12,79 @@ use crate::parser::{ml_space0, ExprWithLocation, Span};
 #[derive(Debug, Eq, PartialEq)]
 pub enum ConfigString<'a> {
     Raw(&'a str),
+    /// The character of an escape sequence like `\n` or `\u{1b}`.
+    Escaped(char),
     Interpolated(ExprWithLocation<'a>),
 }
 
 pub fn parse(input: Span) -> IResult<Span, Vec<ConfigString>> {
-    let (input, (hashes, quote)) =
-        pair(take_while(|x| x == '#'), alt((tag("\""), tag("'"))))(input)?;
+    let (out, (quote, content)) = delimited_string(input)?;
+    Ok((
+        out,
+        if quote.fragment().ends_with('\'') || content.fragment().is_empty() {
+            vec![ConfigString::Raw(content.fragment())]
+        } else {
+            let escapes = has_escapes(quote.fragment());
+            all_consuming(many1(move |x| interpolated_string(x, escapes)))(content)?.1
+        },
+    ))
+}
+
+/// Only `"` strings have escape sequences, `'` and `#"` strings keep backslashes as written.
+pub fn has_escapes(quote: &str) -> bool {
+    quote == "\""
+}
 
+/// Splits a string literal into the opening delimiter, e.g. `#"`, and the content.
+/// The string ends at the first quote followed by as many `#` as the opening quote is preceded by,
+/// in a `"` string a quote escaped by a backslash does not end it.
+pub fn delimited_string(input: Span) -> IResult<Span, (Span, Span)> {
+    let (content, opening) =
+        recognize(pair(take_while(|x| x == '#'), alt((tag("\""), tag("'")))))(input)?;
+    let (hashes, quote) = opening.fragment().split_at(opening.fragment().len() - 1);
     let pattern = format!("{}{}", quote, hashes);
-    let input: Span = input;
 
-    match input.fragment().find_substring(&pattern) {
-        Some(x) => {
-            let out: Span = input.slice(x + pattern.len()..);
-            Ok((
-                out,
-                if quote.fragment() == &"'" {
-                    vec![ConfigString::Raw(&input.fragment()[..x])]
-                } else {
-                    all_consuming(many1(interpolated_string))(input.slice(..x))?.1
-                },
-            ))
+    let end = if has_escapes(opening.fragment()) {
+        let mut chars = content.fragment().char_indices();
+        loop {
+            match chars.next() {
+                Some((_, '\\')) => {
+                    chars.next();
+                }
+                Some((ix, '"')) => break Some(ix),
+                Some(_) => (),
+                None => break None,
+            }
         }
+    } else {
+        content.fragment().find_substring(pattern.as_str())
+    };
+    match end {
+        Some(x) => Ok((
+            content.slice(x + pattern.len()..),
+            (opening, content.slice(..x)),
+        )),
         None => Err(nom::Err::Incomplete(Needed::Unknown)),
     }
 }
 
+/// The position of the first `${` that is not escaped as `\${`.
+pub fn find_interpolation(content: &str, escapes: bool) -> Option<usize> {
+    let mut ix = 0;
+    while ix < content.len() {
+        let rest = &content[ix..];
+        if rest.starts_with("${") {
+            return Some(ix);
+        }
+        let c = rest.chars().next()?;
+        ix += c.len_utf8();
+        if escapes && c == '\\' {
+            ix += content[ix..].chars().next().map_or(0, char::len_utf8);
+        }
+    }
+    None
+}
+
 #[test]
 fn raw_string_vec() {
     assert_eq!(
@@ -54,19 +101,116 @@ fn raw_string_vec() {
     );
 }
 
-fn interpolated_string(input: Span) -> IResult<Span, ConfigString> {
+/// The character and the length of the escape sequence after a backslash,
+/// `\u{...}` takes the hexadecimal code point of any character.
+fn escape_sequence(input: &str) -> Option<(char, usize)> {
+    let c = match input.chars().next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '\\' => '\\',
+        '"' => '"',
+        '$' => '$',
+        'u' => {
+            let hex = input.strip_prefix("u{")?;
+            let hex = &hex[..hex.find('}')?];
+            if hex.is_empty() || hex.len() > 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+            return Some((c, hex.len() + 3));
+        }
+        _ => return None,
+    };
+    Some((c, 1))
+}
+
+/// The text up to the next interpolation or escape sequence, an interpolation, or an escape sequence.
+fn interpolated_string(input: Span, escapes: bool) -> IResult<Span, ConfigString> {
     if input.fragment().is_empty() {
         return Err(nom::Err::Error((input, ErrorKind::Eof)));
     }
-    match input.fragment().find_substring("${") {
-        Some(x) if x == 0 => map(
+    let escape = match escapes {
+        true => input.fragment().find('\\'),
+        false => None,
+    };
+    match (input.fragment().find_substring("${"), escape) {
+        (_, Some(0)) => match escape_sequence(&input.fragment()[1..]) {
+            Some((c, len)) => Ok((input.take_split(1 + len).0, ConfigString::Escaped(c))),
+            None => Err(nom::Err::Failure((input, ErrorKind::Escaped))),
+        },
+        (Some(0), _) => map(
             delimited(ml_space0, expr, pair(ml_space0, tag("}"))),
             ConfigString::Interpolated,
         )(input.take_split(2).0),
-        Some(x) => {
-            let (rest, _res) = input.take_split(x);
-            Ok((rest, ConfigString::Raw(&input.fragment()[..x])))
-        }
-        None => Ok((Span::new(""), ConfigString::Raw(input.fragment()))),
+        (x, y) => match x.into_iter().chain(y).min() {
+            Some(x) => {
+                let (rest, _res) = input.take_split(x);
+                Ok((rest, ConfigString::Raw(&input.fragment()[..x])))
+            }
+            None => Ok((Span::new(""), ConfigString::Raw(input.fragment()))),
+        },
+    }
+}
+
+#[test]
+fn escaped_string() {
+    let parts = |s: &'static str| parse(Span::new(s)).map(|x| x.1);
+    assert_eq!(
+        parts(r#""a\tb\n\\ \"q\" \${x}""#).unwrap(),
+        vec![
+            ConfigString::Raw("a"),
+            ConfigString::Escaped('\t'),
+            ConfigString::Raw("b"),
+            ConfigString::Escaped('\n'),
+            ConfigString::Escaped('\\'),
+            ConfigString::Raw(" "),
+            ConfigString::Escaped('"'),
+            ConfigString::Raw("q"),
+            ConfigString::Escaped('"'),
+            ConfigString::Raw(" "),
+            ConfigString::Escaped('$'),
+            ConfigString::Raw("{x}"),
+        ]
+    );
+    assert!(matches!(
+        parts(r#""a\tb ${x}""#).unwrap().as_slice(),
+        [
+            ConfigString::Raw("a"),
+            ConfigString::Escaped('\t'),
+            ConfigString::Raw("b "),
+            ConfigString::Interpolated(_)
+        ]
+    ));
+    assert_eq!(parts(r#"'a\n'"#).unwrap(), vec![ConfigString::Raw("a\\n")]);
+    assert_eq!(
+        parts(r##"#"a\n${x}"#"##).unwrap().first(),
+        Some(&ConfigString::Raw("a\\n"))
+    );
+    assert!(matches!(
+        parts(r#""a\d""#),
+        Err(nom::Err::Failure((rest, ErrorKind::Escaped))) if rest.location_offset() == 2
+    ));
+    assert!(matches!(parts(r#""a\""#), Err(nom::Err::Incomplete(_))));
+    assert_eq!(
+        parts(r#""\u{1b}[0m\u{1F600}""#).unwrap(),
+        vec![
+            ConfigString::Escaped('\u{1b}'),
+            ConfigString::Raw("[0m"),
+            ConfigString::Escaped('😀'),
+        ]
+    );
+    for x in &[
+        r#""\u{}""#,
+        r#""\u{1234567}""#,
+        r#""\u{d800}""#,
+        r#""\u{x}""#,
+        r#""\u1b""#,
+    ] {
+        assert!(
+            matches!(parts(x), Err(nom::Err::Failure((_, ErrorKind::Escaped)))),
+            "{}",
+            x
+        );
     }
 }
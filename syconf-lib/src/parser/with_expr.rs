@@ -0,0 +1,48 @@
+use nom::bytes::complete::tag;
+use nom::combinator::map;
+use nom::sequence::{pair, tuple};
+use nom::IResult;
+use nom_locate::position;
+
+use crate::parser::expr::{expr, identifier};
+use crate::parser::{hashmap, ml_space0, ml_space1, ConfigValue, Expr, ExprWithLocation, Span};
+
+/// `with config {port: 9090} => body` evaluates the body with `config` bound to a copy of `config`
+/// whose fields are shallowly overridden by the hashmap.
+#[derive(Debug, Eq, PartialEq)]
+pub struct WithExpr<'a> {
+    pub name: &'a str,
+    pub name_location: Span<'a>,
+    pub overrides: ExprWithLocation<'a>,
+    pub body: ExprWithLocation<'a>,
+}
+
+/// The body extends as far as possible, like the body of a function definition.
+pub fn with_expr(input: Span) -> IResult<Span, WithExpr> {
+    map(
+        tuple((
+            pair(tag("with"), ml_space1),
+            pair(position, identifier),
+            ml_space0,
+            pair(position, hashmap),
+            tuple((ml_space0, tag("=>"), ml_space0)),
+            expr,
+        )),
+        |(_, (name_location, name), _, (pos, overrides), _, body)| WithExpr {
+            name,
+            name_location,
+            overrides: Expr::Value(ConfigValue::HashMap(overrides)).with_location(pos),
+            body,
+        },
+    )(input)
+}
+
+#[test]
+fn parse_with_expr() {
+    let (rest, x) = with_expr(Span::new("with config {port: 1} => config.port")).unwrap();
+    assert!(rest.fragment().is_empty());
+    assert_eq!(x.name, "config");
+    assert_eq!(x.name_location.location_offset(), 5);
+    assert!(with_expr(Span::new("within {a: 1} => x")).is_err());
+    assert!(with_expr(Span::new("with config => x")).is_err());
+}
@@ -17,12 +17,142 @@ fn error_location() {
     assert_eq!(line_no, 2);
 }
 
+#[test]
+fn error_positions() {
+    let dir = std::env::temp_dir().join(format!("syconf-error-positions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("main.sy");
+    let lib = dir.join("lib.sy");
+    std::fs::write(&main, "{\n  lib: import \"lib.sy\",\n}\n").unwrap();
+
+    std::fs::write(&lib, "{\n  a: 1,\n} }\n").unwrap();
+    let err = crate::parse_file(main.to_str().unwrap()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}:3:3: Cannot parse '}}'\n  |\n3 | }} }}\n  |   ^",
+            lib.display()
+        )
+    );
+
+    std::fs::write(&lib, "{\n  a: 1,\n  b: {\n    c: [1, 2 3],\n  },\n}\n").unwrap();
+    let err = crate::parse_file(main.to_str().unwrap()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}:4:14: Cannot parse '3],'\n  |\n4 |     c: [1, 2 3],\n  |              ^",
+            lib.display()
+        )
+    );
+    let err = crate::parse_string("let f = (x) => {\n  a: x +,\n}\nin f(1)").unwrap_err();
+    assert_eq!(
+        (
+            err.location.as_ref().unwrap().line_no(),
+            err.location.as_ref().unwrap().column_no()
+        ),
+        (2, 9)
+    );
+
+    std::fs::write(&lib, "let a = 1\nin\n{\n  b: 2 / (a - 1),\n}\n").unwrap();
+    let err = crate::parse_file(main.to_str().unwrap()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("{}:4:8: Division by zero", lib.display())
+    );
+
+    std::fs::write(&lib, "{\n  b: x,\n}\n").unwrap();
+    let err = crate::parse_file(main.to_str().unwrap()).unwrap_err();
+    assert!(
+        err.to_string().starts_with(&format!(
+            "{}:2:6: Variable 'x' is not defined",
+            lib.display()
+        )),
+        "{}",
+        err
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 fn math() {
     assert_eq!(
         parse_string("1 * 2 + 3 * 4 == 14").unwrap(),
         Value::Bool(true)
     );
+    for src in &[
+        "1.5 + 2 == 3.5",
+        "2 * 0.25 == 0.5",
+        "1e-3 == 0.001",
+        "2.5E+2 == 250",
+        "7 / 2 == 3",
+        "7 / 2.0 == 3.5",
+        "1 == 1.0",
+        "1.5 < 2 and 2 > 1.5",
+        "0.1 + 0.2 != 0.3",
+        "\"${1e-3}\" == \"0.001\"",
+        "\"${2.0}\" == \"2.0\"",
+        "4000000000 * 2 == 8000000000",
+        "\"${4000000000}\" == \"4000000000\"",
+        "'{\"size\": 4000000000}'.parse_json().size == 4000000000",
+        "9007199254740993 != 9007199254740992.0",
+        "9007199254740993 > 9007199254740992.0",
+        "9223372036854775807 < 1e19",
+    ] {
+        assert_eq!(parse_string(src).unwrap(), Value::Bool(true), "{}", src);
+    }
+    for (src, expected) in &[
+        ("1 / 0.0", "Division by zero"),
+        ("1e308 * 10", "Float overflow"),
+        (".5", "Cannot parse"),
+        (
+            "9223372036854775807 + 1",
+            "Integer overflow in operation on 9223372036854775807 and 1",
+        ),
+        ("4000000000 * 4000000000", "Integer overflow"),
+    ] {
+        let err = parse_string(src).unwrap_err();
+        assert!(err.message.contains(expected), "{}: {}", src, err);
+    }
+}
+
+#[test]
+fn negative_numbers() {
+    assert_eq!(parse_string("-5").unwrap(), Value::Int(-5));
+    assert_eq!(
+        parse_string("[-1, -2]").unwrap(),
+        parse_string("[0 - 1, 0 - 2]").unwrap()
+    );
+    assert!(matches!(parse_string("-0.0").unwrap(), Value::Float(x) if x.is_sign_negative()));
+    for src in &[
+        "3 - -2 == 5",
+        "3 + -2 == 1",
+        "3 -2 == 1",
+        "2 * -3 == -6",
+        "-1.5e3 == -1500",
+        "- 1 == -1",
+        "--1 == 1",
+        "let a = 7 in [a - 5, a + -5, a-5, -a] == [2, 2, 2, -7]",
+        "-(1 + 2) == -3",
+        "-5.abs() == -5",
+        "(-5).abs() == 5",
+        "{a: -1, b: [-0.5]} == {a: 0 - 1, b: [0 - 0.5]}",
+        "-9223372036854775808 < -9223372036854775807",
+        "\"${-42}\" == \"-42\"",
+    ] {
+        assert_eq!(parse_string(src).unwrap(), Value::Bool(true), "{}", src);
+    }
+    for (src, expected) in &[
+        (
+            "-(-9223372036854775808)",
+            "Integer overflow in negation of -9223372036854775808",
+        ),
+        ("-\"a\"", "'-' expects a number, got \"a\""),
+        ("-9223372036854775809", "Cannot parse"),
+        ("-", "Unexpected end of input"),
+    ] {
+        let err = parse_string(src).unwrap_err();
+        assert!(err.message.contains(expected), "{}: {}", src, err);
+    }
 }
 
 #[test]
@@ -42,6 +172,35 @@ fn interpolated_string() {
     );
 }
 
+#[test]
+fn escaped_strings() {
+    assert_eq!(
+        parse_string(r#"let x = 1 in "a\tb ${x}\n""#).unwrap(),
+        Value::String("a\tb 1\n".into())
+    );
+    assert_eq!(
+        parse_string(r#"let x = 1 in "\${x} is ${x}, \\${x}""#).unwrap(),
+        Value::String("${x} is 1, \\1".into())
+    );
+    assert_eq!(
+        parse_string(r#"{"say \"hi\"": "C:\\dir\r"}"#)
+            .unwrap()
+            .to_json()
+            .unwrap(),
+        r#"{"say \"hi\"":"C:\\dir\r"}"#
+    );
+    assert_eq!(
+        parse_string(r#"['a\n', "a\\n"]"#).unwrap(),
+        parse_string(r##"[#"a\n"#, 'a\n']"##).unwrap()
+    );
+    let err = parse_string(r#"{a: "x\d"}"#).unwrap_err();
+    assert_eq!(
+        err.to_string().lines().next().unwrap(),
+        r#"<input_string>:1:7: Unknown escape sequence '\d', use '\\' for a backslash"#
+    );
+    assert!(parse_string(r#""a\""#).is_err());
+}
+
 #[test]
 fn hashmap() {
     assert_eq!(
@@ -90,6 +249,53 @@ fn conditional() {
         .unwrap(),
         Value::Bool(true)
     );
+    assert_eq!(
+        parse_string("if false then 1 / 0 else 2").unwrap(),
+        Value::Int(2)
+    );
+    let (value, deps) = crate::compile_string(
+        r#"
+    let safe = true
+    in
+    if safe then read_file("Cargo.toml") != "" else read_file("missing-file.txt")
+    "#,
+    )
+    .unwrap()
+    .evaluate_with_dependencies()
+    .unwrap();
+    assert_eq!(value, Value::Bool(true));
+    assert_eq!(
+        deps.files.into_iter().collect::<Vec<_>>(),
+        vec![std::path::PathBuf::from("Cargo.toml")]
+    );
+    assert_eq!(
+        parse_string("if null then 1 else 2").unwrap_err().message,
+        "'if' condition must be a bool, got null, use 'x.is_null()' to test for null"
+    );
+}
+
+#[test]
+fn null_values() {
+    assert_eq!(
+        parse_string(
+            r#"
+    let json = '{"a": null, "b": [null]}'.parse_json()
+    let yaml = "{a: null, b: 1}".parse_yaml()
+    let toml = "a = 1".parse_toml()
+    let port = getenv("SYCONF_TEST_UNSET_PORT", null)
+    in
+    [json.a == null, json.b == [null], null == null, yaml.a == null, yaml.b != null, toml.a != null, port == null, port.is_null()]
+    "#
+        )
+        .unwrap(),
+        parse_string("[true, true, true, true, true, true, true, true]").unwrap()
+    );
+    assert_eq!(
+        parse_string("{a: null}.to_json()").unwrap(),
+        Value::String(r#"{"a":null}"#.into())
+    );
+    // A missing field is an error rather than null.
+    assert!(parse_string("{a: 1}.b == null").is_err());
 }
 
 #[test]
@@ -106,6 +312,33 @@ fn logical() {
         .unwrap(),
         Value::Bool(true)
     );
+    // The right operand is skipped if the left one decides the result.
+    let (value, deps) = crate::compile_string(
+        r#"
+    let cfg = {db: null}
+    in
+    [
+        cfg.db != null and cfg.db.port > 0,
+        cfg.db == null or cfg.db.port > 0,
+        false and read_file("missing-file.txt") != "",
+        true or 1 / 0 == 1,
+        false and 1,
+    ]
+    "#,
+    )
+    .unwrap()
+    .evaluate_with_dependencies()
+    .unwrap();
+    assert_eq!(
+        value,
+        parse_string("[false, true, false, true, false]").unwrap()
+    );
+    assert!(deps.files.is_empty());
+    assert_eq!(
+        parse_string("true and 1").unwrap_err().message,
+        "'and' expects bools, got 1"
+    );
+    assert!(parse_string(r#"true and read_file("missing-file.txt") != """#).is_err());
 }
 
 #[test]
@@ -120,6 +353,40 @@ fn comments() {
         .unwrap(),
         Value::Bool(true)
     );
+
+    let with_comments = r##"# service config
+let port = 8080 /* the default */ ;
+let name = "api" // the name
+in
+{
+    # the address
+    addr: "${name}:${ port /* inline */ }", // interpolated
+    tags: [
+        "a # not a comment", /* first */
+        'b // neither',
+        #"c /* nor */"#, # raw
+    ],
+    /* a block
+       over lines */
+    nested: {a: 1, # one
+        b: 2},
+} // end without a line break"##;
+    let without = r##"let port = 8080 ;
+let name = "api"
+in
+{
+    addr: "${name}:${ port }",
+    tags: ["a # not a comment", 'b // neither', #"c /* nor */"#],
+    nested: {a: 1, b: 2},
+}"##;
+    assert_eq!(
+        parse_string(with_comments).unwrap(),
+        parse_string(without).unwrap()
+    );
+    assert!(parse_string("1 /* unterminated")
+        .unwrap_err()
+        .message
+        .starts_with("Cannot parse"));
 }
 
 #[test]
@@ -153,3 +420,216 @@ fn suffix_operator() {
         Value::Bool(true)
     );
 }
+
+#[test]
+fn predefined_vars() {
+    let mut vars = std::collections::HashMap::new();
+    vars.insert("name".to_string(), Value::String("api".into()));
+    vars.insert("port".to_string(), Value::Int(80));
+    assert_eq!(
+        crate::parse_string_with_vars(
+            r#"
+    let port = port + 1
+    in
+    "${name}:${port}"
+    "#,
+            &vars
+        )
+        .unwrap(),
+        Value::String("api:81".into())
+    );
+}
+
+#[test]
+fn with_expression() {
+    let eval = |s: &str| parse_string(&format!("let config = {{host: \"a\", port: 80}} in {}", s));
+    let cases = [
+        (
+            "with config {port: 9090} => config",
+            r#"{host: "a", port: 9090}"#,
+        ),
+        (
+            "[with config {port: 1} => ((c) => c.port)(config), config.port]",
+            "[1, 80]",
+        ),
+        ("with config {port: config.port + 1} => config.port", "81"),
+        ("with config {port: 1} => config.port + 1", "2"),
+        (
+            r#"with config {port: 1} => with config {host: "b", tls: true} => config"#,
+            r#"{host: "b", port: 1, tls: true}"#,
+        ),
+        (
+            "[with config {} => config.port, (with config {port: 2} => config).port]",
+            "[80, 2]",
+        ),
+    ];
+    for (expr, expected) in cases.iter() {
+        assert_eq!(
+            eval(expr).unwrap(),
+            parse_string(expected).unwrap(),
+            "{}",
+            expr
+        );
+    }
+    assert!(eval("with missing {port: 1} => missing").is_err());
+    assert!(parse_string("let x = 1 in with x {port: 1} => x").is_err());
+}
+
+#[test]
+fn compile_with_loader() {
+    use crate::{EvalOptions, FileLoader};
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    struct MemoryLoader(HashMap<PathBuf, &'static str>);
+    impl FileLoader for MemoryLoader {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.0
+                .get(path)
+                .map(ToString::to_string)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in memory"))
+        }
+    }
+
+    let loader = Rc::new(MemoryLoader(
+        vec![
+            (
+                PathBuf::from("conf/main.sy"),
+                r#"{db: import "db.sy", token: read_file("token.txt")}"#,
+            ),
+            (PathBuf::from("conf/db.sy"), "{port: 5432}"),
+            (PathBuf::from("token.txt"), "secret"),
+        ]
+        .into_iter()
+        .collect(),
+    ));
+    let compiled = crate::compile_file_with_loader("conf/main.sy", loader.clone()).unwrap();
+    let options = EvalOptions {
+        file_loader: loader.clone(),
+        ..Default::default()
+    };
+    assert_eq!(
+        compiled.evaluate_with_options(&options).unwrap(),
+        parse_string(r#"{db: {port: 5432}, token: "secret"}"#).unwrap()
+    );
+    let err = crate::compile_file_with_loader("conf/other.sy", loader).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Cannot open file 'conf/other.sy': not in memory"
+    );
+}
+
+#[test]
+fn import_cycles_and_diamonds() {
+    use crate::FileLoader;
+    use std::cell::RefCell;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    struct RecordingLoader(RefCell<Vec<PathBuf>>);
+    impl FileLoader for RecordingLoader {
+        fn read_to_string(&self, path: &Path) -> io::Result<String> {
+            self.0.borrow_mut().push(path.to_path_buf());
+            std::fs::read_to_string(path)
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("syconf-import-cycles-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let write = |name: &str, content: &str| std::fs::write(dir.join(name), content).unwrap();
+
+    write("a.sy", r#"{b: import "b.sy"}"#);
+    write("b.sy", r#"{a: import "./a.sy"}"#);
+    let err = crate::parse_file(dir.join("a.sy").to_str().unwrap()).unwrap_err();
+    assert_eq!(
+        err.message,
+        format!(
+            "Import cycle: {} -> {} -> {}",
+            dir.join("a.sy").display(),
+            dir.join("b.sy").display(),
+            dir.join("a.sy").display()
+        )
+    );
+    assert_eq!(err.location.unwrap().source.file(), &dir.join("b.sy"));
+
+    write("self.sy", r#"{me: import "lib/../self.sy"}"#);
+    let err = crate::parse_file(dir.join("self.sy").to_str().unwrap()).unwrap_err();
+    assert!(err.message.starts_with("Import cycle: "), "{}", err);
+
+    write("main.sy", r#"{x: import "lib/x.sy", y: import "lib/y.sy"}"#);
+    write("lib/x.sy", r#"{common: import "common.sy", name: "x"}"#);
+    write("lib/y.sy", r#"{common: import "./common.sy", name: "y"}"#);
+    write("lib/common.sy", "{port: 8080}");
+    let loader = Rc::new(RecordingLoader(Default::default()));
+    let compiled =
+        crate::compile_file_with_loader(dir.join("main.sy").to_str().unwrap(), loader.clone())
+            .unwrap();
+    assert_eq!(
+        compiled.evaluate().unwrap(),
+        parse_string(
+            r#"{x: {common: {port: 8080}, name: "x"}, y: {common: {port: 8080}, name: "y"}}"#
+        )
+        .unwrap()
+    );
+    let common_reads = loader
+        .0
+        .borrow()
+        .iter()
+        .filter(|x| x.ends_with("common.sy"))
+        .count();
+    assert_eq!(common_reads, 1);
+
+    // Imports with injected variables are compiled once per set of variable names.
+    write(
+        "main.sy",
+        r#"[import "lib/z.sy" with {n: 1}, import "lib/z.sy" with {n: 2}, import "lib/z.sy"]"#,
+    );
+    write("lib/z.sy", "n");
+    let err = crate::parse_file(dir.join("main.sy").to_str().unwrap()).unwrap_err();
+    assert!(
+        err.message.starts_with("Variable 'n' is not defined"),
+        "{}",
+        err
+    );
+    write(
+        "main.sy",
+        r#"[import "lib/z.sy" with {n: 1}, import "lib/z.sy" with {n: 2}]"#,
+    );
+    assert_eq!(
+        crate::parse_file(dir.join("main.sy").to_str().unwrap()).unwrap(),
+        parse_string("[1, 2]").unwrap()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_from_string() {
+    let dir = std::env::temp_dir().join(format!("syconf-import-string-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/db.sy"), r#"{port: import "port.sy"}"#).unwrap();
+    std::fs::write(dir.join("lib/port.sy"), "5432").unwrap();
+
+    let err = parse_string(r#"{db: import "lib/db.sy"}"#).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Imports are not allowed when parsing from a string"
+    );
+    assert_eq!(err.location.unwrap().column_no(), 6);
+    assert!(err.hint.unwrap().contains("parse_string_with_base"));
+
+    let expected = parse_string("{db: {port: 5432}}").unwrap();
+    let absolute = format!(r#"{{db: import "{}"}}"#, dir.join("lib/db.sy").display());
+    assert_eq!(parse_string(&absolute).unwrap(), expected);
+    assert_eq!(
+        crate::parse_string_with_base(r#"{db: import "lib/db.sy"}"#, &dir).unwrap(),
+        expected
+    );
+    assert_eq!(
+        crate::parse_string_with_base(r#"{db: import "./db.sy"}"#, &dir.join("lib")).unwrap(),
+        expected
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}